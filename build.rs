@@ -0,0 +1,85 @@
+// Parses and validates every WGSL shader with naga while the crate builds, so a mistake in a
+// shader fails the build instead of panicking in create_shader_module() at runtime.
+
+use std::fs;
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+#[path = "src/shader_source.rs"]
+mod shader_source;
+
+// Used by the render passes once their includes are expanded
+const RENDER_SHADERS: [&str; 12] = [
+    "shader.wgsl",
+    "sphere_shader.wgsl",
+    "contact_shader.wgsl",
+    "motion_blur.wgsl",
+    "pip.wgsl",
+    "taa.wgsl",
+    "shadow.wgsl",
+    "edge_shader.wgsl",
+    "sky.wgsl",
+    "oit.wgsl",
+    "dof.wgsl",
+    "grid.wgsl",
+];
+// Appended to compute.wgsl, None for compute.wgsl alone
+const COMPUTE_SHADERS: [Option<&str>; 12] = [
+    None,
+    Some("guard.wgsl"),
+    Some("contacts.wgsl"),
+    Some("implicit.wgsl"),
+    Some("gauss_seidel.wgsl"),
+    Some("self_shadow.wgsl"),
+    Some("convergence.wgsl"),
+    Some("self_collision.wgsl"),
+    Some("checksum.wgsl"),
+    Some("strain.wgsl"),
+    Some("kinematics.wgsl"),
+    Some("energy.wgsl"),
+];
+// The reductions need a power of two, the simulation picks one of them
+const WORKGROUP_SIZES: [u32; 3] = [64, 128, 256];
+
+fn read(name: &str) -> String {
+    let path = format!("src/{name}");
+    println!("cargo:rerun-if-changed={path}");
+    fs::read_to_string(&path).unwrap_or_else(|error| panic!("can't read {path}: {error}"))
+}
+
+// The naga report with the offending lines, on failure
+fn validate(name: &str, source: &str) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|error| error.emit_to_string_with_path(source, name))?;
+    Validator::new(ValidationFlags::all(), Capabilities::default())
+        .validate(&module)
+        .map_err(|error| error.emit_to_string_with_path(source, name))?;
+    Ok(())
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/shader_source.rs");
+    println!("cargo:rerun-if-changed=src/compute.wgsl");
+    println!("cargo:rerun-if-changed=src/wgsl");
+
+    let mut errors = Vec::new();
+    for name in RENDER_SHADERS {
+        errors.extend(validate(name, &shader_source::expand_includes(&read(name))).err());
+    }
+    // Built with --msaa, see dof.rs. A replacement that no longer matches leaves the single sampled
+    // shader, which validates but doesn't fit the multisampled depth buffer.
+    let multisampled = shader_source::depth_of_field_source(true);
+    if !multisampled.contains("texture_depth_multisampled_2d") {
+        errors.push("dof.wgsl (multisampled): the depth_texture declaration wasn't replaced".to_string());
+    }
+    errors.extend(validate("dof.wgsl (multisampled)", &multisampled).err());
+    for name in COMPUTE_SHADERS {
+        let extra_source = name.map(read).unwrap_or_default();
+        for workgroup_size in WORKGROUP_SIZES {
+            let label = format!("compute.wgsl + {} (WORKGROUP_SIZE {workgroup_size})", name.unwrap_or("nothing"));
+            errors.extend(validate(&label, &shader_source::compute_source(&extra_source, workgroup_size)).err());
+        }
+    }
+    if !errors.is_empty() {
+        panic!("invalid WGSL:\n{}", errors.join("\n"));
+    }
+}
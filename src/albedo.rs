@@ -0,0 +1,213 @@
+// Albedo texture of the cloth: every particle takes the color of the texture at its UV, its rest
+// position on the fabric, so a checkered pattern shows where the cloth stretches and shears.
+// Sampled by the vertex shader of the cloth, the particles are drawn as small spheres of one color.
+
+use std::io;
+use std::path::Path;
+
+use wgpu_bootstrap::{wgpu, Context};
+
+const CHECKER_SIZE: u32 = 64; // texels, the default texture
+const CHECKER_SQUARES: u32 = 8; // along each side of it
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AlbedoUniform {
+    enabled: u32,
+    repeat: f32,
+    _padding: [u32; 2],
+}
+
+pub struct AlbedoTexture {
+    pub enabled: bool,
+    pub repeat: f32, // times the texture is tiled across the largest side of a piece
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl AlbedoTexture {
+    // Starts on a checkerboard
+    pub fn new(context: &Context) -> Self {
+        let uniform_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Albedo Uniform Buffer"),
+            size: std::mem::size_of::<AlbedoUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Albedo Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Albedo Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let checker: Vec<u8> = (0..CHECKER_SIZE * CHECKER_SIZE)
+            .flat_map(|texel| {
+                let square = CHECKER_SIZE / CHECKER_SQUARES;
+                let (x, y) = (texel % CHECKER_SIZE / square, texel / CHECKER_SIZE / square);
+                if (x + y) % 2 == 0 {
+                    [230, 230, 225, 255]
+                } else {
+                    [60, 70, 110, 255]
+                }
+            })
+            .collect();
+        let bind_group = create_bind_group(
+            context,
+            &bind_group_layout,
+            &uniform_buffer,
+            &sampler,
+            (CHECKER_SIZE, CHECKER_SIZE),
+            &checker,
+        );
+
+        Self {
+            enabled: false,
+            repeat: 1.0,
+            uniform_buffer,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    // Replaces the texture by an image in any format the image crate reads
+    pub fn load(&mut self, context: &Context, path: &Path) -> io::Result<()> {
+        let image = image::open(path).map_err(io::Error::other)?.into_rgba8();
+        self.bind_group = create_bind_group(
+            context,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.sampler,
+            image.dimensions(),
+            image.as_raw(),
+        );
+        Ok(())
+    }
+
+    // Follows the checkbox and the slider, called once per frame
+    pub fn update(&self, context: &Context) {
+        let uniform = AlbedoUniform {
+            enabled: u32::from(self.enabled),
+            repeat: self.repeat,
+            _padding: [0; 2],
+        };
+        context.queue().write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+// UV of every particle in a vertex buffer stepped per instance, next to the particle states
+pub fn uv_vertex_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 8,
+            format: wgpu::VertexFormat::Float32x2,
+        }],
+    }
+}
+
+// The texture lives as long as the bind group, RGBA8 in sRGB
+fn create_bind_group(
+    context: &Context,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    (width, height): (u32, u32),
+    texels: &[u8],
+) -> wgpu::BindGroup {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = context.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some("Albedo Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    context.queue().write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        texels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Albedo Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
@@ -0,0 +1,163 @@
+// Node animations read from glTF files, played back on the rig the anchors and colliders follow.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use gltf::animation::{util::ReadOutputs, Interpolation};
+use wgpu_bootstrap::cgmath::{Matrix4, Quaternion, Vector3, VectorSpace};
+
+enum Keys {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+struct Channel {
+    node: String,
+    times: Vec<f32>,
+    keys: Keys,
+    step: bool, // holds every key until the next one instead of interpolating
+}
+
+impl Channel {
+    // Keys around `time` and how far between them it is, clamped to the first and last key
+    fn keys_at(&self, time: f32) -> (usize, usize, f32) {
+        let next = self.times.partition_point(|&key_time| key_time <= time);
+        if next == 0 {
+            return (0, 0, 0.0);
+        }
+        if next == self.times.len() || self.step {
+            return (next - 1, next - 1, 0.0);
+        }
+        let (start, end) = (self.times[next - 1], self.times[next]);
+        (next - 1, next, (time - start) / (end - start))
+    }
+}
+
+/// The first animation of a glTF file. Its channels drive the rig nodes of the same name (root,
+/// collider, anchor), on top of their rest transform.
+pub struct RigAnimation {
+    channels: Vec<Channel>,
+    duration: f32,
+    pub time: f32,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl RigAnimation {
+    pub fn load_gltf(path: &Path) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let (document, buffers, _) = gltf::import(path).map_err(|error| invalid(error.to_string()))?;
+        let animation = document
+            .animations()
+            .next()
+            .ok_or_else(|| invalid("the file has no animation".to_string()))?;
+
+        let mut channels = Vec::new();
+        for channel in animation.channels() {
+            let Some(node) = channel.target().node().name().map(str::to_string) else {
+                continue; // unnamed nodes can't match a rig node
+            };
+            let reader = channel.reader(|buffer| Some(&*buffers[buffer.index()]));
+            let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else {
+                continue;
+            };
+            let interpolation = channel.sampler().interpolation();
+            let mut keys = match outputs {
+                ReadOutputs::Translations(values) => Keys::Translation(values.map(Vector3::from).collect()),
+                ReadOutputs::Rotations(values) => Keys::Rotation(
+                    values
+                        .into_f32()
+                        .map(|[x, y, z, w]| Quaternion::new(w, x, y, z))
+                        .collect(),
+                ),
+                ReadOutputs::Scales(values) => Keys::Scale(values.map(Vector3::from).collect()),
+                ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+            // Cubic spline keys come with their in and out tangents, only the values are kept and
+            // interpolated linearly
+            if interpolation == Interpolation::CubicSpline {
+                keys = match keys {
+                    Keys::Translation(values) => Keys::Translation(values.into_iter().skip(1).step_by(3).collect()),
+                    Keys::Rotation(values) => Keys::Rotation(values.into_iter().skip(1).step_by(3).collect()),
+                    Keys::Scale(values) => Keys::Scale(values.into_iter().skip(1).step_by(3).collect()),
+                };
+            }
+            let times: Vec<f32> = inputs.collect();
+            let num_keys = match &keys {
+                Keys::Translation(values) | Keys::Scale(values) => values.len(),
+                Keys::Rotation(values) => values.len(),
+            };
+            if times.is_empty() || num_keys != times.len() {
+                continue;
+            }
+            channels.push(Channel {
+                node,
+                times,
+                keys,
+                step: interpolation == Interpolation::Step,
+            });
+        }
+
+        if channels.is_empty() {
+            return Err(invalid("the animation drives no named node".to_string()));
+        }
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max);
+        Ok(Self {
+            channels,
+            duration,
+            time: 0.0,
+            playing: true,
+            looping: true,
+        })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        if !self.playing {
+            return;
+        }
+        self.time += delta_time;
+        if self.time > self.duration {
+            if self.looping && self.duration > 0.0 {
+                self.time %= self.duration;
+            } else {
+                self.time = self.duration;
+                self.playing = false;
+            }
+        }
+    }
+
+    // Local transform of every animated node at the current time, components without a channel
+    // are left at identity
+    pub fn pose(&self) -> Vec<(&str, Matrix4<f32>)> {
+        let identity = (Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0));
+        let mut nodes = BTreeMap::new();
+        for channel in &self.channels {
+            let (start, end, amount) = channel.keys_at(self.time);
+            let (translation, rotation, scale) = nodes.entry(channel.node.as_str()).or_insert(identity);
+            match &channel.keys {
+                Keys::Translation(values) => *translation = values[start].lerp(values[end], amount),
+                Keys::Rotation(values) => *rotation = values[start].nlerp(values[end], amount),
+                Keys::Scale(values) => *scale = values[start].lerp(values[end], amount),
+            }
+        }
+        nodes
+            .into_iter()
+            .map(|(node, (translation, rotation, scale))| {
+                let transform = Matrix4::from_translation(translation)
+                    * Matrix4::from(rotation)
+                    * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+                (node, transform)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,157 @@
+// A scalar per particle set from the host, drawn over the cloth through a color ramp: region ids,
+// ownership, experiment labels, anything that can be written as one number per particle.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use wgpu_bootstrap::wgpu;
+
+use crate::kinematics::MotionQuantity;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorRamp {
+    Viridis,
+    Heat,
+    Categorical, // rounds the values to ids, each with its own hue
+}
+
+impl ColorRamp {
+    pub const ALL: [ColorRamp; 3] = [ColorRamp::Viridis, ColorRamp::Heat, ColorRamp::Categorical];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorRamp::Viridis => "Viridis",
+            ColorRamp::Heat => "Heat",
+            ColorRamp::Categorical => "Categories",
+        }
+    }
+}
+
+// Where the values come from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttributeSource {
+    None,
+    Piece,      // index of the cloth piece, e.g. to tell overlapping pieces apart
+    RestHeight, // height in the rest state
+    File,       // see load_values()
+    Strain,     // largest stretch of the constraints, written every frame on the GPU, see strain.rs
+    Speed,      // same, see kinematics.rs
+    VerticalSpeed, // positive upwards
+    Acceleration,
+}
+
+impl AttributeSource {
+    pub const ALL: [AttributeSource; 8] = [
+        AttributeSource::None,
+        AttributeSource::Piece,
+        AttributeSource::RestHeight,
+        AttributeSource::File,
+        AttributeSource::Strain,
+        AttributeSource::Speed,
+        AttributeSource::VerticalSpeed,
+        AttributeSource::Acceleration,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AttributeSource::None => "Mesh colors",
+            AttributeSource::Piece => "Piece",
+            AttributeSource::RestHeight => "Rest height",
+            AttributeSource::File => "Values from file",
+            AttributeSource::Strain => "Strain",
+            AttributeSource::Speed => "Speed",
+            AttributeSource::VerticalSpeed => "Vertical speed",
+            AttributeSource::Acceleration => "Acceleration",
+        }
+    }
+
+    // Range of the ramp of the sources computed on the GPU, whose values the host never sees,
+    // and the unit it is in. None for the others, their ramp spans their values.
+    pub fn default_range(self) -> Option<(f32, &'static str)> {
+        match self {
+            AttributeSource::Strain => Some((0.1, "")),
+            AttributeSource::Speed | AttributeSource::VerticalSpeed => Some((1.0, "m/s")),
+            AttributeSource::Acceleration => Some((20.0, "m/s²")),
+            _ => None,
+        }
+    }
+
+    pub fn motion(self) -> Option<MotionQuantity> {
+        match self {
+            AttributeSource::Speed => Some(MotionQuantity::Speed),
+            AttributeSource::VerticalSpeed => Some(MotionQuantity::VerticalSpeed),
+            AttributeSource::Acceleration => Some(MotionQuantity::Acceleration),
+            _ => None,
+        }
+    }
+}
+
+/// How the attribute is mapped to colors, `ramp` is 0 to keep the mesh colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RampUniform {
+    ramp: u32,
+    min: f32,
+    max: f32,
+    _padding: f32,
+}
+
+impl RampUniform {
+    pub const OFF: RampUniform = RampUniform {
+        ramp: 0,
+        min: 0.0,
+        max: 1.0,
+        _padding: 0.0,
+    };
+
+    // For values only known on the GPU
+    pub fn range(ramp: ColorRamp, min: f32, max: f32) -> Self {
+        Self {
+            ramp: ramp as u32 + 1,
+            min,
+            max,
+            _padding: 0.0,
+        }
+    }
+
+    // Spans the range of `values`
+    pub fn new(ramp: ColorRamp, values: &[f32]) -> Self {
+        let (min, max) = values
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| (min.min(value), max.max(value)));
+        Self {
+            ramp: ramp as u32 + 1,
+            min: if values.is_empty() { 0.0 } else { min },
+            max: if values.is_empty() { 1.0 } else { max },
+            _padding: 0.0,
+        }
+    }
+}
+
+// One value per particle in a vertex buffer stepped per instance, next to the particle states
+pub fn vertex_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<f32>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 6,
+            format: wgpu::VertexFormat::Float32,
+        }],
+    }
+}
+
+// One value per line, in particle order, so scripts can produce them with anything
+pub fn load_values(path: &Path) -> io::Result<Vec<f32>> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_index, line)| {
+            line.trim().parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("invalid value on line {}", line_index + 1))
+            })
+        })
+        .collect()
+}
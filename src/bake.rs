@@ -0,0 +1,100 @@
+// Bakes the drape of the cloth into images over its UV domain, one texel per particle, for use as
+// displacement or material input in other tools. Only grid pieces have a UV domain: u follows the
+// columns and v the rows.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::export::EXPORT_DIR;
+
+pub const BAKE_DIR: &str = "bake"; // in EXPORT_DIR
+
+/// Positions of a grid piece laid out row by row, `rows * cols` of them.
+pub struct GridSurface<'a> {
+    pub rows: u32,
+    pub cols: u32,
+    pub positions: &'a [[f32; 3]],
+}
+
+impl GridSurface<'_> {
+    fn at(&self, row: i64, col: i64) -> [f32; 3] {
+        let row = row.clamp(0, i64::from(self.rows) - 1);
+        let col = col.clamp(0, i64::from(self.cols) - 1);
+        self.positions[(row * i64::from(self.cols) + col) as usize]
+    }
+
+    // From central differences along the rows and columns, facing up for a horizontal grid
+    fn normal(&self, row: u32, col: u32) -> [f32; 3] {
+        let (row, col) = (i64::from(row), i64::from(col));
+        let du = sub(self.at(row, col + 1), self.at(row, col - 1));
+        let dv = sub(self.at(row + 1, col), self.at(row - 1, col));
+        let [x, y, z] = cross(dv, du);
+        let length = (x * x + y * y + z * z).sqrt();
+        if length > 1e-12 {
+            [x / length, y / length, z / length]
+        } else {
+            [0.0, 0.0, 0.0]
+        }
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+// Range of heights a baked heightmap spans, black is `min` and white `max`, in meters
+#[derive(Copy, Clone, Debug)]
+struct HeightRange {
+    min: f32,
+    max: f32,
+}
+
+// Saves piece_N_height.png, 16-bit displacement of every particle from its rest position along the
+// rest normal with its range in piece_N_height.txt, and piece_N_normal.png, world space normals of
+// the settled cloth as RGB = n / 2 + 0.5
+pub fn bake_piece(piece: usize, rest: &GridSurface, settled: &GridSurface) -> io::Result<()> {
+    let directory = Path::new(EXPORT_DIR).join(BAKE_DIR);
+    fs::create_dir_all(&directory)?;
+    let (width, height) = (settled.cols, settled.rows);
+
+    let texels = || (0..height).flat_map(|row| (0..width).map(move |col| (row, col)));
+    let heights: Vec<f32> = texels()
+        .map(|(row, col)| {
+            let index = (row * width + col) as usize;
+            dot(sub(settled.positions[index], rest.positions[index]), rest.normal(row, col))
+        })
+        .collect();
+    let range = heights
+        .iter()
+        .fold(HeightRange { min: f32::MAX, max: f32::MIN }, |range, &h| HeightRange {
+            min: range.min.min(h),
+            max: range.max.max(h),
+        });
+    let scale = if range.max > range.min { 1.0 / (range.max - range.min) } else { 0.0 };
+    let heightmap: Vec<u16> = heights
+        .iter()
+        .map(|&h| ((h - range.min) * scale * f32::from(u16::MAX)).round() as u16)
+        .collect();
+
+    let normal_map: Vec<u8> = texels()
+        .flat_map(|(row, col)| settled.normal(row, col))
+        .map(|coordinate| ((coordinate * 0.5 + 0.5) * 255.0).round() as u8)
+        .collect();
+
+    let path = |name: &str| directory.join(format!("piece_{piece}_{name}"));
+    image::save_buffer(path("height.png"), bytemuck::cast_slice(&heightmap), width, height, image::ColorType::L16)
+        .map_err(io::Error::other)?;
+    image::save_buffer(path("normal.png"), &normal_map, width, height, image::ColorType::Rgb8)
+        .map_err(io::Error::other)?;
+    // The heightmap is normalized, the range brings it back to meters
+    fs::write(path("height.txt"), format!("min {}\nmax {}\n", range.min, range.max))
+}
@@ -0,0 +1,259 @@
+// Batch rendering of a saved replay to images, at a resolution unrelated to the window's, one
+// frame per application frame so the interface stays responsive while it runs.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use wgpu_bootstrap::{wgpu, Context};
+
+use cloth::render::{MainView, MotionBlur, OrbitCamera, Tonemapping, Transparency};
+
+use crate::export::{CameraPath, CAMERA_PATH_FILE, EXPORT_DIR};
+use crate::replay::ReplayReader;
+
+pub const FRAMES_DIR: &str = "frames"; // in EXPORT_DIR
+
+/// Renders every frame of a replay from the recorded camera path, or from a fixed view if there
+/// is none, turning if asked, with motion blur if asked. Temporal anti-aliasing converges over several frames of
+/// the same view and isn't applied.
+pub struct BatchRender {
+    replay: ReplayReader,
+    camera_path: Option<CameraPath>,
+    camera: OrbitCamera,
+    turntable: Option<(f32, f32)>, // azimuth of the first frame and radians per step, without a camera path
+    size: (u32, u32),
+    padded_row: u32, // bytes per row of the readback buffer, rows are aligned for the copy
+    frame: u32,
+    directory: PathBuf,
+    color_texture: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback_buffer: wgpu::Buffer,
+    motion_blur: Option<MotionBlur>,
+    main_view: MainView, // renders the scene without motion blur, tonemapped like the window
+}
+
+impl BatchRender {
+    // `camera` gives the projection, and the view when no camera path was recorded
+    pub fn start(
+        context: &Context,
+        replay: ReplayReader,
+        camera: &OrbitCamera,
+        size: (u32, u32),
+        motion_blur: Option<f32>, // strength
+        sample_count: u32,
+        tonemapping: &Tonemapping,
+    ) -> io::Result<Self> {
+        let (width, height) = (size.0.max(1), size.1.max(1));
+        let bytes_per_pixel = 4;
+        if context.format().block_copy_size(None) != Some(bytes_per_pixel) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("can't save frames of the {:?} surface format", context.format()),
+            ));
+        }
+
+        let directory = Path::new(EXPORT_DIR).join(FRAMES_DIR);
+        fs::create_dir_all(&directory)?;
+        let camera_path = match CameraPath::load(&Path::new(EXPORT_DIR).join(CAMERA_PATH_FILE)) {
+            Ok(path) => Some(path),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error),
+        };
+
+        // Only the view of the motion blur or the main view is drawn into the frame, tonemapped
+        let create_texture = |label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages| {
+            context.device().create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let color_texture = create_texture(
+            "Batch Color Texture",
+            context.format(),
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth_texture = create_texture(
+            "Batch Depth Texture",
+            context.depth_stencil_format(),
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let padded_row = (width * bytes_per_pixel).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Batch Readback Buffer"),
+            size: u64::from(padded_row) * u64::from(height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let motion_blur = motion_blur.map(|strength| {
+            let mut motion_blur = MotionBlur::new(context, sample_count, tonemapping);
+            motion_blur.strength = strength;
+            motion_blur
+        });
+
+        Ok(Self {
+            replay,
+            camera_path,
+            camera: camera.duplicate(context),
+            turntable: None,
+            size: (width, height),
+            padded_row,
+            frame: 0,
+            directory,
+            color_texture,
+            color_view,
+            depth_view,
+            readback_buffer,
+            motion_blur,
+            main_view: MainView::new(context, sample_count, tonemapping),
+        })
+    }
+
+    /// Turns the fixed view by `step_angle` radians per simulation step, see Turntable. A recorded
+    /// camera path takes precedence.
+    pub fn with_turntable(mut self, step_angle: Option<f32>) -> Self {
+        self.turntable = step_angle.map(|angle| (self.camera.polar().y, angle));
+        self
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    // Positions of the next frame and the camera to see them from, None once the replay ended
+    pub fn next_frame(&mut self, context: &Context, transparency: &Transparency) -> io::Result<Option<Vec<[f32; 3]>>> {
+        let Some(positions) = self.replay.next_frame()? else {
+            return Ok(None);
+        };
+        let step = u64::from(self.frame) * u64::from(self.replay.interval());
+        if let Some((polar, target)) = self.camera_path.as_ref().and_then(|path| path.sample(step)) {
+            self.camera.set_polar(polar).set_target(target);
+        } else if let Some((azimuth, step_angle)) = self.turntable {
+            let mut polar = self.camera.polar();
+            polar.y = azimuth + step_angle * step as f32;
+            self.camera.set_polar(polar);
+        }
+        self.camera.update_with_aspect(context, self.size.0 as f32 / self.size.1 as f32);
+        match &mut self.motion_blur {
+            Some(motion_blur) => motion_blur.update(context, self.size, transparency),
+            None => self.main_view.update(context, self.size, transparency),
+        }
+        Ok(Some(positions))
+    }
+
+    // Renders the frame and copies it to the readback buffer. `draw_scene` records the scene
+    // draws from a camera, with motion vectors or not, and `draw_transparent` its sheer cloth.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        tonemapping: &Tonemapping,
+        transparency: &Transparency,
+        draw_scene: impl Fn(&mut wgpu::RenderPass<'_>, &wgpu::BindGroup, bool),
+        draw_transparent: impl Fn(&mut wgpu::RenderPass<'_>, &wgpu::BindGroup),
+    ) {
+        let camera_bind_group = self.camera.bind_group();
+        match &self.motion_blur {
+            Some(motion_blur) => motion_blur.render_offscreen(
+                encoder,
+                transparency,
+                |render_pass| draw_scene(render_pass, camera_bind_group, true),
+                |render_pass| draw_transparent(render_pass, camera_bind_group),
+            ),
+            None => self.main_view.render_offscreen(
+                encoder,
+                transparency,
+                |render_pass| draw_scene(render_pass, camera_bind_group, false),
+                |render_pass| draw_transparent(render_pass, camera_bind_group),
+            ),
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Batch Render Pass"),
+                // Covered by the view
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            match &self.motion_blur {
+                Some(motion_blur) => motion_blur.draw(&mut render_pass, tonemapping),
+                None => self.main_view.draw(&mut render_pass, tonemapping),
+            }
+        }
+
+        encoder.copy_texture_to_buffer(
+            self.color_texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.size.0,
+                height: self.size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // Holds the last rendered frame, rows padded to `padded_row` bytes
+    pub fn readback_buffer(&self) -> &wgpu::Buffer {
+        &self.readback_buffer
+    }
+
+    // Saves the read back frame as frame_NNNNN.png
+    pub fn save_frame(&mut self, context: &Context, bytes: &[u8]) -> io::Result<()> {
+        let (width, height) = self.size;
+        let row = width as usize * 4;
+        let mut pixels = Vec::with_capacity(row * height as usize);
+        for padded_row in bytes.chunks(self.padded_row as usize).take(height as usize) {
+            pixels.extend_from_slice(&padded_row[..row]);
+        }
+        if matches!(
+            context.format(),
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let path = self.directory.join(format!("frame_{:05}.png", self.frame));
+        image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8)
+            .map_err(io::Error::other)?;
+        self.frame += 1;
+        Ok(())
+    }
+}
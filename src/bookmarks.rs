@@ -0,0 +1,242 @@
+// Camera bookmarks: named orbit parameters, one for each of the number keys 1 to 9. The key flies
+// the camera there over TRANSITION_TIME, with shift held it jumps, with ctrl (cmd) held it saves
+// the current view instead. Kept with the exports as CSV, so the same views can be found again in
+// a later session: `slot,name,distance,theta,phi,target_x,target_y,target_z`.
+
+use std::f32::consts::{PI, TAU};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use wgpu_bootstrap::{
+    cgmath::{self, Point3},
+    egui, Context,
+};
+
+use cloth::render::OrbitCamera;
+
+const BOOKMARKS_FILE: &str = "camera_bookmarks.csv";
+
+const TRANSITION_TIME: f32 = 1.0; // s
+const KEYS: [egui::Key; 9] = [
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+];
+
+struct Bookmark {
+    name: String, // without commas, they separate the fields of the file
+    polar: Point3<f32>,
+    target: Point3<f32>,
+}
+
+// From the view when the key was pressed to the bookmark
+struct Transition {
+    from: (Point3<f32>, Point3<f32>),
+    to: (Point3<f32>, Point3<f32>),
+    elapsed: f32, // s
+}
+
+impl Transition {
+    // Eased in and out, the azimuth the short way round
+    fn sample(&self) -> (Point3<f32>, Point3<f32>) {
+        let t = (self.elapsed / TRANSITION_TIME).min(1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        let ((from_polar, from_target), (to_polar, to_target)) = (self.from, self.to);
+        let turn = (to_polar.y - from_polar.y + PI).rem_euclid(TAU) - PI;
+        let polar = cgmath::point3(
+            from_polar.x + (to_polar.x - from_polar.x) * t,
+            from_polar.y + turn * t,
+            from_polar.z + (to_polar.z - from_polar.z) * t,
+        );
+        (polar, from_target + (to_target - from_target) * t)
+    }
+}
+
+#[derive(Default)]
+pub struct CameraBookmarks {
+    slots: [Option<Bookmark>; 9], // for the keys 1 to 9
+    transition: Option<Transition>,
+    name: String, // of the next view saved
+    status: String,
+}
+
+impl CameraBookmarks {
+    // Not while a text field has the keyboard
+    pub fn keys(&mut self, ctx: &egui::Context, camera: &mut OrbitCamera, context: &Context) {
+        for (slot, key) in KEYS.into_iter().enumerate() {
+            let (pressed, modifiers) = ctx.input(|input| (input.key_pressed(key), input.modifiers));
+            if !pressed {
+                continue;
+            }
+            if modifiers.command {
+                self.save_view(slot, camera);
+            } else {
+                self.go_to(slot, camera, context, !modifiers.shift);
+            }
+        }
+    }
+
+    // Moves the camera along the transition under way, until it gets there
+    pub fn update(&mut self, delta_time: f32, camera: &mut OrbitCamera, context: &Context) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+        transition.elapsed += delta_time;
+        let (polar, target) = transition.sample();
+        camera.set_polar(polar).set_target(target).update(context);
+        if transition.elapsed >= TRANSITION_TIME {
+            self.transition = None;
+        }
+    }
+
+    // Whether a transition has the camera, dragging it would be overridden
+    pub fn is_moving(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    fn save_view(&mut self, slot: usize, camera: &OrbitCamera) {
+        let name = match self.name.trim() {
+            "" => format!("View {}", slot + 1),
+            name => name.replace(',', " "),
+        };
+        self.slots[slot] = Some(Bookmark {
+            name,
+            polar: camera.polar(),
+            target: camera.target(),
+        });
+        self.name.clear();
+    }
+
+    fn go_to(&mut self, slot: usize, camera: &mut OrbitCamera, context: &Context, smooth: bool) {
+        let Some(bookmark) = &self.slots[slot] else {
+            return;
+        };
+        if smooth {
+            self.transition = Some(Transition {
+                from: (camera.polar(), camera.target()),
+                to: (bookmark.polar, bookmark.target),
+                elapsed: 0.0,
+            });
+        } else {
+            self.transition = None;
+            camera.set_polar(bookmark.polar).set_target(bookmark.target).update(context);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, camera: &mut OrbitCamera, context: &Context, dir: &Path) {
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut self.name)
+                .on_hover_text("Of the view saved next, with ctrl and a number key or a Save button");
+        });
+        let (mut save, mut go) = (None, None);
+        egui::Grid::new("camera bookmarks").striped(true).show(ui, |ui| {
+            for (slot, bookmark) in self.slots.iter().enumerate() {
+                ui.label(format!("{}", slot + 1));
+                ui.label(bookmark.as_ref().map_or("", |bookmark| bookmark.name.as_str()));
+                if ui.button("Save").clicked() {
+                    save = Some(slot);
+                }
+                if ui
+                    .add_enabled(bookmark.is_some(), egui::Button::new("Go"))
+                    .on_hover_text("Shift jumps there")
+                    .clicked()
+                {
+                    go = Some((slot, !ui.input(|input| input.modifiers.shift)));
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(slot) = save {
+            self.save_view(slot, camera);
+        }
+        if let Some((slot, smooth)) = go {
+            self.go_to(slot, camera, context, smooth);
+        }
+        let path = dir.join(BOOKMARKS_FILE);
+        ui.horizontal(|ui| {
+            if ui.button("Save to file").clicked() {
+                self.status = match self.save(&path) {
+                    Ok(()) => format!("Saved {}", path.display()),
+                    Err(error) => format!("Could not save the bookmarks: {error}"),
+                };
+            }
+            if ui.button("Load from file").clicked() {
+                self.status = match self.load(&path) {
+                    Ok(()) => format!("Loaded {}", path.display()),
+                    Err(error) => format!("Could not load the bookmarks: {error}"),
+                };
+            }
+        });
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "slot,name,distance,theta,phi,target_x,target_y,target_z")?;
+        for (slot, bookmark) in self.slots.iter().enumerate() {
+            let Some(Bookmark { name, polar, target }) = bookmark else {
+                continue;
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                slot + 1,
+                name,
+                polar.x,
+                polar.y,
+                polar.z,
+                target.x,
+                target.y,
+                target.z
+            )?;
+        }
+        writer.flush()
+    }
+
+    // Replaces every slot, those missing from the file are emptied
+    fn load(&mut self, path: &Path) -> io::Result<()> {
+        let invalid = |line: usize| io::Error::new(io::ErrorKind::InvalidData, format!("invalid bookmark on line {}", line + 1));
+
+        let mut slots: [Option<Bookmark>; 9] = Default::default();
+        for (line_index, line) in BufReader::new(File::open(path)?).lines().enumerate().skip(1) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 8 {
+                return Err(invalid(line_index));
+            }
+            let slot = fields[0]
+                .parse::<usize>()
+                .ok()
+                .filter(|slot| (1..=slots.len()).contains(slot))
+                .ok_or_else(|| invalid(line_index))?;
+            let values = fields[2..]
+                .iter()
+                .map(|field| field.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid(line_index))?;
+            slots[slot - 1] = Some(Bookmark {
+                name: fields[1].to_owned(),
+                polar: cgmath::point3(values[0], values[1], values[2]),
+                target: cgmath::point3(values[3], values[4], values[5]),
+            });
+        }
+        self.slots = slots;
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+// Cloth builder: a rectangular cloth described in one place, its size, spacing, pins and where it
+// starts, with the fabric it is simulated in. Chained from a size with sensible defaults, so a
+// cloth can be made without spelling out every field of ClothGrid, and const so the presets of the
+// scene are builders too.
+
+use crate::material::{Material, MaterialBlend, Preset, Stiffness};
+use crate::simulation::{AnchorFrame, ClothGrid, ClothPiece, ClothSource, Orientation, Pins, Spacing};
+use crate::tearing::TearMap;
+
+const DEFAULT_SPACING: f32 = 0.004; // m
+const DEFAULT_CENTER: [f32; 3] = [0.0, 1.0, 0.0]; // above the sphere
+
+/// A rectangular cloth and its fabric, horizontal, unpinned and in cotton unless told otherwise.
+#[derive(Copy, Clone, Debug)]
+pub struct ClothBuilder {
+    grid: ClothGrid,
+    fabric: Preset,
+    // Replace those of the fabric, as a custom material
+    stiffness: Option<Stiffness>,
+    density: Option<f32>,
+}
+
+impl ClothBuilder {
+    // Panics below 2 rows or 2 columns, the spacing is measured between neighbors
+    pub const fn new(rows: u32, cols: u32) -> Self {
+        assert!(rows >= 2 && cols >= 2, "a cloth needs at least 2 rows and 2 columns");
+        Self {
+            grid: ClothGrid {
+                rows,
+                cols,
+                spacing: Spacing::Uniform(DEFAULT_SPACING),
+                center: DEFAULT_CENTER,
+                orientation: Orientation::Horizontal,
+                pins: Pins::None,
+            },
+            fabric: Preset::Cotton,
+            stiffness: None,
+            density: None,
+        }
+    }
+
+    pub const fn spacing(self, spacing: Spacing) -> Self {
+        Self {
+            grid: ClothGrid { spacing, ..self.grid },
+            ..self
+        }
+    }
+
+    // Where the center of the grid starts
+    pub const fn center(self, center: [f32; 3]) -> Self {
+        Self {
+            grid: ClothGrid { center, ..self.grid },
+            ..self
+        }
+    }
+
+    pub const fn orientation(self, orientation: Orientation) -> Self {
+        Self {
+            grid: ClothGrid { orientation, ..self.grid },
+            ..self
+        }
+    }
+
+    pub const fn pins(self, pins: Pins) -> Self {
+        Self {
+            grid: ClothGrid { pins, ..self.grid },
+            ..self
+        }
+    }
+
+    pub const fn fabric(self, fabric: Preset) -> Self {
+        Self { fabric, ..self }
+    }
+
+    pub const fn stiffness(self, stiffness: Stiffness) -> Self {
+        Self {
+            stiffness: Some(stiffness),
+            ..self
+        }
+    }
+
+    // Relative to cotton, see Material::density
+    pub const fn density(self, density: f32) -> Self {
+        Self {
+            density: Some(density),
+            ..self
+        }
+    }
+
+    pub fn grid(&self) -> ClothGrid {
+        self.grid
+    }
+
+    pub fn piece(&self, anchor_frame: AnchorFrame, tear_map: TearMap) -> ClothPiece {
+        ClothPiece {
+            source: ClothSource::Grid(self.grid),
+            anchor_frame,
+            tear_map,
+        }
+    }
+
+    // The fabric, or a custom material starting from it when the stiffness or the density is set
+    pub fn material(&self) -> MaterialBlend {
+        let mut blend = MaterialBlend::new(self.fabric);
+        if self.stiffness.is_some() || self.density.is_some() {
+            let fabric = self.fabric.material();
+            blend.custom = Some(Material {
+                stiffness: self.stiffness.unwrap_or(fabric.stiffness),
+                density: self.density.unwrap_or(fabric.density),
+                ..fabric
+            });
+        }
+        blend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{MAX_CONSTRAINTS, NO_NEIGHBOR};
+
+    #[test]
+    fn rest_state_is_a_centered_grid() {
+        let grid = ClothBuilder::new(3, 4)
+            .spacing(Spacing::Uniform(0.1))
+            .center([1.0, 2.0, 3.0])
+            .pins(Pins::TopCorners)
+            .grid();
+        let rest = grid.rest_state();
+        assert_eq!(rest.instances.len(), 12);
+        assert_eq!(rest.constraints.len(), 12 * MAX_CONSTRAINTS);
+
+        // Row after row, rows along z for a horizontal cloth
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close(rest.instances[0].position(), [0.85, 2.0, 2.9]));
+        assert!(close(rest.instances[11].position(), [1.15, 2.0, 3.1]));
+        let pinned: Vec<usize> = (0..12).filter(|&index| rest.instances[index].inverse_mass() == 0.0).collect();
+        assert_eq!(pinned, [0, 3]);
+
+        // The first particle has no left neighbor, its right one is a spacing away
+        let first = &rest.constraints[..MAX_CONSTRAINTS];
+        assert_eq!(first[0].neighbor, NO_NEIGHBOR);
+        assert_eq!(first[1].neighbor, 1);
+        assert!((first[1].rest_length - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2 rows and 2 columns")]
+    fn rejects_a_single_row() {
+        ClothBuilder::new(1, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2 rows and 2 columns")]
+    fn rejects_an_empty_grid() {
+        ClothBuilder::new(0, 0);
+    }
+}
@@ -0,0 +1,180 @@
+// Triangle meshes as colliders, e.g. furniture or scanned objects: a bounding volume hierarchy
+// built on the CPU and traversed by contacts.wgsl for the closest point to every particle.
+//
+// Buffer layout, in vec4s: a header holding the number of nodes, two per node, then three per
+// triangle. A node holds its bounds, the xyz of both vec4s, with the index of its second child or
+// of its first triangle and its number of triangles in their w, bit cast. Interior nodes have no
+// triangles and their first child right after them.
+
+use wgpu_bootstrap::cgmath::{Quaternion, Vector3};
+
+use crate::mesh::ClothMesh;
+use crate::simulation::Collider;
+
+const LEAF_SIZE: usize = 4; // triangles
+const MAX_DEPTH: usize = 32; // must match the traversal stack of contacts.wgsl
+
+struct Node {
+    min: [f32; 3],
+    max: [f32; 3],
+    index: u32, // second child or first triangle
+    count: u32, // triangles, 0 for interior nodes
+}
+
+pub struct TriangleBvh {
+    nodes: Vec<Node>,
+    triangles: Vec<[[f32; 3]; 3]>, // in the order of the leaves
+}
+
+impl TriangleBvh {
+    // Splits at the median of the centroids along the longest axis of the bounds
+    pub fn build(mesh: &ClothMesh) -> Self {
+        Self::from_triangles(mesh.triangle_corners().collect())
+    }
+
+    fn from_triangles(mut triangles: Vec<[[f32; 3]; 3]>) -> Self {
+        let mut nodes = Vec::new();
+        let count = triangles.len();
+        build_node(&mut nodes, &mut triangles, 0, count, 0);
+        Self { nodes, triangles }
+    }
+
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        self.nodes.first().map_or(([0.0; 3], [0.0; 3]), |root| (root.min, root.max))
+    }
+
+    /// The mesh placed with the origin of its space at `center`, there is one mesh per scene.
+    /// Particles are kept `thickness` away from its surface, on the side its faces point to.
+    pub fn collider(&self, center: [f32; 3], rotation: Quaternion<f32>, thickness: f32) -> Collider {
+        Collider::Mesh {
+            center,
+            rotation,
+            thickness,
+        }
+    }
+
+    pub fn to_vec4s(&self) -> Vec<[f32; 4]> {
+        let mut data = Vec::with_capacity(1 + 2 * self.nodes.len() + 3 * self.triangles.len());
+        data.push([f32::from_bits(self.nodes.len() as u32), 0.0, 0.0, 0.0]);
+        for node in &self.nodes {
+            let ([x0, y0, z0], [x1, y1, z1]) = (node.min, node.max);
+            data.push([x0, y0, z0, f32::from_bits(node.index)]);
+            data.push([x1, y1, z1, f32::from_bits(node.count)]);
+        }
+        for triangle in &self.triangles {
+            data.extend(triangle.map(|[x, y, z]| [x, y, z, 0.0]));
+        }
+        data
+    }
+}
+
+// An empty mesh, for simulations without one
+pub fn empty_vec4s() -> Vec<[f32; 4]> {
+    vec![[0.0; 4]]
+}
+
+// Appends the node of triangles[first..first + count] and its descendants, depth first
+fn build_node(nodes: &mut Vec<Node>, triangles: &mut [[[f32; 3]; 3]], first: usize, count: usize, depth: usize) {
+    let slice = &mut triangles[first..first + count];
+    let (min, max) = slice.iter().flatten().fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), corner| {
+        (
+            [0, 1, 2].map(|axis| min[axis].min(corner[axis])),
+            [0, 1, 2].map(|axis| max[axis].max(corner[axis])),
+        )
+    });
+    let index = nodes.len();
+    nodes.push(Node {
+        min,
+        max,
+        index: first as u32,
+        count: count as u32,
+    });
+    // The traversal stack holds one pending child per level
+    if count <= LEAF_SIZE || depth + 1 >= MAX_DEPTH {
+        return;
+    }
+
+    let extent = Vector3::from(max) - Vector3::from(min);
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let centroid = |triangle: &[[f32; 3]; 3]| triangle.iter().map(|corner| corner[axis]).sum::<f32>();
+    let half = count / 2;
+    slice.select_nth_unstable_by(half, |a, b| centroid(a).total_cmp(&centroid(b)));
+
+    nodes[index].count = 0;
+    build_node(nodes, triangles, first, half, depth + 1);
+    nodes[index].index = nodes.len() as u32;
+    build_node(nodes, triangles, first + half, count - half, depth + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two triangles per cell of a bumpy `size` by `size` grid
+    fn terrain(size: usize) -> Vec<[[f32; 3]; 3]> {
+        let corner = |x: usize, z: usize| [x as f32, ((x * 7 + z * 3) % 5) as f32 * 0.1, z as f32];
+        (0..size * size)
+            .flat_map(|cell| {
+                let (x, z) = (cell % size, cell / size);
+                [
+                    [corner(x, z), corner(x + 1, z), corner(x, z + 1)],
+                    [corner(x + 1, z), corner(x + 1, z + 1), corner(x, z + 1)],
+                ]
+            })
+            .collect()
+    }
+
+    fn contains(outer: &Node, min: [f32; 3], max: [f32; 3]) -> bool {
+        (0..3).all(|axis| outer.min[axis] <= min[axis] && max[axis] <= outer.max[axis])
+    }
+
+    // Checks the bounds of the subtree of `index` and returns the triangle ranges of its leaves
+    fn leaves(bvh: &TriangleBvh, index: usize) -> Vec<(u32, u32)> {
+        let node = &bvh.nodes[index];
+        if node.count > 0 {
+            for triangle in &bvh.triangles[node.index as usize..(node.index + node.count) as usize] {
+                for &corner in triangle {
+                    assert!(contains(node, corner, corner), "node {index} misses a corner");
+                }
+            }
+            return vec![(node.index, node.count)];
+        }
+        let children = [index + 1, node.index as usize];
+        for child in children {
+            let child_node = &bvh.nodes[child];
+            assert!(contains(node, child_node.min, child_node.max), "node {index} misses child {child}");
+        }
+        children.into_iter().flat_map(|child| leaves(bvh, child)).collect()
+    }
+
+    #[test]
+    fn every_triangle_is_in_one_leaf_within_the_bounds() {
+        let triangles = terrain(12);
+        let bvh = TriangleBvh::from_triangles(triangles.clone());
+        assert!(bvh.nodes.len() > 1);
+
+        // The leaves cover the reordered triangles once, one range after the other
+        let mut ranges = leaves(&bvh, 0);
+        ranges.sort_unstable();
+        let mut next = 0;
+        for (first, count) in ranges {
+            assert_eq!(first, next, "a leaf overlaps or skips triangles");
+            next += count;
+        }
+        assert_eq!(next as usize, triangles.len());
+
+        // Reordered, not altered
+        let key = |triangle: &[[f32; 3]; 3]| triangle.map(|corner| corner.map(f32::to_bits));
+        let mut expected: Vec<_> = triangles.iter().map(key).collect();
+        let mut built: Vec<_> = bvh.triangles.iter().map(key).collect();
+        expected.sort_unstable();
+        built.sort_unstable();
+        assert_eq!(built, expected);
+    }
+}
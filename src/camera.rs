@@ -0,0 +1,327 @@
+// Orbit camera with the same interface as the one from wgpu_bootstrap, but with a readable state
+// so views can be exported, restored and interpolated. It also flies, see CameraMode::Fly, over
+// the same state so switching keeps the view.
+
+use wgpu_bootstrap::{
+    cgmath::{self, InnerSpace, Matrix4, Point3, Vector3},
+    egui,
+    wgpu::{self, util::DeviceExt},
+    Context,
+};
+
+#[rustfmt::skip]
+pub(crate) const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+const ROTATE_SPEED: f32 = 0.01; // radians per pixel
+const ZOOM_SPEED: f32 = 0.002;
+const MIN_DISTANCE: f32 = 0.1;
+const TOP_ELEVATION: f32 = std::f32::consts::FRAC_PI_2 - 0.01; // radians, straight up has no up vector
+const FRAME_MARGIN: f32 = 1.1; // of the framed box, over the radius of its bounding sphere
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Orbit, // drag to turn around the target, scroll to zoom
+    // Drag to look around from where the camera stands, scroll to go forward, moved with fly(). The
+    // target stays ahead at the same distance, so orbiting again turns around what is in front.
+    Fly,
+}
+
+impl CameraMode {
+    pub const ALL: [CameraMode; 2] = [CameraMode::Orbit, CameraMode::Fly];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "Orbit",
+            CameraMode::Fly => "Fly",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    // As wide at the target as the perspective, so switching keeps the cloth the same size there.
+    // Zooming widens or narrows it.
+    Orthographic,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 2] = [Projection::Perspective, Projection::Orthographic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Perspective => "Perspective",
+            Projection::Orthographic => "Orthographic",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    view: [[f32; 4]; 4],
+    proj: [[f32; 4]; 4],
+}
+
+impl CameraUniform {
+    // For cameras other than the orbit one, `proj` already maps depth to wgpu's 0..1
+    pub fn new(view: Matrix4<f32>, proj: Matrix4<f32>) -> Self {
+        Self {
+            view: view.into(),
+            proj: proj.into(),
+        }
+    }
+
+    pub fn desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            label: Some("Camera Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        }
+    }
+}
+
+pub struct OrbitCamera {
+    polar: Point3<f32>, // distance, azimuth (theta) and elevation (phi), angles in radians
+    target: Point3<f32>,
+    fovy: f32, // degrees
+    projection: Projection,
+    mode: CameraMode,
+    aspect: f32,
+    znear: f32,
+    zfar: f32,
+    jitter: [f32; 2], // offset of the projection in normalized device coordinates
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl OrbitCamera {
+    pub fn new(context: &Context, fovy: f32, aspect: f32, znear: f32, zfar: f32) -> Self {
+        let buffer = context
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::bytes_of(&CameraUniform {
+                    view: Matrix4::from_scale(1.0).into(),
+                    proj: Matrix4::from_scale(1.0).into(),
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let layout = context
+            .device()
+            .create_bind_group_layout(&CameraUniform::desc());
+
+        let bind_group = context
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Camera Bind Group"),
+                layout: &layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+
+        Self {
+            polar: cgmath::point3(1.0, 0.0, 0.0),
+            target: cgmath::point3(0.0, 0.0, 0.0),
+            fovy,
+            projection: Projection::Perspective,
+            mode: CameraMode::Orbit,
+            aspect,
+            znear,
+            zfar,
+            jitter: [0.0, 0.0],
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn polar(&self) -> Point3<f32> {
+        self.polar
+    }
+
+    pub fn set_polar(&mut self, polar: Point3<f32>) -> &mut Self {
+        self.polar = cgmath::point3(
+            polar.x.max(MIN_DISTANCE),
+            polar.y,
+            polar.z.clamp(-TOP_ELEVATION, TOP_ELEVATION),
+        );
+        self
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Point3<f32>) -> &mut Self {
+        self.target = target;
+        self
+    }
+
+    // Same projection and view, with its own buffer, for views rendered with a different jitter
+    pub fn duplicate(&self, context: &Context) -> Self {
+        let mut camera = Self::new(context, self.fovy, self.aspect, self.znear, self.zfar);
+        camera
+            .set_polar(self.polar)
+            .set_target(self.target)
+            .set_projection(self.projection);
+        camera
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn set_jitter(&mut self, jitter: [f32; 2]) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.target + self.offset()
+    }
+
+    // From the target to the eye
+    fn offset(&self) -> Vector3<f32> {
+        let (distance, theta, phi) = (self.polar.x, self.polar.y, self.polar.z);
+        cgmath::vec3(
+            distance * phi.cos() * theta.cos(),
+            distance * phi.sin(),
+            distance * phi.cos() * theta.sin(),
+        )
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.eye(), self.target, cgmath::Vector3::unit_y())
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        let projection = match self.projection {
+            Projection::Perspective => cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar),
+            Projection::Orthographic => {
+                let half_height = self.polar.x * (0.5 * self.fovy).to_radians().tan();
+                let half_width = half_height * self.aspect;
+                cgmath::ortho(-half_width, half_width, -half_height, half_height, self.znear, self.zfar)
+            }
+        };
+        Matrix4::from_translation(cgmath::vec3(self.jitter[0], self.jitter[1], 0.0)) * OPENGL_TO_WGPU_MATRIX * projection
+    }
+
+    // Drag to orbit or look around, scroll to zoom or go forward, see CameraMode
+    pub fn input(&mut self, input: &egui::InputState, context: &Context) {
+        let mut polar = self.polar;
+        if input.pointer.primary_down() {
+            let delta = input.pointer.delta();
+            polar.y += delta.x * ROTATE_SPEED;
+            polar.z += delta.y * ROTATE_SPEED;
+        }
+        match self.mode {
+            CameraMode::Orbit => {
+                polar.x *= 1.0 - input.smooth_scroll_delta.y * ZOOM_SPEED;
+                self.set_polar(polar);
+            }
+            CameraMode::Fly => {
+                // Turns around the eye rather than the target
+                let eye = self.eye();
+                self.set_polar(polar);
+                self.target = eye - self.offset();
+                let forward = input.smooth_scroll_delta.y * ZOOM_SPEED * self.polar.x;
+                self.target -= self.offset().normalize() * forward;
+            }
+        }
+        self.update(context);
+    }
+
+    // Looks at the center of the box between `min` and `max` from far enough for all of it to fit
+    // in the view, from the same angles
+    pub fn frame(&mut self, min: [f32; 3], max: [f32; 3], context: &Context) {
+        let (min, max) = (Point3::from(min), Point3::from(max));
+        let radius = 0.5 * (max - min).magnitude() * FRAME_MARGIN;
+        let half_fovy = (0.5 * self.fovy).to_radians();
+        let half_fovx = (half_fovy.tan() * self.aspect).atan();
+        let distance = radius / half_fovy.min(half_fovx).sin();
+        self.target = min + (max - min) * 0.5;
+        self.set_polar(cgmath::point3(distance, self.polar.y, self.polar.z)).update(context);
+    }
+
+    // Moves the eye and the target together by `offset`, in m along the right, up and backward
+    // axes of the view, whatever the mode
+    pub fn fly(&mut self, offset: Vector3<f32>, context: &Context) {
+        let backward = self.offset().normalize();
+        let right = Vector3::unit_y().cross(backward).normalize();
+        let up = backward.cross(right);
+        self.target += right * offset.x + up * offset.y + backward * offset.z;
+        self.update(context);
+    }
+
+    // Mode, projection and a button to look straight down, applied by the next update
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Camera")
+                .selected_text(self.mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in CameraMode::ALL {
+                        ui.selectable_value(&mut self.mode, mode, mode.name());
+                    }
+                });
+        })
+        .response
+        .on_hover_text("Fly: drag to look around, WASD to move, Q and E to go down and up, scroll to go forward");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Projection")
+                .selected_text(self.projection.name())
+                .show_ui(ui, |ui| {
+                    for projection in Projection::ALL {
+                        ui.selectable_value(&mut self.projection, projection, projection.name());
+                    }
+                });
+            if ui.button("Top view").on_hover_text("Looks straight down at the target").clicked() {
+                self.polar.z = TOP_ELEVATION;
+            }
+        });
+    }
+
+    pub fn update(&mut self, context: &Context) {
+        self.update_with_aspect(context, context.size().x / context.size().y);
+    }
+
+    // For cameras that render to a target other than the window
+    pub fn update_with_aspect(&mut self, context: &Context, aspect: f32) {
+        self.aspect = aspect;
+        let uniform = CameraUniform {
+            view: self.view_matrix().into(),
+            proj: self.projection_matrix().into(),
+        };
+        context
+            .queue()
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
@@ -0,0 +1,74 @@
+// Saves the particle state and restores it later from step hooks, like a game checkpoint.
+
+use std::sync::{Arc, Mutex};
+
+use wgpu_bootstrap::wgpu;
+
+use cloth::scene::Scene;
+
+#[derive(Default)]
+struct CheckpointState {
+    save_requested: bool,
+    restore_requested: bool,
+    buffer: Option<wgpu::Buffer>,
+    saved_step: Option<u64>,
+}
+
+pub struct Checkpoint {
+    state: Arc<Mutex<CheckpointState>>,
+}
+
+impl Checkpoint {
+    // The hooks stay registered for the lifetime of the scene
+    pub fn install(scene: &mut Scene) -> Self {
+        let state = Arc::new(Mutex::new(CheckpointState::default()));
+
+        // Copied after the step so the checkpoint holds a complete state
+        let post_state = state.clone();
+        scene.on_post_step(move |handle| {
+            let mut state = post_state.lock().unwrap();
+            if !std::mem::take(&mut state.save_requested) {
+                return;
+            }
+            let buffer = handle.context.device().create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Checkpoint Buffer"),
+                size: handle.particles.size(),
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            handle.encoder.copy_buffer_to_buffer(handle.particles, 0, &buffer, 0, buffer.size());
+            state.buffer = Some(buffer);
+            state.saved_step = Some(handle.step);
+        });
+
+        // Written back before the step, which then starts from the checkpoint
+        let pre_state = state.clone();
+        scene.on_pre_step(move |handle| {
+            let mut state = pre_state.lock().unwrap();
+            if !std::mem::take(&mut state.restore_requested) {
+                return;
+            }
+            match &state.buffer {
+                Some(buffer) if buffer.size() == handle.particles.size() => {
+                    handle.encoder.copy_buffer_to_buffer(buffer, 0, handle.particles, 0, buffer.size());
+                }
+                // Saved from a cloth that has been rebuilt since
+                _ => log::warn!("No checkpoint matches the current cloth"),
+            }
+        });
+
+        Self { state }
+    }
+
+    pub fn request_save(&self) {
+        self.state.lock().unwrap().save_requested = true;
+    }
+
+    pub fn request_restore(&self) {
+        self.state.lock().unwrap().restore_requested = true;
+    }
+
+    pub fn saved_step(&self) -> Option<u64> {
+        self.state.lock().unwrap().saved_step
+    }
+}
@@ -0,0 +1,180 @@
+// Checksum of the particle state, computed on the GPU once a frame has stepped, see checksum.wgsl.
+// Two runs that should match, a replay and the live run it was recorded from or two mirrored
+// instances, compare their checksums step by step and a desync shows on the step it happens.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::shaders::create_compute_module;
+
+pub const CHECKSUM_FILE: &str = "checksums.csv"; // in EXPORT_DIR
+
+pub struct StateChecksum {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    buffer: TrackedBuffer,
+}
+
+impl StateChecksum {
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        workgroup_size: u32,
+    ) -> Self {
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Checksum Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Checksum Buffer"),
+            size: std::mem::size_of::<[u32; 2]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Checksum Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Checksum Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Checksum Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &create_compute_module(context, "Checksum Shader", include_str!("checksum.wgsl"), workgroup_size),
+                entry_point: "checksum_state",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group,
+            buffer,
+        }
+    }
+
+    // Recomputed from scratch from the first binding of `bind_group`, the latest state
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup, workgroups: u32) {
+        encoder.clear_buffer(&self.buffer, 0, None);
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Checksum Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+
+    // Two u32, see checksum() for the value they make
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+// The sum in the high half, the xor in the low one
+pub fn checksum(words: [u32; 2]) -> u64 {
+    (u64::from(words[0]) << 32) | u64::from(words[1])
+}
+
+/// Checksums of a run since the cloth was dropped, compared with those of a reference run
+/// loaded from a file written by another run. Stored as CSV: `step,checksum` in hex.
+#[derive(Default)]
+pub struct DesyncMonitor {
+    pub enabled: bool,
+    checksums: BTreeMap<u64, u64>,
+    reference: Option<BTreeMap<u64, u64>>,
+    first_desync: Option<u64>, // step
+}
+
+impl DesyncMonitor {
+    // Only the steps both runs have are compared, e.g. those of the frames of a replay
+    pub fn record(&mut self, step: u64, checksum: u64) {
+        self.checksums.insert(step, checksum);
+        let Some(&expected) = self.reference.as_ref().and_then(|reference| reference.get(&step)) else {
+            return;
+        };
+        if expected != checksum && self.first_desync.is_none() {
+            log::warn!("Desync at step {step}, checksum {checksum:016x} where the reference has {expected:016x}");
+            self.first_desync = Some(step);
+        }
+    }
+
+    // Step and checksum
+    pub fn latest(&self) -> Option<(u64, u64)> {
+        self.checksums.last_key_value().map(|(&step, &checksum)| (step, checksum))
+    }
+
+    pub fn first_desync(&self) -> Option<u64> {
+        self.first_desync
+    }
+
+    pub fn has_reference(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    // Starts over with the run, the reference is kept
+    pub fn clear(&mut self) {
+        self.checksums.clear();
+        self.first_desync = None;
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "step,checksum")?;
+        for (step, checksum) in &self.checksums {
+            writeln!(writer, "{step},{checksum:016x}")?;
+        }
+        writer.flush()
+    }
+
+    // The steps already recorded are checked again
+    pub fn load_reference(&mut self, path: &Path) -> io::Result<()> {
+        let invalid = |line: usize| io::Error::new(io::ErrorKind::InvalidData, format!("invalid checksum on line {}", line + 1));
+
+        let mut reference = BTreeMap::new();
+        for (line_index, line) in BufReader::new(File::open(path)?).lines().enumerate().skip(1) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (step, checksum) = line.split_once(',').ok_or_else(|| invalid(line_index))?;
+            let step = step.trim().parse().map_err(|_| invalid(line_index))?;
+            let checksum = u64::from_str_radix(checksum.trim(), 16).map_err(|_| invalid(line_index))?;
+            reference.insert(step, checksum);
+        }
+        self.reference = Some(reference);
+
+        self.first_desync = None;
+        for (step, checksum) in std::mem::take(&mut self.checksums) {
+            self.record(step, checksum);
+        }
+        Ok(())
+    }
+}
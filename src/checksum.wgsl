@@ -0,0 +1,32 @@
+// checksum.wgsl
+// State checksum appended to compute.wgsl, run on the latest state once a frame has stepped.
+//
+// Every particle hashes the bits of its position along with its index, and the hashes are summed
+// into one u32 and xored into another. Neither depends on the order the invocations run in, so
+// two runs agree on the checksum when they agree bit for bit on the positions. The speeds are
+// left out, a replay doesn't have them.
+
+@group(1) @binding(0) var<storage, read_write> checksum: array<atomic<u32>, 2>;
+
+// PCG hash
+fn pcg(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn checksum_state(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+
+    let bits = bitcast<vec3<u32>>(instances_ping[index].position.xyz);
+    var hash = pcg(index);
+    for (var i = 0u; i < 3u; i++) {
+        hash = pcg(hash ^ bits[i]);
+    }
+    atomicAdd(&checksum[0], hash);
+    atomicXor(&checksum[1], pcg(hash));
+}
@@ -3,61 +3,361 @@
 
 
 struct Instance {
-    position: vec4<f32>,
-    speed: vec4<f32>,
+    position: vec4<f32>, // w is the inverse mass, 0.0 for pinned particles
+    speed: vec4<f32>, // w is the index of the material of the particle's cloth
+    previous: vec4<f32>, // w counts the substeps the particle has been still for, see is_asleep()
 };
 
 // Instance storage buffers
 @group(0) @binding(0) var<storage, read_write> instances_ping: array<Instance>;
 @group(0) @binding(1) var<storage, read_write> instances_pong: array<Instance>;
 
-struct TimeUniform {
-    generation_duration: f32,
+struct SimParams {
+    delta_time: f32,
+    relaxation: f32,
+    spring_constant: f32, // implicit solver only, per unit of mass
+    iterations: u32, // per substep
+    substeps: u32, // per frame, delta_time is already divided by it
+    num_particles: u32, // rows * cols, the last workgroup is partially used
+    num_anchors: u32,
+    num_colliders: u32,
+    num_attachments: u32,
+    gravity_scale: f32, // from 0 to 1 while gravity ramps up after a drop
+    max_speed: f32, // m/s, enforced by the guard pass
+    static_friction: f32, // of the sphere surface, scaling the friction of the fabric
+    dynamic_friction: f32, // same
+    num_fans: u32,
+    // m, the colliders are kept half of it away from the particles, other layers all of it
+    thickness: f32,
+    sleep_speed: f32, // m/s, below which a particle counts as still
+    sleep_substeps: u32, // still substeps until it falls asleep, 0 never
 };
 
-@group(0) @binding(2) var<uniform> time: TimeUniform;
+@group(0) @binding(2) var<uniform> params: SimParams;
+
+// Must match the Rust side Constraint struct
+struct Constraint {
+    neighbor: u32,
+    kind: u32,
+    rest_length: f32,
+    tear_strain: f32, // 0.0 never tears
+};
+
+@group(0) @binding(3) var<storage, read_write> constraints: array<Constraint>;
+
+const MAX_CONSTRAINTS: u32 = 12u;
+const NO_NEIGHBOR: u32 = 0xffffffffu;
+
+// Must match MAX_NODES on the Rust side
+const MAX_NODES: u32 = 16u;
+const MAX_ATTACHMENTS: u32 = 32u;
+const MAX_FANS: u32 = 8u;
+const MAX_PIN_GROUPS: u32 = 16u;
+
+// Same as the Rust side, the solver works with unit masses
+const PARTICLE_MASS: f32 = 2.0e-5; // kg
+
+// A particle pulled towards a target placed on the CPU
+struct Attachment {
+    target_position: vec4<f32>, // w is the stiffness
+    particle: u32,
+};
+
+// Blows the air along its axis within a cone, must match the Rust side FanBlock struct
+struct Fan {
+    position: vec4<f32>, // w is the speed of the air leaving it
+    direction: vec4<f32>, // w is the cosine of the cone angle
+    range: f32,
+    falloff: f32, // exponent of the decrease of the speed with the distance
+};
+
+// World transforms of the transform hierarchy, posed on the CPU every frame
+struct Rig {
+    nodes: array<mat4x4<f32>, MAX_NODES>,
+    attachments: array<Attachment, MAX_ATTACHMENTS>,
+    fans: array<Fan, MAX_FANS>,
+    pin_groups: array<vec4<f32>, MAX_PIN_GROUPS>, // compliance in m/N, break force in N
+};
+
+@group(0) @binding(4) var<uniform> rig: Rig;
+
+// A pinned particle that follows a node
+struct Anchor {
+    offset: vec4<f32>, // in the space of the node
+    particle: u32,
+    node: u32,
+    group: u32,
+    broken: u32, // the particle is left free once set
+};
+
+@group(0) @binding(5) var<storage, read_write> anchors: array<Anchor>;
+
+// Compliance and break force of the pins of an anchor, zero for rigid ones
+fn pin_group_of(anchor: Anchor) -> vec4<f32> {
+    if (anchor.group >= MAX_PIN_GROUPS) {
+        return vec4<f32>(0.0);
+    }
+    return rig.pin_groups[anchor.group];
+}
+
+fn anchor_target(anchor: Anchor) -> vec3<f32> {
+    return (rig.nodes[anchor.node] * vec4<f32>(anchor.offset.xyz, 1.0)).xyz;
+}
+
+// Fabric of one cloth, must match the Rust side MaterialBlock struct
+struct ClothMaterial {
+    stiffness: vec4<f32>, // horizontal, vertical, shear, bend
+    linear_drag: f32,
+    quadratic_drag: f32,
+    density: f32, // relative to the default fabric, scales the mass of the particles
+    friction: f32, // against the colliders
+    far: u32, // 1 for pieces far from the camera, they get fewer iterations
+};
+
+@group(0) @binding(6) var<storage, read> materials: array<ClothMaterial>;
+
+fn material_of(instance: Instance) -> ClothMaterial {
+    return materials[u32(instance.speed.w)];
+}
 
 // Gravity constant (downward acceleration)
 const GRAVITY: f32 = -9.8; // m/s² (adjust as needed)
 
-//Ground level
-const GROUND_LEVEL: f32 = 0.0; // Define the ground level
+// The wind fades out over that fraction of the cone towards its edge
+const FAN_EDGE: f32 = 0.2;
+
+// Speed of the air the fans blow at `position`, must match Fan::wind_at() on the Rust side
+fn wind_at(position: vec3<f32>) -> vec3<f32> {
+    var wind = vec3<f32>(0.0);
+    for (var i = 0u; i < params.num_fans; i++) {
+        let fan = rig.fans[i];
+        let offset = position - fan.position.xyz;
+        let distance = length(offset);
+        let along = dot(offset, fan.direction.xyz);
+        if (distance >= fan.range || along <= 0.0) {
+            continue;
+        }
+        let cos_edge = fan.direction.w;
+        let edge = smoothstep(cos_edge, cos_edge + (1.0 - cos_edge) * FAN_EDGE, along / distance);
+        wind += fan.direction.xyz * fan.position.w * edge * pow(1.0 - distance / fan.range, fan.falloff);
+    }
+    return wind;
+}
+
+// Air drag relative to the wind at `position`, integrated implicitly so large coefficients can't
+// flip the speed
+fn apply_drag(speed: vec3<f32>, position: vec3<f32>, material: ClothMaterial, inverse_mass: f32) -> vec3<f32> {
+    let wind = wind_at(position);
+    let relative = speed - wind;
+    let drag = material.linear_drag + material.quadratic_drag * length(relative);
+    return wind + relative / (1.0 + drag * inverse_mass / material.density * params.delta_time);
+}
+
+// Moves the rigidly pinned particles to their node, in place. Elastic and broken pins leave their
+// particle free, with the mass of a free one.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn apply_anchors(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= params.num_anchors) {
+        return;
+    }
+    let anchor = anchors[global_id.x];
+    if (anchor.broken != 0u || pin_group_of(anchor).x > 0.0) {
+        instances_ping[anchor.particle].position.w = 1.0;
+        return;
+    }
+    instances_ping[anchor.particle].position = vec4<f32>(anchor_target(anchor), 0.0);
+}
+
+// Pulls the particles of the elastic pins towards their node, in place, as an XPBD constraint of
+// zero rest length. The pin breaks instead once the stretch left takes more than its break force.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn pull_elastic_anchors(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= params.num_anchors) {
+        return;
+    }
+    let anchor = anchors[global_id.x];
+    let group = pin_group_of(anchor);
+    let compliance = group.x;
+    if (anchor.broken != 0u || compliance <= 0.0) {
+        return;
+    }
+    let position = instances_ping[anchor.particle].position;
+    let target_position = anchor_target(anchor);
+    // Compliance over the squared time step, in units of the inverse mass of the solver
+    let mass = PARTICLE_MASS * material_of(instances_ping[anchor.particle]).density;
+    let alpha = compliance * mass / (params.delta_time * params.delta_time);
+    let stretch = (position.xyz - target_position) * alpha / (position.w + alpha);
+    if (group.y > 0.0 && length(stretch) / compliance > group.y) {
+        anchors[global_id.x].broken = 1u;
+        return;
+    }
+    instances_ping[anchor.particle].position = vec4<f32>(target_position + stretch, position.w);
+}
+
+// Pulls the attached particles towards their target, in place, pinned ones stay with their anchor
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn apply_attachments(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= params.num_attachments) {
+        return;
+    }
+    let attachment = rig.attachments[global_id.x];
+    let position = instances_ping[attachment.particle].position;
+    if (position.w > 0.0) {
+        let target_position = attachment.target_position;
+        instances_ping[attachment.particle].position = vec4<f32>(mix(position.xyz, target_position.xyz, target_position.w), position.w);
+    }
+}
+
+// Pinned particles never sleep, their anchors move them without any speed
+fn is_asleep(instance: Instance) -> bool {
+    return params.sleep_substeps > 0u && instance.position.w > 0.0 && instance.previous.w >= f32(params.sleep_substeps);
+}
 
-// Compute shader entry point
+// First pass: apply gravity and predict the new positions, sleeping particles stay put
 @compute @workgroup_size(WORKGROUP_SIZE)
-fn computeMain(@builtin(global_invocation_id) global_id: vec3<u32>) {
+fn integrate(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
     var instance = instances_ping[index];
+    instance.previous = vec4<f32>(instance.position.xyz, instance.previous.w);
+
+    if (is_asleep(instance)) {
+        instance.speed = vec4<f32>(0.0, 0.0, 0.0, instance.speed.w);
+    } else if (instance.position.w > 0.0) {
+        // Update velocity (using real physics equations)
+        instance.speed.y += GRAVITY * params.gravity_scale * params.delta_time;
+
+        let speed = apply_drag(instance.speed.xyz, instance.position.xyz, material_of(instance), instance.position.w);
+        instance.speed = vec4<f32>(speed, instance.speed.w);
+
+        // Predict position (using real physics equations)
+        instance.position = vec4<f32>(instance.position.xyz + instance.speed.xyz * params.delta_time, instance.position.w);
+    }
+
+    instances_pong[index] = instance;
+}
+
+// A particle moved by the corrections of its own constraints, and the largest violation among
+// them, scaled by the stiffness of each
+struct Projection {
+    instance: Instance,
+    residual: f32,
+};
+
+fn neighbors_asleep(index: u32) -> bool {
+    for (var slot = index * MAX_CONSTRAINTS; slot < (index + 1u) * MAX_CONSTRAINTS; slot++) {
+        let neighbor = constraints[slot].neighbor;
+        if (neighbor != NO_NEIGHBOR && !is_asleep(instances_ping[neighbor])) {
+            return false;
+        }
+    }
+    return true;
+}
+
+fn project_constraints(index: u32) -> Projection {
+    var instance = instances_ping[index];
+    let inverse_mass = instance.position.w;
+    let stiffness = material_of(instance).stiffness;
+
+    var correction = vec3<f32>(0.0, 0.0, 0.0);
+    var count = 0.0;
+    var residual = 0.0;
+
+    // A sleeping particle only moves once one of its neighbors pulls it, which wakes it
+    if (inverse_mass > 0.0 && !(is_asleep(instance) && neighbors_asleep(index))) {
+        for (var slot = 0u; slot < MAX_CONSTRAINTS; slot++) {
+            let constraint = constraints[index * MAX_CONSTRAINTS + slot];
+            if (constraint.neighbor == NO_NEIGHBOR) {
+                continue;
+            }
+
+            let other = instances_ping[constraint.neighbor].position;
+            let delta = instance.position.xyz - other.xyz;
+            let distance = length(delta);
+            if (distance < 1e-6) {
+                continue;
+            }
+
+            let weight = inverse_mass / (inverse_mass + other.w);
+            let stretch = distance - constraint.rest_length;
+            correction -= stiffness[constraint.kind] * weight * stretch * delta / distance;
+            count += 1.0;
+            residual = max(residual, stiffness[constraint.kind] * abs(stretch));
+        }
+    }
+
+    if (count > 0.0) {
+        instance.position = vec4<f32>(instance.position.xyz + correction * params.relaxation / count, inverse_mass);
+    }
 
-    // Since we're updating more frequently, we use the actual delta time
-    let delta_time = time.generation_duration;
+    return Projection(instance, residual);
+}
 
-    // Update velocity (using real physics equations)
-    instance.speed[1] += GRAVITY * 0.016;
+// Breaks the constraints of a particle stretched past their tear strain, on the latest state. The
+// particles at both ends see the same distance, so both of their slots break together.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn tear_constraints(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let position = instances_ping[index].position.xyz;
+    for (var slot = index * MAX_CONSTRAINTS; slot < (index + 1u) * MAX_CONSTRAINTS; slot++) {
+        let constraint = constraints[slot];
+        if (constraint.neighbor == NO_NEIGHBOR || constraint.tear_strain <= 0.0) {
+            continue;
+        }
+        let distance = length(position - instances_ping[constraint.neighbor].position.xyz);
+        if (distance > constraint.rest_length * (1.0 + constraint.tear_strain)) {
+            constraints[slot].neighbor = NO_NEIGHBOR;
+        }
+    }
+}
 
-    // Update position (using real physics equations)
-    instance.position[0] += instance.speed[0] * 0.016;
-    instance.position[1] += instance.speed[1] * 0.016;
-    instance.position[2] += instance.speed[2] * 0.016;
+// Jacobi iteration: every particle gathers the corrections of its own constraints
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn solve_constraints(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    instances_pong[index] = project_constraints(index).instance;
+}
 
-    // Sphere collision check (adjusted for more realistic behavior)
-    let distance = length(instance.position.xyz);
-    let sphere_radius = 0.3;
-    
-    if (distance < sphere_radius) {
-        // Move the point back to the surface of the sphere
-        let normal = normalize(instance.position.xyz);
-        instance.position.x = normal.x * sphere_radius;
-        instance.position.y = normal.y * sphere_radius;
-        instance.position.z = normal.z * sphere_radius;
+// Jacobi iteration past the iterations of the far pieces, their particles are carried over as
+// they are
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn solve_near_constraints(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let instance = instances_ping[index];
+    if (material_of(instance).far != 0u) {
+        instances_pong[index] = instance;
+        return;
+    }
+    instances_pong[index] = project_constraints(index).instance;
+}
+
+// Last pass: derive the speed from the corrected positions, collisions are handled by contacts.wgsl.
+// A particle moving faster than sleep_speed wakes up and starts counting again.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn finalize(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var instance = instances_ping[index];
 
-        // Calculate reflection with energy loss (add damping)
-        let damping = 0.8; // 0.8 = 80% energy preservation
-        let dot_product = dot(instance.speed.xyz, normal);
-        instance.speed.x = (instance.speed.x - 2.0 * dot_product * normal.x) * damping;
-        instance.speed.y = (instance.speed.y - 2.0 * dot_product * normal.y) * damping;
-        instance.speed.z = (instance.speed.z - 2.0 * dot_product * normal.z) * damping;
+    instance.speed = vec4<f32>((instance.position.xyz - instance.previous.xyz) / params.delta_time, instance.speed.w);
+    var still = 0.0;
+    if (length(instance.speed.xyz) < params.sleep_speed) {
+        still = min(instance.previous.w + 1.0, f32(params.sleep_substeps));
     }
+    instance.previous.w = still;
 
     instances_pong[index] = instance;
-}
\ No newline at end of file
+}
@@ -0,0 +1,54 @@
+// contact_shader.wgsl
+// Contact overlay: a line along the normal from the contact point of every particle that touched
+// a collider in the last substep, see contact_markers of contacts.wgsl.
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+const NORMAL_LENGTH: f32 = 0.02; // m
+
+// One instance per particle, drawn as a line list of two vertices
+struct ContactInput {
+    @location(0) position: vec4<f32>, // w is 0.0 without a contact
+    @location(1) normal: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, contact: ContactInput) -> VertexOutput {
+    var out: VertexOutput;
+    // Red at the contact point, yellow at the tip of the normal
+    let tip = f32(vertex);
+    out.color = mix(vec3<f32>(1.0, 0.1, 0.1), vec3<f32>(1.0, 0.9, 0.1), tip);
+    if (contact.position.w == 0.0) {
+        // Outside of the clip volume, the line is dropped
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+    let position = contact.position.xyz + contact.normal.xyz * NORMAL_LENGTH * tip;
+    out.clip_position = camera.proj * camera.view * vec4<f32>(position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
+
+struct MotionOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+// The markers aren't blurred
+@fragment
+fn fs_motion(in: VertexOutput) -> MotionOutput {
+    return MotionOutput(vec4<f32>(in.color, 1.0), vec2<f32>(0.0, 0.0));
+}
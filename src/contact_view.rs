@@ -0,0 +1,111 @@
+// Debug overlay of the contacts of the last substep, drawn over the scene to tell sticking from
+// tunneling: every particle touching a collider gets a short line along the normal of the surface.
+
+use wgpu_bootstrap::{wgpu, Context};
+
+use crate::simulation::{ClothSimulation, CONTACT_MARKER_SIZE};
+
+pub struct ContactView {
+    pub enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    motion_pipeline: wgpu::RenderPipeline, // also fills the velocity target of the motion blur
+}
+
+impl ContactView {
+    // `targets` are the color target, then the velocity one of the motion blur, drawn with
+    // `sample_count` samples like the rest of the scene
+    pub fn new(
+        context: &Context,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        targets: [Option<wgpu::ColorTargetState>; 2],
+        sample_count: u32,
+    ) -> Self {
+        let shader = context.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Contact Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("contact_shader.wgsl").into()),
+        });
+        let layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Contact Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, fragment_entry: &str, targets: &[Option<wgpu::ColorTargetState>]| {
+            context
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[wgpu::VertexBufferLayout {
+                            array_stride: CONTACT_MARKER_SIZE as wgpu::BufferAddress,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &[
+                                wgpu::VertexAttribute {
+                                    offset: 0,
+                                    shader_location: 0,
+                                    format: wgpu::VertexFormat::Float32x4,
+                                },
+                                wgpu::VertexAttribute {
+                                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                                    shader_location: 1,
+                                    format: wgpu::VertexFormat::Float32x4,
+                                },
+                            ],
+                        }],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: fragment_entry,
+                        targets,
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::LineList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: None,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    // Hidden behind the cloth and the colliders like the rest of the scene, without
+                    // hiding them in turn
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: context.depth_stencil_format(),
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::LessEqual,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                })
+        };
+
+        let [color_target, velocity_target] = targets;
+        Self {
+            enabled: false,
+            pipeline: create_pipeline("Contact Render Pipeline", "fs_main", std::slice::from_ref(&color_target)),
+            motion_pipeline: create_pipeline("Motion Contact Render Pipeline", "fs_motion", &[color_target, velocity_target]),
+        }
+    }
+
+    // After the scene, with the camera bind group already set
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, cloth: &ClothSimulation, motion_vectors: bool) {
+        if !self.enabled {
+            return;
+        }
+        render_pass.set_pipeline(if motion_vectors { &self.motion_pipeline } else { &self.pipeline });
+        render_pass.set_vertex_buffer(0, cloth.contact_buffer().slice(..));
+        render_pass.draw(0..2, 0..cloth.num_instances());
+    }
+}
@@ -0,0 +1,483 @@
+// contacts.wgsl
+// Collisions with the colliders, appended to compute.wgsl and run in place on the latest state
+// once the solver is done with a substep.
+//
+// The first collider can be a rigid body pushed around by the cloth: the momentum every particle
+// loses against it is added to it with atomics, then a single invocation integrates its motion.
+
+// Must match the Rust side SphereBody struct
+struct SphereBody {
+    center: vec4<f32>,
+    velocity: vec4<f32>, // w is the inverse mass in particle masses, 0.0 while the rig moves it
+    floor: f32, // lowest height of the center, the sphere rests on the ground there
+    damping: f32, // rolling resistance, 1/s
+    impulse: array<atomic<i32>, 3>, // gathered over a substep, in 1/IMPULSE_SCALE units
+    substep: u32, // of the step, counted by integrate_body() whether the body is dynamic or not
+};
+
+@group(1) @binding(0) var<storage, read_write> body: SphereBody;
+
+#include "sdf.wgsl"
+
+// Must match ColliderKind on the Rust side, the rest are planes
+const SPHERE: u32 = 0u;
+const CAPSULE: u32 = 1u;
+const BOX: u32 = 2u;
+const SDF: u32 = 4u;
+const MESH: u32 = 5u;
+const HEIGHT_FIELD: u32 = 6u;
+
+// One primitive in world space, must match the Rust side ColliderBlock struct
+struct Collider {
+    kind: u32,
+    friction: f32, // scales the coefficients of the sim params
+    radius: f32, // sphere and capsule, mesh thickness
+    one_sided: u32, // 1 leaves alone the particles already inside, see started_inside()
+    a: vec4<f32>, // sphere center, capsule first end, box, volume or mesh center, plane normal with w the offset
+    b: vec4<f32>, // capsule second end, box or volume half extents, terrain half extents in xz
+    rotation: vec4<f32>, // box, volume or mesh orientation, a unit quaternion
+    motion: vec4<f32>, // displacement since the previous step, xyz
+    spin: vec4<f32>, // angular velocity in rad/s about the center, xyz
+};
+
+const MAX_COLLIDERS: u32 = 32u; // must match the Rust side
+
+@group(1) @binding(1) var<uniform> colliders: array<Collider, MAX_COLLIDERS>;
+// Signed distances in m at the centers of the voxels, shared by every SDF collider
+@group(1) @binding(2) var sdf_volume: texture_3d<f32>;
+// Bounding volume hierarchy over the triangles shared by every mesh collider, see bvh.rs for the
+// layout. One buffer for both, the stage is out of storage bindings otherwise.
+@group(1) @binding(3) var<storage, read> mesh_bvh: array<vec4<f32>>;
+
+const BVH_STACK: u32 = 32u; // must match MAX_DEPTH of bvh.rs
+
+// Last contact of every particle in a substep, for the contact overlay, must match
+// CONTACT_MARKER_SIZE on the Rust side
+struct ContactMarker {
+    position: vec4<f32>, // on the surface, w is 1.0 for a contact and 0.0 for none
+    normal: vec4<f32>,
+};
+
+@group(1) @binding(4) var<storage, read_write> contact_markers: array<ContactMarker>;
+
+// Heights in m at the samples of the terrain shared by every height field collider, x along the
+// width and z along the height of the texture
+@group(1) @binding(5) var height_field: texture_2d<f32>;
+
+// Speed of the surface of a spinning collider at `point`, about the middle of a capsule and the
+// center of the other kinds, e.g. a drum carrying the cloth along
+fn spin_speed(collider: Collider, point: vec3<f32>) -> vec3<f32> {
+    var center = collider.a.xyz;
+    if (collider.kind == CAPSULE) {
+        center = 0.5 * (collider.a.xyz + collider.b.xyz);
+    }
+    return cross(collider.spin.xyz, point - center);
+}
+
+// Fixed point, there are no float atomics
+const IMPULSE_SCALE: f32 = 1e4;
+
+fn body_is_dynamic() -> bool {
+    return body.velocity.w > 0.0;
+}
+
+// The body replaces where the rig put the first collider
+fn collider_at(i: u32) -> Collider {
+    var collider = colliders[i];
+    if (i == 0u && body_is_dynamic()) {
+        collider.a = vec4<f32>(body.center.xyz, collider.a.w);
+    }
+    return collider;
+}
+
+struct Contact {
+    hit: bool,
+    position: vec3<f32>, // on the surface
+    normal: vec3<f32>, // pointing out of the collider
+};
+
+fn no_contact() -> Contact {
+    return Contact(false, vec3<f32>(0.0), vec3<f32>(0.0));
+}
+
+fn sphere_contact(position: vec3<f32>, center: vec3<f32>, radius: f32) -> Contact {
+    let offset = position - center;
+    let distance = length(offset);
+    if (distance >= radius || distance <= 1e-6) {
+        return no_contact();
+    }
+    let normal = offset / distance;
+    return Contact(true, center + normal * radius, normal);
+}
+
+// Points inside the box leave through the closest face
+fn box_contact(position: vec3<f32>, center: vec3<f32>, half_extents: vec3<f32>, rotation: vec4<f32>) -> Contact {
+    let inverse = vec4<f32>(-rotation.xyz, rotation.w);
+    var local = rotate(inverse, position - center);
+    let depth = half_extents - abs(local);
+    if (any(depth <= vec3<f32>(0.0))) {
+        return no_contact();
+    }
+    var axis = vec3<f32>(0.0);
+    if (depth.x <= depth.y && depth.x <= depth.z) {
+        axis.x = sign(local.x);
+        local.x = axis.x * half_extents.x;
+    } else if (depth.y <= depth.z) {
+        axis.y = sign(local.y);
+        local.y = axis.y * half_extents.y;
+    } else {
+        axis.z = sign(local.z);
+        local.z = axis.z * half_extents.z;
+    }
+    return Contact(true, center + rotate(rotation, local), rotate(rotation, axis));
+}
+
+// Trilinear by hand, R32Float can't always be filtered. `local` is relative to the center.
+fn volume_distance(local: vec3<f32>, half_extents: vec3<f32>) -> f32 {
+    let size = vec3<i32>(textureDimensions(sdf_volume));
+    let voxel = (local / (2.0 * half_extents) + 0.5) * vec3<f32>(size) - 0.5;
+    let base = vec3<i32>(floor(voxel));
+    let t = voxel - floor(voxel);
+    var distance = 0.0;
+    for (var corner = 0; corner < 8; corner++) {
+        let offset = vec3<i32>(corner & 1, (corner >> 1u) & 1, (corner >> 2u) & 1);
+        let weights = mix(1.0 - t, t, vec3<f32>(offset));
+        let texel = clamp(base + offset, vec3<i32>(0), size - 1);
+        distance += weights.x * weights.y * weights.z * textureLoad(sdf_volume, texel, 0).r;
+    }
+    return distance;
+}
+
+// Points closer than `offset` to the surface leave along the gradient of the distance, by central
+// differences a voxel apart
+fn sdf_contact(position: vec3<f32>, center: vec3<f32>, half_extents: vec3<f32>, rotation: vec4<f32>, offset: f32) -> Contact {
+    let inverse = vec4<f32>(-rotation.xyz, rotation.w);
+    let local = rotate(inverse, position - center);
+    if (any(abs(local) >= half_extents)) {
+        return no_contact();
+    }
+    let distance = volume_distance(local, half_extents);
+    if (distance >= offset) {
+        return no_contact();
+    }
+    let step = 2.0 * half_extents / vec3<f32>(textureDimensions(sdf_volume));
+    let gradient = vec3<f32>(
+        volume_distance(local + vec3<f32>(step.x, 0.0, 0.0), half_extents) - volume_distance(local - vec3<f32>(step.x, 0.0, 0.0), half_extents),
+        volume_distance(local + vec3<f32>(0.0, step.y, 0.0), half_extents) - volume_distance(local - vec3<f32>(0.0, step.y, 0.0), half_extents),
+        volume_distance(local + vec3<f32>(0.0, 0.0, step.z), half_extents) - volume_distance(local - vec3<f32>(0.0, 0.0, step.z), half_extents),
+    ) / (2.0 * step);
+    if (length(gradient) < 1e-6) {
+        return no_contact();
+    }
+    let normal = normalize(gradient);
+    return Contact(true, center + rotate(rotation, local - (distance - offset) * normal), rotate(rotation, normal));
+}
+
+// Bilinear by hand like the volume. `local` is relative to the center, in the xz plane.
+fn terrain_height(local: vec2<f32>, half_extents: vec2<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(height_field));
+    let coordinates = (local / (2.0 * half_extents) + 0.5) * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(coordinates));
+    let t = coordinates - floor(coordinates);
+    var height = 0.0;
+    for (var corner = 0; corner < 4; corner++) {
+        let offset = vec2<i32>(corner & 1, (corner >> 1u) & 1);
+        let weights = mix(1.0 - t, t, vec2<f32>(offset));
+        let texel = clamp(base + offset, vec2<i32>(0), size - 1);
+        height += weights.x * weights.y * textureLoad(height_field, texel, 0).r;
+    }
+    return height;
+}
+
+// Points less than `offset` above the terrain go straight up onto it, whatever the slope, so the
+// cloth never slides sideways off a bump. The normal comes from central differences a sample apart.
+fn height_field_contact(position: vec3<f32>, center: vec3<f32>, half_extents: vec2<f32>, offset: f32) -> Contact {
+    let local = position.xz - center.xz;
+    if (any(abs(local) >= half_extents)) {
+        return no_contact();
+    }
+    let surface = center.y + terrain_height(local, half_extents) + offset;
+    if (position.y >= surface) {
+        return no_contact();
+    }
+    let step = 2.0 * half_extents / vec2<f32>(textureDimensions(height_field));
+    let slope = vec2<f32>(
+        terrain_height(local + vec2<f32>(step.x, 0.0), half_extents) - terrain_height(local - vec2<f32>(step.x, 0.0), half_extents),
+        terrain_height(local + vec2<f32>(0.0, step.y), half_extents) - terrain_height(local - vec2<f32>(0.0, step.y), half_extents),
+    ) / (2.0 * step);
+    return Contact(true, vec3<f32>(position.x, surface, position.z), normalize(vec3<f32>(-slope.x, 1.0, -slope.y)));
+}
+
+// Squared distance to the box, 0 inside
+fn aabb_distance2(p: vec3<f32>, lower: vec3<f32>, upper: vec3<f32>) -> f32 {
+    let outside = max(max(lower - p, p - upper), vec3<f32>(0.0));
+    return dot(outside, outside);
+}
+
+// Points within `thickness` of the closest triangle are moved `thickness` in front of it, on the
+// side its face points to, whichever side they came from. Only the nodes closer than the best
+// triangle so far are visited.
+fn mesh_contact(position: vec3<f32>, center: vec3<f32>, rotation: vec4<f32>, thickness: f32) -> Contact {
+    let num_nodes = bitcast<u32>(mesh_bvh[0].x);
+    if (num_nodes == 0u || thickness <= 0.0) {
+        return no_contact();
+    }
+    let first_triangle = 1u + 2u * num_nodes;
+    let local = rotate(vec4<f32>(-rotation.xyz, rotation.w), position - center);
+
+    var best = thickness * thickness;
+    var closest = vec3<f32>(0.0);
+    var normal = vec3<f32>(0.0);
+    var stack: array<u32, BVH_STACK>;
+    stack[0] = 0u;
+    var top = 1u;
+    while (top > 0u) {
+        top -= 1u;
+        let node = stack[top];
+        let lower = mesh_bvh[1u + 2u * node];
+        let upper = mesh_bvh[2u + 2u * node];
+        if (aabb_distance2(local, lower.xyz, upper.xyz) >= best) {
+            continue;
+        }
+        let index = bitcast<u32>(lower.w);
+        let count = bitcast<u32>(upper.w);
+        if (count == 0u) {
+            // The first child is right after its parent
+            stack[top] = index;
+            stack[top + 1u] = node + 1u;
+            top += 2u;
+            continue;
+        }
+        for (var i = index; i < index + count; i++) {
+            let a = mesh_bvh[first_triangle + 3u * i].xyz;
+            let b = mesh_bvh[first_triangle + 3u * i + 1u].xyz;
+            let c = mesh_bvh[first_triangle + 3u * i + 2u].xyz;
+            let face = cross(b - a, c - a);
+            if (dot(face, face) <= 1e-20) {
+                continue;
+            }
+            let point = closest_on_triangle(local, a, b, c);
+            let offset = local - point;
+            if (dot(offset, offset) < best) {
+                best = dot(offset, offset);
+                closest = point;
+                normal = normalize(face);
+            }
+        }
+    }
+    if (all(normal == vec3<f32>(0.0))) {
+        return no_contact();
+    }
+    return Contact(true, center + rotate(rotation, closest + thickness * normal), rotate(rotation, normal));
+}
+
+// Every collider grows by half the thickness of the cloth, boxes keep their sharp corners
+fn find_contact(collider: Collider, position: vec3<f32>) -> Contact {
+    let offset = 0.5 * params.thickness;
+    if (collider.kind == SPHERE) {
+        return sphere_contact(position, collider.a.xyz, collider.radius + offset);
+    }
+    if (collider.kind == CAPSULE) {
+        // Sphere around the closest point of the segment
+        return sphere_contact(position, closest_on_segment(position, collider.a.xyz, collider.b.xyz), collider.radius + offset);
+    }
+    if (collider.kind == BOX) {
+        return box_contact(position, collider.a.xyz, collider.b.xyz + offset, collider.rotation);
+    }
+    if (collider.kind == SDF) {
+        return sdf_contact(position, collider.a.xyz, collider.b.xyz, collider.rotation, offset);
+    }
+    if (collider.kind == MESH) {
+        return mesh_contact(position, collider.a.xyz, collider.rotation, collider.radius + offset);
+    }
+    if (collider.kind == HEIGHT_FIELD) {
+        return height_field_contact(position, collider.a.xyz, collider.b.xz, offset);
+    }
+    let distance = sd_plane(position, collider.a.xyz, collider.a.w);
+    if (distance >= offset) {
+        return no_contact();
+    }
+    return Contact(true, position - (distance - offset) * collider.a.xyz, collider.a.xyz);
+}
+
+// Whether a particle was past the surface of a one-sided collider, not just in the thickness
+// around it, at the start of the substep. Those came from the other side or spawned inside, e.g.
+// below the top of a plane or within a container, and go on without being pushed out.
+fn started_inside(collider: Collider, previous: vec3<f32>) -> bool {
+    if (collider.one_sided == 0u) {
+        return false;
+    }
+    let contact = find_contact(collider, previous);
+    return contact.hit && dot(contact.position - previous, contact.normal) > 0.5 * params.thickness;
+}
+
+// The rig poses the colliders once per step, so a sphere jumps by its whole motion on the first
+// substep and can pass over particles without any of them ending up inside. Seen from the
+// sphere, the particle moved from `previous` relative to where the sphere was to `position`
+// relative to where it is: the first point of that path on its surface is where the sphere
+// caught the particle, which is carried along with it from there.
+fn swept_sphere_contact(previous: vec3<f32>, position: vec3<f32>, center: vec3<f32>, motion: vec3<f32>, radius: f32) -> Contact {
+    let start = previous - (center - motion);
+    let path = position - center - start;
+    // |start + t * path|² = radius², the particle starts outside so the first root is the entry
+    let a = dot(path, path);
+    let b = dot(start, path);
+    let c = dot(start, start) - radius * radius;
+    let discriminant = b * b - a * c;
+    if (c <= 0.0 || a <= 1e-12 || discriminant < 0.0) {
+        return no_contact();
+    }
+    let t = (-b - sqrt(discriminant)) / a;
+    if (t < 0.0 || t > 1.0) {
+        return no_contact();
+    }
+    let normal = normalize(start + t * path);
+    return Contact(true, center + radius * normal, normal);
+}
+
+// Response of a particle already moved back onto a surface with the given normal, moving at
+// `surface_speed`, everything relative to the surface
+fn respond(input: Instance, normal: vec3<f32>, surface_speed: vec3<f32>, static_friction: f32, dynamic_friction: f32) -> Instance {
+    var instance = input;
+
+    // Inelastic contact: cancel the speed going into the surface, its opposite is the normal
+    // impulse per unit of mass
+    let relative_speed = instance.speed.xyz - surface_speed;
+    let normal_speed = dot(relative_speed, normal);
+    if (normal_speed < 0.0) {
+        var speed = relative_speed - normal_speed * normal;
+        let sliding_speed = length(speed);
+        // Coulomb: the contact sticks while the friction it can hold, μs times the normal
+        // impulse, covers the sliding speed, otherwise μd times it is taken off
+        if (sliding_speed <= -max(static_friction, dynamic_friction) * normal_speed) {
+            speed = vec3<f32>(0.0);
+            // Undo the slide of the substep too, the speed alone would let it creep
+            let slide = instance.position.xyz - instance.previous.xyz - surface_speed * params.delta_time;
+            instance.position = vec4<f32>(instance.position.xyz - (slide - dot(slide, normal) * normal), instance.position.w);
+        } else {
+            speed *= max(1.0 + dynamic_friction * normal_speed / sliding_speed, 0.0);
+        }
+        instance.speed = vec4<f32>(speed + surface_speed, instance.speed.w);
+    }
+
+    return instance;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn collide(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var instance = instances_ping[index];
+    let material = material_of(instance);
+    var marker = ContactMarker(vec4<f32>(0.0), vec4<f32>(0.0));
+
+    for (var i = 0u; i < params.num_colliders; i++) {
+        let collider = collider_at(i);
+        let is_body = i == 0u && body_is_dynamic();
+        if (started_inside(collider, instance.previous.xyz)) {
+            continue;
+        }
+        var contact = find_contact(collider, instance.position.xyz);
+        // Fast spheres are swept, the body moves a substep at a time and is caught by the above
+        if (!contact.hit && collider.kind == SPHERE && !is_body && body.substep == 0u) {
+            let radius = collider.radius + 0.5 * params.thickness;
+            contact = swept_sphere_contact(instance.previous.xyz, instance.position.xyz, collider.a.xyz, collider.motion.xyz, radius);
+        }
+        if (!contact.hit) {
+            continue;
+        }
+        // Moving colliders drag the cloth along, the move is spread over the substeps of the step
+        var surface_speed = collider.motion.xyz / (params.delta_time * f32(params.substeps)) + spin_speed(collider, contact.position);
+        if (is_body) {
+            surface_speed = body.velocity.xyz;
+        }
+        // A moving collider wakes the particles it touches, resting on a still one lets them sleep
+        if (length(surface_speed) >= params.sleep_speed) {
+            instance.previous.w = 0.0;
+        }
+        let speed = instance.speed.xyz;
+        instance.position = vec4<f32>(contact.position, instance.position.w);
+        marker = ContactMarker(vec4<f32>(contact.position, 1.0), vec4<f32>(contact.normal, 0.0));
+        let friction = material.friction * collider.friction;
+        instance = respond(instance, contact.normal, surface_speed, friction * params.static_friction, friction * params.dynamic_friction);
+
+        // Pinned particles hold on to their node, the body gets nothing from them
+        if (is_body && instance.position.w > 0.0) {
+            let impulse = (speed - instance.speed.xyz) * material.density / instance.position.w;
+            let fixed_point = vec3<i32>(round(impulse * IMPULSE_SCALE));
+            if (any(fixed_point != vec3<i32>(0))) {
+                atomicAdd(&body.impulse[0], fixed_point.x);
+                atomicAdd(&body.impulse[1], fixed_point.y);
+                atomicAdd(&body.impulse[2], fixed_point.z);
+            }
+        }
+    }
+
+    instances_ping[index] = instance;
+    contact_markers[index] = marker;
+}
+
+// Overlapping colliders can push a particle from one into the other, a few rounds settle it
+const PLACEMENT_ROUNDS: u32 = 4u;
+
+// Before the first step of a cloth: the particles spawned inside a collider are moved onto its
+// surface, at rest, instead of being shot out by the first contacts. Pinned ones stay put, and so
+// do those inside a one-sided collider, which lets them be.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn place_outside_colliders(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var instance = instances_ping[index];
+    if (instance.position.w <= 0.0) {
+        return;
+    }
+    var moved = false;
+    for (var round = 0u; round < PLACEMENT_ROUNDS; round++) {
+        for (var i = 0u; i < params.num_colliders; i++) {
+            let collider = collider_at(i);
+            if (collider.one_sided != 0u) {
+                continue;
+            }
+            let contact = find_contact(collider, instance.position.xyz);
+            if (contact.hit) {
+                instance.position = vec4<f32>(contact.position, instance.position.w);
+                moved = true;
+            }
+        }
+    }
+    if (moved) {
+        instance.previous = vec4<f32>(instance.position.xyz, 0.0);
+        instance.speed = vec4<f32>(0.0, 0.0, 0.0, instance.speed.w);
+        instances_ping[index] = instance;
+    }
+}
+
+// Single invocation, after collide(): applies the gathered impulse and gravity, then moves the body
+@compute @workgroup_size(1)
+fn integrate_body() {
+    body.substep = (body.substep + 1u) % params.substeps;
+    if (!body_is_dynamic()) {
+        return;
+    }
+    let impulse = vec3<f32>(
+        f32(atomicExchange(&body.impulse[0], 0)),
+        f32(atomicExchange(&body.impulse[1], 0)),
+        f32(atomicExchange(&body.impulse[2], 0)),
+    ) / IMPULSE_SCALE;
+
+    var speed = body.velocity.xyz + impulse * body.velocity.w;
+    speed.y += GRAVITY * params.gravity_scale * params.delta_time;
+    speed /= 1.0 + body.damping * params.delta_time;
+    var center = body.center.xyz + speed * params.delta_time;
+    if (center.y < body.floor) {
+        center.y = body.floor;
+        speed.y = max(speed.y, 0.0);
+    }
+
+    body.center = vec4<f32>(center, body.center.w);
+    body.velocity = vec4<f32>(speed, body.velocity.w);
+}
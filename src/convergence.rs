@@ -0,0 +1,199 @@
+// Early termination of the Jacobi iterations once the constraints are nearly satisfied, see
+// convergence.wgsl. Checked on the GPU, so the CPU records every iteration and never waits.
+
+use bytemuck::Zeroable;
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::shaders::create_compute_module;
+
+/// When the solver stops early, set from the UI.
+#[derive(Copy, Clone, Debug)]
+pub struct ConvergenceSettings {
+    pub enabled: bool,
+    pub tolerance: f32, // m, the largest stretch left, scaled by the stiffness of the constraint
+    pub interval: u32,  // iterations between two checks, each one is a dispatch of its own
+}
+
+impl Default for ConvergenceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tolerance: 1e-6,
+            interval: 4,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ConvergenceState {
+    residual: u32,
+    converged: u32,
+    iteration: u32,
+    skipped: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ConvergenceParams {
+    tolerance: f32,
+    interval: u32,
+    _padding: [u32; 2],
+}
+
+pub struct ConvergenceCheck {
+    reset_pipeline: wgpu::ComputePipeline,
+    solve_pipeline: wgpu::ComputePipeline,
+    check_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    state_buffer: TrackedBuffer,
+    params_buffer: TrackedBuffer,
+    interval: u32, // as last given to configure()
+}
+
+impl ConvergenceCheck {
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        workgroup_size: u32,
+    ) -> Self {
+        let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Convergence Bind Group Layout"),
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Storage { read_only: false }),
+                entry(1, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+
+        let state_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Convergence State Buffer"),
+            size: std::mem::size_of::<ConvergenceState>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Filled by configure()
+        let params_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Convergence Params Buffer"),
+            size: std::mem::size_of::<ConvergenceParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Convergence Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: state_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Convergence Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = create_compute_module(context, "Convergence Shader", include_str!("convergence.wgsl"), workgroup_size);
+
+        let create_pipeline = |entry_point: &str| {
+            context
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    module: &module,
+                    entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: None,
+                })
+        };
+
+        let mut check = Self {
+            reset_pipeline: create_pipeline("reset_convergence"),
+            solve_pipeline: create_pipeline("solve_until_converged"),
+            check_pipeline: create_pipeline("check_convergence"),
+            bind_group,
+            state_buffer,
+            params_buffer,
+            interval: 1,
+        };
+        check.configure(context, &ConvergenceSettings::default());
+        check
+    }
+
+    pub fn configure(&mut self, context: &dyn Gpu, settings: &ConvergenceSettings) {
+        self.interval = settings.interval.max(1);
+        let params = ConvergenceParams {
+            tolerance: settings.tolerance,
+            interval: self.interval,
+            _padding: [0; 2],
+        };
+        context.queue().write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    // `iterations` Jacobi iterations, each reading the first binding of the bind group it gets
+    // and writing the second. Every one flips `current`, whether it did any work or not.
+    pub fn encode(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        bind_groups: &[wgpu::BindGroup; 2],
+        current: &mut usize,
+        workgroups: u32,
+        iterations: usize,
+    ) {
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        compute_pass.set_pipeline(&self.reset_pipeline);
+        compute_pass.set_bind_group(0, &bind_groups[*current], &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+
+        for iteration in 1..=iterations {
+            compute_pass.set_pipeline(&self.solve_pipeline);
+            compute_pass.set_bind_group(0, &bind_groups[*current], &[]);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            *current ^= 1;
+
+            // Nothing left to skip after the last one
+            if iteration % self.interval as usize == 0 && iteration < iterations {
+                compute_pass.set_pipeline(&self.check_pipeline);
+                compute_pass.dispatch_workgroups(1, 1, 1);
+            }
+        }
+    }
+
+    // Dispatches encode() records for `iterations`, the skipped ones included
+    pub fn dispatches(&self, iterations: usize) -> u32 {
+        (1 + iterations + iterations.saturating_sub(1) / self.interval as usize) as u32
+    }
+
+    // Zeroes the count of skipped iterations along with the rest of the state
+    pub fn clear(&self, context: &dyn Gpu) {
+        context
+            .queue()
+            .write_buffer(&self.state_buffer, 0, bytemuck::bytes_of(&ConvergenceState::zeroed()));
+    }
+
+    // A single ConvergenceState, the skipped iterations are its last u32
+    pub fn state_buffer(&self) -> &wgpu::Buffer {
+        &self.state_buffer
+    }
+}
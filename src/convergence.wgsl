@@ -0,0 +1,62 @@
+// convergence.wgsl
+// Early termination of the Jacobi iterations, appended to compute.wgsl: the solve passes gather
+// the largest constraint residual, every few iterations a single invocation compares it to the
+// tolerance. Once below, the remaining iterations of the substep only copy the particles over, the
+// ping-pong buffers still have to swap as many times as the CPU recorded.
+
+// Must match the Rust side ConvergenceState struct
+struct ConvergenceState {
+    residual: atomic<u32>, // largest since the last check, bit cast from f32
+    converged: u32, // 1 once the residual got below the tolerance, until the next substep
+    iteration: u32, // of the substep, as of the last check
+    skipped: u32, // iterations copied over since the cloth was built
+};
+
+// Must match the Rust side ConvergenceParams struct
+struct ConvergenceParams {
+    tolerance: f32, // m, scaled by the stiffness of the constraints
+    interval: u32, // iterations between two checks
+};
+
+@group(1) @binding(0) var<storage, read_write> state: ConvergenceState;
+@group(1) @binding(1) var<uniform> convergence: ConvergenceParams;
+
+// Single invocation, before the first iteration of every substep
+@compute @workgroup_size(1)
+fn reset_convergence() {
+    atomicStore(&state.residual, 0u);
+    state.converged = 0u;
+    state.iteration = 0u;
+}
+
+// Same as solve_constraints() of compute.wgsl, until the iterations converged
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn solve_until_converged(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    if (state.converged != 0u) {
+        instances_pong[index] = instances_ping[index];
+        return;
+    }
+    let projection = project_constraints(index);
+    // Non-negative floats compare like their bits
+    atomicMax(&state.residual, bitcast<u32>(projection.residual));
+    instances_pong[index] = projection.instance;
+}
+
+// Single invocation, after every `interval` iterations
+@compute @workgroup_size(1)
+fn check_convergence() {
+    state.iteration += convergence.interval;
+    if (state.converged != 0u) {
+        return;
+    }
+    if (bitcast<f32>(atomicLoad(&state.residual)) < convergence.tolerance) {
+        state.converged = 1u;
+        state.skipped += params.iterations - min(state.iteration, params.iterations);
+    } else {
+        atomicStore(&state.residual, 0u);
+    }
+}
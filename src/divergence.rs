@@ -0,0 +1,269 @@
+// Debug check of the Jacobi solver of compute.wgsl against a CPU reference, run in lockstep.
+//
+// A probed step copies a window of particles after every stage of its first substep, see
+// ClothSimulation::set_probing(). Each stage is then redone on the CPU from the GPU's own input to
+// it, so a discrepancy shows up at the stage that introduced it instead of spreading to the ones
+// after it. Anchors, attachments, contacts and the guard have no reference, the window is copied
+// around the attachments only so finalize starts from what the GPU had.
+//
+// Probe buffer layout: `num_stages()` copies of the window one after the other, the input of the
+// substep, then the output of integrate, of every iteration, of the attachments and of finalize.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use wgpu_bootstrap::{
+    cgmath::{InnerSpace, Vector3},
+    egui,
+};
+
+use crate::material::Material;
+use crate::simulation::{Constraint, Fan, Instance, MAX_CONSTRAINTS, NO_NEIGHBOR};
+
+const GRAVITY: f32 = -9.8; // must match compute.wgsl
+const WINDOW: u32 = 4096; // particles copied after every stage, from the middle of the cloth
+const SAMPLE_STRIDE: usize = 4; // particles of the window redone on the CPU
+const HISTORY: usize = 300; // checked frames plotted
+const TOLERANCE: f32 = 1.0e-5; // m, float rounding between the two stays well below it
+const PLOT_RANGE: (f32, f32) = (-9.0, -1.0); // log10 of the plotted divergence, in m
+
+/// What the CPU reference needs to redo the stages of a probed substep.
+#[derive(Clone, Debug)]
+pub struct StepInputs {
+    pub window: Range<u32>, // particles copied after every stage
+    pub iterations: usize,
+    pub delta_time: f32,
+    pub relaxation: f32,
+    pub gravity_scale: f32,
+    pub materials: Vec<Material>,
+    pub fans: Vec<Fan>,
+}
+
+impl StepInputs {
+    // The input of the substep, integrate, the iterations, the attachments and finalize
+    pub fn num_stages(&self) -> usize {
+        self.iterations + 4
+    }
+}
+
+// The particles copied for a cloth of `num_instances`, fewer for small cloths
+pub fn probe_window(num_instances: u32) -> Range<u32> {
+    let count = WINDOW.min(num_instances);
+    let first = (num_instances - count) / 2;
+    first..first + count
+}
+
+#[derive(Copy, Clone)]
+struct Particle {
+    position: Vector3<f32>,
+    speed: Vector3<f32>,
+    previous: Vector3<f32>,
+}
+
+impl From<&Instance> for Particle {
+    fn from(instance: &Instance) -> Self {
+        Self {
+            position: instance.position().into(),
+            speed: instance.speed().into(),
+            previous: instance.previous().into(),
+        }
+    }
+}
+
+// integrate() of compute.wgsl
+fn integrate(instance: &Instance, inputs: &StepInputs) -> Particle {
+    let mut particle = Particle::from(instance);
+    particle.previous = particle.position;
+    let inverse_mass = instance.inverse_mass();
+    if inverse_mass > 0.0 {
+        let material = &inputs.materials[instance.material()];
+        particle.speed.y += GRAVITY * inputs.gravity_scale * inputs.delta_time;
+        let wind: Vector3<f32> = inputs.fans.iter().map(|fan| fan.wind_at(particle.position)).sum();
+        let relative = particle.speed - wind;
+        let drag = material.linear_drag + material.quadratic_drag * relative.magnitude();
+        particle.speed = wind + relative / (1.0 + drag * inverse_mass / material.density * inputs.delta_time);
+        particle.position += particle.speed * inputs.delta_time;
+    }
+    particle
+}
+
+// solve_constraints() of compute.wgsl, None when a neighbor is outside of the window
+fn solve(index: usize, window: &[Instance], first: u32, constraints: &[Constraint], inputs: &StepInputs) -> Option<Particle> {
+    let instance = &window[index];
+    let mut particle = Particle::from(instance);
+    let inverse_mass = instance.inverse_mass();
+    if inverse_mass <= 0.0 {
+        return Some(particle);
+    }
+    let stiffness = inputs.materials[instance.material()].stiffness.to_array();
+    let slots = (first as usize + index) * MAX_CONSTRAINTS;
+
+    let mut correction = Vector3::new(0.0, 0.0, 0.0);
+    let mut count = 0.0;
+    for constraint in &constraints[slots..slots + MAX_CONSTRAINTS] {
+        if constraint.neighbor == NO_NEIGHBOR {
+            continue;
+        }
+        let other = window.get(constraint.neighbor.checked_sub(first)? as usize)?;
+        let delta = particle.position - Vector3::from(other.position());
+        let distance = delta.magnitude();
+        if distance < 1e-6 {
+            continue;
+        }
+        let weight = inverse_mass / (inverse_mass + other.inverse_mass());
+        let stretch = distance - constraint.rest_length;
+        correction -= stiffness[constraint.kind as usize] * weight * stretch * delta / distance;
+        count += 1.0;
+    }
+    if count > 0.0 {
+        particle.position += correction * inputs.relaxation / count;
+    }
+    Some(particle)
+}
+
+// finalize() of compute.wgsl
+fn finalize(instance: &Instance, inputs: &StepInputs) -> Particle {
+    let mut particle = Particle::from(instance);
+    particle.speed = (particle.position - particle.previous) / inputs.delta_time;
+    particle
+}
+
+// Largest difference between the redone particles and the GPU ones, in m, the speed counts by
+// how far it moves a particle in one substep
+fn stage_divergence(after: &[Instance], inputs: &StepInputs, redo: impl Fn(usize) -> Option<Particle>) -> f32 {
+    (0..after.len())
+        .step_by(SAMPLE_STRIDE)
+        .filter_map(|index| {
+            let expected = redo(index)?;
+            let actual = Particle::from(&after[index]);
+            let position = (expected.position - actual.position).magnitude();
+            let speed = (expected.speed - actual.speed).magnitude() * inputs.delta_time;
+            let previous = (expected.previous - actual.previous).magnitude();
+            Some(position.max(speed).max(previous))
+        })
+        .fold(0.0, f32::max)
+}
+
+struct StageRecord {
+    name: String,
+    latest: f32,
+    worst: f32,
+}
+
+/// Divergence of every checked stage, for the last probed step and since the monitor started.
+#[derive(Default)]
+pub struct DivergenceMonitor {
+    stages: Vec<StageRecord>, // integrate, the iterations and finalize
+    history: VecDeque<f32>,   // largest divergence of the checked steps, oldest first
+    steps: u64,
+}
+
+impl DivergenceMonitor {
+    // Redoes the stages copied by the last probed step, `probe` holds every copy of the window
+    pub fn check(&mut self, inputs: &StepInputs, constraints: &[Constraint], probe: &[Instance]) {
+        let window = inputs.window.len();
+        if window == 0 || probe.len() != window * inputs.num_stages() {
+            return;
+        }
+        let copies: Vec<&[Instance]> = probe.chunks_exact(window).collect();
+        let first = inputs.window.start;
+
+        let mut divergences = vec![(
+            "integrate".to_string(),
+            stage_divergence(copies[1], inputs, |index| Some(integrate(&copies[0][index], inputs))),
+        )];
+        for iteration in 0..inputs.iterations {
+            let before = copies[iteration + 1];
+            divergences.push((
+                format!("iteration {}", iteration + 1),
+                stage_divergence(copies[iteration + 2], inputs, |index| {
+                    solve(index, before, first, constraints, inputs)
+                }),
+            ));
+        }
+        let attached = copies[inputs.iterations + 2];
+        divergences.push((
+            "finalize".to_string(),
+            stage_divergence(copies[inputs.iterations + 3], inputs, |index| Some(finalize(&attached[index], inputs))),
+        ));
+
+        // Worst values restart when the stages change
+        if self.stages.len() != divergences.len() {
+            *self = Self::default();
+        }
+        self.stages.resize_with(divergences.len(), || StageRecord {
+            name: String::new(),
+            latest: 0.0,
+            worst: 0.0,
+        });
+        for (record, (name, divergence)) in self.stages.iter_mut().zip(divergences) {
+            record.name = name;
+            record.latest = divergence;
+            record.worst = record.worst.max(divergence);
+        }
+
+        let largest = self.stages.iter().map(|record| record.latest).fold(0.0, f32::max);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(largest);
+        self.steps += 1;
+    }
+
+    // The first stage above TOLERANCE in the last probed step, where the GPU started to drift
+    fn first_divergent_stage(&self) -> Option<&StageRecord> {
+        self.stages.iter().find(|record| record.latest > TOLERANCE)
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        if self.steps == 0 {
+            ui.label("Waiting for a Jacobi step...");
+            return;
+        }
+        ui.label(format!("{} steps checked, tolerance {TOLERANCE:.0e} m", self.steps));
+        match self.first_divergent_stage() {
+            Some(record) => ui.colored_label(egui::Color32::RED, format!("Diverges from {}", record.name)),
+            None => ui.label("Within tolerance"),
+        };
+        self.plot(ui);
+        egui::Grid::new("divergence stages").striped(true).show(ui, |ui| {
+            ui.label("Stage");
+            ui.label("Last (m)");
+            ui.label("Worst (m)");
+            ui.end_row();
+            for record in &self.stages {
+                ui.label(&record.name);
+                ui.label(format!("{:.2e}", record.latest));
+                ui.label(format!("{:.2e}", record.worst));
+                ui.end_row();
+            }
+        });
+    }
+
+    // Largest divergence of every checked step over time, on a log scale with the tolerance
+    // drawn across
+    fn plot(&self, ui: &mut egui::Ui) {
+        let size = egui::vec2(ui.available_width(), 60.0);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        let (low, high) = PLOT_RANGE;
+        let y = |divergence: f32| {
+            let t = (divergence.max(f32::MIN_POSITIVE).log10().clamp(low, high) - low) / (high - low);
+            rect.bottom() - t * rect.height()
+        };
+        let tolerance = y(TOLERANCE);
+        painter.hline(rect.x_range(), tolerance, egui::Stroke::new(1.0, egui::Color32::RED));
+
+        let step = rect.width() / (HISTORY - 1) as f32;
+        let points: Vec<egui::Pos2> = self
+            .history
+            .iter()
+            .enumerate()
+            .map(|(index, &divergence)| egui::pos2(rect.left() + index as f32 * step, y(divergence)))
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, ui.visuals().text_color())));
+        ui.label(format!("1e{low} to 1e{high} m, log scale"));
+    }
+}
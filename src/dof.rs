@@ -0,0 +1,230 @@
+// Depth of field of the main view, to make captured footage look shot through a lens. The view
+// is blurred around a focal plane placed on whatever is right-clicked in the scene: the shader
+// reads the depth under that point every frame, so the focus follows the cloth as it moves.
+
+use bytemuck::Zeroable;
+use wgpu_bootstrap::{
+    egui,
+    wgpu::{self, util::DeviceExt},
+    Context,
+};
+
+use crate::camera::{OrbitCamera, Projection};
+use crate::hdr::Tonemapping;
+use crate::shader_source::depth_of_field_source;
+
+const MAX_RADIUS: f32 = 16.0; // pixels
+
+// Must match the DepthOfField struct of dof.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DofUniform {
+    focus: [f32; 2],
+    depth_terms: [f32; 2],
+    aperture: f32,
+    max_radius: f32,
+    orthographic: u32,
+    _padding: f32,
+}
+
+/// Replaces the plain composite of the main view while enabled. Its bind group is created by
+/// the main view along with the targets it reads.
+pub struct DepthOfField {
+    pub enabled: bool,
+    pub aperture: f32, // pixels of blur far behind the focal plane
+    focus: [f32; 2],   // in texture coordinates of the view
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl DepthOfField {
+    // `sample_count` is that of the depth of the main view
+    pub fn new(context: &Context, sample_count: u32, tonemapping: &Tonemapping) -> Self {
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Depth Of Field Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Depth Of Field Uniform Buffer"),
+            contents: bytemuck::bytes_of(&DofUniform::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let multisampled = sample_count > 1;
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Of Field Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let source = depth_of_field_source(multisampled);
+        let shader = context.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Of Field Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Of Field Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, tonemapping.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = context
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Depth Of Field Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: context.format(),
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Replaces the scene like the plain composite of the main view
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: context.depth_stencil_format(),
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            enabled: false,
+            aperture: 6.0,
+            focus: [0.5, 0.5],
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    // Puts the focal plane on what the view shows at `focus`, in texture coordinates
+    pub fn focus_at(&mut self, focus: [f32; 2]) {
+        self.focus = focus.map(|coordinate| coordinate.clamp(0.0, 1.0));
+    }
+
+    // Follows the projection of `camera`, which the main view is seen from, and the sliders
+    pub fn update(&self, context: &Context, camera: &OrbitCamera) {
+        let projection = camera.projection_matrix();
+        let uniform = DofUniform {
+            focus: self.focus,
+            depth_terms: [projection[2][2], projection[3][2]],
+            aperture: self.aperture,
+            max_radius: MAX_RADIUS,
+            orthographic: u32::from(camera.projection() == Projection::Orthographic),
+            _padding: 0.0,
+        };
+        context.queue().write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    // Reads the resolved color and the depth of the main view, `depth_view` has the sample count
+    // the depth of field was created with
+    pub fn create_bind_group(
+        &self,
+        context: &Context,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Of Field Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // Blurs and tonemaps the main view into the pass
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, bind_group: &wgpu::BindGroup, tonemapping: &Tonemapping) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, tonemapping.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // `available` when the main view is drawn, anti-aliasing and motion blur replace it
+    pub fn ui(&mut self, ui: &mut egui::Ui, available: bool) {
+        ui.add_enabled(available, egui::Checkbox::new(&mut self.enabled, "Depth of field"))
+            .on_hover_text("Right-click the scene to focus on it");
+        ui.add_enabled(
+            available && self.enabled,
+            egui::Slider::new(&mut self.aperture, 0.0..=MAX_RADIUS).text("Aperture (px)"),
+        );
+    }
+}
@@ -0,0 +1,67 @@
+// dof.wgsl
+// Depth of field of the main view, see dof.rs. Every pixel averages the colors over a disk whose
+// radius grows with its distance to the focal plane, then is tonemapped into the window.
+
+#include "tonemap.wgsl"
+
+// Must match the Rust side DofUniform struct
+struct DepthOfField {
+    focus: vec2<f32>,       // point in focus, in texture coordinates of the view
+    depth_terms: vec2<f32>, // of the projection, turn a depth into a distance from the eye
+    aperture: f32,          // radius in pixels of the blur far behind the focal plane
+    max_radius: f32,        // pixels, the blur of what is close to the eye stops there
+    orthographic: u32,      // 1 when the depth grows linearly with the distance
+};
+
+@group(0) @binding(0) var color_texture: texture_2d<f32>;
+@group(0) @binding(1) var color_sampler: sampler;
+// Multisampled like the view, dof.rs changes the type then
+@group(0) @binding(2) var depth_texture: texture_depth_2d;
+@group(0) @binding(3) var<uniform> dof: DepthOfField;
+@group(1) @binding(0) var<uniform> tonemapping: Tonemapping;
+
+const TAPS: u32 = 24u;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle over the whole view
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u)) * 2.0;
+    var out: VertexOutput;
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Distance from the eye of what the view shows at `texel`, the first sample is enough
+fn distance_at(texel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    let depth = textureLoad(depth_texture, clamp(texel, vec2<i32>(0), size - 1), 0);
+    if (dof.orthographic != 0u) {
+        return (dof.depth_terms.y - depth) / dof.depth_terms.x;
+    }
+    return dof.depth_terms.y / (depth + dof.depth_terms.x);
+}
+
+// Samples spread evenly over the disk of confusion along a golden angle spiral
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(color_texture));
+    let focus = distance_at(vec2<i32>(dof.focus * size));
+    let distance = distance_at(vec2<i32>(in.clip_position.xy));
+    let radius = min(dof.aperture * abs(distance - focus) / distance, dof.max_radius);
+
+    var color = vec4<f32>(0.0);
+    for (var i = 0u; i < TAPS; i++) {
+        let offset = radius * sqrt((f32(i) + 0.5) / f32(TAPS));
+        let angle = f32(i) * GOLDEN_ANGLE;
+        let uv = in.uv + offset * vec2<f32>(cos(angle), sin(angle)) / size;
+        color += textureSampleLevel(color_texture, color_sampler, uv, 0.0);
+    }
+    return tonemap(tonemapping, color / f32(TAPS));
+}
@@ -0,0 +1,77 @@
+// edge_shader.wgsl
+// Spring overlay: a line from every particle to each of its neighbors, see edge_view.rs
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+// Must match the Rust Instance and Constraint structs
+struct Instance {
+    position: vec4<f32>,
+    speed: vec4<f32>,
+    previous: vec4<f32>,
+};
+
+struct Constraint {
+    neighbor: u32,
+    kind: u32, // horizontal, vertical, shear or bend
+    rest_length: f32,
+    tear_strain: f32,
+};
+
+const MAX_CONSTRAINTS: u32 = 12u; // slots per particle, must match the Rust side
+const NO_NEIGHBOR: u32 = 0xffffffffu;
+
+@group(1) @binding(0) var<storage, read> instances: array<Instance>;
+@group(1) @binding(1) var<storage, read> constraints: array<Constraint>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+// Structural in blue, shear in green, bend in orange
+fn kind_color(kind: u32) -> vec3<f32> {
+    switch kind {
+        case 0u, 1u: { return vec3<f32>(0.2, 0.45, 1.0); }
+        case 2u: { return vec3<f32>(0.2, 0.85, 0.3); }
+        default: { return vec3<f32>(1.0, 0.55, 0.1); }
+    }
+}
+
+// One instance per constraint slot, drawn as a line list of two vertices
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, @builtin(instance_index) slot: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let constraint = constraints[slot];
+    out.color = kind_color(constraint.kind);
+    if (constraint.neighbor == NO_NEIGHBOR) {
+        // Outside of the clip volume, the line is dropped
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+    var particle = slot / MAX_CONSTRAINTS;
+    if (vertex == 1u) {
+        particle = constraint.neighbor;
+    }
+    out.clip_position = camera.proj * camera.view * vec4<f32>(instances[particle].position.xyz, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
+
+struct MotionOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+// The springs aren't blurred
+@fragment
+fn fs_motion(in: VertexOutput) -> MotionOutput {
+    return MotionOutput(vec4<f32>(in.color, 1.0), vec2<f32>(0.0, 0.0));
+}
@@ -0,0 +1,181 @@
+// Stability plots: the kinetic and potential energy of the cloth, its mean constraint violation and
+// its largest strain over time, computed on the GPU from the latest state, see energy.wgsl. A
+// solver blowing up shows as energy growing out of nothing long before the cloth explodes.
+//
+// The samples are read back without waiting for the GPU, they reach the plots a few frames late.
+
+use std::collections::VecDeque;
+
+use wgpu_bootstrap::{egui, wgpu};
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::readback::AsyncReadback;
+use crate::shaders::create_compute_module;
+
+pub const SAMPLE_INTERVAL: u64 = 4; // steps
+const MAX_SAMPLES: usize = 1000; // the oldest are dropped first
+const PLOT_HEIGHT: f32 = 120.0;
+
+// Must match energy.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnergySample {
+    pub kinetic: f32,    // J
+    pub potential: f32,  // J, of gravity above y = 0
+    pub violation: f32,  // mean of |length / rest length - 1| over the constraints
+    pub max_strain: f32, // largest stretch of the structural and shear constraints
+}
+
+pub struct EnergyMeter {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    buffer: TrackedBuffer,
+}
+
+impl EnergyMeter {
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        workgroup_size: u32,
+    ) -> Self {
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Energy Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Energy Buffer"),
+            size: std::mem::size_of::<EnergySample>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Energy Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Energy Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Energy Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &create_compute_module(context, "Energy Shader", include_str!("energy.wgsl"), workgroup_size),
+                entry_point: "measure_energy",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group,
+            buffer,
+        }
+    }
+
+    // A single workgroup over the first binding of `bind_group`, the latest state
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Energy Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    // An EnergySample
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+/// Samples of the latest run against the simulated time, for the plots.
+#[derive(Default)]
+pub struct EnergyPlot {
+    pub enabled: bool,
+    run: u32, // samples of an earlier run still in flight are dropped
+    samples: VecDeque<(f32, EnergySample)>, // s since the cloth was dropped
+    readback: AsyncReadback<(u32, f32)>, // run and time of every copy
+}
+
+impl EnergyPlot {
+    // `buffer` holds the sample of the step ending at `time`, as written by EnergyMeter::encode()
+    pub fn copy(&mut self, context: &dyn Gpu, encoder: &mut wgpu::CommandEncoder, buffer: &wgpu::Buffer, time: f32) {
+        self.readback.copy(context, encoder, buffer, (self.run, time));
+    }
+
+    // Once the frame is submitted, adds the samples the GPU finished
+    pub fn poll(&mut self, context: &dyn Gpu) {
+        for ((run, time), bytes) in self.readback.poll(context) {
+            if run != self.run {
+                continue;
+            }
+            self.samples.push_back((time, bytemuck::pod_read_unaligned(&bytes)));
+            if self.samples.len() > MAX_SAMPLES {
+                self.samples.pop_front();
+            }
+        }
+    }
+
+    // On a new drop, the time starts over
+    pub fn clear(&mut self) {
+        self.run += 1;
+        self.samples.clear();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Record")
+                .on_hover_text(format!("Samples the cloth every {SAMPLE_INTERVAL} steps"));
+            if ui.button("Clear").clicked() {
+                self.clear();
+            }
+        });
+
+        let line = |name: &str, value: fn(&EnergySample) -> f32| {
+            let points: egui_plot::PlotPoints = self
+                .samples
+                .iter()
+                .map(|(time, sample)| [f64::from(*time), f64::from(value(sample))])
+                .collect();
+            egui_plot::Line::new(points).name(name)
+        };
+        egui_plot::Plot::new("energy plot")
+            .height(PLOT_HEIGHT)
+            .legend(egui_plot::Legend::default())
+            .x_axis_label("s")
+            .y_axis_label("J")
+            .show(ui, |plot_ui| {
+                plot_ui.line(line("Kinetic", |sample| sample.kinetic));
+                plot_ui.line(line("Potential", |sample| sample.potential));
+            });
+        egui_plot::Plot::new("constraint plot")
+            .height(PLOT_HEIGHT)
+            .legend(egui_plot::Legend::default())
+            .x_axis_label("s")
+            .show(ui, |plot_ui| {
+                plot_ui.line(line("Mean violation", |sample| sample.violation));
+                plot_ui.line(line("Max strain", |sample| sample.max_strain));
+            });
+    }
+}
@@ -0,0 +1,56 @@
+// energy.wgsl
+// Totals of the latest state for the stability plots, appended to compute.wgsl: a single workgroup
+// walks the particles and their constraints, then reduces what every invocation gathered. Pinned
+// particles move with their anchor and count for neither energy.
+
+#include "reductions.wgsl"
+
+const BEND: u32 = 3u; // kind of the constraints across two spacings
+
+// Must match the Rust side EnergySample struct
+struct EnergySample {
+    kinetic: f32, // J
+    potential: f32, // J, of gravity above y = 0
+    violation: f32, // mean of |length / rest length - 1| over the constraints
+    max_strain: f32, // largest stretch of the structural and shear constraints, as in strain.wgsl
+};
+
+@group(1) @binding(0) var<storage, read_write> energy: EnergySample;
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn measure_energy(@builtin(local_invocation_index) local_index: u32) {
+    var kinetic = 0.0;
+    var potential = 0.0;
+    var violation = 0.0;
+    var count = 0.0;
+    var largest = 0.0;
+    for (var index = local_index; index < params.num_particles; index += u32(WORKGROUP_SIZE)) {
+        let instance = instances_ping[index];
+        if (instance.position.w > 0.0) {
+            kinetic += 0.5 * PARTICLE_MASS * dot(instance.speed.xyz, instance.speed.xyz);
+            potential -= PARTICLE_MASS * GRAVITY * instance.position.y;
+        }
+        for (var slot = 0u; slot < MAX_CONSTRAINTS; slot++) {
+            let constraint = constraints[index * MAX_CONSTRAINTS + slot];
+            if (constraint.neighbor == NO_NEIGHBOR || constraint.rest_length <= 0.0) {
+                continue;
+            }
+            let length = distance(instances_ping[constraint.neighbor].position.xyz, instance.position.xyz);
+            let stretch = length / constraint.rest_length - 1.0;
+            violation += abs(stretch);
+            count += 1.0;
+            if (constraint.kind != BEND) {
+                largest = max(largest, stretch);
+            }
+        }
+    }
+
+    let total_kinetic = workgroup_sum(kinetic, local_index);
+    let total_potential = workgroup_sum(potential, local_index);
+    let total_violation = workgroup_sum(violation, local_index);
+    let total_count = workgroup_sum(count, local_index);
+    let max_strain = workgroup_max(largest, local_index);
+    if (local_index == 0u) {
+        energy = EnergySample(total_kinetic, total_potential, total_violation / max(total_count, 1.0), max_strain);
+    }
+}
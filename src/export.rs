@@ -0,0 +1,200 @@
+// Offline export of a simulation run: a point cache with the particle positions and the camera
+// path that was used while recording, keyed by simulation step so both stay in sync.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use wgpu_bootstrap::cgmath::{self, Point3};
+
+pub const EXPORT_DIR: &str = "export";
+pub const MESH_CACHE_FILE: &str = "cloth.pc2";
+pub const CAMERA_PATH_FILE: &str = "camera_path.csv";
+
+// Byte offset of the sample count in the PC2 header
+const PC2_SAMPLE_COUNT_OFFSET: u64 = 28;
+
+/// Writes particle positions in the Point Cache 2 format (.pc2), which Blender, 3ds Max and
+/// Houdini can apply to a mesh with the same vertex count.
+pub struct MeshCacheWriter {
+    writer: BufWriter<File>,
+    num_points: u32,
+    num_samples: u32,
+}
+
+impl MeshCacheWriter {
+    // `sample_rate` is the number of frames between two samples
+    pub fn create(path: &Path, num_points: u32, sample_rate: f32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(b"POINTCACHE2\0")?;
+        writer.write_all(&1i32.to_le_bytes())?; // file version
+        writer.write_all(&(num_points as i32).to_le_bytes())?;
+        writer.write_all(&0f32.to_le_bytes())?; // start frame
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&0i32.to_le_bytes())?; // sample count, patched by finish()
+
+        Ok(Self {
+            writer,
+            num_points,
+            num_samples: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, positions: impl ExactSizeIterator<Item = [f32; 3]>) -> io::Result<()> {
+        if positions.len() != self.num_points as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "point count changed during the recording",
+            ));
+        }
+        for position in positions {
+            for coordinate in position {
+                self.writer.write_all(&coordinate.to_le_bytes())?;
+            }
+        }
+        self.num_samples += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(PC2_SAMPLE_COUNT_OFFSET))?;
+        self.writer.write_all(&(self.num_samples as i32).to_le_bytes())?;
+        self.writer.flush()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraKey {
+    pub step: u64, // simulation steps since the start of the recording
+    pub polar: Point3<f32>,
+    pub target: Point3<f32>,
+}
+
+/// Camera keys stored as CSV: `step,distance,theta,phi,target_x,target_y,target_z`.
+#[derive(Clone, Debug, Default)]
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+}
+
+impl CameraPath {
+    pub fn push(&mut self, key: CameraKey) {
+        self.keys.push(key);
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "step,distance,theta,phi,target_x,target_y,target_z")?;
+        for key in &self.keys {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                key.step, key.polar.x, key.polar.y, key.polar.z, key.target.x, key.target.y, key.target.z
+            )?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |line: usize| io::Error::new(io::ErrorKind::InvalidData, format!("invalid camera key on line {}", line + 1));
+
+        let mut keys = Vec::new();
+        for (line_index, line) in BufReader::new(File::open(path)?).lines().enumerate().skip(1) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 7 {
+                return Err(invalid(line_index));
+            }
+            let step = fields[0].parse().map_err(|_| invalid(line_index))?;
+            let values = fields[1..]
+                .iter()
+                .map(|field| field.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid(line_index))?;
+            keys.push(CameraKey {
+                step,
+                polar: cgmath::point3(values[0], values[1], values[2]),
+                target: cgmath::point3(values[3], values[4], values[5]),
+            });
+        }
+        keys.sort_by_key(|key| key.step);
+        Ok(Self { keys })
+    }
+
+    pub fn is_finished(&self, step: u64) -> bool {
+        self.keys.last().is_none_or(|key| step >= key.step)
+    }
+
+    // Linear interpolation between the two keys around `step`
+    pub fn sample(&self, step: u64) -> Option<(Point3<f32>, Point3<f32>)> {
+        let next = self.keys.iter().position(|key| key.step >= step);
+        let (a, b) = match next {
+            None => {
+                let last = self.keys.last()?;
+                (last, last)
+            }
+            Some(0) => (&self.keys[0], &self.keys[0]),
+            Some(index) => (&self.keys[index - 1], &self.keys[index]),
+        };
+        let t = if b.step > a.step {
+            (step - a.step) as f32 / (b.step - a.step) as f32
+        } else {
+            0.0
+        };
+        let lerp = |p: Point3<f32>, q: Point3<f32>| p + (q - p) * t;
+        Some((lerp(a.polar, b.polar), lerp(a.target, b.target)))
+    }
+}
+
+/// A mesh cache and its camera path being recorded into `EXPORT_DIR`.
+pub struct Recording {
+    mesh_cache: MeshCacheWriter,
+    camera_path: CameraPath,
+    interval: u64,
+    first_step: u64,
+}
+
+impl Recording {
+    pub fn start(num_points: u32, interval: u64, first_step: u64) -> io::Result<Self> {
+        fs::create_dir_all(EXPORT_DIR)?;
+        let mesh_cache = MeshCacheWriter::create(
+            &Path::new(EXPORT_DIR).join(MESH_CACHE_FILE),
+            num_points,
+            interval as f32,
+        )?;
+        Ok(Self {
+            mesh_cache,
+            camera_path: CameraPath::default(),
+            interval,
+            first_step,
+        })
+    }
+
+    pub fn wants_frame(&self, step: u64) -> bool {
+        (step - self.first_step).is_multiple_of(self.interval)
+    }
+
+    pub fn record(
+        &mut self,
+        step: u64,
+        positions: impl ExactSizeIterator<Item = [f32; 3]>,
+        polar: Point3<f32>,
+        target: Point3<f32>,
+    ) -> io::Result<()> {
+        self.mesh_cache.write_frame(positions)?;
+        self.camera_path.push(CameraKey {
+            step: step - self.first_step,
+            polar,
+            target,
+        });
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.mesh_cache.finish()?;
+        self.camera_path
+            .save(&Path::new(EXPORT_DIR).join(CAMERA_PATH_FILE))
+    }
+}
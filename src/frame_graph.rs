@@ -0,0 +1,190 @@
+// Declares the GPU work of a frame as passes reading and writing resources, and derives their
+// order from that instead of from where they happen to be encoded.
+//
+// The main render pass and the UI are recorded by wgpu_bootstrap once update() returns, so they
+// always run after every pass of the graph.
+
+use wgpu_bootstrap::{wgpu, Context};
+
+use cloth::sim::{copy_to_staging, map_staging};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resource {
+    Particles,
+    PipTarget,
+    MotionTarget,
+    TaaHistory,
+    BatchImage,
+    GuardCount,
+    Colliders, // centers and radii the spheres are drawn with
+    StageProbe, // particles copied after every stage of a probed step
+    Occlusion, // shadow of every particle, drawn with the cloth
+    Convergence, // state of the early termination of the solver
+    Checksum,    // of the latest particle state
+    ShadowMap,   // depth of the scene seen from the sun
+    Attribute,   // value of every particle for the color ramp
+    MainView,    // scene seen from the main camera, in HDR
+    StepTimestamps, // GPU time at the start and end of the latest step
+}
+
+/// Gives the graph access to the buffers behind the resources, to copy them for CPU passes.
+pub trait FrameResources {
+    fn buffer(&self, resource: Resource) -> Option<&wgpu::Buffer>;
+}
+
+type Enabled<T> = fn(&T) -> bool;
+
+enum PassKind<T> {
+    // Records its commands into the frame encoder
+    Gpu(fn(&mut T, &Context, &mut wgpu::CommandEncoder)),
+    // Runs once the frame is submitted, with a copy of every resource it reads
+    Cpu(fn(&mut T, &Context, &Readback)),
+}
+
+pub struct Pass<T> {
+    name: &'static str,
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+    enabled: Enabled<T>,
+    kind: PassKind<T>,
+}
+
+impl<T> Pass<T> {
+    pub fn gpu(name: &'static str, record: fn(&mut T, &Context, &mut wgpu::CommandEncoder)) -> Self {
+        Self::new(name, PassKind::Gpu(record))
+    }
+
+    pub fn cpu(name: &'static str, run: fn(&mut T, &Context, &Readback)) -> Self {
+        Self::new(name, PassKind::Cpu(run))
+    }
+
+    fn new(name: &'static str, kind: PassKind<T>) -> Self {
+        Self {
+            name,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            enabled: |_| true,
+            kind,
+        }
+    }
+
+    pub fn reads(mut self, resource: Resource) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    pub fn writes(mut self, resource: Resource) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    // Checked right before the pass would be recorded, so it sees what earlier passes changed
+    pub fn enabled_if(mut self, enabled: Enabled<T>) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    fn depends_on(&self, other: &Pass<T>) -> bool {
+        other.writes.iter().any(|resource| self.reads.contains(resource))
+    }
+}
+
+/// Copies of the resources read by a CPU pass, taken after the passes writing them.
+pub struct Readback {
+    data: Vec<(Resource, Vec<u8>)>,
+}
+
+impl Readback {
+    pub fn get<P: bytemuck::Pod>(&self, resource: Resource) -> Option<Vec<P>> {
+        self.data
+            .iter()
+            .find(|(candidate, _)| *candidate == resource)
+            .map(|(_, bytes)| bytemuck::pod_collect_to_vec(bytes))
+    }
+}
+
+pub struct FrameGraph<T> {
+    passes: Vec<Pass<T>>, // in execution order
+}
+
+impl<T> Default for FrameGraph<T> {
+    fn default() -> Self {
+        Self { passes: Vec::new() }
+    }
+}
+
+impl<T: FrameResources> FrameGraph<T> {
+    /// Orders the passes so every reader of a resource runs after all of its writers, passes
+    /// without a dependency between them keep their declaration order.
+    pub fn new(passes: Vec<Pass<T>>) -> Self {
+        let mut remaining = passes;
+        let mut ordered = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let ready = (0..remaining.len())
+                .find(|&i| {
+                    remaining
+                        .iter()
+                        .enumerate()
+                        .all(|(j, other)| j == i || !remaining[i].depends_on(other))
+                })
+                .unwrap_or_else(|| {
+                    let names: Vec<_> = remaining.iter().map(|pass| pass.name).collect();
+                    panic!("frame graph has a cycle between {names:?}")
+                });
+            ordered.push(remaining.remove(ready));
+        }
+        Self { passes: ordered }
+    }
+
+    pub fn pass_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().map(|pass| pass.name)
+    }
+
+    // Records the GPU passes into one encoder, with a staging copy of what the CPU passes read
+    // inserted where they are scheduled, then runs the CPU passes on the submitted results.
+    pub fn execute(&self, state: &mut T, context: &Context) {
+        let mut encoder = context.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Frame Encoder"),
+        });
+
+        let mut cpu_passes = Vec::new();
+        for pass in &self.passes {
+            if !(pass.enabled)(state) {
+                continue;
+            }
+            match pass.kind {
+                PassKind::Gpu(record) => record(state, context, &mut encoder),
+                PassKind::Cpu(run) => {
+                    let copies: Vec<_> = pass
+                        .reads
+                        .iter()
+                        .filter_map(|&resource| {
+                            let buffer = state.buffer(resource)?;
+                            Some((resource, copy_to_staging(context, &mut encoder, buffer)))
+                        })
+                        .collect();
+                    cpu_passes.push((run, copies));
+                }
+            }
+        }
+
+        context.queue().submit(std::iter::once(encoder.finish()));
+
+        for (run, copies) in cpu_passes {
+            let readback = Readback {
+                // A copy that failed to map is left out, get() returns None for it
+                data: copies
+                    .into_iter()
+                    .filter_map(|(resource, staging_buffer)| match map_staging(context, &staging_buffer) {
+                        Ok(bytes) => Some((resource, bytes)),
+                        Err(error) => {
+                            log::error!("Failed to map readback buffer: {error}");
+                            None
+                        }
+                    })
+                    .collect(),
+            };
+            run(state, context, &readback);
+        }
+    }
+}
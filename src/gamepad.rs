@@ -0,0 +1,42 @@
+// Optional gamepad input, behind the `gamepad` feature: the left stick moves the sphere across the
+// ground, the right stick up and down.
+
+use gilrs::{Axis, Gilrs};
+use wgpu_bootstrap::cgmath::Vector3;
+
+const DEAD_ZONE: f32 = 0.15; // stick deflection ignored around the center
+
+pub struct Gamepad {
+    gilrs: Option<Gilrs>, // None when no gamepad backend is available
+}
+
+impl Gamepad {
+    pub fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .map_err(|error| log::warn!("Gamepads unavailable: {error}"))
+            .ok();
+        Self { gilrs }
+    }
+
+    // Same axes as the keyboard, from the first connected gamepad
+    pub fn direction(&mut self) -> Vector3<f32> {
+        let Some(gilrs) = &mut self.gilrs else {
+            return Vector3::new(0.0, 0.0, 0.0);
+        };
+        // The state of the gamepads only follows the events once they are drained
+        while gilrs.next_event().is_some() {}
+        let Some((_, gamepad)) = gilrs.gamepads().next() else {
+            return Vector3::new(0.0, 0.0, 0.0);
+        };
+        let stick = |axis| {
+            let value = gamepad.value(axis);
+            if value.abs() < DEAD_ZONE {
+                0.0
+            } else {
+                value
+            }
+        };
+        // Sticks point up for positive values, away from the camera is -z
+        Vector3::new(stick(Axis::LeftStickX), stick(Axis::RightStickY), -stick(Axis::LeftStickY))
+    }
+}
@@ -0,0 +1,244 @@
+// Gauss-Seidel constraint solver, the springs are graph colored once at startup, see gauss_seidel.wgsl.
+
+use std::num::NonZeroU64;
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::simulation::{Constraint, MAX_CONSTRAINTS, NO_NEIGHBOR};
+use crate::shaders::create_compute_module;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Spring {
+    slot: u32, // of the constraint at the first particle, torn constraints are skipped
+    b: u32,
+    kind: u32,
+    rest_length: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColorRange {
+    offset: u32,
+    count: u32,
+}
+
+// Greedy edge coloring: every spring takes the lowest color free at both of its particles.
+// Returns the springs sorted by color and the range of each color.
+fn color_springs(constraints: &[Constraint]) -> (Vec<Spring>, Vec<ColorRange>) {
+    let num_particles = constraints.len() / MAX_CONSTRAINTS;
+    let mut used_colors = vec![0u64; num_particles];
+    let mut colored: Vec<(u32, Spring)> = Vec::new();
+
+    for (slot, constraint) in constraints.iter().enumerate() {
+        let a = (slot / MAX_CONSTRAINTS) as u32;
+        // Both particles list the spring, only keep it once
+        if constraint.neighbor == NO_NEIGHBOR || constraint.neighbor < a {
+            continue;
+        }
+        let b = constraint.neighbor;
+        let free = !(used_colors[a as usize] | used_colors[b as usize]);
+        let color = free.trailing_zeros();
+        assert!(color < 64, "spring graph needs more than 64 colors");
+        used_colors[a as usize] |= 1 << color;
+        used_colors[b as usize] |= 1 << color;
+        colored.push((
+            color,
+            Spring {
+                slot: slot as u32,
+                b,
+                kind: constraint.kind,
+                rest_length: constraint.rest_length,
+            },
+        ));
+    }
+
+    colored.sort_by_key(|(color, _)| *color);
+    let num_colors = colored.last().map_or(0, |(color, _)| color + 1);
+    let ranges = (0..num_colors)
+        .map(|color| {
+            let offset = colored.partition_point(|(c, _)| *c < color) as u32;
+            let end = colored.partition_point(|(c, _)| *c <= color) as u32;
+            ColorRange {
+                offset,
+                count: end - offset,
+            }
+        })
+        .collect();
+    let springs = colored.into_iter().map(|(_, spring)| spring).collect();
+    (springs, ranges)
+}
+
+pub struct GaussSeidelSolver {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    color_ranges: Vec<ColorRange>,
+    range_stride: u32, // the ranges are spread to respect the dynamic offset alignment
+    workgroup_size: u32,
+    _buffers: [TrackedBuffer; 2], // released along with the solver
+}
+
+impl GaussSeidelSolver {
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        constraints: &[Constraint],
+        workgroup_size: u32,
+    ) -> Self {
+        let (springs, color_ranges) = color_springs(constraints);
+
+        let range_stride = context.device().limits().min_uniform_buffer_offset_alignment;
+        let mut range_data = vec![0u8; color_ranges.len() * range_stride as usize];
+        for (color, range) in color_ranges.iter().enumerate() {
+            let start = color * range_stride as usize;
+            range_data[start..start + std::mem::size_of::<ColorRange>()].copy_from_slice(bytemuck::bytes_of(range));
+        }
+
+        let spring_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Colored Spring Buffer"),
+            contents: bytemuck::cast_slice(springs.as_slice()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let range_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Color Range Buffer"),
+            contents: &range_data,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let range_size = NonZeroU64::new(std::mem::size_of::<ColorRange>() as u64);
+
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Gauss-Seidel Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: range_size,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Gauss-Seidel Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: spring_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &range_buffer,
+                        offset: 0,
+                        size: range_size,
+                    }),
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gauss-Seidel Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = create_compute_module(context, "Gauss-Seidel Shader", include_str!("gauss_seidel.wgsl"), workgroup_size);
+
+        let pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Gauss-Seidel Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "solve_color",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group,
+            color_ranges,
+            range_stride,
+            workgroup_size,
+            _buffers: [spring_buffer, range_buffer],
+        }
+    }
+
+    pub fn num_colors(&self) -> usize {
+        self.color_ranges.len()
+    }
+
+    // Dispatches encode() records for `iterations`
+    pub fn dispatches(&self, iterations: usize) -> u32 {
+        (iterations * self.color_ranges.len()) as u32
+    }
+
+    // Projects the constraints in place on the first binding of `instance_bind_group`
+    pub fn encode(&self, compute_pass: &mut wgpu::ComputePass<'_>, instance_bind_group: &wgpu::BindGroup, iterations: usize) {
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, instance_bind_group, &[]);
+        for _ in 0..iterations {
+            for (color, range) in self.color_ranges.iter().enumerate() {
+                compute_pass.set_bind_group(1, &self.bind_group, &[color as u32 * self.range_stride]);
+                compute_pass.dispatch_workgroups(range.count.div_ceil(self.workgroup_size), 1, 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ClothBuilder;
+
+    #[test]
+    fn every_spring_is_colored_once_without_sharing_a_particle() {
+        let constraints = ClothBuilder::new(6, 7).grid().rest_state().constraints;
+        let (springs, ranges) = color_springs(&constraints);
+
+        let particle = |slot: u32| slot / MAX_CONSTRAINTS as u32;
+        let mut expected: Vec<(u32, u32)> = constraints
+            .iter()
+            .enumerate()
+            .filter(|(_, constraint)| constraint.neighbor != NO_NEIGHBOR)
+            .map(|(slot, constraint)| (particle(slot as u32), constraint.neighbor))
+            .filter(|(a, b)| a < b)
+            .collect();
+        let mut colored: Vec<(u32, u32)> = springs.iter().map(|spring| (particle(spring.slot), spring.b)).collect();
+        expected.sort_unstable();
+        colored.sort_unstable();
+        assert_eq!(colored, expected);
+
+        // The ranges cover the springs one after the other
+        assert_eq!(ranges.iter().map(|range| range.count).sum::<u32>() as usize, springs.len());
+        for range in &ranges {
+            let color = &springs[range.offset as usize..(range.offset + range.count) as usize];
+            let mut particles: Vec<u32> = color.iter().flat_map(|spring| [particle(spring.slot), spring.b]).collect();
+            particles.sort_unstable();
+            let count = particles.len();
+            particles.dedup();
+            assert_eq!(particles.len(), count, "two springs of a color share a particle");
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// gauss_seidel.wgsl
+// Gauss-Seidel constraint projection, appended to compute.wgsl.
+//
+// The springs are split into colors in which no two springs share a particle, so a whole color
+// can be projected in parallel, in place, and every color sees the corrections of the previous ones.
+
+struct Spring {
+    slot: u32, // of the constraint at its first particle, the spring is gone once it tore
+    b: u32,
+    kind: u32,
+    rest_length: f32,
+};
+
+struct ColorRange {
+    offset: u32,
+    count: u32,
+};
+
+@group(1) @binding(0) var<storage, read> springs: array<Spring>;
+@group(1) @binding(1) var<uniform> color_range: ColorRange;
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn solve_color(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= color_range.count) {
+        return;
+    }
+
+    let spring = springs[color_range.offset + global_id.x];
+    if (constraints[spring.slot].neighbor == NO_NEIGHBOR) {
+        return;
+    }
+    let index = spring.slot / MAX_CONSTRAINTS;
+    if (is_asleep(instances_ping[index]) && is_asleep(instances_ping[spring.b])) {
+        return;
+    }
+    let a = instances_ping[index].position;
+    let b = instances_ping[spring.b].position;
+
+    let inverse_mass_sum = a.w + b.w;
+    let delta = a.xyz - b.xyz;
+    let distance = length(delta);
+    if (inverse_mass_sum == 0.0 || distance < 1e-6) {
+        return;
+    }
+
+    // Both ends belong to the same cloth
+    let stiffness = material_of(instances_ping[index]).stiffness[spring.kind];
+    let correction = stiffness * (distance - spring.rest_length) / inverse_mass_sum * delta / distance;
+    instances_ping[index].position = vec4<f32>(a.xyz - a.w * correction, a.w);
+    instances_ping[spring.b].position = vec4<f32>(b.xyz + b.w * correction, b.w);
+}
@@ -0,0 +1,54 @@
+// The device and queue the solver runs on. The app hands it the wgpu_bootstrap Context of its
+// window, the smoke test of main.rs a Headless device without one.
+
+use wgpu_bootstrap::{wgpu, Context};
+
+pub trait Gpu {
+    fn device(&self) -> &wgpu::Device;
+    fn queue(&self) -> &wgpu::Queue;
+}
+
+impl Gpu for Context {
+    fn device(&self) -> &wgpu::Device {
+        Context::device(self)
+    }
+
+    fn queue(&self) -> &wgpu::Queue {
+        Context::queue(self)
+    }
+}
+
+pub struct Headless {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+impl Headless {
+    // Any adapter will do, with no surface to be compatible with
+    pub fn new() -> Result<Self, String> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok_or("no GPU adapter found")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("Headless Device"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))
+        .map_err(|error| error.to_string())?;
+        Ok(Self { device, queue })
+    }
+}
+
+impl Gpu for Headless {
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+}
@@ -0,0 +1,86 @@
+// GPU buffers owned by a scene, tracked so that tearing a scene down can be checked for leaks.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use wgpu_bootstrap::wgpu::{self, util::DeviceExt};
+
+use crate::gpu::Gpu;
+
+/// Counts the live buffers created through it, one per owner so that tearing that owner down
+/// can be checked for leaks while others keep their buffers. Clones share the counts.
+#[derive(Clone, Default)]
+pub struct BufferTracker {
+    live_buffers: Arc<AtomicUsize>,
+    live_bytes: Arc<AtomicU64>,
+}
+
+impl BufferTracker {
+    pub fn create_buffer(&self, context: &dyn Gpu, descriptor: &wgpu::BufferDescriptor) -> TrackedBuffer {
+        TrackedBuffer::track(self, context.device().create_buffer(descriptor))
+    }
+
+    pub fn create_buffer_init(
+        &self,
+        context: &dyn Gpu,
+        descriptor: &wgpu::util::BufferInitDescriptor,
+    ) -> TrackedBuffer {
+        TrackedBuffer::track(self, context.device().create_buffer_init(descriptor))
+    }
+
+    pub fn live_buffers(&self) -> usize {
+        self.live_buffers.load(Ordering::Relaxed)
+    }
+
+    pub fn live_bytes(&self) -> u64 {
+        self.live_bytes.load(Ordering::Relaxed)
+    }
+
+    // Called once the owner is torn down, nothing else creates buffers through this tracker
+    pub fn debug_assert_released(&self) {
+        debug_assert_eq!(
+            self.live_buffers(),
+            0,
+            "{} scene buffers ({} bytes) outlived their scene",
+            self.live_buffers(),
+            self.live_bytes()
+        );
+    }
+}
+
+/// Buffer that frees its GPU memory as soon as it is dropped.
+///
+/// A plain `wgpu::Buffer` stays allocated until every bind group referencing it is gone as
+/// well, this one calls `destroy()` on drop so the memory is released at a known point.
+pub struct TrackedBuffer {
+    buffer: wgpu::Buffer,
+    tracker: BufferTracker, // the buffer is counted by
+}
+
+impl TrackedBuffer {
+    fn track(tracker: &BufferTracker, buffer: wgpu::Buffer) -> Self {
+        tracker.live_buffers.fetch_add(1, Ordering::Relaxed);
+        tracker.live_bytes.fetch_add(buffer.size(), Ordering::Relaxed);
+        Self {
+            buffer,
+            tracker: tracker.clone(),
+        }
+    }
+}
+
+impl Deref for TrackedBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+impl Drop for TrackedBuffer {
+    fn drop(&mut self) {
+        self.tracker.live_buffers.fetch_sub(1, Ordering::Relaxed);
+        self.tracker.live_bytes.fetch_sub(self.buffer.size(), Ordering::Relaxed);
+        self.buffer.destroy();
+    }
+}
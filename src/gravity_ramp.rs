@@ -0,0 +1,61 @@
+// Eases gravity in after every drop from a pre-step hook, which scales the gravity of the step
+// about to run and times the ramp with the simulated time of the steps it scaled.
+
+use std::sync::{Arc, Mutex};
+
+use cloth::scene::Scene;
+
+#[derive(Default)]
+struct RampState {
+    duration: f32, // simulated seconds until full gravity, 0 for none
+    elapsed: f32,  // simulated seconds since the drop
+}
+
+impl RampState {
+    fn scale(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed / self.duration).min(1.0)
+    }
+}
+
+pub struct GravityRamp {
+    state: Arc<Mutex<RampState>>,
+}
+
+impl GravityRamp {
+    // The hook stays registered for the lifetime of the scene
+    pub fn install(scene: &mut Scene) -> Self {
+        let state = Arc::new(Mutex::new(RampState::default()));
+
+        // Counts simulated time, so runs with different solver settings or time scales see the
+        // same forces at the same simulated time
+        let hook_state = state.clone();
+        scene.on_pre_step(move |handle| {
+            let mut state = hook_state.lock().unwrap();
+            handle.params.scale_gravity(state.scale());
+            state.elapsed += handle.params.step_time();
+        });
+
+        Self { state }
+    }
+
+    // Called whenever the cloth starts over from its rest state
+    pub fn restart(&self) {
+        self.state.lock().unwrap().elapsed = 0.0;
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.state.lock().unwrap().duration
+    }
+
+    pub fn set_duration(&self, duration: f32) {
+        self.state.lock().unwrap().duration = duration;
+    }
+
+    // Fraction of gravity applied by the next step
+    pub fn scale(&self) -> f32 {
+        self.state.lock().unwrap().scale()
+    }
+}
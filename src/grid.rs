@@ -0,0 +1,132 @@
+// Grid floor: lines every 10 cm and every meter on the ground plane, out to the horizon, so the
+// size of the cloth and how far it falls can be read at a glance. Drawn right after the sky and
+// covered by everything else, see grid.wgsl.
+
+use wgpu_bootstrap::{egui, wgpu, Context};
+
+use crate::shader_source::expand_includes;
+
+pub struct GridFloor {
+    pub enabled: bool,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    motion_pipeline: wgpu::RenderPipeline, // also fills the velocity target of the motion blur
+}
+
+impl GridFloor {
+    // Same targets and sample count as the sky, the lines are blended over it
+    pub fn new(
+        context: &Context,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        lighting_bind_group_layout: &wgpu::BindGroupLayout,
+        targets: [Option<wgpu::ColorTargetState>; 2],
+        sample_count: u32,
+    ) -> Self {
+        let buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = context.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Grid Shader"),
+            source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("grid.wgsl")).into()),
+        });
+        let layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, lighting_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, fragment_entry: &str, targets: &[Option<wgpu::ColorTargetState>]| {
+            context
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: fragment_entry,
+                        targets,
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    // Like the sky, what is drawn after covers it
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: context.depth_stencil_format(),
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Always,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                })
+        };
+
+        let [color_target, velocity_target] = targets;
+        let color_target = color_target.map(|target| wgpu::ColorTargetState {
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            ..target
+        });
+        Self {
+            enabled: true,
+            buffer,
+            bind_group,
+            pipeline: create_pipeline("Grid Render Pipeline", "fs_main", std::slice::from_ref(&color_target)),
+            motion_pipeline: create_pipeline("Motion Grid Render Pipeline", "fs_motion", &[color_target, velocity_target]),
+        }
+    }
+
+    // Follows the ground, `height` in m
+    pub fn update(&self, context: &Context, height: f32) {
+        context.queue().write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[height, 0.0, 0.0, 0.0]));
+    }
+
+    // After the sky, with the camera bind group already set
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, lighting_bind_group: &wgpu::BindGroup, motion_vectors: bool) {
+        render_pass.set_pipeline(if motion_vectors { &self.motion_pipeline } else { &self.pipeline });
+        render_pass.set_bind_group(1, lighting_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Grid floor")
+            .on_hover_text("Lines every 10 cm and every meter on the ground");
+    }
+}
@@ -0,0 +1,76 @@
+// grid.wgsl
+// Infinite floor at the height of the ground, lines every 10 cm and every meter fading with the
+// distance, drawn over the sky and under everything else, see grid.rs.
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+#include "lighting.wgsl"
+
+@group(1) @binding(0) var<uniform> lighting: Lighting;
+@group(2) @binding(0) var<uniform> floor_height: vec4<f32>; // x, in m
+
+const LINE_COLOR: vec3<f32> = vec3<f32>(0.25, 0.25, 0.25);
+const FADE_DISTANCE: f32 = 20.0; // m, the lines are gone past it
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>, // world space
+    @location(1) origin: vec3<f32>, // of the ray, the eye unless the projection is orthographic
+};
+
+// One triangle over the whole view. The rays of a perspective spread from the eye, those of an
+// orthographic projection, whose proj[3][3] is 1, are parallel and spread over the plane of the eye.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    let orthographic = camera.proj[3][3];
+    let offset = vec2<f32>(ndc.x / camera.proj[0][0], ndc.y / camera.proj[1][1]);
+    let rotation = transpose(mat3x3<f32>(camera.view[0].xyz, camera.view[1].xyz, camera.view[2].xyz));
+    out.direction = rotation * vec3<f32>(offset * (1.0 - orthographic), -1.0);
+    out.origin = eye_position(camera.view) + rotation * vec3<f32>(offset * orthographic, 0.0);
+    return out;
+}
+
+// Coverage of the lines every `spacing` m around `position` on the floor, one pixel wide
+fn lines(position: vec2<f32>, spacing: f32) -> f32 {
+    let coordinates = position / spacing;
+    let distance = abs(fract(coordinates - 0.5) - 0.5) / fwidth(coordinates);
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+// Lines where the view ray meets the floor, transparent elsewhere. The derivatives are taken
+// before anything is left out, they need every pixel of the quad.
+fn grid(in: VertexOutput) -> vec4<f32> {
+    let eye = in.origin;
+    let direction = normalize(in.direction);
+    let distance = (floor_height.x - eye.y) / direction.y;
+    let position = eye + distance * direction;
+    let coverage = max(0.4 * lines(position.xz, 0.1), lines(position.xz, 1.0));
+    if (distance <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    let fade = 1.0 - smoothstep(0.25 * FADE_DISTANCE, FADE_DISTANCE, distance);
+    return vec4<f32>(apply_fog(lighting, LINE_COLOR, distance), coverage * fade);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return grid(in);
+}
+
+struct MotionOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+// The floor doesn't move with the particles
+@fragment
+fn fs_motion(in: VertexOutput) -> MotionOutput {
+    return MotionOutput(grid(in), vec2<f32>(0.0, 0.0));
+}
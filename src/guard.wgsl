@@ -0,0 +1,41 @@
+// guard.wgsl
+// Safety pass appended to compute.wgsl, run in place on the latest state after every substep.
+//
+// Particles whose position or speed is no longer finite go back to where they started the
+// substep, at rest, and are counted so the application can warn about the explosion. The other
+// ones get their speed clamped, which keeps a blow-up from spreading before it turns into NaNs.
+
+@group(1) @binding(0) var<storage, read_write> guard_count: atomic<u32>;
+
+// Checks the exponent bits, comparisons with NaN may be optimized away
+fn is_finite(v: vec3<f32>) -> bool {
+    let exponent = bitcast<vec3<u32>>(v) & vec3<u32>(0x7f800000u);
+    return all(exponent != vec3<u32>(0x7f800000u));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn guard(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var instance = instances_ping[index];
+
+    if (!is_finite(instance.position.xyz) || !is_finite(instance.speed.xyz)) {
+        atomicAdd(&guard_count, 1u);
+        var position = instance.previous.xyz;
+        if (!is_finite(position)) {
+            position = vec3<f32>(0.0, 0.0, 0.0);
+        }
+        instance.position = vec4<f32>(position, instance.position.w);
+        instance.previous = vec4<f32>(instance.position.xyz, 0.0);
+        instance.speed = vec4<f32>(0.0, 0.0, 0.0, instance.speed.w);
+    } else {
+        let speed = length(instance.speed.xyz);
+        if (speed > params.max_speed) {
+            instance.speed = vec4<f32>(instance.speed.xyz * (params.max_speed / speed), instance.speed.w);
+        }
+    }
+
+    instances_ping[index] = instance;
+}
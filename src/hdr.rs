@@ -0,0 +1,406 @@
+// High dynamic range: the scene is rendered in floating point colors, so the highlights of the
+// spheres and the cloth go past 1 instead of clipping, and every view of it is tonemapped into the
+// window with an exposure, see wgsl/tonemap.wgsl. The main view is drawn offscreen by MainView
+// unless anti-aliasing or motion blur draw their own.
+
+use wgpu_bootstrap::{
+    egui,
+    wgpu::{self, util::DeviceExt},
+    Context,
+};
+
+use crate::dof::DepthOfField;
+use crate::msaa;
+use crate::oit::{OitTargets, Transparency};
+use crate::shader_source::expand_includes;
+
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Window clear color of the runner, (245, 245, 245) in sRGB
+const BACKGROUND: wgpu::Color = wgpu::Color { r: 0.913, g: 0.913, b: 0.913, a: 1.0 };
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TonemapOperator {
+    Clip,
+    Reinhard,
+    Aces,
+}
+
+impl TonemapOperator {
+    pub const ALL: [TonemapOperator; 3] = [TonemapOperator::Clip, TonemapOperator::Reinhard, TonemapOperator::Aces];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TonemapOperator::Clip => "Clip",
+            TonemapOperator::Reinhard => "Reinhard",
+            TonemapOperator::Aces => "ACES filmic",
+        }
+    }
+}
+
+// Must match the Tonemapping struct of wgsl/tonemap.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    exposure: f32,
+    curve: u32,
+    _padding: [u32; 2],
+}
+
+/// Exposure and curve every view of the scene is brought into the window with. Bound to group 1
+/// of the passes compositing into the window.
+pub struct Tonemapping {
+    pub exposure: f32, // stops, 0 keeps the colors of the scene
+    pub operator: TonemapOperator,
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Tonemapping {
+    pub fn new(context: &Context) -> Self {
+        let buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tonemapping Buffer"),
+            size: std::mem::size_of::<TonemapUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tonemapping Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tonemapping Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            exposure: 0.0,
+            operator: TonemapOperator::Aces,
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    // Follows the sliders, called once per frame
+    pub fn update(&self, context: &Context) {
+        let uniform = TonemapUniform {
+            exposure: self.exposure.exp2(),
+            curve: self.operator as u32,
+            _padding: [0; 2],
+        };
+        context.queue().write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.exposure, -4.0..=4.0).text("Exposure (EV)"))
+            .on_hover_text("Each stop doubles the brightness of the scene");
+        egui::ComboBox::from_label("Tonemapping")
+            .selected_text(self.operator.name())
+            .show_ui(ui, |ui| {
+                for operator in TonemapOperator::ALL {
+                    ui.selectable_value(&mut self.operator, operator, operator.name());
+                }
+            });
+    }
+}
+
+// Offscreen targets of the main view, sized like the window
+struct Targets {
+    size: (u32, u32),
+    color_view: wgpu::TextureView,
+    multisampled_color_view: Option<wgpu::TextureView>, // drawn into and resolved into the one above
+    depth_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup, // reads the color view
+    dof_bind_group: wgpu::BindGroup, // reads the color and depth views
+    oit: Option<OitTargets>,
+}
+
+/// Main view rendered in HDR, with every sample, then tonemapped into the window's pass.
+pub struct MainView {
+    pub depth_of_field: DepthOfField,
+    sample_count: u32,
+    targets: Option<Targets>, // created on the first update
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl MainView {
+    pub fn new(context: &Context, sample_count: u32, tonemapping: &Tonemapping) -> Self {
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Main View Sampler"),
+            ..Default::default()
+        });
+        // The whole window, see pip.wgsl
+        let uniform_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Main View Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[-1.0f32, -1.0, 1.0, 1.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Main View Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // Same screen-space quad as the picture-in-picture, over the whole window
+        let shader = context
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Main View Shader"),
+                source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("pip.wgsl")).into()),
+            });
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Main View Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, tonemapping.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = context
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Main View Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: context.format(),
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                // Replaces the scene, which isn't drawn in the window's pass
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: context.depth_stencil_format(),
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            depth_of_field: DepthOfField::new(context, sample_count, tonemapping),
+            sample_count,
+            targets: None,
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // Follows the size of the view, in pixels
+    pub fn update(&mut self, context: &Context, size: (u32, u32), transparency: &Transparency) {
+        if self.targets.as_ref().map(|targets| targets.size) != Some(size) {
+            self.targets = Some(self.create_targets(context, size));
+        }
+        if let Some(targets) = &mut self.targets {
+            transparency.prepare(context, &mut targets.oit, size, self.sample_count);
+        }
+    }
+
+    fn create_targets(&self, context: &Context, (width, height): (u32, u32)) -> Targets {
+        let create_view = |label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages, sample_count: u32| {
+            context
+                .device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: width.max(1),
+                        height: height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let attachment = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        let color_view = create_view(
+            "Main View Color Texture",
+            HDR_FORMAT,
+            attachment | wgpu::TextureUsages::TEXTURE_BINDING,
+            1,
+        );
+        let multisampled_color_view = msaa::create_multisampled_view(
+            context,
+            "Main View Multisampled Color Texture",
+            HDR_FORMAT,
+            (width, height),
+            self.sample_count,
+        );
+        // Also read by the depth of field
+        let depth_view = create_view(
+            "Main View Depth Texture",
+            context.depth_stencil_format(),
+            attachment | wgpu::TextureUsages::TEXTURE_BINDING,
+            self.sample_count,
+        );
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Main View Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Targets {
+            size: (width, height),
+            dof_bind_group: self.depth_of_field.create_bind_group(context, &color_view, &depth_view),
+            color_view,
+            multisampled_color_view,
+            depth_view,
+            bind_group,
+            oit: None,
+        }
+    }
+
+    // Renders the main view, `draw_scene` records its draws and `draw_transparent` those of the
+    // sheer cloth
+    pub fn render_offscreen(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        transparency: &Transparency,
+        draw_scene: impl FnOnce(&mut wgpu::RenderPass<'_>),
+        draw_transparent: impl FnOnce(&mut wgpu::RenderPass<'_>),
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Main View Scene Pass"),
+                color_attachments: &[Some(msaa::color_attachment(
+                    &targets.color_view,
+                    targets.multisampled_color_view.as_ref(),
+                    BACKGROUND,
+                ))],
+                // Kept for the sheer cloth
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            draw_scene(&mut render_pass);
+        }
+        transparency.render(
+            encoder,
+            targets.oit.as_ref(),
+            &targets.color_view,
+            &targets.depth_view,
+            draw_transparent,
+        );
+    }
+
+    // Tonemaps the rendered view into the pass, through the depth of field when enabled
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, tonemapping: &Tonemapping) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        if self.depth_of_field.enabled {
+            self.depth_of_field.draw(render_pass, &targets.dof_bind_group, tonemapping);
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &targets.bind_group, &[]);
+        render_pass.set_bind_group(1, tonemapping.bind_group(), &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
@@ -0,0 +1,125 @@
+// Uneven terrain for the cloth to land on: a grid of heights over a rectangle, sampled bilinearly
+// by contacts.wgsl from a 2D texture. Generated from noise or loaded from a grayscale image, e.g.
+// a heightmap written by the drape bake.
+
+use std::io;
+use std::path::Path;
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::simulation::Collider;
+
+const OCTAVES: u32 = 4; // of the noise, each one half as large and half as high as the previous
+
+/// Heights in m at the samples of a grid, x varying fastest, then z.
+pub struct HeightField {
+    resolution: [u32; 2], // samples along x and z
+    heights: Vec<f32>,
+}
+
+impl HeightField {
+    // Bumps about `feature` samples across and up to `amplitude` m high, from value noise. The
+    // same seed gives the same terrain.
+    pub fn from_noise(resolution: [u32; 2], amplitude: f32, feature: f32, seed: u32) -> Self {
+        let [width, depth] = resolution.map(|count| count.max(2));
+        let lattice = |octave: u32, x: i32, z: i32| -> f32 {
+            let mut hash = seed ^ octave.wrapping_mul(0x27d4_eb2d);
+            hash ^= (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841);
+            hash = (hash ^ (hash >> 16)).wrapping_mul(0x7feb_352d);
+            hash = (hash ^ (hash >> 15)).wrapping_mul(0x846c_a68b);
+            (hash ^ (hash >> 16)) as f32 / u32::MAX as f32
+        };
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+
+        let mut heights = Vec::with_capacity((width * depth) as usize);
+        for z in 0..depth {
+            for x in 0..width {
+                let (mut height, mut scale, mut total) = (0.0, 1.0, 0.0);
+                for octave in 0..OCTAVES {
+                    let size = (feature / (1 << octave) as f32).max(1.0);
+                    let (u, v) = (x as f32 / size, z as f32 / size);
+                    let (x0, z0) = (u.floor() as i32, v.floor() as i32);
+                    let (tu, tv) = (smooth(u.fract()), smooth(v.fract()));
+                    let near = lattice(octave, x0, z0) + (lattice(octave, x0 + 1, z0) - lattice(octave, x0, z0)) * tu;
+                    let far = lattice(octave, x0, z0 + 1) + (lattice(octave, x0 + 1, z0 + 1) - lattice(octave, x0, z0 + 1)) * tu;
+                    height += scale * (near + (far - near) * tv);
+                    total += scale;
+                    scale *= 0.5;
+                }
+                heights.push(amplitude * height / total);
+            }
+        }
+        Self {
+            resolution: [width, depth],
+            heights,
+        }
+    }
+
+    // The brightness of the pixels, from black at 0.0 to white at `max_height` m, the image
+    // spanning x from left to right and z from top to bottom
+    pub fn load_image(path: &Path, max_height: f32) -> io::Result<Self> {
+        let image = image::open(path).map_err(io::Error::other)?.into_luma16();
+        let (width, depth) = image.dimensions();
+        if width < 2 || depth < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "a terrain needs at least 2×2 pixels"));
+        }
+        Ok(Self {
+            resolution: [width, depth],
+            heights: image
+                .pixels()
+                .map(|pixel| max_height * f32::from(pixel.0[0]) / f32::from(u16::MAX))
+                .collect(),
+        })
+    }
+
+    /// The terrain stretched over `half_extents` along x and z around `center`, where its heights
+    /// start from. There is one terrain per scene.
+    pub fn collider(&self, center: [f32; 3], half_extents: [f32; 2]) -> Collider {
+        Collider::HeightField { center, half_extents }
+    }
+
+    // An R32Float 2D texture, read with textureLoad() like the SDF volume
+    pub fn create_texture(&self, context: &dyn Gpu) -> wgpu::Texture {
+        create_height_texture(context, self.resolution, bytemuck::cast_slice(&self.heights))
+    }
+}
+
+// Flat, standing in for the terrain while the scene has none
+pub fn create_empty_texture(context: &dyn Gpu) -> wgpu::Texture {
+    create_height_texture(context, [1, 1], bytemuck::bytes_of(&0.0f32))
+}
+
+fn create_height_texture(context: &dyn Gpu, [width, depth]: [u32; 2], data: &[u8]) -> wgpu::Texture {
+    let size = wgpu::Extent3d {
+        width,
+        height: depth,
+        depth_or_array_layers: 1,
+    };
+    let texture = context.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some("Height Field Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    context.queue().write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: Some(depth),
+        },
+        size,
+    );
+    texture
+}
@@ -0,0 +1,170 @@
+// Stiff solver: implicit Euler with a matrix-free conjugate gradient, see implicit.wgsl.
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::shaders::create_compute_module;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CgVectors {
+    x: [f32; 4],
+    r: [f32; 4],
+    p: [f32; 4],
+    q: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CgScalars {
+    rr: f32,
+    rr_old: f32,
+    pq: f32,
+    _padding: f32,
+}
+
+pub struct ImplicitSolver {
+    prepare_pipeline: wgpu::ComputePipeline,
+    apply_pipeline: wgpu::ComputePipeline,
+    reduce_pq_pipeline: wgpu::ComputePipeline,
+    update_x_r_pipeline: wgpu::ComputePipeline,
+    reduce_rr_pipeline: wgpu::ComputePipeline,
+    update_p_pipeline: wgpu::ComputePipeline,
+    finalize_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    _buffers: [TrackedBuffer; 3], // released along with the solver
+}
+
+impl ImplicitSolver {
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        num_instances: u32,
+        workgroup_size: u32,
+    ) -> Self {
+        let storage_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Conjugate Gradient Bind Group Layout"),
+            entries: &[storage_entry(0), storage_entry(1), storage_entry(2)],
+        });
+
+        let vectors_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Conjugate Gradient Vectors Buffer"),
+            size: (num_instances as usize * std::mem::size_of::<CgVectors>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let partial_sums_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Conjugate Gradient Partial Sums Buffer"),
+            size: (num_instances.div_ceil(workgroup_size) as usize * std::mem::size_of::<f32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let scalars_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Conjugate Gradient Scalars Buffer"),
+            size: std::mem::size_of::<CgScalars>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Conjugate Gradient Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: vectors_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: partial_sums_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: scalars_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Implicit Solver Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = create_compute_module(context, "Implicit Solver Shader", include_str!("implicit.wgsl"), workgroup_size);
+
+        let create_pipeline = |entry_point: &str| {
+            context
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    module: &module,
+                    entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: None,
+                })
+        };
+
+        Self {
+            prepare_pipeline: create_pipeline("implicit_prepare"),
+            apply_pipeline: create_pipeline("cg_apply"),
+            reduce_pq_pipeline: create_pipeline("cg_reduce_pq"),
+            update_x_r_pipeline: create_pipeline("cg_update_x_r"),
+            reduce_rr_pipeline: create_pipeline("cg_reduce_rr"),
+            update_p_pipeline: create_pipeline("cg_update_p"),
+            finalize_pipeline: create_pipeline("implicit_finalize"),
+            bind_group,
+            _buffers: [vectors_buffer, partial_sums_buffer, scalars_buffer],
+        }
+    }
+
+    // Dispatches encode() records for `iterations`
+    pub fn dispatches(&self, iterations: usize) -> u32 {
+        (3 + 5 * iterations) as u32
+    }
+
+    // Reads the instances from the first binding of `instance_bind_group` and writes the next
+    // state to the second one, so the caller swaps its ping-pong buffers once.
+    pub fn encode(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        instance_bind_group: &wgpu::BindGroup,
+        workgroups: u32,
+        iterations: usize,
+    ) {
+        compute_pass.set_bind_group(0, instance_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+
+        let mut dispatch = |pipeline: &wgpu::ComputePipeline, workgroups: u32| {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        };
+
+        dispatch(&self.prepare_pipeline, workgroups);
+        dispatch(&self.reduce_rr_pipeline, 1);
+        for _ in 0..iterations {
+            dispatch(&self.apply_pipeline, workgroups);
+            dispatch(&self.reduce_pq_pipeline, 1);
+            dispatch(&self.update_x_r_pipeline, workgroups);
+            dispatch(&self.reduce_rr_pipeline, 1);
+            dispatch(&self.update_p_pipeline, workgroups);
+        }
+        dispatch(&self.finalize_pipeline, workgroups);
+    }
+}
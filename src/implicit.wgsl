@@ -0,0 +1,222 @@
+// implicit.wgsl
+// Implicit Euler step solved with a matrix-free conjugate gradient, appended to compute.wgsl.
+//
+// Every spring is linearized around the current positions and the system
+//     (M + h² Σ K) Δv = h (f - h Σ K v)
+// is solved for the speed change Δv, where K is the stiffness matrix of a spring.
+
+struct CgVectors {
+    x: vec4<f32>, // Δv, the solution
+    r: vec4<f32>, // residual
+    p: vec4<f32>, // search direction
+    q: vec4<f32>, // A p
+};
+
+struct CgScalars {
+    rr: f32,
+    rr_old: f32,
+    pq: f32,
+};
+
+@group(1) @binding(0) var<storage, read_write> cg: array<CgVectors>;
+@group(1) @binding(1) var<storage, read_write> partial_sums: array<f32>;
+@group(1) @binding(2) var<storage, read_write> cg_scalars: CgScalars;
+
+#include "reductions.wgsl"
+
+fn store_partial_sum(value: f32, local_index: u32, workgroup_index: u32) {
+    let sum = workgroup_sum(value, local_index);
+    if (local_index == 0u) {
+        partial_sums[workgroup_index] = sum;
+    }
+}
+
+// Meant to be dispatched with a single workgroup
+fn sum_partials(local_index: u32) -> f32 {
+    var sum = 0.0;
+    for (var i = local_index; i < arrayLength(&partial_sums); i += u32(WORKGROUP_SIZE)) {
+        sum += partial_sums[i];
+    }
+    return workgroup_sum(sum, local_index);
+}
+
+// K such that the force derivative is -K, with the compressive part clamped to keep A definite
+fn spring_stiffness(delta: vec3<f32>, rest_length: f32, spring_constant: f32) -> mat3x3<f32> {
+    let distance = max(length(delta), 1e-6);
+    let n = delta / distance;
+    let outer = mat3x3<f32>(n * n.x, n * n.y, n * n.z);
+    let identity = mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    let transverse = max(1.0 - rest_length / distance, 0.0);
+    return spring_constant * (outer + transverse * (identity - outer));
+}
+
+// Spring constant of a constraint of particle `index`, both ends belong to the same cloth
+fn constraint_spring_constant(index: u32, constraint: Constraint) -> f32 {
+    return material_of(instances_ping[index]).stiffness[constraint.kind] * params.spring_constant;
+}
+
+// Σ K (a_i - a_j) over the springs of a particle, for a per-particle field selected by `field`
+// (0: speed, 1: search direction p)
+fn stiffness_product(index: u32, field: u32) -> vec3<f32> {
+    let position = instances_ping[index].position.xyz;
+    var own: vec3<f32>;
+    if (field == 0u) {
+        own = instances_ping[index].speed.xyz;
+    } else {
+        own = cg[index].p.xyz;
+    }
+
+    var result = vec3<f32>(0.0, 0.0, 0.0);
+    for (var slot = 0u; slot < MAX_CONSTRAINTS; slot++) {
+        let constraint = constraints[index * MAX_CONSTRAINTS + slot];
+        if (constraint.neighbor == NO_NEIGHBOR) {
+            continue;
+        }
+        var other: vec3<f32>;
+        if (field == 0u) {
+            other = instances_ping[constraint.neighbor].speed.xyz;
+        } else {
+            other = cg[constraint.neighbor].p.xyz;
+        }
+        let delta = position - instances_ping[constraint.neighbor].position.xyz;
+        result += spring_stiffness(delta, constraint.rest_length, constraint_spring_constant(index, constraint)) * (own - other);
+    }
+    return result;
+}
+
+fn spring_force(index: u32) -> vec3<f32> {
+    let position = instances_ping[index].position.xyz;
+    var force = vec3<f32>(0.0, 0.0, 0.0);
+    for (var slot = 0u; slot < MAX_CONSTRAINTS; slot++) {
+        let constraint = constraints[index * MAX_CONSTRAINTS + slot];
+        if (constraint.neighbor == NO_NEIGHBOR) {
+            continue;
+        }
+        let delta = position - instances_ping[constraint.neighbor].position.xyz;
+        let distance = max(length(delta), 1e-6);
+        force -= constraint_spring_constant(index, constraint) * (distance - constraint.rest_length) * delta / distance;
+    }
+    return force;
+}
+
+// Builds the right hand side, starts from Δv = 0 so r = p = b
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn implicit_prepare(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let index = global_id.x;
+    var rr = 0.0;
+    if (index < params.num_particles) {
+        let instance = instances_ping[index];
+        var b = vec3<f32>(0.0, 0.0, 0.0);
+        if (instance.position.w > 0.0) {
+            let mass = material_of(instance).density / instance.position.w;
+            let force = mass * vec3<f32>(0.0, GRAVITY * params.gravity_scale, 0.0) + spring_force(index);
+            let h = params.delta_time;
+            b = h * (force - h * stiffness_product(index, 0u));
+        }
+        cg[index].x = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        cg[index].r = vec4<f32>(b, 0.0);
+        cg[index].p = vec4<f32>(b, 0.0);
+        rr = dot(b, b);
+    }
+    store_partial_sum(rr, local_index, workgroup_id.x);
+}
+
+// q = A p
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cg_apply(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let index = global_id.x;
+    var pq = 0.0;
+    if (index < params.num_particles) {
+        let instance = instances_ping[index];
+        let inverse_mass = instance.position.w / material_of(instance).density;
+        var q = vec3<f32>(0.0, 0.0, 0.0);
+        if (inverse_mass > 0.0) {
+            let h = params.delta_time;
+            q = cg[index].p.xyz / inverse_mass + h * h * stiffness_product(index, 1u);
+        }
+        cg[index].q = vec4<f32>(q, 0.0);
+        pq = dot(cg[index].p.xyz, q);
+    }
+    store_partial_sum(pq, local_index, workgroup_id.x);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cg_reduce_pq(@builtin(local_invocation_index) local_index: u32) {
+    let sum = sum_partials(local_index);
+    if (local_index == 0u) {
+        cg_scalars.pq = sum;
+    }
+}
+
+// x += α p, r -= α q
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cg_update_x_r(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let index = global_id.x;
+    var rr = 0.0;
+    if (index < params.num_particles) {
+        var alpha = 0.0;
+        if (cg_scalars.pq > 1e-20) {
+            alpha = cg_scalars.rr / cg_scalars.pq;
+        }
+        let vectors = cg[index];
+        let r = vectors.r - alpha * vectors.q;
+        cg[index].x = vectors.x + alpha * vectors.p;
+        cg[index].r = r;
+        rr = dot(r.xyz, r.xyz);
+    }
+    store_partial_sum(rr, local_index, workgroup_id.x);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cg_reduce_rr(@builtin(local_invocation_index) local_index: u32) {
+    let sum = sum_partials(local_index);
+    if (local_index == 0u) {
+        cg_scalars.rr_old = cg_scalars.rr;
+        cg_scalars.rr = sum;
+    }
+}
+
+// p = r + β p
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cg_update_p(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var beta = 0.0;
+    if (cg_scalars.rr_old > 1e-20) {
+        beta = cg_scalars.rr / cg_scalars.rr_old;
+    }
+    cg[index].p = cg[index].r + beta * cg[index].p;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn implicit_finalize(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var instance = instances_ping[index];
+    // Always awake, the implicit solver doesn't let particles sleep
+    instance.previous = vec4<f32>(instance.position.xyz, 0.0);
+
+    if (instance.position.w > 0.0) {
+        let speed = apply_drag(instance.speed.xyz + cg[index].x.xyz, instance.position.xyz, material_of(instance), instance.position.w);
+        instance.speed = vec4<f32>(speed, instance.speed.w);
+        instance.position = vec4<f32>(instance.position.xyz + speed * params.delta_time, instance.position.w);
+    }
+
+    instances_pong[index] = instance;
+}
@@ -0,0 +1,407 @@
+// Particle inspector: a click on the cloth picks the particle drawn closest to the camera under the
+// pointer, and a window shows its state for as long as it stays selected. The pick waits for a copy
+// of every particle, the window is refreshed from a copy of the selected one alone, read back
+// without waiting for the GPU.
+//
+// With the pin tool, the same click pins or frees the particle instead, and a drag with shift held
+// toggles every particle inside the box, hidden ones included. With the grab tool, pressing the
+// button holds the particle under the pointer by an attachment pulling it to the pointer ray, at
+// the depth it was picked at, until the button is released. With the cut tool, the constraints
+// crossing the stroke drawn on the screen are severed. With the poke tool, a click pushes the
+// particles around the picked one away from it, see PokeSettings.
+
+use wgpu_bootstrap::{
+    cgmath::{Matrix4, SquareMatrix, Vector4},
+    egui, wgpu, Context,
+};
+
+use cloth::colliders::Attachment;
+use cloth::sim::{AsyncReadback, ClothSimulation, ConstraintKind, Instance};
+
+const PICK_RADIUS: f32 = 8.0; // points on screen around the pointer
+const GRAB_STIFFNESS: f32 = 0.3;
+const STROKE_SPACING: f32 = 4.0; // points between the recorded points of a cut
+
+/// What a click on the cloth does.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ClickTool {
+    #[default]
+    Inspect,
+    Pin,
+    Grab,
+    Cut,
+    Poke,
+}
+
+impl ClickTool {
+    pub const ALL: [ClickTool; 5] = [
+        ClickTool::Inspect,
+        ClickTool::Pin,
+        ClickTool::Grab,
+        ClickTool::Cut,
+        ClickTool::Poke,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClickTool::Inspect => "Inspect",
+            ClickTool::Pin => "Pin",
+            ClickTool::Grab => "Grab",
+            ClickTool::Cut => "Cut",
+            ClickTool::Poke => "Poke",
+        }
+    }
+}
+
+/// Impulse of the poke tool, outward from the picked particle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PokeSettings {
+    pub radius: f32,   // m, the impulse fades out linearly to it
+    pub strength: f32, // m/s added next to the picked particle
+}
+
+impl Default for PokeSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.1,
+            strength: 2.0,
+        }
+    }
+}
+
+// What was drawn on the screen to pick with
+enum PickShape {
+    Near(egui::Pos2),        // the particle drawn closest to the camera around the point
+    Inside(egui::Rect),      // every particle
+    Across(Vec<egui::Pos2>), // every constraint crossing the stroke
+}
+
+// With the camera it was seen through
+struct PickRequest {
+    view_projection: Matrix4<f32>,
+    screen: egui::Rect,
+    tool: ClickTool,
+    shape: PickShape,
+}
+
+/// Change to the cloth a pick asks for, made by the app.
+pub enum ClothEdit {
+    TogglePins(Vec<u32>),
+    Cut(Vec<(u32, u32)>), // particles at both ends of the constraints, each pair once
+    Poke { center: [f32; 3], settings: PokeSettings },
+}
+
+// From the press of the button to its release
+#[derive(Default)]
+struct Grab {
+    particle: Option<(u32, f32)>, // with its depth in normalized device coordinates, once picked
+    target: Option<[f32; 3]>,     // where the pointer ray crosses that depth
+}
+
+// The point at normalized device depth `depth` under `pointer`
+fn unproject(view_projection: Matrix4<f32>, screen: egui::Rect, pointer: egui::Pos2, depth: f32) -> Option<[f32; 3]> {
+    let x = (pointer.x - screen.min.x) / screen.width() * 2.0 - 1.0;
+    let y = 1.0 - (pointer.y - screen.min.y) / screen.height() * 2.0;
+    let world = view_projection.invert()? * Vector4::new(x, y, depth, 1.0);
+    Some([world.x / world.w, world.y / world.w, world.z / world.w])
+}
+
+// Proper crossings only, segments touching at an end don't cross
+fn segments_cross(a: egui::Pos2, b: egui::Pos2, c: egui::Pos2, d: egui::Pos2) -> bool {
+    let side = |from: egui::Pos2, to: egui::Pos2, point: egui::Pos2| (to - from).x * (point - from).y - (to - from).y * (point - from).x;
+    side(a, b, c) * side(a, b, d) < 0.0 && side(c, d, a) * side(c, d, b) < 0.0
+}
+
+// Constraints of `cloth` crossing `stroke` as drawn on the screen, `on_screen` has every particle
+fn cut_constraints(cloth: &ClothSimulation, on_screen: &[Option<(egui::Pos2, f32)>], stroke: &[egui::Pos2]) -> Vec<(u32, u32)> {
+    let bounds = egui::Rect::from_points(stroke);
+    let mut pairs = Vec::new();
+    for (particle, drawn) in (0u32..).zip(on_screen) {
+        let Some((a, _)) = *drawn else {
+            continue;
+        };
+        for constraint in cloth.particle_constraints(particle) {
+            let Some(&Some((b, _))) = on_screen.get(constraint.neighbor as usize) else {
+                continue;
+            };
+            if constraint.neighbor > particle
+                && bounds.intersects(egui::Rect::from_two_pos(a, b))
+                && stroke.windows(2).any(|segment| segments_cross(a, b, segment[0], segment[1]))
+            {
+                pairs.push((particle, constraint.neighbor));
+            }
+        }
+    }
+    pairs
+}
+
+#[derive(Default)]
+pub struct Inspector {
+    pub tool: ClickTool,
+    pub poke: PokeSettings,
+    box_start: Option<egui::Pos2>, // of the shift drag of the pin tool
+    grab: Option<Grab>,
+    stroke: Option<Vec<egui::Pos2>>, // of the cut tool, while the button is down
+    pick: Option<PickRequest>,
+    selected: Option<u32>,
+    state: Option<Instance>,       // of the selected particle, as last read back
+    readback: AsyncReadback<u32>,  // particle of every copy
+}
+
+impl Inspector {
+    // Clicks, boxes and strokes on the scene rather than on a window, answered by pick() once the
+    // particles are read back
+    pub fn pointer_input(&mut self, ctx: &egui::Context, view_projection: Matrix4<f32>) {
+        let (pressed, down, released, clicked, shift, pointer) = ctx.input(|input| {
+            (
+                input.pointer.primary_pressed(),
+                input.pointer.primary_down(),
+                input.pointer.primary_released(),
+                input.pointer.primary_clicked(),
+                input.modifiers.shift,
+                input.pointer.interact_pos(),
+            )
+        });
+        let Some(pointer) = pointer else {
+            return;
+        };
+        let tool = self.tool;
+        let request = |shape| PickRequest {
+            view_projection,
+            screen: ctx.screen_rect(),
+            tool,
+            shape,
+        };
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("pick shape")));
+        let outline = ctx.style().visuals.selection.stroke;
+        if !down || tool != ClickTool::Grab {
+            self.grab = None;
+        }
+        if tool == ClickTool::Grab {
+            if pressed && !ctx.is_pointer_over_area() {
+                self.grab = Some(Grab::default());
+                self.pick = Some(request(PickShape::Near(pointer)));
+            }
+            if let Some(Grab {
+                particle: Some((_, depth)),
+                target,
+            }) = &mut self.grab
+            {
+                *target = unproject(view_projection, ctx.screen_rect(), pointer, *depth);
+            }
+            return;
+        }
+        if tool == ClickTool::Cut {
+            if pressed && !ctx.is_pointer_over_area() {
+                self.stroke = Some(vec![pointer]);
+            }
+            let Some(stroke) = &mut self.stroke else {
+                return;
+            };
+            if stroke.last().is_some_and(|last| last.distance(pointer) > STROKE_SPACING) {
+                stroke.push(pointer);
+            }
+            if !released {
+                painter.add(egui::Shape::line(stroke.clone(), outline));
+                return;
+            }
+            if let Some(stroke) = self.stroke.take().filter(|stroke| stroke.len() > 1) {
+                self.pick = Some(request(PickShape::Across(stroke)));
+            }
+            return;
+        }
+        if pressed && shift && tool == ClickTool::Pin && !ctx.is_pointer_over_area() {
+            self.box_start = Some(pointer);
+        }
+        if let Some(start) = self.box_start {
+            let area = egui::Rect::from_two_pos(start, pointer);
+            if !released {
+                painter.rect_stroke(area, 0.0, outline);
+                return;
+            }
+            self.box_start = None;
+            // Barely moved, taken as the click it is
+            if !clicked {
+                self.pick = Some(request(PickShape::Inside(area)));
+                return;
+            }
+        }
+        if clicked && !ctx.is_pointer_over_area() {
+            self.pick = Some(request(PickShape::Near(pointer)));
+        }
+    }
+
+    // Not while the shift drag of the pin tool is drawing a box, a particle is held or a cut drawn
+    pub fn wants_drag(&self, input: &egui::InputState) -> bool {
+        self.box_start.is_some()
+            || self.grab.is_some()
+            || self.stroke.is_some()
+            || (self.tool == ClickTool::Pin && input.modifiers.shift)
+    }
+
+    // Pulls the held particle to the pointer, for the steps until the button is released
+    pub fn grab_attachment(&self) -> Option<Attachment> {
+        match self.grab {
+            Some(Grab {
+                particle: Some((particle, _)),
+                target: Some(target),
+            }) => Some(Attachment {
+                particle,
+                target,
+                stiffness: GRAB_STIFFNESS,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn wants_pick(&self) -> bool {
+        self.pick.is_some()
+    }
+
+    pub fn selected(&self) -> Option<u32> {
+        self.selected
+    }
+
+    // The pins to toggle or the constraints to cut of `cloth`, the inspected particle is selected
+    // right here. A click next to no particle leaves the selection as it was.
+    pub fn pick(&mut self, instances: &[Instance], cloth: &ClothSimulation) -> Option<ClothEdit> {
+        let request = self.pick.take()?;
+        let size = request.screen.size();
+        // Where every particle is drawn, with its depth in normalized device coordinates
+        let on_screen: Vec<Option<(egui::Pos2, f32)>> = instances
+            .iter()
+            .map(|instance| {
+                let [x, y, z] = instance.position();
+                let clip = request.view_projection * Vector4::new(x, y, z, 1.0);
+                if clip.w <= 0.0 {
+                    return None;
+                }
+                let position = request.screen.min
+                    + egui::vec2((clip.x / clip.w + 1.0) * 0.5 * size.x, (1.0 - clip.y / clip.w) * 0.5 * size.y);
+                Some((position, clip.z / clip.w))
+            })
+            .collect();
+
+        let pointer = match request.shape {
+            PickShape::Near(pointer) => pointer,
+            PickShape::Inside(area) => {
+                let inside = (0u32..)
+                    .zip(&on_screen)
+                    .filter(|(_, drawn)| drawn.is_some_and(|(position, _)| area.contains(position)))
+                    .map(|(particle, _)| particle);
+                return Some(ClothEdit::TogglePins(inside.collect()));
+            }
+            PickShape::Across(stroke) => return Some(ClothEdit::Cut(cut_constraints(cloth, &on_screen, &stroke))),
+        };
+        let nearest = (0u32..)
+            .zip(&on_screen)
+            .filter_map(|(particle, drawn)| {
+                let (position, depth) = (*drawn)?;
+                (position.distance(pointer) < PICK_RADIUS).then_some((particle, depth))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((particle, depth)) = nearest else {
+            // Nothing to hold, the drag goes back to the camera
+            self.grab = None;
+            return None;
+        };
+        match request.tool {
+            ClickTool::Inspect => {
+                self.selected = Some(particle);
+                self.state = Some(instances[particle as usize]);
+                None
+            }
+            ClickTool::Pin => Some(ClothEdit::TogglePins(vec![particle])),
+            ClickTool::Grab => {
+                // Unless released in the meantime
+                if let Some(grab) = &mut self.grab {
+                    grab.particle = Some((particle, depth));
+                }
+                None
+            }
+            ClickTool::Poke => Some(ClothEdit::Poke {
+                center: instances[particle as usize].position(),
+                settings: self.poke,
+            }),
+            ClickTool::Cut => None, // draws strokes only
+        }
+    }
+
+    // Copies the selected particle out of `instance_buffer`, the latest state
+    pub fn copy(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder, instance_buffer: &wgpu::Buffer) {
+        let Some(particle) = self.selected else {
+            return;
+        };
+        let size = std::mem::size_of::<Instance>() as wgpu::BufferAddress;
+        let start = wgpu::BufferAddress::from(particle) * size;
+        if start + size <= instance_buffer.size() {
+            self.readback.copy_range(context, encoder, instance_buffer, start..start + size, particle);
+        }
+    }
+
+    // Once the frame is submitted, keeps the latest copy of the particle still selected
+    pub fn poll(&mut self, context: &Context) {
+        for (particle, bytes) in self.readback.poll(context) {
+            if Some(particle) == self.selected {
+                self.state = Some(bytemuck::pod_read_unaligned(&bytes));
+            }
+        }
+    }
+
+    // The indices mean nothing once the particles are rebuilt
+    pub fn clear(&mut self) {
+        self.box_start = None;
+        self.grab = None;
+        self.stroke = None;
+        self.pick = None;
+        self.selected = None;
+        self.state = None;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, cloth: Option<&ClothSimulation>) {
+        let (Some(particle), Some(state), Some(cloth)) = (self.selected, self.state, cloth) else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new(format!("Particle {particle}"))
+            .open(&mut open)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                let [x, y, z] = state.position();
+                let [vx, vy, vz] = state.speed();
+                ui.label(format!("Position: ({x:.4}, {y:.4}, {z:.4}) m"));
+                ui.label(format!("Velocity: ({vx:.4}, {vy:.4}, {vz:.4}) m/s"));
+                ui.label(format!("Speed: {:.4} m/s", (vx * vx + vy * vy + vz * vz).sqrt()));
+                ui.label(format!("Inverse mass: {}", state.inverse_mass()));
+                ui.label(if state.inverse_mass() == 0.0 { "Pinned" } else { "Free" });
+
+                ui.separator();
+                ui.label("Constraints, as built").on_hover_text("Torn ones are still listed");
+                egui::Grid::new("inspected constraints").striped(true).show(ui, |ui| {
+                    ui.strong("Neighbor");
+                    ui.strong("Kind");
+                    ui.strong("Rest length");
+                    ui.strong("Tears at");
+                    ui.end_row();
+                    for constraint in cloth.particle_constraints(particle) {
+                        let kind = ConstraintKind::ALL
+                            .into_iter()
+                            .find(|&kind| kind as u32 == constraint.kind)
+                            .map_or("?", ConstraintKind::name);
+                        ui.label(constraint.neighbor.to_string());
+                        ui.label(kind);
+                        ui.label(format!("{:.2} mm", constraint.rest_length * 1e3));
+                        if constraint.tear_strain > 0.0 {
+                            ui.label(format!("{:.0}%", constraint.tear_strain * 100.0));
+                        } else {
+                            ui.label("Never");
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        if !open {
+            self.clear();
+        }
+    }
+}
@@ -1,14 +1,48 @@
 use wgpu_bootstrap::{
     cgmath::{self, InnerSpace}, egui,
-    util::{
-        geometry::icosphere,
-        orbit_camera::{CameraUniform, OrbitCamera},
-    },
+    util::geometry::icosphere,
     wgpu::{self, util::DeviceExt},
     App, Context,
 };
+use std::fs;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+use cloth::colliders::{Attachment, Collider, Fan, PinGroup, MAX_COLLIDERS, MAX_FANS};
+use cloth::render::{
+    attribute_vertex_desc, expand_includes, load_attribute_values, occlusion_vertex_desc, supported_sample_count,
+    tint_vertex_desc, transparent_targets, uv_vertex_desc, AlbedoTexture, AttributeSource, CameraMode, CameraUniform,
+    ColorRamp, ContactView, EdgeView, GridFloor, Highlights, Lighting, MainView, MotionBlur, OrbitCamera, ParticleLod,
+    PictureInPicture, PipView, RampUniform, ShadowMap, ShadowSettings, Sky, TemporalAntiAliasing, Tonemapping,
+    Transparency, HDR_FORMAT, VELOCITY_FORMAT,
+};
+use cloth::scene::{Asset, AssetLoad, ColliderMotion, Scene, ScenePreset, DROPPED_SQUARE};
+use cloth::sim::{
+    checksum, ClothSimulation, ConvergenceSettings, DesyncMonitor, DivergenceMonitor, EnergyPlot, Instance,
+    LodController, LodSettings, Material, MaterialBlend, SelfCollisionSettings, SimParams, SolverMode, TearMap,
+    CHECKSUM_FILE, DYNAMIC_FRICTION, ENERGY_SAMPLE_INTERVAL, MAX_COLLISION_PIECES, SLEEP_SPEED, STATIC_FRICTION,
+    THICKNESS, TIME_STEP,
+};
+
+use crate::bake::{self, GridSurface, BAKE_DIR};
+use crate::batch_render::{BatchRender, FRAMES_DIR};
+use crate::bookmarks::CameraBookmarks;
+use crate::checkpoint::Checkpoint;
+use crate::export::{CameraPath, Recording, CAMERA_PATH_FILE, EXPORT_DIR};
+use crate::frame_graph::{FrameGraph, FrameResources, Pass, Readback, Resource};
+#[cfg(feature = "gamepad")]
+use crate::gamepad::Gamepad;
+use crate::gravity_ramp::GravityRamp;
+use crate::inspector::{ClickTool, ClothEdit, Inspector};
+use crate::perf_hud::PerfHud;
+use crate::replay::{ReplayReader, ReplayWriter, REPLAY_FILE};
+use crate::scene_file::SceneFile;
+use crate::study::{self, Study, SAMPLE_INTERVAL, STUDY_FILE};
+use crate::turntable::Turntable;
+
+mod panels;
+mod passes;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
@@ -46,89 +80,145 @@ impl Vertex {
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Instance {
-    position: [f32; 4],
-    speed: [f32; 4],
-}
-
-impl Instance {
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[
-                wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 3,
-                format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                offset: std::mem::size_of::<[f32;3]>() as wgpu::BufferAddress,
-                shader_location: 4,
-                format: wgpu::VertexFormat::Float32x3,
-                },
-            ],
-        
-        }
-    
+// One instance per collider, its center and radius
+fn collider_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 3,
+            format: wgpu::VertexFormat::Float32x4,
+        }],
     }
 }
 
-struct Spring {
-    stiffness: f32,
-    rest_length: f32,
-    index_a: u32,
-    index_b: u32,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct TimeUniform {
-    generation_duration: f32,
-}
-
 pub struct InstanceApp {
     vertex_buffer: wgpu::Buffer,
-    instance_buffer: [wgpu::Buffer; 2],
     index_buffer: wgpu::Buffer,
     render_pipeline: wgpu::RenderPipeline,
-    compute_pipeline: wgpu::ComputePipeline,
-    num_indices: u32,
-    num_instances: u32,
+    motion_render_pipeline: wgpu::RenderPipeline, // also writes the motion of every fragment
+    double_sided_pipelines: [wgpu::RenderPipeline; 2], // same two without back-face culling
+    transparent_pipeline: wgpu::RenderPipeline,        // sheer fabrics, both faces, see oit.rs
+    double_sided: bool,
+    interpolation_buffer: wgpu::Buffer,
+    interpolation_bind_group: wgpu::BindGroup,
+    interpolate: bool, // draws the cloth in between the last two steps instead of at the latest one
+    ramp_buffer: wgpu::Buffer,
+    lighting: Lighting,
+    shadow_map: ShadowMap,
+    albedo: AlbedoTexture,
+    albedo_path: String,
+    albedo_status: String, // why the last texture didn't load
+    attribute_source: AttributeSource,
+    attribute_ramp: ColorRamp,
+    attribute_path: String,
+    attribute_status: String,
+    highlights: Highlights,
+    perf_hud: PerfHud,
+    energy_plot: EnergyPlot,
+    inspector: Inspector,
+    ramp_range: f32, // top of the color ramp of the sources computed on the GPU
+    kinematics_step: u64, // step count at the last kinematics pass
+    self_shadow: ShadowSettings,
+    scene: Scene,
+    scene_status: String, // why the last load failed
+    loading: Option<AssetLoad>, // read in the background, one at a time, see finish_loading()
+    wave_corner: bool,    // a hand holding the last corner of the first piece
+    #[cfg(feature = "gamepad")]
+    gamepad: Gamepad, // moves the sphere along with the keyboard
+    wave_time: f32,
+    checkpoint: Checkpoint,
+    time_scale: f32, // simulated time per step over the time step
+    paused: bool,
+    substep_requested: bool, // one substep taken while paused
+    start_paused: bool, // applied whenever the cloth is dropped again
+    gravity_ramp: GravityRamp,
+    gravity: f32,       // m/s², at the end of the ramp
+    time_step: f32,     // simulated seconds per step, TIME_STEP unless changed
+    drop_step: u64,     // step_count when the cloth was last dropped
+    guard_count: u32, // particles reset by the guard pass after an explosion, since the last drop
+    convergence: ConvergenceSettings,
+    self_collision: SelfCollisionSettings,
+    lod: LodSettings,
+    lod_controller: LodController,
+    desync: DesyncMonitor, // checksums of this run since the last drop
+    terrain_seed: u32,     // of the last generated terrain
+    skipped_iterations: u32, // by the early termination of the solver, since the last drop
+    static_friction: f32, // of the sphere, see SimParams::set_friction()
+    thickness: f32,       // m, see SimParams::set_thickness()
+    sleep_steps: u32,     // still steps until a particle sleeps, 0 never, see SimParams::set_sleeping()
+    sleep_speed: f32,     // m/s
+    dynamic_friction: f32,
+    solver_mode: SolverMode,
+    substeps: u32,
+    iterations: u32,
+    particle_lod: ParticleLod,
     camera: OrbitCamera,
+    comparison_camera: OrbitCamera, // follows the camera into each half of a comparison
     generation_duration: Duration,
     last_generation: Instant,
-    bind_group: [wgpu::BindGroup; 2],
+    stepped_this_frame: bool,
+    frame_graph: FrameGraph<InstanceApp>,
     sphere_index_buffer: wgpu::Buffer,
     sphere_vertex_buffer: wgpu::Buffer,
+    sphere_instance_buffer: wgpu::Buffer, // center and radius of every collider
     num_sphere_indices: u32,
+    num_spheres: u32,
     sphere_render_pipeline: wgpu::RenderPipeline,
-    time_buffer: wgpu::Buffer, // Add this field
+    motion_sphere_render_pipeline: wgpu::RenderPipeline,
+    contact_view: ContactView,
+    edge_view: EdgeView,
+    sky: Sky,
+    grid: GridFloor,
+    step_count: u64,
+    recording: Option<Recording>,
+    replay: Option<ReplayWriter>,
+    replay_playback: Option<ReplayReader>, // replaces the solver while it plays
+    batch: Option<BatchRender>,            // same
+    batch_size: [u32; 2],
+    bake_requested: bool, // the drape is baked from the state of the next frame
+    frame_requested: bool, // the camera frames the cloth as it is in the next frame
+    divergence: Option<DivergenceMonitor>, // checks the Jacobi steps against the CPU reference
+    study: Option<Study>,
+    study_runs: u32,
+    study_seed: u64,
+    export_interval: u64,
+    camera_playback: Option<(CameraPath, u64)>, // path and the step its playback started at
+    bookmarks: CameraBookmarks,
+    turntable: Turntable,
+    export_status: String,
+    controls: ControlsPlacement,
+    pip: PictureInPicture,
+    main_view: MainView,
+    tonemapping: Tonemapping,
+    transparency: Transparency,
+    motion_blur: MotionBlur,
+    taa: TemporalAntiAliasing,
 }
 
-fn generate_grid(
+// Small sphere drawn at every particle, at every level of detail of particle_lod.rs
+fn generate_particle_mesh(
     context: &Context,
-    rows: u32,
-    cols: u32,
-    spacing: f32,
-    displacement: f32,
     sphere_scale: f32,
     sphere_color: [f32; 3],
-) -> (Vec<Vertex>, wgpu::Buffer, Vec<Instance>, Vec<Instance>, Vec<u32>) {  // Added Vec<u32> to return type, and Added second instances list
-    // Generate icosphere
-    let (positions, indices) = icosphere(2);
-
-    // Create vertices with positions and colors
-    let vertices: Vec<Vertex> = positions
-        .iter()
-        .map(|position| Vertex {
+) -> (Vec<Vertex>, wgpu::Buffer, ParticleLod) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    // Coarsest first
+    let counts = [0, 1, 2].map(|subdivisions| {
+        // Generate icosphere
+        let (positions, level_indices) = icosphere(subdivisions);
+
+        // Create vertices with positions and colors
+        vertices.extend(positions.iter().map(|position| Vertex {
             position: (*position * sphere_scale).into(),
-            normal: [0.0, 0.0, 0.0],
+            normal: position.normalize().into(),
             color: sphere_color,
-        })
-        .collect();
+        }));
+        indices.extend_from_slice(&level_indices);
+        (level_indices.len() as u32, positions.len() as u32)
+    });
 
     // Create index buffer
     let index_buffer = context
@@ -139,59 +229,59 @@ fn generate_grid(
             usage: wgpu::BufferUsages::INDEX,
         });
 
-    // Generate grid of instances
-    let instances: Vec<Instance> = (0..rows)
-        .flat_map(|row| {
-            (0..cols).map(move |col| {
-                Instance {
-                    position: [
-                        (col as f32 - cols as f32 / 2.0) * spacing,
-                        displacement,
-                        (row as f32 - rows as f32 / 2.0) * spacing,
-                        0.0,
-                    ],
-                    speed: [0.0, 0.0, 0.0, 0.0],
-                }
-            })
-        })
-        .collect();
+    (vertices, index_buffer, ParticleLod::new(sphere_scale, counts))
+}
 
-    // Create a second copy of the instances list
-    let instances_copy = instances.clone();
+const DEFAULT_SUBSTEPS: u32 = 1;
+const TIME_SCALES: std::ops::RangeInclusive<f32> = 0.1..=4.0;
+const DROP_AGAIN_KEY: egui::Key = egui::Key::R;
+const PAUSE_KEY: egui::Key = egui::Key::Space;
+const SUBSTEP_KEY: egui::Key = egui::Key::N; // while paused
+const SLOWER_KEY: egui::Key = egui::Key::OpenBracket; // halves the time scale
+const FASTER_KEY: egui::Key = egui::Key::CloseBracket; // doubles it
+const FRAME_KEY: egui::Key = egui::Key::F;
+const SPHERE_SPEED: f32 = 0.5; // m/s, of the sphere driven from the keyboard or a gamepad
+const FLY_SPEED: f32 = 1.0; // m/s, of the flying camera
+const GUARD_READBACK_INTERVAL: u64 = 30; // steps, every readback waits for the GPU
+const CONVERGENCE_READBACK_INTERVAL: u64 = 30; // steps, same
+const PERF_READBACK_INTERVAL: u64 = 30; // steps, same
+const PIN_COMPLIANCE: f32 = 0.01; // m/N, of pins made elastic, a few mm under the tablecloth
+const DRUM_SPIN: f32 = 4.0; // rad/s, 0.4 m/s at the surface of the added drum
+const DEFAULT_BACK_COLOR: [f32; 3] = [0.55, 0.2, 0.2];
+const STANDARD_GRAVITY: f32 = 9.8; // m/s², the GRAVITY of the shaders, scaled from there
 
-    (vertices, index_buffer, instances, instances_copy, indices)  // Return indices as well
+// Where the controls are shown
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ControlsPlacement {
+    SidePanel, // docked to the right of the window
+    Window,    // floating over the scene
+    Detached,  // own OS window when the backend supports multiple viewports, floating otherwise
 }
 
+impl ControlsPlacement {
+    const ALL: [ControlsPlacement; 3] = [ControlsPlacement::SidePanel, ControlsPlacement::Window, ControlsPlacement::Detached];
 
-const WORKGROUP_SIZE: u32 = 128;
-const GRID_SIZE: u32 = 256;
+    fn name(self) -> &'static str {
+        match self {
+            ControlsPlacement::SidePanel => "Side panel",
+            ControlsPlacement::Window => "Window",
+            ControlsPlacement::Detached => "Own window",
+        }
+    }
+}
 
 impl InstanceApp {
-    pub fn new(context: &Context) -> Self {
-
-        let (vertices, index_buffer, instances, instances_copy , indices) = generate_grid(
-            &context,
-            GRID_SIZE,          // rows
-            GRID_SIZE,          // cols
-            0.002,        // spacing (closer together for cloth-like appearance)
-            1.0,         // displacement, where it starts on the y axis
+    // `sample_count` is the requested multisampling of the scene, lowered to what the device
+    // supports
+    pub fn new(context: &Context, preset: ScenePreset, mesh_path: String, sample_count: u32) -> Self {
+        let sample_count = supported_sample_count(context, sample_count);
+        let tonemapping = Tonemapping::new(context);
+
+        let (vertices, index_buffer, particle_lod) = generate_particle_mesh(
+            context,
             0.003,        // sphere_scale (smaller spheres to look like connection points)
             [0.1, 0.1, 0.1]    // color
         );
-        
-
-        let num_indices = indices.len() as u32;
-        let num_instances = instances.len() as u32;
-
-        let time_uniform = TimeUniform {
-            generation_duration: Duration::new(0, 1_000_000).as_secs_f32(), // Use the generation_duration from the struct
-        };
-        
-        let time_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Time Uniform Buffer"),
-            contents: bytemuck::cast_slice(&[time_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
 
         let vertex_buffer =
             context
@@ -202,33 +292,16 @@ impl InstanceApp {
                     usage: wgpu::BufferUsages::VERTEX,
                 });
 
-
-        let instance_buffer = [
-            context
-                .device()
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Instance Buffer Ping"),
-                    contents: bytemuck::cast_slice(&instances.as_slice()),
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX, // Add VERTEX usage
-                }),
-            context
-                .device()
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Instance Buffer Pong"),
-                    contents: bytemuck::cast_slice(&instances_copy.as_slice()),
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX, // Add VERTEX usage
-                }),
-        ];
         // Création de la sphère
         let (positions, indices) = icosphere(3);
-        let sphere_radius = 0.3;
 
+        // Unit sphere, scaled to every collider in the vertex shader
         let vertices: Vec<Vertex> = positions
             .iter()
             .map(|position| {
                 let normal = position.normalize();
                 Vertex {
-                    position: (normal * sphere_radius).into(),
+                    position: normal.into(),
                     normal: normal.into(),
                     color: [0.8, 0.3, 0.3],
                 }
@@ -243,6 +316,13 @@ impl InstanceApp {
                 usage: wgpu::BufferUsages::VERTEX,
             });
 
+        let sphere_instance_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sphere Instance Buffer"),
+            size: (MAX_COLLIDERS * std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let sphere_index_buffer = context
             .device()
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -258,104 +338,117 @@ impl InstanceApp {
             .device()
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+                source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("shader.wgsl")).into()),
             });
 
-        let compute_shader = context
-        .device()
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(
-            include_str!("compute.wgsl")
-                .replace("WORKGROUP_SIZE", &format!("{}", WORKGROUP_SIZE))
-                .into()
-            ),
-        });
-
         let camera_bind_group_layout = context
             .device()
             .create_bind_group_layout(&CameraUniform::desc());
+        let albedo = AlbedoTexture::new(context);
 
-        let instance_bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Bind Group Layout"),
+        // Written every frame by update()
+        let interpolation_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Interpolation Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Written by apply_attribute()
+        let ramp_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Color Ramp Buffer"),
+            contents: bytemuck::bytes_of(&RampUniform::OFF),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let interpolation_bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Interpolation Bind Group Layout"),
+                    entries: &[uniform_entry(0), uniform_entry(2)],
+                });
+        let interpolation_bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Interpolation Bind Group"),
+            layout: &interpolation_bind_group_layout,
             entries: &[
-                wgpu::BindGroupLayoutEntry {
+                wgpu::BindGroupEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+                    resource: interpolation_buffer.as_entire_binding(),
                 },
-
-                // Uniform buffer for time
-                wgpu::BindGroupLayoutEntry {
+                wgpu::BindGroupEntry {
                     binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },    
+                    resource: ramp_buffer.as_entire_binding(),
+                },
             ],
         });
+        let shadow_map = ShadowMap::new(
+            context,
+            &interpolation_bind_group_layout,
+            &[Vertex::desc(), Instance::desc(), Instance::previous_desc()],
+            &[Vertex::desc(), collider_desc()],
+        );
+        let lighting = Lighting::new(context, &shadow_map);
 
         let pipeline_layout =
             context
                 .device()
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("Render Pipeline Layout"),
-                    bind_group_layouts: &[&camera_bind_group_layout],
+                    bind_group_layouts: &[
+                        &camera_bind_group_layout,
+                        &interpolation_bind_group_layout,
+                        lighting.bind_group_layout(),
+                        albedo.bind_group_layout(),
+                    ],
                     push_constant_ranges: &[],
                 });
 
-        let compute_pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Pipeline Layout"),
-            bind_group_layouts: &[&instance_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline =
+        // The motion variant also writes the screen-space motion of every fragment, the double-sided
+        // one draws the back faces too and the transparent one leaves the depth to the opaque scene
+        let create_render_pipeline = |label: &str,
+                                      fragment_entry: &str,
+                                      targets: &[Option<wgpu::ColorTargetState>],
+                                      cull_mode: Option<wgpu::Face>,
+                                      depth_write_enabled: bool| {
             context
                 .device()
                 .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
+                    label: Some(label),
                     layout: Some(&pipeline_layout),
                     vertex: wgpu::VertexState {
                         module: &shader,
                         entry_point: "vs_main",
-                        buffers: &[Vertex::desc(), Instance::desc()],
+                        buffers: &[
+                            Vertex::desc(),
+                            Instance::desc(),
+                            Instance::previous_desc(),
+                            attribute_vertex_desc(),
+                            occlusion_vertex_desc(),
+                            uv_vertex_desc(),
+                            tint_vertex_desc(),
+                        ],
                         compilation_options: wgpu::PipelineCompilationOptions::default(),
                     },
                     fragment: Some(wgpu::FragmentState {
                         module: &shader,
-                        entry_point: "fs_main",
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: context.format(),
-                            blend: Some(wgpu::BlendState::REPLACE),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })],
+                        entry_point: fragment_entry,
+                        targets,
                         compilation_options: wgpu::PipelineCompilationOptions::default(),
                     }),
                     primitive: wgpu::PrimitiveState {
                         topology: wgpu::PrimitiveTopology::TriangleList,
                         strip_index_format: None,
                         front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: Some(wgpu::Face::Back),
+                        cull_mode,
                         // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
                         polygon_mode: wgpu::PolygonMode::Fill,
                         // Requires Features::DEPTH_CLIP_CONTROL
@@ -365,219 +458,796 @@ impl InstanceApp {
                     },
                     depth_stencil: Some(wgpu::DepthStencilState {
                         format: context.depth_stencil_format(),
-                        depth_write_enabled: true,
+                        depth_write_enabled,
                         depth_compare: wgpu::CompareFunction::Less,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     }),
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: sample_count,
                         mask: !0,
                         alpha_to_coverage_enabled: false,
                     },
                     multiview: None,
                     cache: None,
-                });
+                })
+        };
+        let color_target = Some(wgpu::ColorTargetState {
+            format: HDR_FORMAT,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let velocity_target = Some(wgpu::ColorTargetState {
+            format: VELOCITY_FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let cull = Some(wgpu::Face::Back);
+        let render_pipeline = create_render_pipeline("Render Pipeline", "fs_main", std::slice::from_ref(&color_target), cull, true);
+        let motion_targets = [color_target.clone(), velocity_target.clone()];
+        let motion_render_pipeline =
+            create_render_pipeline("Motion Render Pipeline", "fs_motion", &motion_targets, cull, true);
+        let double_sided_pipelines = [
+            create_render_pipeline("Double-Sided Render Pipeline", "fs_main", std::slice::from_ref(&color_target), None, true),
+            create_render_pipeline("Double-Sided Motion Render Pipeline", "fs_motion", &motion_targets, None, true),
+        ];
+        let transparent_pipeline = create_render_pipeline(
+            "Transparent Render Pipeline",
+            "fs_transparent",
+            &transparent_targets(),
+            None,
+            false,
+        );
+
+        let mut scene = Scene::new(preset, mesh_path);
+        // Built on the first frames, the window shows up right away even for a large mesh
+        let loading = Some(scene.load_cloth());
+        let checkpoint = Checkpoint::install(&mut scene);
+        let gravity_ramp = GravityRamp::install(&mut scene);
 
         let aspect = context.size().x / context.size().y;
         let mut camera = OrbitCamera::new(context, 45.0, aspect, 0.1, 100.0);
         camera
             .set_polar(cgmath::point3(1.5, 0.0, 0.0))
             .update(context);
-
-        let compute_pipeline =
-        context
-            .device()
-            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader,
-            entry_point: "computeMain",
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
-            });
-        
-
-        let bind_group = [
-            context
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("Bind Group Ping"),
-                    layout: &instance_bind_group_layout,
-                    entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: instance_buffer[0].as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: instance_buffer[1].as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: time_buffer.as_entire_binding(),
-                    }
-                    ],
-                }),
-            context
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Bind Group Pong"),
-                layout: &instance_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: instance_buffer[1].as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: instance_buffer[0].as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: time_buffer.as_entire_binding(),
-                    }
-                ],
-                }),
-            ];
+        let taa = TemporalAntiAliasing::new(context, &camera, sample_count, &tonemapping);
+        let comparison_camera = camera.duplicate(context);
 
         let sphere_shader = context
         .device()
         .create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Sphere Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("sphere_shader.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("sphere_shader.wgsl")).into()),
         });
     
         let sphere_pipeline_layout = context
             .device()
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Sphere Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout], // Use the same camera bind group
+                bind_group_layouts: &[&camera_bind_group_layout, lighting.bind_group_layout()], // Use the same camera bind group
                 push_constant_ranges: &[],
             });
 
-        let sphere_render_pipeline = context
-            .device()
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Sphere Render Pipeline"),
-                layout: Some(&sphere_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &sphere_shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::desc()], // Use the same vertex layout as the grid
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &sphere_shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: context.format(),
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: context.depth_stencil_format(),
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-                cache: None,
-            });
+        let create_sphere_pipeline = |label: &str, fragment_entry: &str, targets: &[Option<wgpu::ColorTargetState>]| {
+            context
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&sphere_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &sphere_shader,
+                        entry_point: "vs_main",
+                        buffers: &[
+                            Vertex::desc(), // Use the same vertex layout as the grid
+                            collider_desc(),
+                        ],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &sphere_shader,
+                        entry_point: fragment_entry,
+                        targets,
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: Some(wgpu::Face::Back),
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: context.depth_stencil_format(),
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                })
+        };
+        let contact_view = ContactView::new(
+            context,
+            &camera_bind_group_layout,
+            [color_target.clone(), velocity_target.clone()],
+            sample_count,
+        );
+        let edge_view = EdgeView::new(
+            context,
+            &camera_bind_group_layout,
+            [color_target.clone(), velocity_target.clone()],
+            sample_count,
+        );
+        let sky = Sky::new(
+            context,
+            &camera_bind_group_layout,
+            lighting.bind_group_layout(),
+            [color_target.clone(), velocity_target.clone()],
+            sample_count,
+        );
+        let grid = GridFloor::new(
+            context,
+            &camera_bind_group_layout,
+            lighting.bind_group_layout(),
+            [color_target.clone(), velocity_target.clone()],
+            sample_count,
+        );
+        let sphere_render_pipeline = create_sphere_pipeline("Sphere Render Pipeline", "fs_main", std::slice::from_ref(&color_target));
+        let motion_sphere_render_pipeline =
+            create_sphere_pipeline("Motion Sphere Render Pipeline", "fs_motion", &[color_target, velocity_target]);
         Self {
             vertex_buffer,
-            instance_buffer,
             index_buffer,
             render_pipeline,
-            compute_pipeline,
-            num_indices,
-            num_instances,
+            motion_render_pipeline,
+            double_sided_pipelines,
+            transparent_pipeline,
+            double_sided: false,
+            interpolation_buffer,
+            interpolation_bind_group,
+            interpolate: true,
+            ramp_buffer,
+            lighting,
+            shadow_map,
+            albedo,
+            albedo_path: "fabric.png".to_string(),
+            albedo_status: String::new(),
+            attribute_source: AttributeSource::None,
+            attribute_ramp: ColorRamp::Viridis,
+            attribute_path: "attribute.txt".to_string(),
+            attribute_status: String::new(),
+            highlights: Highlights::default(),
+            perf_hud: PerfHud::new(context),
+            energy_plot: EnergyPlot::default(),
+            inspector: Inspector::default(),
+            ramp_range: 1.0,
+            kinematics_step: 0,
+            self_shadow: ShadowSettings::default(),
+            scene,
+            scene_status: String::new(),
+            loading,
+            wave_corner: false,
+            #[cfg(feature = "gamepad")]
+            gamepad: Gamepad::new(),
+            wave_time: 0.0,
+            checkpoint,
+            time_scale: 1.0,
+            paused: false,
+            substep_requested: false,
+            start_paused: false,
+            gravity_ramp,
+            gravity: STANDARD_GRAVITY,
+            time_step: TIME_STEP,
+            drop_step: 0,
+            guard_count: 0,
+            convergence: ConvergenceSettings::default(),
+            self_collision: SelfCollisionSettings::default(),
+            lod: LodSettings::default(),
+            lod_controller: LodController::default(),
+            desync: DesyncMonitor::default(),
+            terrain_seed: 0,
+            skipped_iterations: 0,
+            static_friction: STATIC_FRICTION,
+            thickness: THICKNESS,
+            sleep_steps: 0,
+            sleep_speed: SLEEP_SPEED,
+            dynamic_friction: DYNAMIC_FRICTION,
+            solver_mode: SolverMode::Jacobi,
+            substeps: DEFAULT_SUBSTEPS,
+            iterations: SolverMode::Jacobi.default_iterations(),
+            particle_lod,
             camera,
+            comparison_camera,
             generation_duration: Duration::from_micros(1_600), // 1.6ms
             last_generation: Instant::now(),
-            bind_group,
+            stepped_this_frame: false,
+            frame_graph: Self::frame_graph(),
             sphere_index_buffer,
             sphere_vertex_buffer,
+            sphere_instance_buffer,
             num_sphere_indices: indices.len() as u32,
+            num_spheres: 0,
             sphere_render_pipeline,
-            time_buffer,
+            motion_sphere_render_pipeline,
+            contact_view,
+            edge_view,
+            sky,
+            grid,
+            step_count: 0,
+            recording: None,
+            replay: None,
+            bake_requested: false,
+            frame_requested: false,
+            divergence: None,
+            study: None,
+            study_runs: 8,
+            study_seed: 1,
+            replay_playback: None,
+            batch: None,
+            batch_size: [3840, 2160],
+            export_interval: 1,
+            camera_playback: None,
+            bookmarks: CameraBookmarks::default(),
+            turntable: Turntable::default(),
+            export_status: String::new(),
+            controls: ControlsPlacement::SidePanel,
+            pip: PictureInPicture::new(context, sample_count, &tonemapping),
+            main_view: MainView::new(context, sample_count, &tonemapping),
+            motion_blur: MotionBlur::new(context, sample_count, &tonemapping),
+            tonemapping,
+            transparency: Transparency::new(context),
+            taa,
+        }
+    }
+
+    // Whether the main view is drawn offscreen by its pass, anti-aliasing and motion blur draw
+    // their own view instead
+    fn draws_main_view(&self) -> bool {
+        !self.taa.enabled && !self.motion_blur.enabled
+    }
+
+    // The scene drawn by the pass of the main view, or its sheer fabrics if `transparent`. Split in
+    // halves of the `size` of the view while comparing, the cloth on the left and its twin on the
+    // right, seen from the same camera.
+    fn draw_main_view(&self, render_pass: &mut wgpu::RenderPass<'_>, size: (u32, u32), transparent: bool) {
+        let Some(twin) = self.scene.comparison() else {
+            if transparent {
+                self.draw_transparent(render_pass, self.camera.bind_group(), self.scene.cloth());
+            } else {
+                self.draw_scene(render_pass, self.camera.bind_group(), false, self.scene.cloth());
+            }
+            return;
+        };
+        let (half_width, height) = (0.5 * size.0 as f32, size.1 as f32);
+        for (x, cloth) in [(0.0, self.scene.cloth()), (half_width, Some(twin))] {
+            render_pass.set_viewport(x, 0.0, half_width, height, 0.0, 1.0);
+            if transparent {
+                self.draw_transparent(render_pass, self.comparison_camera.bind_group(), cloth);
+            } else {
+                self.draw_scene(render_pass, self.comparison_camera.bind_group(), false, cloth);
+            }
+        }
+    }
+
+    // `cloth` and the sphere as seen from `camera_bind_group`, shared by the main view and the picture-in-picture.
+    // `motion_vectors` selects the pipelines that also fill the velocity target of the motion blur.
+    // The sheer fabrics are left out, see draw_transparent().
+    fn draw_scene(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        camera_bind_group: &wgpu::BindGroup,
+        motion_vectors: bool,
+        cloth: Option<&ClothSimulation>,
+    ) {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        self.sky.draw(render_pass, self.lighting.bind_group(), motion_vectors);
+        if self.grid.enabled && self.scene.has_ground {
+            self.grid.draw(render_pass, self.lighting.bind_group(), motion_vectors);
+        }
+
+        // Render the grid
+        if let Some(cloth) = cloth {
+            let pipeline = match (self.double_sided, motion_vectors) {
+                (false, false) => &self.render_pipeline,
+                (false, true) => &self.motion_render_pipeline,
+                (true, motion_vectors) => &self.double_sided_pipelines[usize::from(motion_vectors)],
+            };
+            self.draw_cloth(render_pass, cloth, pipeline);
+        }
+
+        // Render the sphere
+        // Use the sphere's pipeline
+        render_pass.set_pipeline(if motion_vectors {
+            &self.motion_sphere_render_pipeline
+        } else {
+            &self.sphere_render_pipeline
+        });
+        render_pass.set_bind_group(1, self.lighting.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.sphere_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.sphere_instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.sphere_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_sphere_indices, 0, 0..self.num_spheres);
+
+        if let Some(cloth) = cloth {
+            self.contact_view.draw(render_pass, cloth, motion_vectors);
+            self.edge_view.draw(render_pass, cloth, motion_vectors);
+        }
+    }
+
+    // Sheer fabrics of `cloth` as seen from `camera_bind_group`, into the accumulation targets of a view
+    fn draw_transparent(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        camera_bind_group: &wgpu::BindGroup,
+        cloth: Option<&ClothSimulation>,
+    ) {
+        if let Some(cloth) = cloth {
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            self.draw_cloth(render_pass, cloth, &self.transparent_pipeline);
+        }
+    }
+
+    fn draw_cloth(&self, render_pass: &mut wgpu::RenderPass<'_>, cloth: &ClothSimulation, pipeline: &wgpu::RenderPipeline) {
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(1, &self.interpolation_bind_group, &[]);
+        render_pass.set_bind_group(2, self.lighting.bind_group(), &[]);
+        render_pass.set_bind_group(3, self.albedo.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, cloth.instance_buffer().slice(..)); // Use the updated buffer
+        render_pass.set_vertex_buffer(2, cloth.previous_instance_buffer().slice(..));
+        render_pass.set_vertex_buffer(3, cloth.attribute_buffer().slice(..));
+        render_pass.set_vertex_buffer(4, cloth.occlusion_buffer().slice(..));
+        render_pass.set_vertex_buffer(5, cloth.uv_buffer().slice(..));
+        render_pass.set_vertex_buffer(6, cloth.tint_buffer().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.particle_lod.draw(render_pass, 0..cloth.num_instances());
+    }
+
+    // Moves the attachment of the waved corner, it circles above its rest position
+    fn update_wave(&mut self, delta_time: f32) {
+        let corner = self.scene.cloth().and_then(|cloth| {
+            let piece = cloth.pieces().first()?;
+            let particle = piece.offset + piece.count - 1;
+            Some((particle, cloth.rest_position(particle)))
+        });
+        let (true, Some((particle, [x, y, z]))) = (self.wave_corner, corner) else {
+            self.scene.attachments.clear();
+            return;
+        };
+
+        self.wave_time += delta_time;
+        let angle = 3.0 * self.wave_time;
+        self.scene.attachments = vec![Attachment {
+            particle,
+            target: [x, y + 0.1 + 0.08 * angle.sin(), z + 0.08 * angle.cos()],
+            stiffness: 0.5,
+        }];
+    }
+
+    /// Restarts the simulation from the rest state of the current cloth, with zero velocity, without
+    /// rebuilding it.
+    pub fn drop_again(&mut self, context: &Context) {
+        self.scene.reset(context);
+        self.wave_time = 0.0;
+        self.restart();
+    }
+
+    /// Builds the cloths of `file` instead of those of the preset, among its colliders and in its
+    /// wind, seen from its camera.
+    pub fn with_scene_file(mut self, context: &Context, file: &SceneFile) -> Self {
+        file.apply(&mut self.scene);
+        // The load started by new() is dropped, its rest state is the preset's
+        self.loading = Some(self.scene.load_cloth());
+        if let Some((polar, target)) = file.camera() {
+            self.camera.set_polar(polar).set_target(target).update(context);
+        }
+        self
+    }
+
+    /// Starts paused instead of dropping right away, from now on and for every later drop.
+    pub fn with_start_paused(mut self, start_paused: bool) -> Self {
+        self.start_paused = start_paused;
+        self.paused = start_paused;
+        self
+    }
+
+    // Fills the attribute of the cloth from the selected source and sets the ramp to its range,
+    // again after every rebuild since the particles may have changed
+    fn apply_attribute(&mut self, context: &Context) {
+        let Some(cloth) = self.scene.cloth() else {
+            return;
+        };
+        let values = match self.attribute_source {
+            AttributeSource::None => Ok(None),
+            AttributeSource::Piece => Ok(Some(
+                cloth
+                    .pieces()
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, piece)| std::iter::repeat_n(index as f32, piece.count as usize))
+                    .collect(),
+            )),
+            AttributeSource::RestHeight => Ok(Some(
+                (0..cloth.num_instances()).map(|particle| cloth.rest_position(particle)[1]).collect(),
+            )),
+            AttributeSource::File => load_attribute_values(Path::new(&self.attribute_path)).map(Some),
+            // Written by the strain and kinematics passes
+            AttributeSource::Strain
+            | AttributeSource::Speed
+            | AttributeSource::VerticalSpeed
+            | AttributeSource::Acceleration => Ok(None),
+        };
+
+        let ramp = match values {
+            Ok(Some(values)) => {
+                for cloth in self.scene.cloths() {
+                    cloth.set_attribute(context, &values);
+                }
+                self.attribute_status = if values.len() == cloth.num_instances() as usize {
+                    String::new()
+                } else {
+                    format!("{} values for {} particles", values.len(), cloth.num_instances())
+                };
+                RampUniform::new(self.attribute_ramp, &values)
+            }
+            Ok(None) => {
+                self.attribute_status.clear();
+                match self.attribute_source {
+                    AttributeSource::None => RampUniform::OFF,
+                    AttributeSource::VerticalSpeed => {
+                        RampUniform::range(self.attribute_ramp, -self.ramp_range, self.ramp_range)
+                    }
+                    _ => RampUniform::range(self.attribute_ramp, 0.0, self.ramp_range),
+                }
+            }
+            Err(error) => {
+                self.attribute_status = format!("Could not load values: {error}");
+                RampUniform::OFF
+            }
+        };
+        context.queue().write_buffer(&self.ramp_buffer, 0, bytemuck::bytes_of(&ramp));
+    }
+
+    // Marks out the particles the highlights pick, again after every rebuild like the attribute
+    fn apply_tints(&self, context: &Context) {
+        for cloth in self.scene.cloths() {
+            cloth.set_tints(context, &self.highlights.tints(cloth));
+        }
+    }
+
+    // Called whenever the cloth starts over from its rest state
+    fn restart(&mut self) {
+        self.paused = self.start_paused;
+        self.drop_step = self.step_count;
+        self.gravity_ramp.restart();
+        self.desync.clear();
+        self.energy_plot.clear();
+        self.guard_count = 0;
+        self.skipped_iterations = 0;
+    }
+
+    // Hands the asset loaded in the background to the scene once it's ready, the GPU upload
+    // is the only part left for the main thread
+    fn finish_loading(&mut self, context: &Context) {
+        let Some(result) = self.loading.as_ref().and_then(AssetLoad::poll) else {
+            return;
+        };
+        let what = self.loading.take().map(|load| load.what).unwrap_or_default();
+        let placed = match result {
+            Ok(Asset::Cloth(sources)) => {
+                self.stop_recording();
+                self.inspector.clear();
+                self.scene.rebuild(context, sources);
+                self.restart();
+                self.apply_attribute(context);
+                self.apply_tints(context);
+                Some(())
+            }
+            Ok(Asset::Sdf(volume)) => self.scene.place_sdf(context, volume).map(|_| ()),
+            Ok(Asset::CollisionMesh(bvh)) => self.scene.place_collision_mesh(context, bvh).map(|_| ()),
+            Ok(Asset::HeightField(field)) => self.scene.place_height_field(context, field).map(|_| ()),
+            Ok(Asset::Animation(animation)) => {
+                self.scene.animation = Some(animation);
+                Some(())
+            }
+            Err(error) => {
+                self.scene_status = format!("{what} failed: {error}");
+                return;
+            }
+        };
+        self.scene_status = match placed {
+            Some(()) => String::new(),
+            None => format!("At most {MAX_COLLIDERS} colliders are supported"),
+        };
+    }
+
+    // The mesh cache and replays are only valid for the particles they started with
+    fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            self.export_status = match recording.finish() {
+                Ok(()) => format!("Saved to {EXPORT_DIR}/"),
+                Err(error) => format!("Export failed: {error}"),
+            };
+        }
+        self.stop_replay();
+        self.replay_playback = None;
+    }
+
+    // Drops the cloth again from the start of the current run of the study, without pausing
+    fn drop_for_study(&mut self, context: &Context) {
+        self.drop_again(context);
+        self.paused = false;
+        if let (Some(current), Some(cloth)) = (&self.study, self.scene.cloth_mut()) {
+            let seed = current.seed();
+            cloth.reset_jittered(context, |particle| study::jitter(seed, particle));
+        }
+    }
+
+    // Replays hold positions only, the rest state of the scene provides everything else
+    fn open_replay(&self) -> Result<ReplayReader, String> {
+        let num_instances = self.scene.cloth().map_or(0, |cloth| cloth.num_instances());
+        match ReplayReader::open(&Path::new(EXPORT_DIR).join(REPLAY_FILE)) {
+            Ok(replay) if replay.num_particles() != num_instances => Err(format!(
+                "The replay has {} particles, the scene {num_instances}, rebuild the scene it was recorded with",
+                replay.num_particles()
+            )),
+            Ok(replay) => Ok(replay),
+            Err(error) => Err(format!("Could not load replay: {error}")),
+        }
+    }
+
+    fn start_replay_playback(&mut self) {
+        match self.open_replay() {
+            Ok(replay) => self.replay_playback = Some(replay),
+            Err(status) => self.export_status = status,
+        }
+    }
+
+    fn start_batch_render(&mut self, context: &Context) {
+        let replay = match self.open_replay() {
+            Ok(replay) => replay,
+            Err(status) => {
+                self.export_status = status;
+                return;
+            }
+        };
+        let [width, height] = self.batch_size;
+        let motion_blur = self.motion_blur.enabled.then_some(self.motion_blur.strength);
+        match BatchRender::start(
+            context,
+            replay,
+            &self.camera,
+            (width, height),
+            motion_blur,
+            self.main_view.sample_count(),
+            &self.tonemapping,
+        ) {
+            // The replay doesn't know its time step, taken to be the current one
+            Ok(batch) => self.batch = Some(batch.with_turntable(self.turntable.step_angle(self.time_step))),
+            Err(error) => self.export_status = format!("Batch render failed: {error}"),
         }
     }
 
+    fn play_camera_path(&mut self, context: &Context) {
+        let Some((path, start_step)) = &self.camera_playback else {
+            return;
+        };
+        let step = self.step_count - start_step;
+        if let Some((polar, target)) = path.sample(step) {
+            self.camera.set_polar(polar).set_target(target).update(context);
+        }
+        if path.is_finished(step) {
+            self.camera_playback = None;
+        }
+    }
+
+    fn stop_replay(&mut self) {
+        if let Some(replay) = self.replay.take() {
+            self.export_status = match replay.finish() {
+                Ok(()) => format!("Saved to {EXPORT_DIR}/{REPLAY_FILE}"),
+                Err(error) => format!("Replay recording failed: {error}"),
+            };
+        }
+    }
+
+    // A whole step, or only its first substep when stepping through a paused run
+    fn sim_params(&self, single_substep: bool) -> SimParams {
+        // Fast motion takes more substeps rather than longer ones, to stay as stable
+        let substeps = self.substeps * self.time_scale.ceil() as u32;
+        let taken = if single_substep { 1 } else { substeps };
+        let mut params = SimParams::new(taken, self.iterations);
+        params.scale_time(self.time_step * self.time_scale * taken as f32 / (TIME_STEP * substeps as f32));
+        params.set_gravity_scale(self.gravity / STANDARD_GRAVITY); // ramped by a pre-step hook
+        params.set_friction(self.static_friction, self.dynamic_friction);
+        params.set_thickness(self.thickness);
+        params.set_sleeping(self.sleep_speed, self.sleep_steps * substeps);
+        params
+    }
 
+    // Pausing and the time scale only change the steps, the frames keep being drawn
+    fn playback_keys(&mut self, ctx: &egui::Context) {
+        let [pause, substep, slower, faster] =
+            [PAUSE_KEY, SUBSTEP_KEY, SLOWER_KEY, FASTER_KEY].map(|key| ctx.input(|input| input.key_pressed(key)));
+        if pause {
+            self.paused = !self.paused;
+        }
+        self.substep_requested |= substep && self.paused;
+        if slower || faster {
+            let factor = if faster { 2.0 } else { 0.5 };
+            self.time_scale = (self.time_scale * factor).clamp(*TIME_SCALES.start(), *TIME_SCALES.end());
+        }
+    }
+}
+// WASD or the arrows move the sphere across the ground, Q and E down and up. Right, up and
+// backward for the flying camera.
+fn sphere_direction(input: &egui::InputState) -> cgmath::Vector3<f32> {
+    use egui::Key;
+    let axis = |negative: &[Key], positive: &[Key]| {
+        let down = |keys: &[Key]| keys.iter().any(|&key| input.key_down(key));
+        f32::from(u8::from(down(positive))) - f32::from(u8::from(down(negative)))
+    };
+    cgmath::Vector3::new(
+        axis(&[Key::A, Key::ArrowLeft], &[Key::D, Key::ArrowRight]),
+        axis(&[Key::Q], &[Key::E]),
+        axis(&[Key::W, Key::ArrowUp], &[Key::S, Key::ArrowDown]),
+    )
 }
 
 impl App for InstanceApp {
     fn input(&mut self, input: egui::InputState, context: &Context) {
-        self.camera.input(input, context);
+        // The imported camera path has control while it plays, a bookmark while the camera flies
+        // there, the pin tool while it draws a box
+        if self.camera_playback.is_none() && !self.bookmarks.is_moving() && !self.inspector.wants_drag(&input) {
+            self.camera.input(&input, context);
+        }
+        // A right click focuses the depth of field on what is under the pointer
+        if let (true, Some(position)) = (input.pointer.secondary_clicked(), input.pointer.interact_pos()) {
+            let size = input.screen_rect.size();
+            self.main_view.depth_of_field.focus_at([position.x / size.x, position.y / size.y]);
+        }
     }
     
     fn update(&mut self, delta_time: f32, context: &Context) {
-        if self.last_generation + self.generation_duration < Instant::now() {
-            let mut encoder = context.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Compute Encoder"),
-            });
-
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Compute Pass"),
-                    timestamp_writes: None,
-                });
+        let update_start = Instant::now();
+        self.stepped_this_frame = false;
+        self.finish_loading(context);
 
-                compute_pass.set_pipeline(&self.compute_pipeline);
-                compute_pass.set_bind_group(0, &self.bind_group[0], &[]);
-                compute_pass.dispatch_workgroups(self.num_instances / WORKGROUP_SIZE, 1, 1);
-            }
+        self.scene.update_rig(delta_time);
+        self.scene.update_colliders(delta_time);
+        self.update_wave(delta_time);
+        self.scene.attachments.extend(self.inspector.grab_attachment());
+        let spheres = self.scene.collider_spheres();
+        context.queue().write_buffer(&self.sphere_instance_buffer, 0, bytemuck::cast_slice(&spheres));
+        self.num_spheres = spheres.len() as u32;
 
-            context.queue().submit(std::iter::once(encoder.finish()));
-            self.last_generation = Instant::now();
+        self.particle_lod.update(&self.camera, context.size().y);
+        self.transparency.active = self.scene.materials.iter().any(|blend| blend.material().opacity < 1.0);
+        let steps_before = self.step_count;
+        let frame_graph = std::mem::take(&mut self.frame_graph);
+        frame_graph.execute(self, context);
+        self.frame_graph = frame_graph;
+        self.energy_plot.poll(context);
+        self.inspector.poll(context);
 
-            // Swap the ping-pong buffers
-            self.instance_buffer.swap(0, 1);
-            self.bind_group.swap(0, 1);
+        if self.stepped_this_frame {
+            self.play_camera_path(context);
+        }
+        if self.camera_playback.is_none() {
+            self.bookmarks.update(delta_time, &mut self.camera, context);
+        }
+        if self.camera_playback.is_none() && !self.bookmarks.is_moving() {
+            let seconds = if self.turntable.simulated_time {
+                (self.step_count - steps_before) as f32 * self.time_step
+            } else {
+                delta_time
+            };
+            self.turntable.turn(&mut self.camera, context, seconds);
         }
-    }
-    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
-
-        render_pass.set_bind_group(0, self.camera.bind_group(), &[]);
 
+        // Renders one step behind, at the point between the last two steps matching the time
+        // elapsed since the latest one, so the cloth moves smoothly when frames outpace steps
+        // Batch frames are rendered at the replayed positions
+        let alpha = if self.interpolate && self.batch.is_none() {
+            (self.last_generation.elapsed().as_secs_f32() / self.generation_duration.as_secs_f32()).min(1.0)
+        } else {
+            1.0
+        };
+        context
+            .queue()
+            .write_buffer(&self.interpolation_buffer, 0, bytemuck::bytes_of(&[alpha, 0.0, 0.0, 0.0]));
+        let fabrics: Vec<Material> = self.scene.materials.iter().map(MaterialBlend::material).collect();
+        self.lighting.update(context, &fabrics, &self.shadow_map, &self.sky);
+        self.tonemapping.update(context);
+        self.grid.update(context, self.scene.ground.height);
+        self.albedo.update(context);
+        self.perf_hud.record_frame(delta_time, update_start.elapsed());
+    }
 
-        
+    fn render_gui(&mut self, ctx: &egui::Context, context: &Context) {
+        // Not while a text field has the keyboard
+        if !ctx.wants_keyboard_input() {
+            if ctx.input(|input| input.key_pressed(DROP_AGAIN_KEY)) {
+                self.drop_again(context);
+            }
+            self.playback_keys(ctx);
+            self.bookmarks.keys(ctx, &mut self.camera, context);
+            self.frame_requested |= ctx.input(|input| input.key_pressed(FRAME_KEY)) && self.scene.cloth().is_some();
+            let (mut direction, delta_time) = ctx.input(|input| (sphere_direction(input), input.stable_dt));
+            // The same keys fly the camera instead, along its own axes
+            if self.camera.mode() == CameraMode::Fly {
+                if direction != cgmath::Vector3::new(0.0, 0.0, 0.0) {
+                    self.camera.fly(direction * FLY_SPEED * delta_time, context);
+                }
+                direction = cgmath::Vector3::new(0.0, 0.0, 0.0);
+            }
+            #[cfg(feature = "gamepad")]
+            let direction = direction + self.gamepad.direction();
+            if direction != cgmath::Vector3::new(0.0, 0.0, 0.0) {
+                self.scene.move_sphere(direction * SPHERE_SPEED * delta_time);
+            }
+        }
+        // A click on the scene inspects, pins or grabs a particle. Not while comparing, the halves
+        // have cameras of their own.
+        if self.scene.comparison().is_none() {
+            let view_projection = self.camera.projection_matrix() * self.camera.view_matrix();
+            self.inspector.pointer_input(ctx, view_projection);
+        }
 
-        // Render the grid
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_vertex_buffer(1, self.instance_buffer[0].slice(..)); // Use the updated buffer
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+        match self.controls {
+            ControlsPlacement::SidePanel => {
+                egui::SidePanel::right("controls").resizable(true).show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| self.controls_ui(ui, context));
+                });
+            }
+            ControlsPlacement::Window => {
+                egui::Window::new("Controls").show(ctx, |ui| self.controls_ui(ui, context));
+            }
+            ControlsPlacement::Detached => {
+                self.detached_controls_ui(ctx, context);
+            }
+        }
 
+        // Which half of a comparison takes how many iterations, at the bottom left of each, clear
+        // of the side panel
+        if self.scene.comparison().is_some() && self.draws_main_view() {
+            let screen = ctx.screen_rect();
+            let halves = [self.iterations, self.scene.comparison_iterations];
+            for (half, iterations) in halves.into_iter().enumerate() {
+                let x = screen.left() + half as f32 * 0.5 * screen.width() + 8.0;
+                egui::Area::new(egui::Id::new(("comparison label", half)))
+                    .fixed_pos([x, screen.bottom() - 28.0])
+                    .interactable(false)
+                    .show(ctx, |ui| ui.label(format!("{iterations} iterations per substep")));
+            }
+        }
 
-        // Render the sphere
-        render_pass.set_pipeline(&self.sphere_render_pipeline); // Use the sphere's pipeline
-        render_pass.set_vertex_buffer(0, self.sphere_vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.sphere_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..self.num_sphere_indices, 0, 0..1);
+        let cloths: Vec<_> = self.scene.cloths().collect();
+        self.perf_hud.show(ctx, &cloths);
+        self.inspector.show(ctx, self.scene.cloth());
+    }
 
+    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        // The scene was rendered in HDR by the pass of the anti-aliased, blurred or main view, and
+        // is tonemapped into the window
+        if self.taa.enabled {
+            self.taa.draw(render_pass, &self.tonemapping);
+        } else if self.motion_blur.enabled {
+            self.motion_blur.draw(render_pass, &self.tonemapping);
+        } else {
+            self.main_view.draw(render_pass, &self.tonemapping);
+        }
 
-        
+        if self.pip.enabled {
+            self.pip.draw(render_pass, &self.tonemapping);
+        }
     }
     
 }
-
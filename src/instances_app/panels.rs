@@ -0,0 +1,944 @@
+// The egui panels of the app, shown by render_gui() docked or in their own window.
+
+use super::*;
+
+impl InstanceApp {
+    pub(super) fn controls_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        egui::ComboBox::from_label("Controls")
+            .selected_text(self.controls.name())
+            .show_ui(ui, |ui| {
+                for placement in ControlsPlacement::ALL {
+                    ui.selectable_value(&mut self.controls, placement, placement.name());
+                }
+            });
+        egui::CollapsingHeader::new("Scene")
+            .default_open(true)
+            .show(ui, |ui| self.scene_ui(ui, context));
+        egui::CollapsingHeader::new("Material")
+            .default_open(true)
+            .show(ui, |ui| self.material_ui(ui));
+        egui::CollapsingHeader::new("Solver")
+            .default_open(true)
+            .show(ui, |ui| self.solver_ui(ui, context));
+        egui::CollapsingHeader::new("Lighting").show(ui, |ui| {
+            self.lighting.ui(ui);
+            self.sky.ui(ui);
+            self.grid.ui(ui);
+            self.shadow_map.ui(ui);
+            self.tonemapping.ui(ui);
+        });
+        egui::CollapsingHeader::new("View").show(ui, |ui| {
+            ui.checkbox(&mut self.interpolate, "Interpolate between steps");
+            ui.checkbox(&mut self.perf_hud.enabled, "Performance overlay")
+                .on_hover_text("Frame rate, CPU and GPU times, dispatches and sizes, at the top left");
+            self.camera.ui(ui);
+            if ui
+                .add_enabled(self.scene.cloth().is_some(), egui::Button::new("Frame cloth"))
+                .on_hover_text(format!("Fits the cloth as it is now in the view, {FRAME_KEY:?}"))
+                .clicked()
+            {
+                self.frame_requested = true;
+            }
+            self.turntable.ui(ui);
+            egui::CollapsingHeader::new("Camera bookmarks").show(ui, |ui| {
+                self.bookmarks.ui(ui, &mut self.camera, context, Path::new(EXPORT_DIR));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.double_sided, "Double-sided")
+                    .on_hover_text("Also draws the back faces of the cloth, lit from their own side");
+                if self.double_sided {
+                    let mut distinct = self.lighting.back_color.is_some();
+                    ui.checkbox(&mut distinct, "Back color");
+                    match (&mut self.lighting.back_color, distinct) {
+                        (Some(color), true) => {
+                            ui.color_edit_button_rgb(color);
+                        }
+                        (back_color, distinct) => *back_color = distinct.then_some(DEFAULT_BACK_COLOR),
+                    }
+                }
+            });
+            self.particle_lod.ui(ui);
+            ui.label(format!("Multisampling: {}x", self.main_view.sample_count()))
+                .on_hover_text("Set with --msaa when starting, limited by the device");
+            ui.checkbox(&mut self.taa.enabled, "Temporal anti-aliasing");
+            // Both replace the scene with their own offscreen view, anti-aliasing wins
+            ui.add_enabled(!self.taa.enabled, egui::Checkbox::new(&mut self.motion_blur.enabled, "Motion blur"));
+            ui.add_enabled(
+                self.motion_blur.enabled && !self.taa.enabled,
+                egui::Slider::new(&mut self.motion_blur.strength, 0.0..=4.0).text("Blur strength"),
+            );
+            let draws_main_view = self.draws_main_view();
+            self.main_view.depth_of_field.ui(ui, draws_main_view);
+            ui.checkbox(&mut self.pip.enabled, "Picture in picture");
+            egui::ComboBox::from_label("Secondary camera")
+                .selected_text(self.pip.view.name())
+                .show_ui(ui, |ui| {
+                    for view in PipView::ALL {
+                        ui.selectable_value(&mut self.pip.view, view, view.name());
+                    }
+                });
+            self.attribute_ui(ui, context);
+            if self.highlights.ui(ui) {
+                self.apply_tints(context);
+            }
+            self.albedo_ui(ui, context);
+            self.self_shadow_ui(ui, context);
+            ui.checkbox(&mut self.contact_view.enabled, "Contact markers")
+                .on_hover_text("A line along the normal wherever a particle touched a collider in the last substep");
+            ui.checkbox(&mut self.edge_view.enabled, "Springs")
+                .on_hover_text("A line along every constraint: structural in blue, shear in green, bend in orange");
+            let passes: Vec<_> = self.frame_graph.pass_names().collect();
+            ui.label(format!("Frame passes: {}", passes.join(" → ")));
+        });
+        egui::CollapsingHeader::new("Plots").show(ui, |ui| self.energy_plot.ui(ui));
+        egui::CollapsingHeader::new("Export").show(ui, |ui| self.export_ui(ui, context));
+    }
+
+    fn albedo_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        ui.checkbox(&mut self.albedo.enabled, "Texture")
+            .on_hover_text("Colors the particles from a texture over the rest shape, a checkerboard until one is loaded");
+        if !self.albedo.enabled {
+            return;
+        }
+        ui.add(egui::Slider::new(&mut self.albedo.repeat, 0.25..=16.0).logarithmic(true).text("Texture repeat"));
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.albedo_path);
+            if ui.button("Load").clicked() {
+                self.albedo_status = match self.albedo.load(context, Path::new(&self.albedo_path)) {
+                    Ok(()) => String::new(),
+                    Err(error) => format!("Could not load the texture: {error}"),
+                };
+            }
+        });
+        if !self.albedo_status.is_empty() {
+            ui.label(&self.albedo_status);
+        }
+    }
+
+    fn self_shadow_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        let shadow = &mut self.self_shadow;
+        if ui.checkbox(&mut shadow.enabled, "Self shadows").changed() && !shadow.enabled {
+            if let Some(cloth) = self.scene.cloth() {
+                cloth.clear_self_shadow(context);
+            }
+        }
+        ui.add_enabled(
+            shadow.enabled,
+            egui::Slider::new(&mut shadow.radius, 0.002..=0.03).text("Shadow radius (m)"),
+        );
+        ui.add_enabled(shadow.enabled, egui::Slider::new(&mut shadow.strength, 0.0..=1.0).text("Shadow strength"));
+    }
+
+    fn lod_ui(&mut self, ui: &mut egui::Ui) {
+        let lod = &mut self.lod;
+        ui.checkbox(&mut lod.enabled, "Level of detail")
+            .on_hover_text("Pieces far from the camera get fewer iterations and no self collisions");
+        if !lod.enabled {
+            return;
+        }
+        ui.add(egui::Slider::new(&mut lod.distance, 0.5..=50.0).logarithmic(true).text("Far past (m)"));
+        ui.add(egui::Slider::new(&mut lod.hysteresis, 0.0..=5.0).text("Hysteresis (m)"));
+        ui.add(egui::Slider::new(&mut lod.far_iterations, 1..=64).text("Iterations of far pieces"));
+        ui.label(format!("{} far pieces", self.lod_controller.num_far()));
+    }
+
+    fn convergence_ui(&mut self, ui: &mut egui::Ui) {
+        let convergence = &mut self.convergence;
+        ui.checkbox(&mut convergence.enabled, "Stop once converged")
+            .on_hover_text("The remaining iterations of a substep are skipped once every constraint is within the tolerance");
+        if !convergence.enabled {
+            return;
+        }
+        ui.add(
+            egui::Slider::new(&mut convergence.tolerance, 1e-8..=1e-4)
+                .logarithmic(true)
+                .text("Tolerance (m)"),
+        );
+        ui.add(egui::Slider::new(&mut convergence.interval, 1..=16).text("Iterations between checks"));
+        let steps = self.step_count - self.drop_step;
+        if steps > 0 {
+            let per_substep = self.skipped_iterations as f32 / (steps * u64::from(self.substeps)) as f32;
+            ui.label(format!("{per_substep:.1} iterations skipped per substep"));
+        }
+    }
+
+    fn attribute_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        let (previous_source, previous_ramp) = (self.attribute_source, self.attribute_ramp);
+        egui::ComboBox::from_label("Color by")
+            .selected_text(self.attribute_source.name())
+            .show_ui(ui, |ui| {
+                for source in AttributeSource::ALL {
+                    ui.selectable_value(&mut self.attribute_source, source, source.name());
+                }
+            });
+        if self.attribute_source == AttributeSource::None {
+            return;
+        }
+        egui::ComboBox::from_label("Color ramp")
+            .selected_text(self.attribute_ramp.name())
+            .show_ui(ui, |ui| {
+                for ramp in ColorRamp::ALL {
+                    ui.selectable_value(&mut self.attribute_ramp, ramp, ramp.name());
+                }
+            });
+        let mut reload = false;
+        if let Some((default_range, unit)) = self.attribute_source.default_range() {
+            if self.attribute_source != previous_source {
+                self.ramp_range = default_range;
+            }
+            reload = ui
+                .add(
+                    egui::Slider::new(&mut self.ramp_range, 0.001..=100.0)
+                        .logarithmic(true)
+                        .suffix(format!(" {unit}"))
+                        .text("Ramp range"),
+                )
+                .on_hover_text("Value at the top of the ramp. Strain is the length of a constraint over its rest length minus one.")
+                .changed();
+        }
+        if self.attribute_source == AttributeSource::File {
+            ui.horizontal(|ui| {
+                ui.label("One value per line");
+                ui.text_edit_singleline(&mut self.attribute_path);
+                reload = ui.button("Load").clicked();
+            });
+        }
+        if reload || (self.attribute_source, self.attribute_ramp) != (previous_source, previous_ramp) {
+            self.apply_attribute(context);
+        }
+        if !self.attribute_status.is_empty() {
+            ui.label(&self.attribute_status);
+        }
+    }
+
+    fn solver_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        let previous_mode = self.solver_mode;
+        egui::ComboBox::from_label("Mode")
+            .selected_text(self.solver_mode.name())
+            .show_ui(ui, |ui| {
+                for mode in SolverMode::ALL {
+                    ui.selectable_value(&mut self.solver_mode, mode, mode.name());
+                }
+            });
+        if self.solver_mode != previous_mode {
+            self.iterations = self.solver_mode.default_iterations();
+        }
+        ui.add(egui::Slider::new(&mut self.substeps, 1..=16).text("Substeps per frame"));
+        ui.add(egui::Slider::new(&mut self.iterations, 1..=64).text("Iterations per substep"));
+        self.comparison_ui(ui, context);
+        if self.solver_mode == SolverMode::Jacobi {
+            self.convergence_ui(ui);
+        }
+        ui.add(egui::Slider::new(&mut self.thickness, 0.0..=0.02).text("Cloth thickness (m)"))
+            .on_hover_text("Kept between the particles and the colliders, and between the layers of a fold");
+        if self.solver_mode != SolverMode::Implicit {
+            ui.add(egui::Slider::new(&mut self.sleep_steps, 0..=120).text("Sleep after still steps"))
+                .on_hover_text("Particles slower than the speed below stop being simulated until a neighbor or a moving collider wakes them, 0 never");
+            if self.sleep_steps > 0 {
+                ui.add(egui::Slider::new(&mut self.sleep_speed, 0.0..=0.05).text("Sleep speed (m/s)"));
+            }
+        }
+        if self.solver_mode != SolverMode::Implicit {
+            let self_collision = &mut self.self_collision;
+            ui.checkbox(&mut self_collision.enabled, "Self collisions")
+                .on_hover_text("Keeps folds from passing through themselves, and the pieces checked below through each other");
+            if self_collision.enabled {
+                ui.add(egui::Slider::new(&mut self_collision.stiffness, 0.0..=1.0).text("Self collision stiffness"));
+                let num_pieces = self.scene.cloth().map_or(0, |cloth| cloth.pieces().len()).min(MAX_COLLISION_PIECES);
+                // A piece with itself, then every pair of pieces
+                for a in 0..num_pieces {
+                    for b in a..num_pieces {
+                        let mut collides = self_collision.collides(a, b);
+                        let label = if a == b {
+                            format!("Piece {a} with itself")
+                        } else {
+                            format!("Piece {a} with piece {b}")
+                        };
+                        if ui.checkbox(&mut collides, label).changed() {
+                            self_collision.set_collides(a, b, collides);
+                        }
+                    }
+                }
+            }
+        }
+        if self.solver_mode == SolverMode::Jacobi {
+            self.lod_ui(ui);
+        }
+        if let (SolverMode::GaussSeidel, Some(cloth)) = (self.solver_mode, self.scene.cloth()) {
+            ui.label(format!("{} spring colors", cloth.num_colors()));
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .button(if self.paused { "Resume" } else { "Pause" })
+                .on_hover_text(format!("{} key", PAUSE_KEY.name()))
+                .clicked()
+            {
+                self.paused = !self.paused;
+            }
+            if ui
+                .add_enabled(self.paused, egui::Button::new("Substep"))
+                .on_hover_text(format!("One substep of the paused run, {} key", SUBSTEP_KEY.name()))
+                .clicked()
+            {
+                self.substep_requested = true;
+            }
+            ui.checkbox(&mut self.start_paused, "Start paused");
+        });
+        ui.add(egui::Slider::new(&mut self.time_scale, TIME_SCALES).logarithmic(true).text("Time scale"))
+            .on_hover_text(format!(
+                "Simulated time per step, {} and {} keys. Faster takes more substeps.",
+                SLOWER_KEY.name(),
+                FASTER_KEY.name()
+            ));
+        ui.add(egui::Slider::new(&mut self.time_step, 0.002..=0.033).text("Time step (s)"))
+            .on_hover_text("Simulated time per step, split between the substeps");
+        ui.add(egui::Slider::new(&mut self.gravity, 0.0..=20.0).text("Gravity (m/s²)"));
+        let mut gravity_ramp = self.gravity_ramp.duration();
+        if ui.add(egui::Slider::new(&mut gravity_ramp, 0.0..=5.0).text("Gravity ramp (s)")).changed() {
+            self.gravity_ramp.set_duration(gravity_ramp);
+        }
+        if gravity_ramp > 0.0 {
+            ui.label(format!("Gravity at {:.0}%", 100.0 * self.gravity_ramp.scale()));
+        }
+        ui.add(egui::Slider::new(&mut self.static_friction, 0.0..=4.0).text("Sphere static friction"))
+            .on_hover_text("Times the friction of the fabric, the cloth sticks below it");
+        ui.add(egui::Slider::new(&mut self.dynamic_friction, 0.0..=4.0).text("Sphere dynamic friction"))
+            .on_hover_text("Times the friction of the fabric, while it slides");
+        if self.guard_count > 0 {
+            ui.colored_label(
+                egui::Color32::from_rgb(200, 60, 40),
+                format!("{} particles reset after exploding, try more substeps", self.guard_count),
+            );
+        }
+        let mut monitor = self.divergence.is_some();
+        if ui
+            .checkbox(&mut monitor, "Divergence monitor")
+            .on_hover_text("Redoes every stage of the Jacobi solver on the CPU, each step waits for the GPU")
+            .changed()
+        {
+            self.divergence = monitor.then(DivergenceMonitor::default);
+            self.scene.probe_stages = monitor;
+        }
+        if let Some(monitor) = &self.divergence {
+            monitor.ui(ui);
+        }
+    }
+
+    // Runs a twin of the cloth with other iterations in the right half of the main view
+    fn comparison_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        let mut comparing = self.scene.comparison().is_some();
+        let available = comparing || (self.draws_main_view() && self.scene.cloth().is_some());
+        let checkbox = ui
+            .add_enabled(available, egui::Checkbox::new(&mut comparing, "Compare side by side"))
+            .on_hover_text("Drops the cloth again next to a twin with other iterations, shown without anti-aliasing or motion blur");
+        if checkbox.changed() {
+            if !comparing {
+                self.scene.stop_comparison();
+            } else if self.scene.start_comparison(context) {
+                self.wave_time = 0.0;
+                self.restart();
+                self.apply_attribute(context);
+                self.apply_tints(context);
+            }
+        }
+        if comparing {
+            ui.add(egui::Slider::new(&mut self.scene.comparison_iterations, 1..=64).text("Iterations on the right"));
+        }
+    }
+
+    fn scene_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        let previous = self.scene.preset;
+        ui.add_enabled_ui(self.loading.is_none(), |ui| {
+            egui::ComboBox::from_label("Rest state")
+                .selected_text(self.scene.preset.name())
+                .show_ui(ui, |ui| {
+                    for preset in ScenePreset::ALL {
+                        ui.selectable_value(&mut self.scene.preset, preset, preset.name());
+                    }
+                });
+        });
+        // Picking another one rebuilds right away, a mesh from the file named below, and leaves the
+        // cloths of a scene file behind
+        if self.scene.preset != previous {
+            self.scene.file_cloths.clear();
+            self.loading = Some(self.scene.load_cloth());
+        }
+        if self.scene.preset == ScenePreset::Mesh {
+            ui.horizontal(|ui| {
+                ui.label("OBJ file");
+                ui.text_edit_singleline(&mut self.scene.mesh_path);
+            });
+            ui.checkbox(&mut self.scene.pin_mesh_top, "Pin top vertices");
+        }
+        ui.horizontal(|ui| {
+            ui.label("Click tool");
+            for tool in ClickTool::ALL {
+                ui.selectable_value(&mut self.inspector.tool, tool, tool.name());
+            }
+        })
+        .response
+        .on_hover_text(
+            "Pin toggles the pin of the clicked particle, or of all inside a box drawn with shift held. \
+             Grab pulls the particle under the pointer along while the button is held. \
+             Cut severs the constraints crossing the stroke drawn, until the cloth is dropped again. \
+             Poke pushes the particles around the clicked one away from it.",
+        );
+        if self.inspector.tool == ClickTool::Poke {
+            let poke = &mut self.inspector.poke;
+            ui.add(egui::Slider::new(&mut poke.radius, 0.01..=0.5).text("Poke radius (m)"));
+            ui.add(egui::Slider::new(&mut poke.strength, 0.1..=10.0).logarithmic(true).text("Poke strength (m/s)"));
+        }
+        tear_map_ui(ui, &mut self.scene.tear_map);
+        self.rig_animation_ui(ui);
+        self.colliders_ui(ui);
+        self.fans_ui(ui);
+        ui.checkbox(&mut self.wave_corner, "Wave a corner");
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.loading.is_none(), egui::Button::new("Rebuild")).clicked() {
+                self.loading = Some(self.scene.load_cloth());
+            }
+            if ui.button("Drop again").on_hover_text(format!("{DROP_AGAIN_KEY:?}")).clicked() {
+                self.drop_again(context);
+            }
+            if ui.button("Clear").clicked() {
+                self.stop_recording();
+                self.inspector.clear();
+                self.scene.clear();
+                self.scene_status.clear();
+            }
+        });
+        if let Some(load) = &self.loading {
+            ui.add(egui::ProgressBar::new(load.progress()).text(&load.what).animate(true));
+        }
+        if !self.scene_status.is_empty() {
+            ui.label(&self.scene_status);
+        }
+        if let Some(cloth) = self.scene.cloth() {
+            for (index, piece) in cloth.pieces().iter().enumerate() {
+                ui.label(format!(
+                    "Piece {index}: particles {}..{}",
+                    piece.offset,
+                    piece.offset + piece.count
+                ));
+            }
+            if ui.button("Add piece").on_hover_text("Drops a square over the cloth without restarting it").clicked()
+                && self.scene.add_piece(context, DROPPED_SQUARE)
+            {
+                self.apply_attribute(context);
+                self.apply_tints(context);
+            }
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Save checkpoint").clicked() {
+                self.checkpoint.request_save();
+            }
+            // Written back by a pre-step hook, nothing happens while paused until the next step
+            if ui
+                .button("Restore on next step")
+                .on_hover_text("Resume or step once to restore the cloth while paused")
+                .clicked()
+            {
+                self.checkpoint.request_restore();
+            }
+            if let Some(step) = self.checkpoint.saved_step() {
+                ui.label(format!("saved at step {step}"));
+            }
+        });
+        ui.label(format!(
+            "{} GPU buffers, {:.1} MiB",
+            self.scene.tracker().live_buffers(),
+            self.scene.tracker().live_bytes() as f64 / (1024.0 * 1024.0)
+        ));
+    }
+
+    fn material_ui(&mut self, ui: &mut egui::Ui) {
+        let num_materials = self.scene.materials.len();
+        let pieces = self.scene.materials.iter_mut().zip(&mut self.scene.pin_groups);
+        for (index, (material, pins)) in pieces.enumerate() {
+            ui.push_id(index, |ui| {
+                if num_materials > 1 {
+                    ui.label(format!("Piece {index}"));
+                }
+                material.ui(ui);
+                pin_group_ui(ui, pins);
+            });
+        }
+    }
+
+    // Colliders added at runtime, next to the sphere of the rig. Only spheres are drawn.
+    fn colliders_ui(&mut self, ui: &mut egui::Ui) {
+        let (mut added, mut moving, mut spinning) = (None, false, false);
+        ui.horizontal(|ui| {
+            if ui.button("Add sphere").clicked() {
+                added = Some(Collider::Sphere {
+                    center: [0.0, 0.2, 0.35],
+                    radius: 0.1,
+                });
+            }
+            if ui.button("Add capsule").clicked() {
+                added = Some(Collider::Capsule {
+                    a: [-0.3, 0.2, 0.35],
+                    b: [0.3, 0.2, 0.35],
+                    radius: 0.05,
+                });
+            }
+            if ui.button("Add box").clicked() {
+                added = Some(Collider::Box {
+                    center: [0.0, 0.0, -0.45],
+                    half_extents: [0.15, 0.2, 0.15],
+                    rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                });
+            }
+            if ui.button("Add moving sphere").on_hover_text("Sweeps through the hanging cloth").clicked() {
+                added = Some(Collider::Sphere {
+                    center: [0.0, 0.55, 0.0],
+                    radius: 0.08,
+                });
+                moving = true;
+            }
+            if ui.button("Add drum").on_hover_text("A spinning cylinder that carries the cloth along").clicked() {
+                added = Some(Collider::Capsule {
+                    a: [-0.4, 0.05, 0.35],
+                    b: [0.4, 0.05, 0.35],
+                    radius: 0.1,
+                });
+                spinning = true;
+            }
+        });
+        if let Some(collider) = added {
+            match self.scene.add_collider(collider) {
+                Some(id) if moving => {
+                    let sweep = vec![(0.0, [-0.6, 0.0, 0.0]), (3.0, [0.6, 0.0, 0.0]), (6.0, [-0.6, 0.0, 0.0])];
+                    self.scene.animate_collider(id, ColliderMotion::Path(sweep));
+                }
+                // About its axis, the top of the drum moves along +z
+                Some(id) if spinning => {
+                    self.scene.spin_collider(id, [DRUM_SPIN, 0.0, 0.0]);
+                }
+                Some(_) => {}
+                None => self.scene_status = format!("At most {MAX_COLLIDERS} colliders are supported"),
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label("SDF volume");
+            ui.text_edit_singleline(&mut self.scene.sdf_path)
+                .on_hover_text("An OBJ mesh is baked first, which can take a while");
+            if ui.add_enabled(self.loading.is_none(), egui::Button::new("Load")).clicked() {
+                self.loading = Some(self.scene.load_sdf());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Mesh");
+            ui.text_edit_singleline(&mut self.scene.collision_mesh_path)
+                .on_hover_text("An OBJ mesh the cloth drapes over, open or closed");
+            if ui.add_enabled(self.loading.is_none(), egui::Button::new("Load")).clicked() {
+                self.loading = Some(self.scene.load_collision_mesh());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Terrain");
+            ui.text_edit_singleline(&mut self.scene.height_field_path)
+                .on_hover_text("A grayscale image, white is the highest");
+            ui.add_enabled_ui(self.loading.is_none(), |ui| {
+                if ui.button("Load").clicked() {
+                    self.loading = Some(self.scene.load_height_field());
+                }
+                if ui.button("Noise").on_hover_text("A new random terrain instead").clicked() {
+                    self.terrain_seed += 1;
+                    self.loading = Some(self.scene.generate_height_field(self.terrain_seed));
+                }
+            });
+        });
+        let (mut removed, mut edited, mut sided) = (None, None, None);
+        for (id, collider, moving) in self.scene.added_colliders() {
+            ui.horizontal(|ui| {
+                ui.label(match collider {
+                    Collider::Sphere { .. } => "Sphere",
+                    Collider::Capsule { .. } => "Capsule",
+                    Collider::Box { .. } => "Box",
+                    Collider::Plane { .. } => "Plane",
+                    Collider::Sdf { .. } => "SDF volume",
+                    Collider::Mesh { .. } => "Mesh",
+                    Collider::HeightField { .. } => "Terrain",
+                });
+                if moving {
+                    ui.label("(moving)");
+                }
+                let mut one_sided = self.scene.is_one_sided(id);
+                if ui
+                    .checkbox(&mut one_sided, "One-sided")
+                    .on_hover_text("Only pushes out the cloth coming from outside, what starts inside stays there")
+                    .changed()
+                {
+                    sided = Some((id, one_sided));
+                }
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(id);
+                }
+            });
+            // Picked up by the next step and frame, nothing is rebuilt
+            if let (&Collider::Sphere { mut center, mut radius }, false) = (collider, moving) {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    for axis in &mut center {
+                        changed |= ui.add(egui::DragValue::new(axis).speed(0.01)).changed();
+                    }
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut radius).speed(0.005).range(0.01..=1.0).suffix(" m"))
+                        .changed();
+                });
+                if changed {
+                    edited = Some((id, Collider::Sphere { center, radius }));
+                }
+            }
+        }
+        if let Some(id) = removed {
+            self.scene.remove_collider(id);
+        }
+        if let Some((id, collider)) = edited {
+            self.scene.set_collider(id, collider);
+        }
+        if let Some((id, one_sided)) = sided {
+            self.scene.set_one_sided(id, one_sided);
+        }
+    }
+
+    // Blowing across the cloth from its side by default, edited in place
+    fn fans_ui(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .add_enabled(self.scene.fans.len() < MAX_FANS, egui::Button::new("Add fan"))
+            .clicked()
+        {
+            self.scene.fans.push(Fan {
+                position: [-0.8, 0.7, 0.0],
+                direction: [1.0, 0.0, 0.0],
+                cone_angle: 20f32.to_radians(),
+                speed: 4.0,
+                range: 1.5,
+                falloff: 1.0,
+            });
+        }
+        let mut removed = None;
+        for (index, fan) in self.scene.fans.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Fan {index}"));
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+                let vector = |ui: &mut egui::Ui, label: &str, vector: &mut [f32; 3]| {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        for coordinate in vector {
+                            ui.add(egui::DragValue::new(coordinate).speed(0.01));
+                        }
+                    });
+                };
+                vector(ui, "Position", &mut fan.position);
+                vector(ui, "Direction", &mut fan.direction);
+                ui.add(egui::Slider::new(&mut fan.cone_angle, 0.01..=1.5).text("Cone angle (rad)"));
+                ui.add(egui::Slider::new(&mut fan.speed, 0.0..=20.0).text("Air speed (m/s)"));
+                ui.add(egui::Slider::new(&mut fan.range, 0.1..=5.0).text("Range (m)"));
+                ui.add(egui::Slider::new(&mut fan.falloff, 0.0..=4.0).text("Falloff"));
+            });
+        }
+        if let Some(index) = removed {
+            self.scene.fans.remove(index);
+        }
+    }
+
+    fn rig_animation_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("glTF animation");
+            ui.text_edit_singleline(&mut self.scene.animation_path);
+            if ui.add_enabled(self.loading.is_none(), egui::Button::new("Load")).clicked() {
+                self.loading = Some(self.scene.load_animation());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.scene.has_ground, "Ground");
+            ui.add_enabled_ui(self.scene.has_ground, |ui| {
+                ui.add(egui::DragValue::new(&mut self.scene.ground.height).speed(0.01).suffix(" m"));
+                ui.add(egui::Slider::new(&mut self.scene.ground.friction, 0.0..=4.0).text("Friction"));
+            });
+        });
+        ui.label("WASD or the arrows move the sphere, Q and E down and up");
+        let mut sphere_radius = self.scene.sphere_radius();
+        if ui
+            .add(egui::Slider::new(&mut sphere_radius, 0.02..=0.6).text("Sphere radius (m)"))
+            .changed()
+        {
+            self.scene.set_sphere_radius(sphere_radius);
+        }
+        ui.checkbox(&mut self.scene.dynamic_sphere, "Cloth pushes the sphere")
+            .on_hover_text("The sphere gets a mass and leaves the rig once a step runs");
+        ui.add_enabled(
+            self.scene.dynamic_sphere,
+            egui::Slider::new(&mut self.scene.sphere_mass, 0.05..=20.0)
+                .logarithmic(true)
+                .text("Sphere mass (kg)"),
+        );
+        let Some(animation) = &mut self.scene.animation else {
+            ui.checkbox(&mut self.scene.animate_rig, "Animate rig");
+            return;
+        };
+        let duration = animation.duration();
+        ui.horizontal(|ui| {
+            let label = if animation.playing { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                // Playing a finished animation starts it over
+                if !animation.playing && animation.time >= duration {
+                    animation.time = 0.0;
+                }
+                animation.playing = !animation.playing;
+            }
+            ui.checkbox(&mut animation.looping, "Loop");
+        });
+        ui.add(egui::Slider::new(&mut animation.time, 0.0..=duration).text("Time (s)"));
+        if ui.button("Unload animation").clicked() {
+            self.scene.animation = None;
+        }
+    }
+
+    fn export_ui(&mut self, ui: &mut egui::Ui, context: &Context) {
+        if self.recording.is_some() {
+            if ui.button("Stop recording").clicked() {
+                self.stop_recording();
+            }
+        } else if let Some(num_instances) = self.scene.cloth().map(|cloth| cloth.num_instances()) {
+            ui.add(egui::Slider::new(&mut self.export_interval, 1..=10).text("Steps per frame"));
+            if ui.button("Record mesh cache").clicked() {
+                match Recording::start(num_instances, self.export_interval, self.step_count) {
+                    Ok(recording) => {
+                        self.recording = Some(recording);
+                        self.export_status = "Recording...".to_string();
+                    }
+                    Err(error) => self.export_status = format!("Export failed: {error}"),
+                }
+            }
+        } else {
+            ui.label("Nothing to record in an empty scene");
+        }
+
+        if let Some(replay) = &self.replay {
+            ui.label(format!("Replay: {} frames", replay.num_frames()));
+            if ui.button("Stop replay recording").clicked() {
+                self.stop_replay();
+            }
+        } else if self.replay_playback.is_some() {
+            if ui.button("Stop replay").clicked() {
+                self.replay_playback = None;
+            }
+        } else if let Some(num_instances) = self.scene.cloth().map(|cloth| cloth.num_instances()) {
+            ui.horizontal(|ui| {
+                if ui.button("Record replay").clicked() {
+                    match ReplayWriter::start(num_instances, self.export_interval, self.step_count) {
+                        Ok(replay) => self.replay = Some(replay),
+                        Err(error) => self.export_status = format!("Replay recording failed: {error}"),
+                    }
+                }
+                if ui.button("Play replay").clicked() {
+                    self.start_replay_playback();
+                }
+            });
+        }
+
+        if let Some(batch) = &self.batch {
+            ui.label(format!("Rendering frame {}...", batch.frame()));
+            if ui.button("Stop batch render").clicked() {
+                self.batch = None;
+            }
+        } else {
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.batch_size[0]).range(16..=8192));
+                ui.label("×");
+                ui.add(egui::DragValue::new(&mut self.batch_size[1]).range(16..=8192));
+                // From the replay and the camera path, with the motion blur settings of the view and
+                // the turntable
+                if ui.button("Render replay to images").clicked() {
+                    self.start_batch_render(context);
+                }
+            });
+        }
+
+        if let Some(study) = &self.study {
+            ui.label(format!("Study run {} of {}...", study.run() + 1, study.runs()));
+            if ui.button("Stop study").clicked() {
+                self.study = None;
+            }
+        } else if self.scene.cloth().is_some() {
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.study_runs).range(2..=256).suffix(" runs"));
+                ui.add(egui::DragValue::new(&mut self.study_seed).prefix("seed "));
+                if ui
+                    .button("Run study")
+                    .on_hover_text("Drops the cloth again from jittered starts and averages settle time and strain")
+                    .clicked()
+                {
+                    self.study = Some(Study::new(self.study_runs, self.study_seed));
+                    self.drop_for_study(context);
+                }
+            });
+        }
+
+        if self.scene.cloth().is_some() {
+            ui.add_enabled_ui(!self.bake_requested, |ui| {
+                if ui
+                    .button("Bake drape")
+                    .on_hover_text("Heightmap and normal map of every grid piece, over its rows and columns")
+                    .clicked()
+                {
+                    self.bake_requested = true;
+                }
+            });
+        }
+
+        if self.camera_playback.is_some() {
+            if ui.button("Stop camera path").clicked() {
+                self.camera_playback = None;
+            }
+        } else if ui.button("Play camera path").clicked() {
+            match CameraPath::load(&Path::new(EXPORT_DIR).join(CAMERA_PATH_FILE)) {
+                Ok(path) => self.camera_playback = Some((path, self.step_count)),
+                Err(error) => self.export_status = format!("Could not load camera path: {error}"),
+            }
+        }
+
+        self.checksum_ui(ui);
+
+        if !self.export_status.is_empty() {
+            ui.label(&self.export_status);
+        }
+    }
+
+    fn checksum_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.desync.enabled, "State checksum")
+            .on_hover_text("Hashes the particles after every step, to catch a desync with another run or a replay");
+        if !self.desync.enabled {
+            return;
+        }
+        if let Some((step, checksum)) = self.desync.latest() {
+            ui.label(format!("Step {step}: {checksum:016x}"));
+        }
+        match self.desync.first_desync() {
+            Some(step) => {
+                ui.label(format!("Desync with the reference at step {step}"));
+            }
+            None if self.desync.has_reference() => {
+                ui.label("In sync with the reference");
+            }
+            None => {}
+        }
+        let path = Path::new(EXPORT_DIR).join(CHECKSUM_FILE);
+        ui.horizontal(|ui| {
+            if ui.button("Save checksums").clicked() {
+                self.export_status = match fs::create_dir_all(EXPORT_DIR).and_then(|()| self.desync.save(&path)) {
+                    Ok(()) => format!("Saved to {EXPORT_DIR}/{CHECKSUM_FILE}"),
+                    Err(error) => format!("Could not save the checksums: {error}"),
+                };
+            }
+            if ui.button("Load reference").on_hover_text(format!("From {EXPORT_DIR}/{CHECKSUM_FILE}")).clicked() {
+                if let Err(error) = self.desync.load_reference(&path) {
+                    self.export_status = format!("Could not load the checksums: {error}");
+                }
+            }
+        });
+    }
+
+    // Own OS window when the backend supports multiple viewports, embedded window otherwise.
+    // Closing the OS window docks the controls again.
+    pub(super) fn detached_controls_ui(&mut self, ctx: &egui::Context, context: &Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("controls"),
+            egui::ViewportBuilder::default()
+                .with_title("Cloth Controls")
+                .with_inner_size([320.0, 640.0]),
+            |ctx, class| {
+                if class == egui::ViewportClass::Embedded {
+                    egui::Window::new("Controls").show(ctx, |ui| self.controls_ui(ui, context));
+                } else {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        egui::ScrollArea::vertical().show(ui, |ui| self.controls_ui(ui, context));
+                    });
+                    if ctx.input(|input| input.viewport().close_requested()) {
+                        self.controls = ControlsPlacement::SidePanel;
+                    }
+                }
+            },
+        );
+    }
+}
+
+// Applied by the next rebuild, strains are the stretch over the rest length
+fn tear_map_ui(ui: &mut egui::Ui, map: &mut TearMap) {
+    let defaults = [
+        TearMap::Never,
+        TearMap::Uniform(0.5),
+        TearMap::Gradient {
+            axis: [1.0, 0.0, 0.0],
+            from: 0.2,
+            to: 2.0,
+        },
+        TearMap::Perforation {
+            point: [0.0; 3],
+            normal: [1.0, 0.0, 0.0],
+            strain: 0.0,
+            perforated: 0.3,
+        },
+    ];
+    egui::ComboBox::from_label("Tearing")
+        .selected_text(map.name())
+        .show_ui(ui, |ui| {
+            for default in defaults {
+                if ui.selectable_label(map.name() == default.name(), default.name()).clicked() {
+                    *map = default;
+                }
+            }
+        });
+    fn strain_slider<'a>(value: &'a mut f32, text: &str) -> egui::Slider<'a> {
+        egui::Slider::new(value, 0.0..=3.0).text(text)
+    }
+    match map {
+        TearMap::Never => {}
+        TearMap::Uniform(strain) => {
+            ui.add(strain_slider(strain, "Tear strain"));
+        }
+        TearMap::Gradient { from, to, .. } => {
+            ui.add(strain_slider(from, "Tear strain, left"));
+            ui.add(strain_slider(to, "Tear strain, right"));
+        }
+        TearMap::Perforation {
+            point, strain, perforated, ..
+        } => {
+            ui.add(egui::Slider::new(&mut point[0], -1.0..=1.0).text("Perforation x (m)"));
+            ui.add(strain_slider(perforated, "Tear strain, perforation"));
+            ui.add(strain_slider(strain, "Tear strain, elsewhere")).on_hover_text("0 never tears");
+        }
+    }
+}
+
+// Rigid pins by default, elastic ones stretch under the weight of the cloth and can snap
+fn pin_group_ui(ui: &mut egui::Ui, pins: &mut PinGroup) {
+    let mut elastic = pins.compliance > 0.0;
+    if ui.checkbox(&mut elastic, "Elastic pins").changed() {
+        *pins = if elastic {
+            PinGroup {
+                compliance: PIN_COMPLIANCE,
+                ..*pins
+            }
+        } else {
+            PinGroup::default()
+        };
+    }
+    if !elastic {
+        return;
+    }
+    ui.add(
+        egui::Slider::new(&mut pins.compliance, 1e-4..=1e-1)
+            .logarithmic(true)
+            .text("Pin compliance (m/N)"),
+    );
+    ui.add(egui::Slider::new(&mut pins.break_force, 0.0..=2.0).text("Pin break force (N)"))
+        .on_hover_text("0 never breaks, broken pins hold again once the cloth is dropped again");
+}
@@ -0,0 +1,557 @@
+// The frame graph of the app: the passes recorded every frame, in dependency order, and the
+// CPU passes reading their results back.
+
+use super::*;
+
+impl InstanceApp {
+    pub(super) fn frame_graph() -> FrameGraph<InstanceApp> {
+        FrameGraph::new(vec![
+            Pass::gpu("simulate", Self::simulate_pass)
+                .writes(Resource::Particles)
+                .writes(Resource::StageProbe)
+                .writes(Resource::StepTimestamps)
+                .enabled_if(|app| {
+                    (!app.paused || app.substep_requested)
+                        && app.replay_playback.is_none()
+                        && app.batch.is_none()
+                        && app.last_generation + app.generation_duration < Instant::now()
+                }),
+            Pass::gpu("replay playback", Self::replay_playback_pass)
+                .writes(Resource::Particles)
+                .enabled_if(|app| {
+                    app.batch.is_none()
+                        && app.replay_playback.as_ref().is_some_and(|replay| {
+                        app.last_generation + app.generation_duration * replay.interval() < Instant::now()
+                    })
+                }),
+            Pass::gpu("checksum", Self::checksum_pass)
+                .reads(Resource::Particles)
+                .writes(Resource::Checksum)
+                .enabled_if(|app| app.desync.enabled && app.stepped_this_frame && app.scene.cloth().is_some()),
+            Pass::gpu("energy", Self::energy_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| {
+                    app.energy_plot.enabled
+                        && app.stepped_this_frame
+                        && app.scene.cloth().is_some()
+                        && (app.step_count - app.drop_step) % ENERGY_SAMPLE_INTERVAL == 0
+                }),
+            Pass::gpu("inspector", Self::inspector_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| app.inspector.selected().is_some() && app.scene.cloth().is_some()),
+            Pass::gpu("self shadow", Self::self_shadow_pass)
+                .reads(Resource::Particles)
+                .writes(Resource::Occlusion)
+                .enabled_if(|app| app.self_shadow.enabled && app.scene.cloth().is_some()),
+            Pass::gpu("strain", Self::strain_pass)
+                .reads(Resource::Particles)
+                .writes(Resource::Attribute)
+                .enabled_if(|app| app.attribute_source == AttributeSource::Strain && app.scene.cloth().is_some()),
+            Pass::gpu("kinematics", Self::kinematics_pass)
+                .reads(Resource::Particles)
+                .writes(Resource::Attribute)
+                .enabled_if(|app| app.attribute_source.motion().is_some() && app.scene.cloth().is_some()),
+            Pass::gpu("sphere body", Self::sphere_body_pass)
+                .reads(Resource::Particles)
+                .writes(Resource::Colliders)
+                .enabled_if(|app| app.scene.sphere_body_buffer().is_some()),
+            Pass::gpu("shadow map", Self::shadow_map_pass)
+                .reads(Resource::Particles)
+                .reads(Resource::Colliders)
+                .writes(Resource::ShadowMap)
+                .enabled_if(|app| app.shadow_map.enabled),
+            Pass::gpu("picture in picture", Self::pip_pass)
+                .reads(Resource::Particles)
+                .reads(Resource::Colliders)
+                .reads(Resource::Occlusion)
+                .reads(Resource::Attribute)
+                .reads(Resource::ShadowMap)
+                .writes(Resource::PipTarget)
+                .enabled_if(|app| app.pip.enabled),
+            Pass::gpu("motion blur", Self::motion_blur_pass)
+                .reads(Resource::Particles)
+                .reads(Resource::Colliders)
+                .reads(Resource::Occlusion)
+                .reads(Resource::Attribute)
+                .reads(Resource::ShadowMap)
+                .writes(Resource::MotionTarget)
+                .enabled_if(|app| app.motion_blur.enabled && !app.taa.enabled),
+            Pass::gpu("temporal anti-aliasing", Self::taa_pass)
+                .reads(Resource::Particles)
+                .reads(Resource::Colliders)
+                .reads(Resource::Occlusion)
+                .reads(Resource::Attribute)
+                .reads(Resource::ShadowMap)
+                .writes(Resource::TaaHistory)
+                .enabled_if(|app| app.taa.enabled),
+            Pass::gpu("main view", Self::main_view_pass)
+                .reads(Resource::Particles)
+                .reads(Resource::Colliders)
+                .reads(Resource::Occlusion)
+                .reads(Resource::Attribute)
+                .reads(Resource::ShadowMap)
+                .writes(Resource::MainView)
+                .enabled_if(|app| app.draws_main_view()),
+            Pass::cpu("export", Self::export_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| {
+                    app.stepped_this_frame
+                        && app.recording.as_ref().is_some_and(|recording| recording.wants_frame(app.step_count))
+                }),
+            Pass::cpu("replay record", Self::replay_record_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| {
+                    app.stepped_this_frame && app.replay.as_ref().is_some_and(|replay| replay.wants_frame(app.step_count))
+                }),
+            Pass::cpu("guard readback", Self::guard_readback_pass)
+                .reads(Resource::GuardCount)
+                .enabled_if(|app| app.stepped_this_frame && app.step_count % GUARD_READBACK_INTERVAL == 0),
+            Pass::cpu("checksum readback", Self::checksum_readback_pass)
+                .reads(Resource::Checksum)
+                .enabled_if(|app| app.desync.enabled && app.stepped_this_frame),
+            Pass::cpu("convergence readback", Self::convergence_readback_pass)
+                .reads(Resource::Convergence)
+                .enabled_if(|app| {
+                    app.convergence.enabled
+                        && app.stepped_this_frame
+                        && app.step_count % CONVERGENCE_READBACK_INTERVAL == 0
+                }),
+            Pass::cpu("performance readback", Self::perf_readback_pass)
+                .reads(Resource::StepTimestamps)
+                .enabled_if(|app| {
+                    app.perf_hud.enabled && app.stepped_this_frame && app.step_count % PERF_READBACK_INTERVAL == 0
+                }),
+            Pass::cpu("frame", Self::frame_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| app.frame_requested && app.scene.cloth().is_some()),
+            Pass::cpu("pick", Self::pick_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| app.inspector.wants_pick() && app.scene.cloth().is_some()),
+            Pass::cpu("study", Self::study_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| {
+                    app.study.is_some()
+                        && app.stepped_this_frame
+                        && (app.step_count - app.drop_step) % SAMPLE_INTERVAL == 0
+                }),
+            Pass::cpu("divergence", Self::divergence_pass)
+                .reads(Resource::StageProbe)
+                .enabled_if(|app| {
+                    app.divergence.is_some() && app.stepped_this_frame && app.solver_mode == SolverMode::Jacobi
+                }),
+            Pass::cpu("bake", Self::bake_pass)
+                .reads(Resource::Particles)
+                .enabled_if(|app| app.bake_requested),
+            Pass::gpu("batch render", Self::batch_render_pass)
+                .writes(Resource::Particles)
+                .writes(Resource::BatchImage)
+                .enabled_if(|app| app.batch.is_some()),
+            Pass::cpu("batch save", Self::batch_save_pass)
+                .reads(Resource::BatchImage)
+                .enabled_if(|app| app.batch.is_some()),
+        ])
+    }
+
+    fn simulate_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        let single_substep = std::mem::take(&mut self.substep_requested);
+        let params = self.sim_params(single_substep);
+        if let Some(centers) = self.scene.cloth().map(ClothSimulation::piece_centers) {
+            // Far pieces don't collide with themselves, they still do with the others
+            let far = self.lod_controller.update(&self.lod, self.camera.eye(), &centers);
+            let mut self_collision = self.self_collision;
+            for (piece, &far) in far.iter().enumerate() {
+                if far {
+                    self_collision.set_collides(piece, piece, false);
+                }
+            }
+            // The twin of a comparison sees the same settings
+            for cloth in self.scene.cloths_mut() {
+                cloth.set_lod(far, self.lod.far_iterations);
+                cloth.set_convergence(context, &self.convergence);
+                cloth.set_self_collision(context, &self_collision);
+            }
+        }
+        // Only timed while shown, the timestamps split the step into passes of their own
+        if self.perf_hud.enabled {
+            self.perf_hud.begin_step(encoder);
+        }
+        self.scene.encode_step(context, encoder, self.step_count, &params, self.solver_mode);
+        if self.perf_hud.enabled {
+            self.perf_hud.end_step(encoder);
+        }
+        self.last_generation = Instant::now();
+        self.stepped_this_frame = true;
+        self.step_count += 1;
+    }
+
+    fn checksum_pass(&mut self, _context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(cloth) = self.scene.cloth() {
+            cloth.encode_checksum(encoder);
+        }
+    }
+
+    // Copied out without waiting, the plot picks the sample up a few frames later
+    fn energy_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        let Some(cloth) = self.scene.cloth() else {
+            return;
+        };
+        cloth.encode_energy(encoder);
+        let time = (self.step_count - self.drop_step) as f32 * self.time_step;
+        self.energy_plot.copy(context, encoder, cloth.energy_buffer(), time);
+    }
+
+    fn inspector_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(cloth) = self.scene.cloth() {
+            self.inspector.copy(context, encoder, cloth.instance_buffer());
+        }
+    }
+
+    fn self_shadow_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        for cloth in self.scene.cloths() {
+            cloth.encode_self_shadow(context, encoder, &self.self_shadow);
+        }
+    }
+
+    fn strain_pass(&mut self, _context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        for cloth in self.scene.cloths() {
+            cloth.encode_strain(encoder);
+        }
+    }
+
+    fn kinematics_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        let interval = self.step_count.saturating_sub(self.kinematics_step) as f32 * self.time_step;
+        self.kinematics_step = self.step_count;
+        let Some(quantity) = self.attribute_source.motion() else {
+            return;
+        };
+        for cloth in self.scene.cloths() {
+            cloth.encode_kinematics(context, encoder, quantity, interval);
+        }
+    }
+
+    // Draws the sphere where the cloth pushed it rather than where the rig would have it
+    fn sphere_body_pass(&mut self, _context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(body) = self.scene.sphere_body_buffer() {
+            let center_size = std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress;
+            encoder.copy_buffer_to_buffer(body, 0, &self.sphere_instance_buffer, 0, center_size);
+        }
+    }
+
+    // Depth of the particles and colliders from the sun, for the shadows of the views drawn after.
+    // The cloth casts none while compared, its shadow would fall on the twin as well.
+    fn shadow_map_pass(&mut self, _context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        self.shadow_map.render(encoder, |render_pass, [particle_pipeline, collider_pipeline]| {
+            if let (Some(cloth), None) = (self.scene.cloth(), self.scene.comparison()) {
+                render_pass.set_pipeline(particle_pipeline);
+                render_pass.set_bind_group(1, &self.interpolation_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, cloth.instance_buffer().slice(..));
+                render_pass.set_vertex_buffer(2, cloth.previous_instance_buffer().slice(..));
+                render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                self.particle_lod.draw(render_pass, 0..cloth.num_instances());
+            }
+            render_pass.set_pipeline(collider_pipeline);
+            render_pass.set_vertex_buffer(0, self.sphere_vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.sphere_instance_buffer.slice(..));
+            render_pass.set_index_buffer(self.sphere_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..self.num_sphere_indices, 0, 0..self.num_spheres);
+        });
+    }
+
+    fn pip_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        self.pip.update(context, &self.transparency);
+        self.pip.render_offscreen(
+            encoder,
+            &self.transparency,
+            |render_pass, camera_bind_group| self.draw_scene(render_pass, camera_bind_group, false, self.scene.cloth()),
+            |render_pass, camera_bind_group| self.draw_transparent(render_pass, camera_bind_group, self.scene.cloth()),
+        );
+    }
+
+    fn motion_blur_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        self.motion_blur
+            .update(context, (context.size().x as u32, context.size().y as u32), &self.transparency);
+        self.motion_blur.render_offscreen(
+            encoder,
+            &self.transparency,
+            |render_pass| self.draw_scene(render_pass, self.camera.bind_group(), true, self.scene.cloth()),
+            |render_pass| self.draw_transparent(render_pass, self.camera.bind_group(), self.scene.cloth()),
+        );
+    }
+
+    fn taa_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        self.taa.update(context, &self.camera, self.generation_duration, &self.transparency);
+        self.taa.render_offscreen(
+            encoder,
+            &self.transparency,
+            |render_pass, camera_bind_group| self.draw_scene(render_pass, camera_bind_group, true, self.scene.cloth()),
+            |render_pass, camera_bind_group| self.draw_transparent(render_pass, camera_bind_group, self.scene.cloth()),
+        );
+    }
+
+    fn main_view_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        let size = (context.size().x as u32, context.size().y as u32);
+        self.main_view.update(context, size, &self.transparency);
+        self.main_view.depth_of_field.update(context, &self.camera);
+        if self.scene.comparison().is_some() {
+            self.comparison_camera
+                .set_polar(self.camera.polar())
+                .set_target(self.camera.target())
+                .set_projection(self.camera.projection())
+                .update_with_aspect(context, 0.5 * size.0 as f32 / size.1 as f32);
+        }
+        self.main_view.render_offscreen(
+            encoder,
+            &self.transparency,
+            |render_pass| self.draw_main_view(render_pass, size, false),
+            |render_pass| self.draw_main_view(render_pass, size, true),
+        );
+    }
+
+    // Appends the state the simulate pass just wrote to the recording
+    fn export_pass(&mut self, _context: &Context, readback: &Readback) {
+        let (Some(recording), Some(instances)) = (&mut self.recording, readback.get::<Instance>(Resource::Particles)) else {
+            return;
+        };
+
+        let positions = instances.iter().map(Instance::position);
+        if let Err(error) = recording.record(self.step_count, positions, self.camera.polar(), self.camera.target()) {
+            log::error!("Recording failed: {error}");
+            self.export_status = format!("Recording failed: {error}");
+            self.recording = None;
+        }
+    }
+
+    // Shows the next frame of the replay, the steps it stands for count as simulated so the export
+    // records it like a live run
+    fn replay_playback_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        let Some(replay) = &mut self.replay_playback else {
+            return;
+        };
+        let frame = match replay.next_frame() {
+            Ok(None) => replay.rewind().and_then(|()| replay.next_frame()),
+            frame => frame,
+        };
+        let interval = replay.interval();
+        match frame {
+            Ok(Some(positions)) => {
+                if let Some(cloth) = self.scene.cloth_mut() {
+                    cloth.encode_replay_frame(context, encoder, &positions);
+                }
+            }
+            Ok(None) => {
+                self.export_status = "The replay has no frames".to_string();
+                self.replay_playback = None;
+            }
+            Err(error) => {
+                self.export_status = format!("Replay failed: {error}");
+                self.replay_playback = None;
+            }
+        }
+        self.last_generation = Instant::now();
+        self.stepped_this_frame = true;
+        self.step_count += u64::from(interval);
+    }
+
+    fn study_pass(&mut self, context: &Context, readback: &Readback) {
+        let (Some(study), Some(cloth), Some(instances)) =
+            (&mut self.study, self.scene.cloth(), readback.get::<Instance>(Resource::Particles))
+        else {
+            return;
+        };
+        if !study.sample(cloth, &instances, self.step_count - self.drop_step) {
+            return;
+        }
+        if study.next_run() {
+            self.drop_for_study(context);
+            return;
+        }
+        self.export_status = match study.finish() {
+            Ok(summary) => format!("{summary}, runs in {EXPORT_DIR}/{STUDY_FILE}"),
+            Err(error) => format!("Study failed: {error}"),
+        };
+        self.study = None;
+    }
+
+    fn divergence_pass(&mut self, _context: &Context, readback: &Readback) {
+        let (Some(monitor), Some(cloth), Some(probe)) =
+            (&mut self.divergence, self.scene.cloth(), readback.get::<Instance>(Resource::StageProbe))
+        else {
+            return;
+        };
+        if let Some(inputs) = cloth.probe_inputs() {
+            monitor.check(inputs, cloth.constraints(), &probe);
+        }
+    }
+
+    // Bakes every grid piece of the cloth as it is now, meant for once it has settled
+    fn bake_pass(&mut self, _context: &Context, readback: &Readback) {
+        self.bake_requested = false;
+        let (Some(cloth), Some(instances)) = (self.scene.cloth(), readback.get::<Instance>(Resource::Particles)) else {
+            return;
+        };
+
+        let mut baked = 0;
+        for (index, piece) in cloth.pieces().iter().enumerate() {
+            let Some((rows, cols)) = piece.grid else {
+                continue;
+            };
+            let particles = piece.offset..piece.offset + piece.count;
+            let rest: Vec<_> = particles.clone().map(|particle| cloth.rest_position(particle)).collect();
+            let settled: Vec<_> = particles.map(|particle| instances[particle as usize].position()).collect();
+            let rest = GridSurface { rows, cols, positions: &rest };
+            let settled = GridSurface { rows, cols, positions: &settled };
+            if let Err(error) = bake::bake_piece(index, &rest, &settled) {
+                self.export_status = format!("Bake failed: {error}");
+                return;
+            }
+            baked += 1;
+        }
+        self.export_status = if baked == 0 {
+            "Only grid pieces can be baked, meshes have no UV domain".to_string()
+        } else {
+            format!("Baked {baked} pieces to {EXPORT_DIR}/{BAKE_DIR}")
+        };
+    }
+
+    fn guard_readback_pass(&mut self, _context: &Context, readback: &Readback) {
+        let Some(&[count]) = readback.get::<u32>(Resource::GuardCount).as_deref() else {
+            return;
+        };
+        if count > self.guard_count {
+            log::warn!(
+                "The simulation exploded, {} particles were reset to their last valid position",
+                count - self.guard_count
+            );
+        }
+        self.guard_count = count;
+    }
+
+    // Steps counted from the last drop, so runs started at different times line up
+    fn checksum_readback_pass(&mut self, _context: &Context, readback: &Readback) {
+        if let Some(&[sum, xor]) = readback.get::<u32>(Resource::Checksum).as_deref() {
+            self.desync.record(self.step_count - self.drop_step, checksum([sum, xor]));
+        }
+    }
+
+    fn convergence_readback_pass(&mut self, _context: &Context, readback: &Readback) {
+        if let Some(&[.., skipped]) = readback.get::<u32>(Resource::Convergence).as_deref() {
+            self.skipped_iterations = skipped;
+        }
+    }
+
+    fn frame_pass(&mut self, context: &Context, readback: &Readback) {
+        self.frame_requested = false;
+        let Some(instances) = readback.get::<Instance>(Resource::Particles) else {
+            return;
+        };
+        let bounds = instances.iter().map(Instance::position).fold(None, |bounds, position| {
+            let ([x0, y0, z0], [x1, y1, z1]) = bounds.unwrap_or((position, position));
+            let [x, y, z] = position;
+            Some(([x0.min(x), y0.min(y), z0.min(z)], [x1.max(x), y1.max(y), z1.max(z)]))
+        });
+        if let Some((min, max)) = bounds {
+            self.camera.frame(min, max, context);
+        }
+    }
+
+    fn pick_pass(&mut self, context: &Context, readback: &Readback) {
+        let (Some(instances), Some(cloth)) = (readback.get::<Instance>(Resource::Particles), self.scene.cloth_mut()) else {
+            return;
+        };
+        match self.inspector.pick(&instances, cloth) {
+            Some(ClothEdit::TogglePins(particles)) => {
+                cloth.toggle_pins(context, &particles, &instances);
+                self.apply_tints(context);
+            }
+            Some(ClothEdit::Cut(pairs)) => cloth.cut(context, &pairs),
+            Some(ClothEdit::Poke { center, settings }) => {
+                cloth.poke(context, &instances, center, settings.radius, settings.strength);
+            }
+            None => {}
+        }
+    }
+
+    fn perf_readback_pass(&mut self, _context: &Context, readback: &Readback) {
+        if let Some(timestamps) = readback.get::<u64>(Resource::StepTimestamps) {
+            self.perf_hud.read_step_time(&timestamps);
+        }
+    }
+
+    fn replay_record_pass(&mut self, _context: &Context, readback: &Readback) {
+        let (Some(replay), Some(instances)) = (&mut self.replay, readback.get::<Instance>(Resource::Particles)) else {
+            return;
+        };
+
+        if let Err(error) = replay.record(instances.iter().map(Instance::position)) {
+            log::error!("Replay recording failed: {error}");
+            self.export_status = format!("Replay recording failed: {error}");
+            self.replay = None;
+        }
+    }
+
+    // Moves the cloth to the next frame of the replay and renders it, the batch ends with the replay
+    fn batch_render_pass(&mut self, context: &Context, encoder: &mut wgpu::CommandEncoder) {
+        let Some(batch) = &mut self.batch else {
+            return;
+        };
+        let positions = match batch.next_frame(context, &self.transparency) {
+            Ok(Some(positions)) => positions,
+            Ok(None) => {
+                self.export_status = format!("Rendered {} frames to {EXPORT_DIR}/{FRAMES_DIR}/", batch.frame());
+                self.batch = None;
+                return;
+            }
+            Err(error) => {
+                self.export_status = format!("Batch render failed: {error}");
+                self.batch = None;
+                return;
+            }
+        };
+        if let Some(cloth) = self.scene.cloth_mut() {
+            cloth.encode_replay_frame(context, encoder, &positions);
+        }
+        if let Some(batch) = &self.batch {
+            batch.render(
+                encoder,
+                &self.tonemapping,
+                &self.transparency,
+                |render_pass, camera_bind_group, motion_vectors| {
+                    self.draw_scene(render_pass, camera_bind_group, motion_vectors, self.scene.cloth())
+                },
+                |render_pass, camera_bind_group| self.draw_transparent(render_pass, camera_bind_group, self.scene.cloth()),
+            );
+        }
+    }
+
+    fn batch_save_pass(&mut self, context: &Context, readback: &Readback) {
+        let (Some(batch), Some(bytes)) = (&mut self.batch, readback.get::<u8>(Resource::BatchImage)) else {
+            return;
+        };
+        if let Err(error) = batch.save_frame(context, &bytes) {
+            self.export_status = format!("Batch render failed: {error}");
+            self.batch = None;
+        }
+    }
+}
+
+impl FrameResources for InstanceApp {
+    fn buffer(&self, resource: Resource) -> Option<&wgpu::Buffer> {
+        match resource {
+            Resource::Particles => self.scene.cloth().map(|cloth| cloth.instance_buffer()),
+            Resource::GuardCount => self.scene.cloth().map(|cloth| cloth.guard_count_buffer()),
+            Resource::Convergence => self.scene.cloth().map(|cloth| cloth.convergence_buffer()),
+            Resource::Checksum => self.scene.cloth().map(|cloth| cloth.checksum_buffer()),
+            Resource::Attribute => self.scene.cloth().map(|cloth| cloth.attribute_buffer()),
+            Resource::BatchImage => self.batch.as_ref().map(|batch| batch.readback_buffer()),
+            Resource::PipTarget
+            | Resource::MotionTarget
+            | Resource::TaaHistory
+            | Resource::ShadowMap
+            | Resource::MainView => None, // textures, only sampled by the main pass
+            Resource::Colliders | Resource::Occlusion => None, // only drawn
+            Resource::StageProbe => self.scene.cloth().and_then(|cloth| cloth.probe_buffer()),
+            Resource::StepTimestamps => self.perf_hud.timestamp_buffer(),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// False colors of the motion of the cloth: a compute pass writing the speed, the vertical speed or
+// the acceleration of every particle into its attribute, so ringing or overdamping shows at a
+// glance, see kinematics.wgsl.
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::shaders::create_compute_module;
+
+// Must match kinematics.wgsl
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MotionQuantity {
+    Speed = 0,
+    VerticalSpeed = 1,
+    Acceleration = 2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct KinematicsParams {
+    quantity: u32,
+    interval: f32,
+    _padding: [u32; 2],
+}
+
+pub struct Kinematics {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: TrackedBuffer,
+    _last_speed_buffer: TrackedBuffer, // only reached through the bind group
+    workgroup_size: u32,
+}
+
+impl Kinematics {
+    // Writes into `attribute_buffer`, a f32 per particle
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        attribute_buffer: &wgpu::Buffer,
+        num_instances: u32,
+        workgroup_size: u32,
+    ) -> Self {
+        let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = wgpu::BufferBindingType::Storage { read_only: false };
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Kinematics Bind Group Layout"),
+            entries: &[entry(0, storage), entry(1, storage), entry(2, wgpu::BufferBindingType::Uniform)],
+        });
+
+        let last_speed_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Last Speed Buffer"),
+            size: (num_instances.max(1) as usize * std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // Filled by encode()
+        let params_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Kinematics Params Buffer"),
+            size: std::mem::size_of::<KinematicsParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Kinematics Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: attribute_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: last_speed_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Kinematics Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let module = create_compute_module(context, "Kinematics Shader", include_str!("kinematics.wgsl"), workgroup_size);
+        let pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("particle_motion"),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "particle_motion",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            _last_speed_buffer: last_speed_buffer,
+            workgroup_size,
+        }
+    }
+
+    // Reads the latest state from the first binding of `instance_bind_group`. `interval` is the
+    // time simulated since the last call, 0 keeps the acceleration of the last one.
+    pub fn encode(
+        &self,
+        context: &dyn Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        instance_bind_group: &wgpu::BindGroup,
+        num_instances: u32,
+        quantity: MotionQuantity,
+        interval: f32,
+    ) {
+        let params = KinematicsParams {
+            quantity: quantity as u32,
+            interval,
+            _padding: [0; 2],
+        };
+        context.queue().write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Kinematics Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, instance_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(num_instances.div_ceil(self.workgroup_size), 1, 1);
+    }
+}
@@ -0,0 +1,37 @@
+// kinematics.wgsl
+// Motion of the cloth for the color ramp, appended to compute.wgsl and run on the latest state.
+// The acceleration is the change of speed since the last pass that saw a step, over the time the
+// steps in between simulated.
+
+struct KinematicsParams {
+    quantity: u32, // speed, vertical speed or acceleration, see MotionQuantity
+    interval: f32, // s simulated since the last pass, 0 without a step in between
+};
+
+const SPEED: u32 = 0u;
+const VERTICAL_SPEED: u32 = 1u;
+
+@group(1) @binding(0) var<storage, read_write> motion: array<f32>; // the attribute of the particles
+@group(1) @binding(1) var<storage, read_write> last_speeds: array<vec4<f32>>;
+@group(1) @binding(2) var<uniform> kinematics: KinematicsParams;
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn particle_motion(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let speed = instances_ping[index].speed.xyz;
+    switch kinematics.quantity {
+        case SPEED: { motion[index] = length(speed); }
+        case VERTICAL_SPEED: { motion[index] = speed.y; }
+        default: {
+            if (kinematics.interval > 0.0) {
+                motion[index] = length(speed - last_speeds[index].xyz) / kinematics.interval;
+            }
+        }
+    }
+    if (kinematics.interval > 0.0) {
+        last_speeds[index] = vec4<f32>(speed, 0.0);
+    }
+}
@@ -0,0 +1,112 @@
+// The cloth solver and its renderer, without the demo app of main.rs. The renderer takes the
+// wgpu_bootstrap Context the app runs in, the solver any sim::Gpu, that Context or a Headless
+// device without a window.
+//
+// A cloth is built and stepped on its own with sim::ClothSimulation, see step(), positions(),
+// add_collider() and pin(), or together with a rig, colliders and wind in a scene::Scene. The
+// rest is exported by what it is for in `sim`, `colliders` and `render`, the modules behind them
+// are private.
+
+mod albedo;
+mod animation;
+mod attribute;
+mod builder;
+mod bvh;
+mod camera;
+mod checksum;
+mod contact_view;
+mod convergence;
+mod divergence;
+mod dof;
+mod edge_view;
+mod energy;
+mod gauss_seidel;
+mod gpu;
+mod gpu_resources;
+mod grid;
+mod hdr;
+mod heightfield;
+mod implicit;
+mod kinematics;
+mod lighting;
+mod loading;
+mod lod;
+mod material;
+mod mesh;
+mod motion_blur;
+mod msaa;
+mod oit;
+mod particle_lod;
+mod pip;
+mod readback;
+pub mod scene;
+mod sdf;
+mod self_collision;
+mod self_shadow;
+mod shader_source;
+mod shaders;
+mod shadow_map;
+mod simulation;
+mod sky;
+mod strain;
+mod taa;
+mod tearing;
+mod tint;
+mod transform;
+
+/// The solver: the cloths, their fabrics, the settings of a step and what is read back from it.
+pub mod sim {
+    pub use crate::builder::ClothBuilder;
+    pub use crate::checksum::{checksum, DesyncMonitor, CHECKSUM_FILE};
+    pub use crate::convergence::ConvergenceSettings;
+    pub use crate::divergence::DivergenceMonitor;
+    pub use crate::energy::{EnergyPlot, SAMPLE_INTERVAL as ENERGY_SAMPLE_INTERVAL};
+    pub use crate::gpu::{Gpu, Headless};
+    pub use crate::gpu_resources::BufferTracker;
+    pub use crate::lod::{LodController, LodSettings};
+    pub use crate::material::{Material, MaterialBlend, Preset, Stiffness};
+    pub use crate::readback::{copy_to_staging, map_staging, AsyncReadback};
+    pub use crate::self_collision::{SelfCollisionSettings, MAX_COLLISION_PIECES};
+    pub use crate::simulation::{
+        AnchorFrame, ClothGrid, ClothPiece, ClothSimulation, ClothSource, ConstraintKind, Instance, Orientation,
+        PieceRange, Pins, SimParams, SolverMode, Spacing, StepHandle, DYNAMIC_FRICTION, SLEEP_SPEED, STATIC_FRICTION,
+        THICKNESS, TIME_STEP,
+    };
+    pub use crate::tearing::TearMap;
+}
+
+/// What the cloth collides with and what blows on it.
+pub mod colliders {
+    pub use crate::bvh::TriangleBvh;
+    pub use crate::heightfield::HeightField;
+    pub use crate::sdf::SdfVolume;
+    pub use crate::simulation::{Attachment, Collider, Fan, PinGroup, MAX_COLLIDERS, MAX_FANS};
+}
+
+/// Drawing the cloth and the scene around it. The vertex buffers of the cloth pipelines are, in
+/// order, the particles, their previous state, then attribute_vertex_desc(),
+/// occlusion_vertex_desc(), uv_vertex_desc() and tint_vertex_desc().
+pub mod render {
+    pub use crate::albedo::{uv_vertex_desc, AlbedoTexture};
+    pub use crate::attribute::{
+        load_values as load_attribute_values, vertex_desc as attribute_vertex_desc, AttributeSource, ColorRamp,
+        RampUniform,
+    };
+    pub use crate::camera::{CameraMode, CameraUniform, OrbitCamera, Projection};
+    pub use crate::contact_view::ContactView;
+    pub use crate::edge_view::EdgeView;
+    pub use crate::grid::GridFloor;
+    pub use crate::hdr::{MainView, TonemapOperator, Tonemapping, HDR_FORMAT};
+    pub use crate::lighting::Lighting;
+    pub use crate::motion_blur::{MotionBlur, VELOCITY_FORMAT};
+    pub use crate::msaa::supported_sample_count;
+    pub use crate::oit::{transparent_targets, Transparency};
+    pub use crate::particle_lod::ParticleLod;
+    pub use crate::pip::{PictureInPicture, PipView};
+    pub use crate::self_shadow::{vertex_desc as occlusion_vertex_desc, ShadowSettings};
+    pub use crate::shader_source::expand_includes;
+    pub use crate::shadow_map::ShadowMap;
+    pub use crate::sky::Sky;
+    pub use crate::taa::TemporalAntiAliasing;
+    pub use crate::tint::{vertex_desc as tint_vertex_desc, Highlights};
+}
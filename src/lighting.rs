@@ -0,0 +1,207 @@
+// Lights of the scene, a directional one like the sun and a point one like a lamp, shared by the
+// cloth and the colliders, with the ambient light of the sky. Both shaders shade with Blinn-Phong, or the cloth with the sheen of its
+// fabric, see wgsl/lighting.wgsl.
+
+use wgpu_bootstrap::{egui, wgpu, Context};
+
+use crate::material::Material;
+use crate::shadow_map::ShadowMap;
+use crate::sky::Sky;
+
+// Fabrics with their own sheen, the pieces past them look like the last of those. Must match
+// lighting.wgsl.
+const MAX_FABRICS: usize = 16;
+
+// Must match the Lighting struct of wgsl/lighting.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniform {
+    direction: [f32; 4], // towards the directional light, w is its intensity
+    sun_color: [f32; 4], // w is unused
+    point: [f32; 4],     // position of the point light, w is its intensity
+    point_color: [f32; 4], // w is the distance in m at which it fell to a half
+    ambient: [f32; 4],   // w is the shininess of the highlights
+    back_color: [f32; 4], // w is 1 to use it, 0 keeps the colors of the front
+    fabric_brdf: u32,
+    _padding: [u32; 3],
+    shadow: [f32; 4], // strength, bias in depth and texel size of the shadow map, see shadow_map.rs
+    shadow_matrix: [[f32; 4]; 4], // world space to the clip space of the sun
+    sky: [[f32; 4]; 3], // zenith, horizon and ground colors, w of the first is 1 to light with them
+    fog: [f32; 4],      // color, w is the density in 1/m
+}
+
+pub struct Lighting {
+    pub direction: [f32; 3], // towards the light, normalized on upload
+    pub sun_color: [f32; 3],
+    pub sun_intensity: f32,
+    pub point: [f32; 3], // m
+    pub point_color: [f32; 3],
+    pub point_intensity: f32,
+    pub point_range: f32, // m, distance at which the point light fell to a half
+    pub ambient: f32,
+    pub shininess: f32, // Blinn-Phong exponent, higher for smaller and sharper highlights
+    pub fabric_brdf: bool, // shades the cloth with the sheen of its material instead
+    pub back_color: Option<[f32; 3]>, // of the back faces of the cloth, when they are drawn
+    buffer: wgpu::Buffer,
+    fabric_buffer: wgpu::Buffer, // sheen color and roughness, then opacity, of every material
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Lighting {
+    // Every fragment looks itself up in `shadow_map`
+    pub fn new(context: &Context, shadow_map: &ShadowMap) -> Self {
+        let buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lighting Buffer"),
+            size: std::mem::size_of::<LightingUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let fabric_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Fabric Buffer"),
+            size: std::mem::size_of::<[[[f32; 4]; 2]; MAX_FABRICS]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // The colliders only read the lights
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Bind Group Layout"),
+            entries: &[
+                uniform_entry(0),
+                uniform_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lighting Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: fabric_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(shadow_map.depth_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(shadow_map.sampler()),
+                },
+            ],
+        });
+
+        Self {
+            direction: [1.0, 1.0, 1.0],
+            sun_color: [1.0, 0.97, 0.9],
+            sun_intensity: 0.8,
+            point: [0.0, 1.2, 0.8],
+            point_color: [1.0, 0.85, 0.7],
+            point_intensity: 0.4,
+            point_range: 1.0,
+            ambient: 0.25,
+            shininess: 32.0,
+            fabric_brdf: true,
+            back_color: None,
+            buffer,
+            fabric_buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    // Follows the sliders and the materials of the pieces, called once per frame. Also points the
+    // camera of `shadow_map` at the sun.
+    pub fn update(&self, context: &Context, fabrics: &[Material], shadow_map: &ShadowMap, sky: &Sky) {
+        let [x, y, z] = self.direction;
+        let length = (x * x + y * y + z * z).sqrt().max(1e-6);
+        let with = |[r, g, b]: [f32; 3], w: f32| [r, g, b, w];
+        let uniform = LightingUniform {
+            direction: [x / length, y / length, z / length, self.sun_intensity],
+            sun_color: with(self.sun_color, 0.0),
+            point: with(self.point, self.point_intensity),
+            point_color: with(self.point_color, self.point_range),
+            ambient: [self.ambient, self.ambient, self.ambient, self.shininess],
+            back_color: self.back_color.map_or([0.0; 4], |color| with(color, 1.0)),
+            fabric_brdf: u32::from(self.fabric_brdf),
+            _padding: [0; 3],
+            shadow: shadow_map.lookup(),
+            shadow_matrix: shadow_map.update(context, self.direction).into(),
+            sky: sky.environment(),
+            fog: sky.fog(),
+        };
+        context.queue().write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+
+        // A roughness of 0 would mean Blinn-Phong to the shader
+        let mut blocks = [[[0.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 0.0]]; MAX_FABRICS];
+        for (block, fabric) in blocks.iter_mut().zip(fabrics) {
+            *block = [with(fabric.sheen, fabric.roughness.max(0.05)), [fabric.opacity, 0.0, 0.0, 0.0]];
+        }
+        context.queue().write_buffer(&self.fabric_buffer, 0, bytemuck::cast_slice(&blocks));
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Sun");
+        ui.horizontal(|ui| {
+            for axis in &mut self.direction {
+                ui.add(egui::DragValue::new(axis).speed(0.02).range(-1.0..=1.0));
+            }
+            ui.color_edit_button_rgb(&mut self.sun_color);
+        });
+        ui.add(egui::Slider::new(&mut self.sun_intensity, 0.0..=10.0).text("Sun intensity"))
+            .on_hover_text("Past 1 the highlights are brought back by the tonemapping");
+        ui.label("Lamp");
+        ui.horizontal(|ui| {
+            for axis in &mut self.point {
+                ui.add(egui::DragValue::new(axis).speed(0.01).suffix(" m"));
+            }
+            ui.color_edit_button_rgb(&mut self.point_color);
+        });
+        ui.add(egui::Slider::new(&mut self.point_intensity, 0.0..=10.0).text("Lamp intensity"));
+        ui.add(egui::Slider::new(&mut self.point_range, 0.1..=5.0).text("Lamp range (m)"))
+            .on_hover_text("Distance at which the light of the lamp fell to a half");
+        ui.add(egui::Slider::new(&mut self.ambient, 0.0..=1.0).text("Ambient"))
+            .on_hover_text("Gray, or the colors of the sky around the normal when lit by the sky");
+        ui.checkbox(&mut self.fabric_brdf, "Fabric sheen")
+            .on_hover_text("Shades the cloth like fabric, with the sheen color and roughness of its material");
+        ui.add(egui::Slider::new(&mut self.shininess, 1.0..=256.0).logarithmic(true).text("Shininess"))
+            .on_hover_text("Of the highlights of the colliders, and of the cloth without fabric sheen");
+    }
+}
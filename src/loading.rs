@@ -0,0 +1,73 @@
+// Loads the heavy assets of a scene on a background thread, OBJ meshes, SDF bakes, BVHs and glTF
+// animations, so the window keeps drawing and answering the OS meanwhile. Only the upload to the
+// GPU is left to the main thread once the asset is ready.
+
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::animation::RigAnimation;
+use crate::bvh::TriangleBvh;
+use crate::heightfield::HeightField;
+use crate::sdf::SdfVolume;
+use crate::simulation::ClothSource;
+
+pub enum Asset {
+    Cloth(Vec<ClothSource>),
+    Sdf(SdfVolume),
+    CollisionMesh(TriangleBvh),
+    HeightField(HeightField),
+    Animation(RigAnimation),
+}
+
+/// Fraction of a load done so far, from 0.0 to 1.0, written by the loading thread.
+#[derive(Clone, Default)]
+pub struct Progress(Arc<AtomicU32>); // bits of the f32
+
+impl Progress {
+    pub fn set(&self, fraction: f32) {
+        self.0.store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+pub struct AssetLoad {
+    pub what: String, // shown along the progress, e.g. "Loading cape.obj"
+    progress: Progress,
+    result: Mutex<Receiver<io::Result<Asset>>>, // keeps the app Sync, only the main thread polls
+}
+
+impl AssetLoad {
+    pub fn spawn(what: String, load: impl FnOnce(&Progress) -> io::Result<Asset> + Send + 'static) -> Self {
+        let progress = Progress::default();
+        let (sender, result) = mpsc::channel();
+        let thread_progress = progress.clone();
+        thread::spawn(move || {
+            // Nobody is waiting anymore if the load was dropped
+            let _ = sender.send(load(&thread_progress));
+        });
+        Self {
+            what,
+            progress,
+            result: Mutex::new(result),
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress.get()
+    }
+
+    // None while the thread is still at it, called once per frame
+    pub fn poll(&self) -> Option<io::Result<Asset>> {
+        match self.result.lock().unwrap().try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(io::Error::other("the loading thread panicked"))),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Level of detail of the simulation in scenes of many pieces: the pieces far from the camera get
+// fewer Jacobi iterations and no self-collisions, see ClothSimulation::set_lod().
+
+use wgpu_bootstrap::cgmath::{MetricSpace, Point3};
+
+/// When pieces count as far, set from the UI.
+#[derive(Copy, Clone, Debug)]
+pub struct LodSettings {
+    pub enabled: bool,
+    pub distance: f32,       // m, from the camera to the center of a piece at rest
+    pub hysteresis: f32,     // m, a far piece only comes back that much closer than it left
+    pub far_iterations: u32, // Jacobi iterations per substep of the far pieces
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            distance: 5.0,
+            hysteresis: 0.5,
+            far_iterations: 4,
+        }
+    }
+}
+
+/// Which pieces are far, kept from one frame to the next so a piece right at the distance doesn't
+/// switch back and forth.
+#[derive(Default)]
+pub struct LodController {
+    far: Vec<bool>,
+}
+
+impl LodController {
+    // `centers` in the order of the pieces, pieces added since the last update start near
+    pub fn update(&mut self, settings: &LodSettings, eye: Point3<f32>, centers: &[[f32; 3]]) -> &[bool] {
+        self.far.resize(centers.len(), false);
+        for (far, &center) in self.far.iter_mut().zip(centers) {
+            let distance = eye.distance(Point3::from(center));
+            let threshold = if *far {
+                settings.distance - settings.hysteresis
+            } else {
+                settings.distance
+            };
+            *far = settings.enabled && distance > threshold;
+        }
+        &self.far
+    }
+
+    pub fn num_far(&self) -> usize {
+        self.far.iter().filter(|&&far| far).count()
+    }
+}
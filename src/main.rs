@@ -1,11 +1,82 @@
+mod bake;
+mod batch_render;
+mod bookmarks;
+mod checkpoint;
+mod export;
+mod frame_graph;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod gravity_ramp;
+mod inspector;
 mod instances_app;
+mod perf_hud;
+mod replay;
+mod scene_file;
+mod study;
+mod turntable;
 
+use std::path::Path;
 use std::sync::Arc;
 
 use crate::instances_app::InstanceApp;
+use crate::scene_file::SceneFile;
+use cloth::scene::{ScenePreset, DEFAULT_MESH_PATH};
+use cloth::sim::{ClothBuilder, ClothSimulation, Headless, TIME_STEP};
 use wgpu_bootstrap::{egui, Runner};
 
 fn main() {
+    // The rest state can be picked on the command line, e.g. `cargo run -- flag`, or loaded
+    // from a mesh with `cargo run -- cape.obj`. `--paused` waits for the Resume button.
+    // `--smoke-test` steps a small cloth SMOKE_TEST_STEPS times on a device without a window and
+    // exits with status 0 if it stayed finite, to check a driver works. `--msaa 4` draws the scene with 4 samples per pixel, or as many up
+    // to 4 as the device supports, out of 1, 2, 4 and 8. `--scene experiment.json` starts from a
+    // scene file instead of a preset, see scene_file.rs.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--smoke-test") {
+        smoke_test();
+    }
+    let sample_count = match args.iter().position(|arg| arg == "--msaa") {
+        None => 1,
+        Some(index) => {
+            let count = args.get(index + 1).and_then(|count| count.parse::<u32>().ok());
+            let Some(count) = count.filter(|count| [1, 2, 4, 8].contains(count)) else {
+                eprintln!("--msaa expects a sample count of 1, 2, 4 or 8");
+                std::process::exit(1);
+            };
+            args.drain(index..index + 2);
+            count
+        }
+    };
+    let scene_file = args.iter().position(|arg| arg == "--scene").map(|index| {
+        let Some(path) = args.get(index + 1).cloned() else {
+            eprintln!("--scene expects the path of a scene file");
+            std::process::exit(1);
+        };
+        args.drain(index..index + 2);
+        SceneFile::load(Path::new(&path)).unwrap_or_else(|error| {
+            eprintln!("Could not load the scene {path}: {error}");
+            std::process::exit(1);
+        })
+    });
+    let start_paused = args.iter().any(|arg| arg == "--paused");
+    args.retain(|arg| arg != "--paused");
+    let (preset, mesh_path) = match args.into_iter().next() {
+        None => (ScenePreset::default(), DEFAULT_MESH_PATH.to_string()),
+        Some(path) if path.to_ascii_lowercase().ends_with(".obj") => (ScenePreset::Mesh, path),
+        Some(name) => {
+            let preset = ScenePreset::from_name(&name).unwrap_or_else(|| {
+                let names: Vec<_> = ScenePreset::ALL.iter().map(|preset| preset.name()).collect();
+                eprintln!("Unknown scene \"{name}\", expected one of {} or an .obj file", names.join(", "));
+                std::process::exit(1);
+            });
+            (preset, DEFAULT_MESH_PATH.to_string())
+        }
+    };
+    let (preset, mesh_path) = match &scene_file {
+        Some(file) => (file.preset, file.mesh_path.clone()),
+        None => (preset, mesh_path),
+    };
+
     let mut runner = Runner::new(
         "Gui App",
         800,
@@ -13,8 +84,40 @@ fn main() {
         egui::Color32::from_rgb(245, 245, 245),
         32,
         0,
-        Box::new(|context| Arc::new(InstanceApp::new(context))),
+        Box::new(move |context| {
+            let mut app = InstanceApp::new(context, preset, mesh_path.clone(), sample_count)
+                .with_start_paused(start_paused);
+            if let Some(file) = &scene_file {
+                app = app.with_scene_file(context, file);
+            }
+            Arc::new(app)
+        }),
     );
     runner.run();
 }
 
+const SMOKE_TEST_STEPS: u32 = 100;
+
+// Exits with the outcome, 1 when there is no GPU to run on either
+fn smoke_test() -> ! {
+    let gpu = Headless::new().unwrap_or_else(|error| {
+        eprintln!("Smoke test failed: {error}");
+        std::process::exit(1);
+    });
+    let piece = ClothBuilder::new(16, 16).piece(Default::default(), Default::default());
+    let mut simulation = ClothSimulation::new(&gpu, &[piece]);
+    for _ in 0..SMOKE_TEST_STEPS {
+        simulation.step(&gpu, TIME_STEP);
+    }
+    let positions = simulation.positions(&gpu).unwrap_or_else(|error| {
+        eprintln!("Smoke test failed, the particles could not be read back: {error}");
+        std::process::exit(1);
+    });
+    let not_finite = positions.iter().filter(|position| !position.iter().all(|value| value.is_finite())).count();
+    if not_finite > 0 {
+        eprintln!("Smoke test failed: {not_finite} of {} particles not finite", positions.len());
+        std::process::exit(1);
+    }
+    println!("Smoke test passed: {} particles stayed finite for {SMOKE_TEST_STEPS} steps", positions.len());
+    std::process::exit(0);
+}
@@ -0,0 +1,223 @@
+// Fabric material parameters shared by the simulation setup and the compute shader, along with
+// how the fabric looks under the sheen shading of lighting.rs.
+
+use wgpu_bootstrap::egui;
+
+/// Stiffness of each constraint group, in the `[0, 1]` range of the position based solver.
+///
+/// Woven fabric is usually stiffer along the warp (vertical threads) than along the weft
+/// (horizontal threads), so each group gets its own value.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stiffness {
+    pub horizontal: f32,
+    pub vertical: f32,
+    pub shear: f32,
+    pub bend: f32,
+}
+
+impl Stiffness {
+    // Same order as `ConstraintKind`, which is what the shader indexes with
+    pub fn to_array(self) -> [f32; 4] {
+        [self.horizontal, self.vertical, self.shear, self.bend]
+    }
+
+    pub fn lerp(self, other: Stiffness, t: f32) -> Stiffness {
+        Stiffness {
+            horizontal: lerp(self.horizontal, other.horizontal, t),
+            vertical: lerp(self.vertical, other.vertical, t),
+            shear: lerp(self.shear, other.shear, t),
+            bend: lerp(self.bend, other.bend, t),
+        }
+    }
+}
+
+impl Default for Stiffness {
+    fn default() -> Self {
+        Self {
+            horizontal: 0.8, // weft
+            vertical: 1.0,   // warp
+            shear: 0.5,
+            bend: 0.2,
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Every parameter that describes how a fabric behaves.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    pub stiffness: Stiffness,
+    pub linear_drag: f32,    // 1/s, air drag proportional to the speed
+    pub quadratic_drag: f32, // 1/m, air drag proportional to the squared speed, 0.0 disables it
+    pub density: f32,        // relative to cotton, heavier fabrics are slowed down less by the air
+    pub friction: f32,       // Coulomb coefficient against the colliders
+    pub roughness: f32,      // of the sheen, from 0 for a thin rim of light to 1 for a soft haze
+    pub sheen: [f32; 3],     // color of the light scattered back by the fibers at grazing angles
+    pub opacity: f32,        // share of the light the fabric stops, below 1 for sheer fabrics, see oit.rs
+}
+
+impl Material {
+    pub fn lerp(&self, other: &Material, t: f32) -> Material {
+        Material {
+            stiffness: self.stiffness.lerp(other.stiffness, t),
+            linear_drag: lerp(self.linear_drag, other.linear_drag, t),
+            quadratic_drag: lerp(self.quadratic_drag, other.quadratic_drag, t),
+            density: lerp(self.density, other.density, t),
+            friction: lerp(self.friction, other.friction, t),
+            roughness: lerp(self.roughness, other.roughness, t),
+            sheen: [0, 1, 2].map(|i| lerp(self.sheen[i], other.sheen[i], t)),
+            opacity: lerp(self.opacity, other.opacity, t),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    Silk,
+    Cotton,
+    Denim,
+    Leather,
+    Voile, // sheer curtain fabric, seen through
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [Preset::Silk, Preset::Cotton, Preset::Denim, Preset::Leather, Preset::Voile];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Silk => "Silk",
+            Preset::Cotton => "Cotton",
+            Preset::Denim => "Denim",
+            Preset::Leather => "Leather",
+            Preset::Voile => "Voile",
+        }
+    }
+
+    pub fn material(self) -> Material {
+        match self {
+            Preset::Silk => Material {
+                stiffness: Stiffness { horizontal: 0.4, vertical: 0.5, shear: 0.1, bend: 0.01 },
+                linear_drag: 0.6,
+                quadratic_drag: 0.8,
+                density: 0.4,
+                friction: 0.2,
+                roughness: 0.3,
+                sheen: [0.9, 0.9, 0.85],
+                opacity: 1.0,
+            },
+            Preset::Cotton => Material {
+                stiffness: Stiffness::default(),
+                linear_drag: 0.4,
+                quadratic_drag: 0.3,
+                density: 1.0,
+                friction: 0.5,
+                roughness: 0.8,
+                sheen: [0.35, 0.35, 0.35],
+                opacity: 1.0,
+            },
+            Preset::Denim => Material {
+                stiffness: Stiffness { horizontal: 0.9, vertical: 1.0, shear: 0.7, bend: 0.5 },
+                linear_drag: 0.2,
+                quadratic_drag: 0.1,
+                density: 2.5,
+                friction: 0.6,
+                roughness: 0.7,
+                sheen: [0.25, 0.3, 0.4],
+                opacity: 1.0,
+            },
+            Preset::Leather => Material {
+                stiffness: Stiffness { horizontal: 1.0, vertical: 1.0, shear: 0.9, bend: 0.8 },
+                linear_drag: 0.1,
+                quadratic_drag: 0.05,
+                density: 4.0,
+                friction: 0.8,
+                roughness: 0.5,
+                sheen: [0.1, 0.08, 0.06],
+                opacity: 1.0,
+            },
+            Preset::Voile => Material {
+                stiffness: Stiffness { horizontal: 0.3, vertical: 0.4, shear: 0.05, bend: 0.005 },
+                linear_drag: 0.9,
+                quadratic_drag: 1.2,
+                density: 0.2,
+                friction: 0.3,
+                roughness: 0.6,
+                sheen: [0.8, 0.8, 0.8],
+                opacity: 0.35,
+            },
+        }
+    }
+}
+
+/// Interpolates between two presets, driven by a slider in the GUI, unless the values were
+/// edited one by one.
+#[derive(Copy, Clone, Debug)]
+pub struct MaterialBlend {
+    pub from: Preset,
+    pub to: Preset,
+    pub t: f32,
+    pub custom: Option<Material>, // starts from the blend, replaces it
+}
+
+impl MaterialBlend {
+    // Starts on `preset` alone
+    pub fn new(preset: Preset) -> Self {
+        Self {
+            from: preset,
+            to: Preset::Denim,
+            t: 0.0,
+            custom: None,
+        }
+    }
+
+    pub fn material(&self) -> Material {
+        self.custom
+            .unwrap_or_else(|| self.from.material().lerp(&self.to.material(), self.t))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut edited = self.custom.is_some();
+        if ui.checkbox(&mut edited, "Edit values").on_hover_text("Starts from the blend of the presets").changed() {
+            self.custom = edited.then(|| self.material());
+        }
+        if let Some(custom) = &mut self.custom {
+            custom_ui(ui, custom);
+        } else {
+            preset_combo_box(ui, "From", &mut self.from);
+            preset_combo_box(ui, "To", &mut self.to);
+            ui.add(egui::Slider::new(&mut self.t, 0.0..=1.0).text("Blend"));
+        }
+        let opacity = self.material().opacity;
+        if opacity < 1.0 {
+            ui.label(format!("Sheer, {:.0}% opaque", opacity * 100.0));
+        }
+    }
+}
+
+
+// How the fabric behaves, its look stays that of the blend it started from
+fn custom_ui(ui: &mut egui::Ui, material: &mut Material) {
+    let stiffness = &mut material.stiffness;
+    ui.add(egui::Slider::new(&mut stiffness.horizontal, 0.0..=1.0).text("Weft stiffness"));
+    ui.add(egui::Slider::new(&mut stiffness.vertical, 0.0..=1.0).text("Warp stiffness"));
+    ui.add(egui::Slider::new(&mut stiffness.shear, 0.0..=1.0).text("Shear stiffness"));
+    ui.add(egui::Slider::new(&mut stiffness.bend, 0.0..=1.0).logarithmic(true).text("Bend stiffness"));
+    ui.add(egui::Slider::new(&mut material.linear_drag, 0.0..=2.0).text("Linear drag (1/s)"));
+    ui.add(egui::Slider::new(&mut material.quadratic_drag, 0.0..=2.0).text("Quadratic drag (1/m)"));
+    ui.add(egui::Slider::new(&mut material.density, 0.1..=5.0).logarithmic(true).text("Density"))
+        .on_hover_text("Relative to cotton, heavier fabrics are slowed down less by the air");
+    ui.add(egui::Slider::new(&mut material.friction, 0.0..=2.0).text("Friction"));
+}
+
+fn preset_combo_box(ui: &mut egui::Ui, label: &str, preset: &mut Preset) {
+    egui::ComboBox::from_label(label)
+        .selected_text(preset.name())
+        .show_ui(ui, |ui| {
+            for candidate in Preset::ALL {
+                ui.selectable_value(preset, candidate, candidate.name());
+            }
+        });
+}
@@ -0,0 +1,231 @@
+// Cloth rest states read from triangle meshes (Wavefront OBJ), for shapes a grid can't describe:
+// shirts, capes, flags with holes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::simulation::{Constraint, ConstraintKind, Instance, RestState, MAX_CONSTRAINTS};
+
+const PIN_TOLERANCE: f32 = 0.01; // fraction of the mesh size below its highest vertex that is still pinned
+
+/// Particles at the vertices of the mesh, a distance constraint along every edge and a bend
+/// constraint across every edge shared by two triangles.
+pub struct ClothMesh {
+    positions: Vec<[f32; 3]>,
+    triangles: Vec<[u32; 3]>,
+    pub pin_top: bool, // pins the highest vertices, e.g. the shoulders of a cape
+}
+
+impl ClothMesh {
+    // Only the vertices and faces are read, polygons are split into triangles. Vertices at the
+    // same position are merged so seams in the texture coordinates don't tear the cloth.
+    pub fn load_obj(path: &Path) -> io::Result<Self> {
+        let invalid = |line: usize, what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid {what} on line {}", line + 1));
+
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut welded: Vec<u32> = Vec::new(); // OBJ vertex to particle
+        let mut particles: HashMap<[u32; 3], u32> = HashMap::new();
+        let mut triangles = Vec::new();
+        for (line_index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let coordinates = tokens
+                        .take(3)
+                        .map(str::parse::<f32>)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid(line_index, "vertex"))?;
+                    let [x, y, z] = coordinates[..] else {
+                        return Err(invalid(line_index, "vertex"));
+                    };
+                    let key = [x.to_bits(), y.to_bits(), z.to_bits()];
+                    let particle = *particles.entry(key).or_insert_with(|| {
+                        positions.push([x, y, z]);
+                        positions.len() as u32 - 1
+                    });
+                    welded.push(particle);
+                }
+                Some("f") => {
+                    // v, v/vt, v//vn or v/vt/vn, negative indices count back from the last vertex
+                    let corners = tokens
+                        .map(|token| {
+                            let index: i64 = token.split('/').next()?.parse().ok()?;
+                            let vertex = if index < 0 { welded.len() as i64 + index } else { index - 1 };
+                            welded.get(usize::try_from(vertex).ok()?).copied()
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|corners| corners.len() >= 3)
+                        .ok_or_else(|| invalid(line_index, "face"))?;
+                    for i in 1..corners.len() - 1 {
+                        let triangle = [corners[0], corners[i], corners[i + 1]];
+                        // Merged vertices can collapse a triangle
+                        if triangle[0] != triangle[1] && triangle[1] != triangle[2] && triangle[2] != triangle[0] {
+                            triangles.push(triangle);
+                        }
+                    }
+                }
+                _ => {} // normals, texture coordinates, groups and materials don't matter here
+            }
+        }
+
+        if triangles.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the mesh has no faces"));
+        }
+        Ok(Self {
+            positions,
+            triangles,
+            pin_top: true,
+        })
+    }
+
+    // Scales the mesh uniformly so its largest extent is `size` and centers it on `center`,
+    // models come in all units
+    pub fn fit(&mut self, center: [f32; 3], size: f32) {
+        let (min, max) = self.bounds();
+        let scale = size / (0..3).map(|axis| max[axis] - min[axis]).fold(f32::EPSILON, f32::max);
+        for position in &mut self.positions {
+            for axis in 0..3 {
+                position[axis] = center[axis] + (position[axis] - (min[axis] + max[axis]) / 2.0) * scale;
+            }
+        }
+    }
+
+    // Corners of every triangle, for meshes used as collision shapes
+    pub fn triangle_corners(&self) -> impl Iterator<Item = [[f32; 3]; 3]> + '_ {
+        self.triangles
+            .iter()
+            .map(|triangle| triangle.map(|corner| self.positions[corner as usize]))
+    }
+
+    fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        self.positions.iter().fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), position| {
+            (
+                [0, 1, 2].map(|axis| min[axis].min(position[axis])),
+                [0, 1, 2].map(|axis| max[axis].max(position[axis])),
+            )
+        })
+    }
+
+    // Planar projection onto the two largest extents of the mesh, scaled by the largest one so the
+    // texture keeps its aspect. OBJ texture coordinates aren't read.
+    pub(crate) fn uvs(&self) -> Vec<[f32; 2]> {
+        let (min, max) = self.bounds();
+        let extent = [0, 1, 2].map(|axis| max[axis] - min[axis]);
+        let size = extent.into_iter().fold(1e-6, f32::max);
+        let mut axes = [0, 1, 2];
+        axes.sort_by(|&a, &b| extent[b].total_cmp(&extent[a]));
+        self.positions
+            .iter()
+            .map(|position| [0, 1].map(|i| (position[axes[i]] - min[axes[i]]) / size))
+            .collect()
+    }
+
+    pub(crate) fn rest_state(&self) -> RestState {
+        let (min, max) = self.bounds();
+        let extent = [0, 1, 2].map(|axis| max[axis] - min[axis]);
+        let size = extent.into_iter().fold(0.0, f32::max);
+
+        let instances: Vec<Instance> = self
+            .positions
+            .iter()
+            .map(|&position| {
+                let pinned = self.pin_top && position[1] >= max[1] - PIN_TOLERANCE * size;
+                Instance::at_rest(position, if pinned { 0.0 } else { 1.0 })
+            })
+            .collect();
+
+        // Meshes have no warp and weft, edges go to the horizontal or the vertical group by their
+        // main direction in the plane of the two largest extents of the mesh
+        let mut axes = [0, 1, 2];
+        axes.sort_by(|&a, &b| extent[b].total_cmp(&extent[a]));
+        let kind = |a: u32, b: u32| {
+            let (a, b) = (self.positions[a as usize], self.positions[b as usize]);
+            if (a[axes[0]] - b[axes[0]]).abs() >= (a[axes[1]] - b[axes[1]]).abs() {
+                ConstraintKind::Horizontal
+            } else {
+                ConstraintKind::Vertical
+            }
+        };
+
+        // Every edge with the corners opposite to it, sorted so the result doesn't depend on hashing
+        let mut edges: BTreeMap<(u32, u32), Vec<u32>> = BTreeMap::new();
+        for &[a, b, c] in &self.triangles {
+            for (p, q, opposite) in [(a, b, c), (b, c, a), (c, a, b)] {
+                edges.entry((p.min(q), p.max(q))).or_default().push(opposite);
+            }
+        }
+
+        let mut slots = ConstraintSlots::new(&self.positions);
+        for &(a, b) in edges.keys() {
+            slots.add(a, b, kind(a, b));
+        }
+        // Added last, so they are the ones dropped around vertices with too many neighbors
+        for opposite in edges.values() {
+            if let [c, d] = opposite[..] {
+                if c != d && !edges.contains_key(&(c.min(d), c.max(d))) {
+                    slots.add(c, d, ConstraintKind::Bend);
+                }
+            }
+        }
+        if slots.dropped > 0 {
+            log::warn!(
+                "{} constraints of the mesh dropped, a particle has at most {MAX_CONSTRAINTS}",
+                slots.dropped
+            );
+        }
+
+        RestState {
+            instances,
+            constraints: slots.constraints,
+        }
+    }
+}
+
+// The MAX_CONSTRAINTS slots of every particle, filled on both ends of a constraint at once since
+// the solvers expect each one to be listed by its two particles
+struct ConstraintSlots<'a> {
+    positions: &'a [[f32; 3]],
+    constraints: Vec<Constraint>,
+    used: Vec<usize>,
+    dropped: usize,
+}
+
+impl<'a> ConstraintSlots<'a> {
+    fn new(positions: &'a [[f32; 3]]) -> Self {
+        Self {
+            positions,
+            constraints: vec![Constraint::NONE; positions.len() * MAX_CONSTRAINTS],
+            used: vec![0; positions.len()],
+            dropped: 0,
+        }
+    }
+
+    fn add(&mut self, a: u32, b: u32, kind: ConstraintKind) {
+        let (a, b) = (a as usize, b as usize);
+        let listed = self.constraints[a * MAX_CONSTRAINTS..(a + 1) * MAX_CONSTRAINTS]
+            .iter()
+            .any(|constraint| constraint.neighbor == b as u32);
+        if listed {
+            return;
+        }
+        if self.used[a] == MAX_CONSTRAINTS || self.used[b] == MAX_CONSTRAINTS {
+            self.dropped += 1;
+            return;
+        }
+
+        let (p, q) = (self.positions[a], self.positions[b]);
+        let rest_length = ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2)).sqrt();
+        for (particle, neighbor) in [(a, b), (b, a)] {
+            self.constraints[particle * MAX_CONSTRAINTS + self.used[particle]] = Constraint {
+                neighbor: neighbor as u32,
+                kind: kind as u32,
+                rest_length,
+                tear_strain: 0.0,
+            };
+            self.used[particle] += 1;
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// Motion blur: the scene rendered offscreen along with the screen-space motion of every pixel,
+// then smeared along it when composited into the window.
+
+use wgpu_bootstrap::{
+    wgpu::{self, util::DeviceExt},
+    Context,
+};
+
+use crate::hdr::{Tonemapping, HDR_FORMAT};
+use crate::msaa;
+use crate::oit::{OitTargets, Transparency};
+use crate::shader_source::expand_includes;
+
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+// Window clear color of the runner, (245, 245, 245) in sRGB
+const BACKGROUND: wgpu::Color = wgpu::Color { r: 0.913, g: 0.913, b: 0.913, a: 1.0 };
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurUniform {
+    strength: f32,
+    _padding: [f32; 3],
+}
+
+// Offscreen targets, sized like the view they blur
+struct Targets {
+    size: (u32, u32),
+    color_view: wgpu::TextureView,
+    velocity_view: wgpu::TextureView,
+    // Drawn into and resolved into the two above when multisampling
+    multisampled_color_view: Option<wgpu::TextureView>,
+    multisampled_velocity_view: Option<wgpu::TextureView>,
+    depth_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    oit: Option<OitTargets>,
+}
+
+pub struct MotionBlur {
+    pub enabled: bool,
+    pub strength: f32,
+    sample_count: u32,
+    targets: Option<Targets>, // created on the first update
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl MotionBlur {
+    pub fn new(context: &Context, sample_count: u32, tonemapping: &Tonemapping) -> Self {
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Motion Blur Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Motion Blur Uniform Buffer"),
+            contents: bytemuck::bytes_of(&BlurUniform {
+                strength: 1.0,
+                _padding: [0.0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Motion Blur Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = context
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Motion Blur Shader"),
+                source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("motion_blur.wgsl")).into()),
+            });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Blur Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, tonemapping.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Motion Blur Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: context.format(),
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                // Replaces the scene, which isn't drawn in the main pass while blurring
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: context.depth_stencil_format(),
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            enabled: false,
+            strength: 1.0,
+            sample_count,
+            targets: None,
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    // Follows the size of the view, in pixels, and the strength slider
+    pub fn update(&mut self, context: &Context, size: (u32, u32), transparency: &Transparency) {
+        if self.targets.as_ref().map(|targets| targets.size) != Some(size) {
+            self.targets = Some(self.create_targets(context, size));
+        }
+        if let Some(targets) = &mut self.targets {
+            transparency.prepare(context, &mut targets.oit, size, self.sample_count);
+        }
+        let uniform = BlurUniform {
+            strength: self.strength,
+            _padding: [0.0; 3],
+        };
+        context.queue().write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    fn create_targets(&self, context: &Context, (width, height): (u32, u32)) -> Targets {
+        let create_view = |label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages, sample_count: u32| {
+            context
+                .device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: width.max(1),
+                        height: height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let sampled = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let color_view = create_view("Motion Blur Color Texture", HDR_FORMAT, sampled, 1);
+        let velocity_view = create_view("Motion Blur Velocity Texture", VELOCITY_FORMAT, sampled, 1);
+        let depth_view = create_view(
+            "Motion Blur Depth Texture",
+            context.depth_stencil_format(),
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+            self.sample_count,
+        );
+        let multisampled_color_view = msaa::create_multisampled_view(
+            context,
+            "Motion Blur Multisampled Color Texture",
+            HDR_FORMAT,
+            (width, height),
+            self.sample_count,
+        );
+        let multisampled_velocity_view = msaa::create_multisampled_view(
+            context,
+            "Motion Blur Multisampled Velocity Texture",
+            VELOCITY_FORMAT,
+            (width, height),
+            self.sample_count,
+        );
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Motion Blur Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&velocity_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Targets {
+            size: (width, height),
+            color_view,
+            velocity_view,
+            multisampled_color_view,
+            multisampled_velocity_view,
+            depth_view,
+            bind_group,
+            oit: None,
+        }
+    }
+
+    // Renders the color and motion of the scene, `draw_scene` records the motion variant of the
+    // main view's draws and `draw_transparent` the sheer cloth, which keeps the motion of what is
+    // behind it
+    pub fn render_offscreen(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        transparency: &Transparency,
+        draw_scene: impl FnOnce(&mut wgpu::RenderPass<'_>),
+        draw_transparent: impl FnOnce(&mut wgpu::RenderPass<'_>),
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Motion Blur Scene Pass"),
+                color_attachments: &[
+                    Some(msaa::color_attachment(
+                        &targets.color_view,
+                        targets.multisampled_color_view.as_ref(),
+                        BACKGROUND,
+                    )),
+                    Some(msaa::color_attachment(
+                        &targets.velocity_view,
+                        targets.multisampled_velocity_view.as_ref(),
+                        wgpu::Color::TRANSPARENT,
+                    )),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            draw_scene(&mut render_pass);
+        }
+        transparency.render(
+            encoder,
+            targets.oit.as_ref(),
+            &targets.color_view,
+            &targets.depth_view,
+            draw_transparent,
+        );
+    }
+
+    // Composites the blurred view into the main pass, in place of the scene
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, tonemapping: &Tonemapping) {
+        if let Some(targets) = &self.targets {
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &targets.bind_group, &[]);
+            render_pass.set_bind_group(1, tonemapping.bind_group(), &[]);
+            render_pass.draw(0..4, 0..1);
+        }
+    }
+}
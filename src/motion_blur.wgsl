@@ -0,0 +1,44 @@
+// motion_blur.wgsl
+// Smears the offscreen view of the scene along the motion of every pixel, drawn over the whole
+// window and tonemapped.
+
+#include "tonemap.wgsl"
+
+struct BlurUniform {
+    strength: f32, // fraction of the motion of the last step the smear covers
+};
+
+@group(0) @binding(0) var color_texture: texture_2d<f32>;
+@group(0) @binding(1) var velocity_texture: texture_2d<f32>;
+@group(0) @binding(2) var blur_sampler: sampler;
+@group(0) @binding(3) var<uniform> blur: BlurUniform;
+@group(1) @binding(0) var<uniform> tonemapping: Tonemapping;
+
+const SAMPLES: i32 = 8;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Four vertices drawn as a triangle strip
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var out: VertexOutput;
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let velocity = textureSample(velocity_texture, blur_sampler, in.uv).xy * blur.strength;
+    var color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    // Centered on the pixel, half the smear behind it and half ahead
+    for (var i = 0; i < SAMPLES; i++) {
+        let offset = f32(i) / f32(SAMPLES - 1) - 0.5;
+        color += textureSample(color_texture, blur_sampler, in.uv + velocity * offset);
+    }
+    return tonemap(tonemapping, color / f32(SAMPLES));
+}
@@ -0,0 +1,86 @@
+// Multisample anti-aliasing of the thin cloth. The sample count is picked when the pipelines are
+// created: every scene pipeline draws with it, and every pass drawing the scene into a texture
+// draws into multisampled attachments resolved into that texture. The scene is never drawn in the
+// window's own pass, see hdr.rs.
+
+use wgpu_bootstrap::{wgpu, Context};
+
+use crate::hdr::HDR_FORMAT;
+use crate::motion_blur::VELOCITY_FORMAT;
+use crate::oit::{ACCUMULATION_FORMAT, REVEALAGE_FORMAT};
+
+// The highest count up to `requested` that the color, velocity, transparency and depth formats all
+// support on this device, 1 when `requested` is 1
+pub fn supported_sample_count(context: &Context, requested: u32) -> u32 {
+    let features = context.device().features();
+    let supports = |format: wgpu::TextureFormat, count: u32| {
+        format.guaranteed_format_features(features).flags.sample_count_supported(count)
+    };
+    [8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| {
+            supports(HDR_FORMAT, count)
+                && supports(VELOCITY_FORMAT, count)
+                && supports(ACCUMULATION_FORMAT, count)
+                && supports(REVEALAGE_FORMAT, count)
+                && supports(context.depth_stencil_format(), count)
+        })
+        .unwrap_or(1)
+}
+
+// Multisampled twin of a color target of the scene, None for a single sample
+pub fn create_multisampled_view(
+    context: &Context,
+    label: &str,
+    format: wgpu::TextureFormat,
+    (width, height): (u32, u32),
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    (sample_count > 1).then(|| {
+        context
+            .device()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: width.max(1),
+                    height: height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    })
+}
+
+// Draws into `multisampled` and resolves into `view` when there is one, straight into `view`
+// otherwise. The samples themselves aren't kept.
+pub fn color_attachment<'a>(
+    view: &'a wgpu::TextureView,
+    multisampled: Option<&'a wgpu::TextureView>,
+    clear: wgpu::Color,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    match multisampled {
+        Some(multisampled) => wgpu::RenderPassColorAttachment {
+            view: multisampled,
+            resolve_target: Some(view),
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear),
+                store: wgpu::StoreOp::Discard,
+            },
+        },
+        None => wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear),
+                store: wgpu::StoreOp::Store,
+            },
+        },
+    }
+}
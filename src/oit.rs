@@ -0,0 +1,274 @@
+// Order-independent transparency of the sheer fabrics, weighted and blended after McGuire and
+// Bavoil. Every view draws its opaque scene first, the sheer cloth then adds up into an
+// accumulation and a revealage target against the depth of that scene, and the result is blended
+// over its color. Folds of a curtain layer correctly whatever order their triangles come in.
+
+use wgpu_bootstrap::{wgpu, Context};
+
+use crate::hdr::HDR_FORMAT;
+use crate::msaa;
+
+pub const ACCUMULATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+// Targets of the sheer cloth pipeline, the sums of all layers and the product of what they let through
+pub fn transparent_targets() -> [Option<wgpu::ColorTargetState>; 2] {
+    [
+        Some(wgpu::ColorTargetState {
+            format: ACCUMULATION_FORMAT,
+            blend: Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        }),
+        Some(wgpu::ColorTargetState {
+            format: REVEALAGE_FORMAT,
+            blend: Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::REPLACE,
+            }),
+            write_mask: wgpu::ColorWrites::RED,
+        }),
+    ]
+}
+
+fn composite_bind_group_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Transparency Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    }
+}
+
+/// Accumulation and revealage of one view, created by the view the first time it has sheer cloth
+/// to draw.
+pub struct OitTargets {
+    accumulation_view: wgpu::TextureView,
+    revealage_view: wgpu::TextureView,
+    // Drawn into and resolved into the two above when multisampling
+    multisampled_accumulation_view: Option<wgpu::TextureView>,
+    multisampled_revealage_view: Option<wgpu::TextureView>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl OitTargets {
+    // `sample_count` is that of the depth of the view
+    pub fn new(context: &Context, (width, height): (u32, u32), sample_count: u32) -> Self {
+        let create_view = |label: &str, format: wgpu::TextureFormat| {
+            context
+                .device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: width.max(1),
+                        height: height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let accumulation_view = create_view("Accumulation Texture", ACCUMULATION_FORMAT);
+        let revealage_view = create_view("Revealage Texture", REVEALAGE_FORMAT);
+        let multisampled_accumulation_view = msaa::create_multisampled_view(
+            context,
+            "Multisampled Accumulation Texture",
+            ACCUMULATION_FORMAT,
+            (width, height),
+            sample_count,
+        );
+        let multisampled_revealage_view = msaa::create_multisampled_view(
+            context,
+            "Multisampled Revealage Texture",
+            REVEALAGE_FORMAT,
+            (width, height),
+            sample_count,
+        );
+
+        let layout = context.device().create_bind_group_layout(&composite_bind_group_layout_desc());
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transparency Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&accumulation_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&revealage_view),
+                },
+            ],
+        });
+
+        Self {
+            accumulation_view,
+            revealage_view,
+            multisampled_accumulation_view,
+            multisampled_revealage_view,
+            bind_group,
+        }
+    }
+}
+
+/// Blends the sheer cloth of every view over its opaque scene.
+pub struct Transparency {
+    pub active: bool, // some material is sheer, followed every frame
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Transparency {
+    pub fn new(context: &Context) -> Self {
+        let shader = context.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Transparency Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("oit.wgsl").into()),
+        });
+        let layout = context.device().create_bind_group_layout(&composite_bind_group_layout_desc());
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Transparency Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = context
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Transparency Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            });
+
+        Self { active: false, pipeline }
+    }
+
+    // Creates the targets of a view of `size` the first time it has sheer cloth to draw
+    pub fn prepare(&self, context: &Context, targets: &mut Option<OitTargets>, size: (u32, u32), sample_count: u32) {
+        if self.active && targets.is_none() {
+            *targets = Some(OitTargets::new(context, size, sample_count));
+        }
+    }
+
+    // Adds the sheer cloth `draw_transparent` records to `color_view`, the resolved color of a
+    // view whose opaque scene was drawn with `depth_view`. Does nothing without `targets`, which
+    // the view only creates while active.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: Option<&OitTargets>,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        draw_transparent: impl FnOnce(&mut wgpu::RenderPass<'_>),
+    ) {
+        let Some(targets) = targets.filter(|_| self.active) else {
+            return;
+        };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparency Accumulation Pass"),
+                color_attachments: &[
+                    Some(msaa::color_attachment(
+                        &targets.accumulation_view,
+                        targets.multisampled_accumulation_view.as_ref(),
+                        wgpu::Color::TRANSPARENT,
+                    )),
+                    Some(msaa::color_attachment(
+                        &targets.revealage_view,
+                        targets.multisampled_revealage_view.as_ref(),
+                        wgpu::Color::WHITE,
+                    )),
+                ],
+                // Hidden behind the opaque scene, without hiding one another
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            draw_transparent(&mut render_pass);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transparency Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &targets.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,24 @@
+// oit.wgsl
+// Blends the accumulated sheer fabrics over the opaque scene, see fs_transparent of shader.wgsl.
+
+@group(0) @binding(0) var accumulation_texture: texture_2d<f32>;
+@group(0) @binding(1) var revealage_texture: texture_2d<f32>;
+
+// One triangle over the whole view
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let ndc = vec2<f32>(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    return vec4<f32>(ndc, 0.0, 1.0);
+}
+
+// Weighted average color of the layers, with the opacity of them all, blended by alpha
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(position.xy);
+    let revealage = textureLoad(revealage_texture, texel, 0).r;
+    if (revealage >= 1.0) {
+        discard;
+    }
+    let accumulation = textureLoad(accumulation_texture, texel, 0);
+    return vec4<f32>(accumulation.rgb / max(accumulation.a, 1e-5), 1.0 - revealage);
+}
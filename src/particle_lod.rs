@@ -0,0 +1,86 @@
+// Level of detail of the spheres drawn at every particle. Icospheres of 0, 1 and 2 subdivisions
+// are stored one after the other in the same vertex and index buffers, and every frame one of them
+// is picked from the size of a sphere on screen, so a large grid seen from afar draws 20 triangles
+// per particle instead of 320.
+
+use std::ops::Range;
+
+use wgpu_bootstrap::{cgmath::MetricSpace, egui, wgpu};
+
+use crate::camera::{OrbitCamera, Projection};
+
+// Pixels of radius on screen from which the next finer level is drawn
+const FINER_ABOVE: [f32; 2] = [1.5, 4.0];
+
+const LEVEL_NAMES: [&str; 3] = ["Low", "Medium", "High"];
+
+// Range of a level in the index buffer, with the offset of its vertices
+struct Level {
+    indices: Range<u32>,
+    base_vertex: i32,
+}
+
+pub struct ParticleLod {
+    forced: Option<usize>, // level picked in the UI, from the camera otherwise
+    radius: f32,           // m, of the spheres
+    level: usize,          // drawn this frame
+    levels: [Level; 3],
+}
+
+impl ParticleLod {
+    // `counts` are the index and vertex counts of every level, coarsest first
+    pub fn new(radius: f32, counts: [(u32, u32); 3]) -> Self {
+        let (mut first_index, mut first_vertex) = (0, 0);
+        let levels = counts.map(|(index_count, vertex_count)| {
+            let level = Level {
+                indices: first_index..first_index + index_count,
+                base_vertex: first_vertex as i32,
+            };
+            first_index += index_count;
+            first_vertex += vertex_count;
+            level
+        });
+        Self {
+            forced: None,
+            radius,
+            level: levels.len() - 1,
+            levels,
+        }
+    }
+
+    // Picks the level for the spheres around the target of `camera`, seen in a view `height` pixels high
+    pub fn update(&mut self, camera: &OrbitCamera, height: f32) {
+        // Orthographic projections don't shrink with the distance
+        let distance = match camera.projection() {
+            Projection::Perspective => camera.eye().distance(camera.target()).max(1e-3),
+            Projection::Orthographic => 1.0,
+        };
+        let pixels = self.radius * camera.projection_matrix()[1][1] * 0.5 * height / distance;
+        let picked = FINER_ABOVE.iter().filter(|&&above| pixels > above).count();
+        self.level = self.forced.unwrap_or(picked);
+    }
+
+    // Draws the spheres of `instances` at the level of this frame, the vertex buffer of the levels
+    // in slot 0 and their index buffer already set
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        let level = &self.levels[self.level];
+        render_pass.draw_indexed(level.indices.clone(), level.base_vertex, instances);
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let triangles = self.levels[self.level].indices.len() / 3;
+        egui::ComboBox::from_label("Particle detail")
+            .selected_text(match self.forced {
+                Some(level) => LEVEL_NAMES[level],
+                None => "Auto",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.forced, None, "Auto");
+                for (level, name) in LEVEL_NAMES.into_iter().enumerate() {
+                    ui.selectable_value(&mut self.forced, Some(level), name);
+                }
+            })
+            .response
+            .on_hover_text(format!("{triangles} triangles per particle, from the size of the spheres on screen when Auto"));
+    }
+}
@@ -0,0 +1,136 @@
+// Performance overlay in a corner of the window: frame rate, CPU time of update(), GPU time of the
+// simulation step, compute dispatches per step and the size of the cloths.
+//
+// The GPU time comes from two timestamps written around the compute passes of a step, resolved
+// into a buffer read back every few steps. Devices without timestamp queries show no GPU time.
+
+use std::time::Duration;
+
+use wgpu_bootstrap::{egui, wgpu, Context};
+
+use cloth::sim::ClothSimulation;
+
+// Weight of the latest sample in the running averages, about the last second at 60 FPS
+const SMOOTHING: f32 = 0.05;
+
+const NUM_TIMESTAMPS: u32 = 2;
+
+// Timestamps written by empty compute passes before and after the step
+struct StepTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    period: f32, // ns per timestamp tick
+}
+
+impl StepTimer {
+    fn new(context: &Context) -> Option<Self> {
+        if !context.device().features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = context.device().create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Step Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: NUM_TIMESTAMPS,
+        });
+        let resolve_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Step Timestamp Buffer"),
+            size: (NUM_TIMESTAMPS as usize * std::mem::size_of::<u64>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            period: context.queue().get_timestamp_period(),
+        })
+    }
+
+    fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        drop(encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Step Timestamp Pass"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: None,
+            }),
+        }));
+    }
+}
+
+/// Running averages shown by the overlay, the GPU time only once read back.
+pub struct PerfHud {
+    pub enabled: bool,
+    frame_time: f32,          // s, between two updates
+    update_time: f32,         // s, spent in update()
+    step_time: Option<f32>,   // s, of the latest step read back
+    timer: Option<StepTimer>, // None without timestamp queries
+}
+
+impl PerfHud {
+    pub fn new(context: &Context) -> Self {
+        Self {
+            enabled: false,
+            frame_time: 0.0,
+            update_time: 0.0,
+            step_time: None,
+            timer: StepTimer::new(context),
+        }
+    }
+
+    pub fn record_frame(&mut self, delta_time: f32, update_duration: Duration) {
+        self.frame_time += (delta_time - self.frame_time) * SMOOTHING;
+        self.update_time += (update_duration.as_secs_f32() - self.update_time) * SMOOTHING;
+    }
+
+    // Both around the passes of a step, recorded into the same encoder
+    pub fn begin_step(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(timer) = &self.timer {
+            timer.write_timestamp(encoder, 0);
+        }
+    }
+
+    pub fn end_step(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(timer) = &self.timer {
+            timer.write_timestamp(encoder, 1);
+            encoder.resolve_query_set(&timer.query_set, 0..NUM_TIMESTAMPS, &timer.resolve_buffer, 0);
+        }
+    }
+
+    pub fn timestamp_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.timer.as_ref().map(|timer| &timer.resolve_buffer)
+    }
+
+    pub fn read_step_time(&mut self, timestamps: &[u64]) {
+        if let (Some(timer), &[begin, end]) = (&self.timer, timestamps) {
+            self.step_time = Some(end.saturating_sub(begin) as f32 * timer.period * 1e-9);
+        }
+    }
+
+    pub fn show(&self, ctx: &egui::Context, cloths: &[&ClothSimulation]) {
+        if !self.enabled {
+            return;
+        }
+        let particles: u32 = cloths.iter().map(|cloth| cloth.num_instances()).sum();
+        let constraints: usize = cloths.iter().map(|cloth| cloth.num_constraints()).sum();
+        let dispatches: u32 = cloths.iter().map(|cloth| cloth.dispatches()).sum();
+        let step_time = match (&self.timer, self.step_time) {
+            (None, _) => "n/a".to_owned(),
+            (Some(_), None) => "…".to_owned(),
+            (Some(_), Some(time)) => format!("{:.2} ms", time * 1e3),
+        };
+
+        egui::Area::new(egui::Id::new("performance overlay"))
+            .fixed_pos([8.0, 8.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("{:.0} FPS", 1.0 / self.frame_time.max(1e-6)));
+                    ui.label(format!("CPU update: {:.2} ms", self.update_time * 1e3));
+                    ui.label(format!("GPU step: {step_time}"))
+                        .on_hover_text("Compute passes of the latest step read back, without the drawing");
+                    ui.label(format!("Dispatches per step: {dispatches}"));
+                    ui.label(format!("Particles: {particles}"));
+                    ui.label(format!("Constraints: {constraints}"));
+                });
+            });
+    }
+}
@@ -0,0 +1,296 @@
+// Picture-in-picture: a second camera rendered offscreen and composited into a corner of the window.
+
+use wgpu_bootstrap::{
+    cgmath,
+    wgpu::{self, util::DeviceExt},
+    Context,
+};
+
+use crate::camera::OrbitCamera;
+use crate::hdr::{Tonemapping, HDR_FORMAT};
+use crate::msaa;
+use crate::oit::{OitTargets, Transparency};
+use crate::shader_source::expand_includes;
+
+const PIP_SIZE: u32 = 256; // pixels, the view is square
+const PIP_MARGIN: f32 = 16.0; // pixels from the window corner
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PipView {
+    TopDown,
+    Light, // looking along the light direction of the sphere shader
+}
+
+impl PipView {
+    pub const ALL: [PipView; 2] = [PipView::TopDown, PipView::Light];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PipView::TopDown => "Top-down",
+            PipView::Light => "Light's eye",
+        }
+    }
+
+    fn polar(self) -> cgmath::Point3<f32> {
+        match self {
+            PipView::TopDown => cgmath::point3(2.0, 0.0, std::f32::consts::FRAC_PI_2),
+            // Light direction (1, 1, 1)
+            PipView::Light => cgmath::point3(2.0, std::f32::consts::FRAC_PI_4, (1.0f32 / 3.0f32.sqrt()).asin()),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PipUniform {
+    rect: [f32; 4],
+}
+
+pub struct PictureInPicture {
+    pub enabled: bool,
+    pub view: PipView,
+    camera: OrbitCamera,
+    color_view: wgpu::TextureView,
+    multisampled_color_view: Option<wgpu::TextureView>, // drawn into and resolved into the one above
+    depth_view: wgpu::TextureView,
+    sample_count: u32,
+    oit: Option<OitTargets>, // created the first time there is sheer cloth
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PictureInPicture {
+    pub fn new(context: &Context, sample_count: u32, tonemapping: &Tonemapping) -> Self {
+        let size = wgpu::Extent3d {
+            width: PIP_SIZE,
+            height: PIP_SIZE,
+            depth_or_array_layers: 1,
+        };
+
+        let color_texture = context.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Picture In Picture Color Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let depth_texture = context.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Picture In Picture Depth Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: context.depth_stencil_format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let multisampled_color_view = msaa::create_multisampled_view(
+            context,
+            "Picture In Picture Multisampled Color Texture",
+            HDR_FORMAT,
+            (PIP_SIZE, PIP_SIZE),
+            sample_count,
+        );
+
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Picture In Picture Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Picture In Picture Uniform Buffer"),
+            contents: bytemuck::bytes_of(&PipUniform { rect: [0.0; 4] }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Picture In Picture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Picture In Picture Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = context
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Picture In Picture Shader"),
+                source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("pip.wgsl")).into()),
+            });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Picture In Picture Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, tonemapping.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Picture In Picture Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: context.format(),
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                // Always on top of the scene
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: context.depth_stencil_format(),
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            });
+
+        let mut camera = OrbitCamera::new(context, 45.0, 1.0, 0.1, 100.0);
+        camera.set_polar(PipView::TopDown.polar()).update_with_aspect(context, 1.0);
+
+        Self {
+            enabled: false,
+            view: PipView::TopDown,
+            camera,
+            color_view,
+            multisampled_color_view,
+            depth_view,
+            sample_count,
+            oit: None,
+            uniform_buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn update(&mut self, context: &Context, transparency: &Transparency) {
+        self.camera.set_polar(self.view.polar()).update_with_aspect(context, 1.0);
+        transparency.prepare(context, &mut self.oit, (PIP_SIZE, PIP_SIZE), self.sample_count);
+
+        // Top right corner, keeping the view square whatever the window size
+        let size = context.size();
+        let (width, height) = (2.0 * PIP_SIZE as f32 / size.x, 2.0 * PIP_SIZE as f32 / size.y);
+        let (margin_x, margin_y) = (2.0 * PIP_MARGIN / size.x, 2.0 * PIP_MARGIN / size.y);
+        let uniform = PipUniform {
+            rect: [1.0 - margin_x - width, 1.0 - margin_y - height, 1.0 - margin_x, 1.0 - margin_y],
+        };
+        context.queue().write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    // Renders the scene from the secondary camera, `draw_scene` and `draw_transparent` record the
+    // same draws as the main view
+    pub fn render_offscreen(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        transparency: &Transparency,
+        draw_scene: impl FnOnce(&mut wgpu::RenderPass<'_>, &wgpu::BindGroup),
+        draw_transparent: impl FnOnce(&mut wgpu::RenderPass<'_>, &wgpu::BindGroup),
+    ) {
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Picture In Picture Pass"),
+                color_attachments: &[Some(msaa::color_attachment(
+                    &self.color_view,
+                    self.multisampled_color_view.as_ref(),
+                    wgpu::Color { r: 0.8, g: 0.8, b: 0.85, a: 1.0 },
+                ))],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            draw_scene(&mut render_pass, self.camera.bind_group());
+        }
+        transparency.render(encoder, self.oit.as_ref(), &self.color_view, &self.depth_view, |render_pass| {
+            draw_transparent(render_pass, self.camera.bind_group())
+        });
+    }
+
+    // Composites the offscreen view into the main pass
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, tonemapping: &Tonemapping) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, tonemapping.bind_group(), &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
@@ -0,0 +1,33 @@
+// pip.wgsl
+// Draws the offscreen picture-in-picture view as a screen-space quad, tonemapped.
+
+#include "tonemap.wgsl"
+
+struct PipUniform {
+    rect: vec4<f32>, // min and max corners in normalized device coordinates
+};
+
+@group(0) @binding(0) var pip_texture: texture_2d<f32>;
+@group(0) @binding(1) var pip_sampler: sampler;
+@group(0) @binding(2) var<uniform> pip: PipUniform;
+@group(1) @binding(0) var<uniform> tonemapping: Tonemapping;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Four vertices drawn as a triangle strip
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var out: VertexOutput;
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.clip_position = vec4<f32>(mix(pip.rect.xy, pip.rect.zw, corner), 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return tonemap(tonemapping, textureSample(pip_texture, pip_sampler, in.uv));
+}
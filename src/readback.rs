@@ -0,0 +1,122 @@
+// Copying GPU buffers back to the CPU.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+
+// Records a copy of `buffer` into a new mappable buffer, to be read with map_staging() once
+// the encoder is submitted. The source buffer needs the COPY_SRC usage.
+pub fn copy_to_staging(context: &dyn Gpu, encoder: &mut wgpu::CommandEncoder, buffer: &wgpu::Buffer) -> wgpu::Buffer {
+    let staging_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+    staging_buffer
+}
+
+// Blocks until the copy is done, only meant for occasional reads (exports, debugging). Fails
+// when the buffer could not be mapped, e.g. once the device is lost.
+pub fn map_staging(context: &dyn Gpu, staging_buffer: &wgpu::Buffer) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    let slice = staging_buffer.slice(..);
+    let mapped = Arc::new(OnceLock::new());
+    let done = mapped.clone();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = done.set(result);
+    });
+    context.device().poll(wgpu::Maintain::Wait);
+
+    // The callback has run once the wait returns, a missing result is a map that never finished
+    mapped.get().cloned().unwrap_or(Err(wgpu::BufferAsyncError))?;
+    let data = slice.get_mapped_range().to_vec();
+    staging_buffer.unmap();
+    Ok(data)
+}
+
+const MAX_IN_FLIGHT: usize = 4; // copies the GPU is behind on before new ones are dropped
+
+/// Copies of a small buffer read back without waiting for the GPU, each with a `tag` of what it
+/// was copied at. A copy is mapped once the frame that recorded it is submitted, and picked up by
+/// a later poll() once the GPU got to it, a few frames late.
+pub struct AsyncReadback<T> {
+    free: Vec<wgpu::Buffer>,
+    recorded: Vec<(T, wgpu::Buffer)>, // by the frame not submitted yet
+    mapping: VecDeque<(T, wgpu::Buffer, Arc<OnceLock<bool>>)>, // whether the map succeeded, once done
+}
+
+impl<T> Default for AsyncReadback<T> {
+    fn default() -> Self {
+        Self {
+            free: Vec::new(),
+            recorded: Vec::new(),
+            mapping: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> AsyncReadback<T> {
+    // Skipped while too many copies are in flight. The source buffer needs the COPY_SRC usage.
+    pub fn copy(&mut self, context: &dyn Gpu, encoder: &mut wgpu::CommandEncoder, buffer: &wgpu::Buffer, tag: T) {
+        self.copy_range(context, encoder, buffer, 0..buffer.size(), tag);
+    }
+
+    // Same for the bytes of `range` only, a multiple of 4 like any buffer copy
+    pub fn copy_range(
+        &mut self,
+        context: &dyn Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+        range: Range<wgpu::BufferAddress>,
+        tag: T,
+    ) {
+        if self.recorded.len() + self.mapping.len() >= MAX_IN_FLIGHT {
+            return;
+        }
+        let size = range.end - range.start;
+        let staging_buffer = match self.free.iter().position(|free| free.size() == size) {
+            Some(index) => self.free.swap_remove(index),
+            None => context.device().create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Async Readback Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        };
+        encoder.copy_buffer_to_buffer(buffer, range.start, &staging_buffer, 0, size);
+        self.recorded.push((tag, staging_buffer));
+    }
+
+    // Called after the submission of the copies, returns those the GPU finished, oldest first
+    pub fn poll(&mut self, context: &dyn Gpu) -> Vec<(T, Vec<u8>)> {
+        for (tag, staging_buffer) in self.recorded.drain(..) {
+            let mapped = Arc::new(OnceLock::new());
+            let done = mapped.clone();
+            staging_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                if let Err(error) = &result {
+                    log::error!("Failed to map readback buffer: {error}");
+                }
+                let _ = done.set(result.is_ok());
+            });
+            self.mapping.push_back((tag, staging_buffer, mapped));
+        }
+        context.device().poll(wgpu::Maintain::Poll);
+
+        let mut finished = Vec::new();
+        while let Some(&success) = self.mapping.front().and_then(|(_, _, mapped)| mapped.get()) {
+            let (tag, staging_buffer, _) = self.mapping.pop_front().expect("front was checked");
+            // A buffer that failed to map is dropped with its copy
+            if success {
+                finished.push((tag, staging_buffer.slice(..).get_mapped_range().to_vec()));
+                staging_buffer.unmap();
+                self.free.push(staging_buffer);
+            }
+        }
+        finished
+    }
+}
@@ -0,0 +1,249 @@
+// Replays of long simulations: particle positions recorded to disk at a chosen rate and streamed
+// back later for rendering and export, without running the solver.
+//
+// Positions are quantized to QUANTUM and every frame stores the difference to the previous one,
+// zigzag and varint encoded, so particles at rest cost a byte per coordinate. The first frame is
+// the difference to the origin. Frames are prefixed with their size, a recording cut short by a
+// crash stays readable up to its last complete frame.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::export::EXPORT_DIR;
+
+pub const REPLAY_FILE: &str = "cloth.replay";
+
+const MAGIC: &[u8; 8] = b"CLOTHRPL";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 8 + 4 + 4 + 4 + 4;
+const QUANTUM: f32 = 1e-4; // meters, finer than a particle moves in a step at rest
+
+fn quantize(position: [f32; 3]) -> [i32; 3] {
+    position.map(|coordinate| (coordinate / QUANTUM).round() as i32)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u32> {
+    let mut value = 0;
+    for shift in (0..32).step_by(7) {
+        let byte = bytes.next()?;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// Appends the quantized differences of `positions` to the previous frame, which they replace
+fn encode_frame(bytes: &mut Vec<u8>, previous: &mut [[i32; 3]], positions: impl Iterator<Item = [f32; 3]>) {
+    for (position, previous) in positions.zip(previous) {
+        let quantized = quantize(position);
+        for axis in 0..3 {
+            let delta = quantized[axis].wrapping_sub(previous[axis]);
+            write_varint(bytes, ((delta << 1) ^ (delta >> 31)) as u32);
+        }
+        *previous = quantized;
+    }
+}
+
+// Adds the differences of a frame to the quantized positions of the previous one, None if the
+// frame is too short
+fn decode_frame(bytes: &mut impl Iterator<Item = u8>, current: &mut [[i32; 3]]) -> Option<()> {
+    for position in current {
+        for coordinate in position.iter_mut() {
+            let zigzag = read_varint(bytes)?;
+            let delta = (zigzag >> 1) as i32 ^ -((zigzag & 1) as i32);
+            *coordinate = coordinate.wrapping_add(delta);
+        }
+    }
+    Some(())
+}
+
+/// Writes a replay into `EXPORT_DIR`, one frame every `interval` steps.
+pub struct ReplayWriter {
+    writer: BufWriter<File>,
+    previous: Vec<[i32; 3]>,
+    interval: u64,
+    first_step: u64,
+    num_frames: u32,
+}
+
+impl ReplayWriter {
+    pub fn start(num_particles: u32, interval: u64, first_step: u64) -> io::Result<Self> {
+        fs::create_dir_all(EXPORT_DIR)?;
+        let mut writer = BufWriter::new(File::create(Path::new(EXPORT_DIR).join(REPLAY_FILE))?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&num_particles.to_le_bytes())?;
+        writer.write_all(&(interval as u32).to_le_bytes())?;
+        writer.write_all(&QUANTUM.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            previous: vec![[0; 3]; num_particles as usize],
+            interval,
+            first_step,
+            num_frames: 0,
+        })
+    }
+
+    pub fn wants_frame(&self, step: u64) -> bool {
+        (step - self.first_step).is_multiple_of(self.interval)
+    }
+
+    pub fn num_frames(&self) -> u32 {
+        self.num_frames
+    }
+
+    pub fn record(&mut self, positions: impl ExactSizeIterator<Item = [f32; 3]>) -> io::Result<()> {
+        if positions.len() != self.previous.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "particle count changed during the recording",
+            ));
+        }
+        let mut bytes = Vec::new();
+        encode_frame(&mut bytes, &mut self.previous, positions);
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.num_frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Streams the frames of a replay back, one at a time, from the start again once it ends.
+pub struct ReplayReader {
+    reader: BufReader<File>,
+    current: Vec<[i32; 3]>,
+    interval: u32,
+    quantum: f32,
+}
+
+impl ReplayReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; HEADER_SIZE as usize];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(invalid("not a cloth replay"));
+        }
+        let field = |index: usize| u32::from_le_bytes(header[8 + 4 * index..12 + 4 * index].try_into().unwrap());
+        if field(0) != VERSION {
+            return Err(invalid("unsupported replay version"));
+        }
+
+        Ok(Self {
+            reader,
+            current: vec![[0; 3]; field(1) as usize],
+            interval: field(2),
+            quantum: f32::from_bits(field(3)),
+        })
+    }
+
+    pub fn num_particles(&self) -> u32 {
+        self.current.len() as u32
+    }
+
+    // Simulation steps between two frames of the recording
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    // Positions of the next frame, None at the end of the replay
+    pub fn next_frame(&mut self) -> io::Result<Option<Vec<[f32; 3]>>> {
+        let mut size = [0; 4];
+        match self.reader.read_exact(&mut size) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let mut bytes = vec![0; u32::from_le_bytes(size) as usize];
+        match self.reader.read_exact(&mut bytes) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None), // cut short
+            result => result?,
+        }
+
+        decode_frame(&mut bytes.into_iter(), &mut self.current)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated replay frame"))?;
+        let quantum = self.quantum;
+        Ok(Some(
+            self.current
+                .iter()
+                .map(|position| position.map(|coordinate| coordinate as f32 * quantum))
+                .collect(),
+        ))
+    }
+
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(HEADER_SIZE))?;
+        self.current.fill([0; 3]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_decode_within_a_quantum() {
+        // A particle at rest, one falling, one far away and one crossing the origin
+        let frames: Vec<Vec<[f32; 3]>> = (0..10)
+            .map(|frame| {
+                let t = frame as f32 * 0.1;
+                vec![
+                    [0.5, 1.0, -0.25],
+                    [0.0, 2.0 - 4.9 * t * t, 0.0],
+                    [120.0 + t, -75.5, 33.3],
+                    [t - 0.5, 0.5 - t, 0.0],
+                ]
+            })
+            .collect();
+
+        let mut previous = vec![[0; 3]; 4];
+        let encoded: Vec<Vec<u8>> = frames
+            .iter()
+            .map(|frame| {
+                let mut bytes = Vec::new();
+                encode_frame(&mut bytes, &mut previous, frame.iter().copied());
+                bytes
+            })
+            .collect();
+        // The resting particle costs a byte per coordinate after the first frame
+        assert!(encoded[1].len() < encoded[0].len());
+
+        let mut current = vec![[0; 3]; 4];
+        for (frame, bytes) in frames.iter().zip(encoded) {
+            let mut bytes = bytes.into_iter();
+            assert_eq!(decode_frame(&mut bytes, &mut current), Some(()));
+            assert_eq!(bytes.next(), None);
+            for (position, decoded) in frame.iter().zip(&current) {
+                for (coordinate, quantized) in position.iter().zip(decoded) {
+                    assert!((coordinate - *quantized as f32 * QUANTUM).abs() <= QUANTUM);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_truncated_frame_is_rejected() {
+        let mut bytes = Vec::new();
+        encode_frame(&mut bytes, &mut [[0; 3]; 2], [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]].into_iter());
+        bytes.pop();
+        assert_eq!(decode_frame(&mut bytes.into_iter(), &mut [[0; 3]; 2]), None);
+    }
+}
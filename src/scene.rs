@@ -0,0 +1,971 @@
+// Everything that gets simulated, rebuilt or torn down as a whole.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use wgpu_bootstrap::{
+    cgmath::{self, Matrix4, One, Quaternion, Rad, SquareMatrix, Vector3, VectorSpace},
+    wgpu, Context,
+};
+
+use crate::animation::RigAnimation;
+use crate::builder::ClothBuilder;
+use crate::bvh::TriangleBvh;
+use crate::gpu_resources::BufferTracker;
+use crate::heightfield::HeightField;
+pub use crate::loading::{Asset, AssetLoad}; // returned by the loads of the scene
+use crate::material::{Material, MaterialBlend, Preset};
+use crate::mesh::ClothMesh;
+use crate::sdf::SdfVolume;
+use crate::simulation::{
+    AnchorFrame, Attachment, ClothPiece, ClothSimulation, ClothSource, Collider, Fan, Orientation, PinGroup, Pins, SimParams,
+    SolverMode, Spacing, StepHandle, MAX_COLLIDERS,
+};
+use crate::tearing::TearMap;
+use crate::transform::{NodeId, TransformHierarchy};
+
+const SPACING: f32 = 0.002; // closer together for cloth-like appearance
+const SPHERE_RADIUS: f32 = 0.3;
+const SPHERE_MASS: f32 = 0.5; // kg, light enough for the tablecloth to nudge it
+const COMPARISON_ITERATIONS: u32 = 20; // per substep of the twin of a comparison, see Scene::start_comparison()
+// Under the sphere, where it rests
+const GROUND: GroundPlane = GroundPlane {
+    height: -SPHERE_RADIUS,
+    friction: 1.0,
+};
+const MESH_SIZE: f32 = 0.5; // largest extent of a loaded mesh
+// Segments of the arm from the shoulder, length and radius in m
+const ARM: [(&str, f32, f32); 3] = [("shoulder", 0.3, 0.06), ("elbow", 0.28, 0.05), ("wrist", 0.1, 0.04)];
+const SHOULDER: [f32; 3] = [-0.35, 0.6, 0.0];
+
+pub const DEFAULT_MESH_PATH: &str = "cloth.obj";
+pub const DEFAULT_ANIMATION_PATH: &str = "rig.glb";
+pub const DEFAULT_SDF_PATH: &str = "statue.obj";
+const SDF_RESOLUTION: u32 = 48; // voxels along the largest extent of a baked mesh
+const SDF_SIZE: f32 = 0.6; // largest extent of a baked mesh
+pub const DEFAULT_COLLISION_MESH_PATH: &str = "chair.obj";
+const COLLISION_MESH_SIZE: f32 = 0.6; // largest extent of a collision mesh
+const COLLISION_MESH_THICKNESS: f32 = 0.004; // m, kept between the cloth and the mesh
+pub const DEFAULT_HEIGHT_FIELD_PATH: &str = "terrain.png";
+const TERRAIN_SIZE: f32 = 2.0; // m along x and z
+const TERRAIN_HEIGHT: f32 = 0.25; // m, of white pixels and of the highest noise
+const TERRAIN_RESOLUTION: u32 = 128; // samples along x and z of generated terrains
+const TERRAIN_FEATURE: f32 = 32.0; // samples across the largest bumps of generated terrains
+
+// Finer where the sphere hits, coarser towards the hanging edges
+const TABLECLOTH: ClothBuilder = ClothBuilder::new(256, 256).spacing(Spacing::Graded { center: 0.0015, edge: 0.0025 });
+
+// 2:3 like most flags, hanging beside the sphere rather than on top of it
+const FLAG: ClothBuilder = ClothBuilder::new(192, 288)
+    .spacing(Spacing::Uniform(SPACING))
+    .center([0.0, 0.7, 0.5])
+    .orientation(Orientation::Vertical)
+    .pins(Pins::LeftEdge);
+
+const BANNER: ClothBuilder = ClothBuilder::new(96, 384)
+    .spacing(Spacing::Uniform(SPACING))
+    .center([0.0, 0.7, 0.0])
+    .orientation(Orientation::Vertical)
+    .pins(Pins::TopCorners);
+
+// Behind the sphere, sagging between the hooks of a rod along its top
+const CURTAIN: ClothBuilder = ClothBuilder::new(384, 256)
+    .spacing(Spacing::Uniform(SPACING))
+    .center([0.0, 0.6, -0.45])
+    .orientation(Orientation::Vertical)
+    .pins(Pins::TopHooks(32));
+
+// Falls across the arm, the forearm and the hand stick out on the side
+const DRAPE: ClothBuilder = ClothBuilder::new(128, 128).center([0.0, 0.9, 0.0]);
+
+/// A small square dropped over the others while they keep moving, see Scene::add_piece().
+pub const DROPPED_SQUARE: ClothBuilder = ClothBuilder::new(64, 64).center([0.0, 1.4, 0.0]);
+
+/// Canonical rest states the cloth can start from.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScenePreset {
+    #[default]
+    Tablecloth, // horizontal sheet dropped on the sphere
+    Flag,       // vertical, pinned along its left edge
+    Banner,     // vertical, pinned at its two top corners
+    Curtain,    // vertical, hung from hooks along its top edge
+    Showcase,   // the tablecloth between two flags, simulated together
+    Arm,        // a square dropped on an arm of three capsules, bending at the joints when animated
+    Mesh,       // the OBJ file at Scene::mesh_path
+}
+
+impl ScenePreset {
+    pub const ALL: [ScenePreset; 7] = [
+        ScenePreset::Tablecloth,
+        ScenePreset::Flag,
+        ScenePreset::Banner,
+        ScenePreset::Curtain,
+        ScenePreset::Showcase,
+        ScenePreset::Arm,
+        ScenePreset::Mesh,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScenePreset::Tablecloth => "Tablecloth",
+            ScenePreset::Flag => "Flag",
+            ScenePreset::Banner => "Banner",
+            ScenePreset::Curtain => "Curtain",
+            ScenePreset::Showcase => "Showcase",
+            ScenePreset::Arm => "Arm",
+            ScenePreset::Mesh => "Mesh",
+        }
+    }
+
+    // Case insensitive, for the command line
+    pub fn from_name(name: &str) -> Option<ScenePreset> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    // Empty for a mesh, its rest state comes from a file
+    pub fn cloths(self) -> Vec<ClothBuilder> {
+        match self {
+            ScenePreset::Tablecloth => vec![TABLECLOTH],
+            ScenePreset::Flag => vec![FLAG],
+            ScenePreset::Banner => vec![BANNER],
+            ScenePreset::Curtain => vec![CURTAIN],
+            ScenePreset::Showcase => vec![
+                TABLECLOTH,
+                FLAG.fabric(Preset::Silk),
+                FLAG.center([0.0, 0.7, -0.5]).fabric(Preset::Denim),
+            ],
+            ScenePreset::Arm => vec![DRAPE],
+            ScenePreset::Mesh => Vec::new(),
+        }
+    }
+
+    fn center(self) -> [f32; 3] {
+        self.cloths().first().map_or([0.0, 0.7, 0.0], |cloth| cloth.grid().center)
+    }
+
+    // A root carrying the collider and the node the pinned particles hang from, posed by
+    // Scene::update_rig() when animated. The arm is a chain of joints instead of the collider
+    // node, every joint at the end of the segment before it.
+    fn rig(self) -> TransformHierarchy {
+        let [x, y, z] = self.center();
+        let mut rig = TransformHierarchy::default();
+        let root = rig.add_node("root", None, Matrix4::identity());
+        if self == ScenePreset::Arm {
+            let mut parent = root;
+            let mut offset = Vector3::from(SHOULDER);
+            for (joint, length, _) in ARM {
+                parent = rig.add_node(joint, Some(parent), Matrix4::from_translation(offset));
+                offset = cgmath::vec3(length, 0.0, 0.0);
+            }
+        } else {
+            rig.add_node("collider", Some(root), Matrix4::identity());
+        }
+        rig.add_node("anchor", Some(root), Matrix4::from_translation(cgmath::vec3(x, y, z)));
+        rig
+    }
+
+    // The sphere on the collider node, or a capsule along every segment of the arm
+    fn rig_colliders(self, rig: &TransformHierarchy, sphere_radius: f32) -> Vec<RigCollider> {
+        let find = |name| rig.find(name).expect("preset rigs have a node for every collider");
+        if self == ScenePreset::Arm {
+            return ARM
+                .into_iter()
+                .map(|(joint, length, radius)| RigCollider {
+                    node: find(joint),
+                    collider: Collider::Capsule {
+                        a: [0.0; 3],
+                        b: [length, 0.0, 0.0],
+                        radius,
+                    },
+                })
+                .collect();
+        }
+        vec![RigCollider {
+            node: find("collider"),
+            collider: Collider::Sphere {
+                center: [0.0; 3],
+                radius: sphere_radius,
+            },
+        }]
+    }
+}
+
+// A collider following a node of the rig, in the space of the node
+struct RigCollider {
+    node: NodeId,
+    collider: Collider,
+}
+
+/// Infinite horizontal plane the cloth lands on.
+#[derive(Copy, Clone, Debug)]
+pub struct GroundPlane {
+    pub height: f32,
+    pub friction: f32, // scales the friction coefficients of the contacts
+}
+
+impl GroundPlane {
+    fn collider(&self) -> Collider {
+        Collider::Plane {
+            normal: [0.0, 1.0, 0.0],
+            offset: self.height,
+            friction: self.friction,
+        }
+    }
+}
+
+/// Handle of a collider added with Scene::add_collider().
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColliderId(u32);
+
+/// Moves an added collider every frame, relative to where it was added.
+pub enum ColliderMotion {
+    // Translations at times in seconds, interpolated linearly and looped after the last one
+    Path(Vec<(f32, [f32; 3])>),
+    // Transform at a time in seconds since the scene was reset
+    Script(Box<dyn FnMut(f32) -> Matrix4<f32> + Send + Sync>),
+}
+
+impl ColliderMotion {
+    fn transform(&mut self, time: f32) -> Matrix4<f32> {
+        match self {
+            ColliderMotion::Path(keys) => {
+                let Some(&(duration, last)) = keys.last() else {
+                    return Matrix4::identity();
+                };
+                let time = if duration > 0.0 { time.rem_euclid(duration) } else { 0.0 };
+                let next = keys.partition_point(|&(key_time, _)| key_time <= time);
+                let offset = match (next.checked_sub(1), keys.get(next)) {
+                    (Some(previous), Some(&(end, to))) => {
+                        let (start, from) = keys[previous];
+                        Vector3::from(from).lerp(Vector3::from(to), (time - start) / (end - start))
+                    }
+                    (None, Some(&(_, first))) => Vector3::from(first),
+                    _ => Vector3::from(last),
+                };
+                Matrix4::from_translation(offset)
+            }
+            ColliderMotion::Script(script) => script(time),
+        }
+    }
+}
+
+struct AddedCollider {
+    id: ColliderId,
+    placed: Collider, // where it was added, in world space
+    motion: Option<ColliderMotion>,
+    current: Collider,
+    spin: [f32; 3], // rad/s, see spin_collider()
+    one_sided: bool, // see set_one_sided()
+}
+
+pub type StepHook = Box<dyn FnMut(&mut StepHandle<'_>) + Send + Sync>;
+
+/// Owns the cloth simulation, nothing is left on the GPU once it is cleared.
+pub struct Scene {
+    pub preset: ScenePreset, // used by the next rebuild
+    pub mesh_path: String,   // same, for the mesh preset
+    pub pin_mesh_top: bool,
+    pub tear_map: TearMap, // same, for every piece
+    // Same, from a scene file, built instead of those of the preset when there are any
+    pub file_cloths: Vec<ClothBuilder>,
+    pub animate_rig: bool,
+    pub materials: Vec<MaterialBlend>, // one per piece of the cloth
+    pub pin_groups: Vec<PinGroup>,     // same, rigid until edited
+    pub attachments: Vec<Attachment>,  // targets moved from the CPU, emptied with the cloth
+    pub fans: Vec<Fan>,                // at most MAX_FANS, kept across rebuilds
+    pub animation_path: String,
+    pub animation: Option<RigAnimation>, // replaces the built-in motion of the rig when loaded
+    // The cloth pushes the sphere around instead of the rig moving it
+    pub dynamic_sphere: bool,
+    pub sphere_mass: f32, // kg
+    sphere_radius: f32,   // m, see set_sphere_radius()
+    pub has_ground: bool,
+    pub ground: GroundPlane,
+    pub probe_stages: bool, // for the divergence monitor, see ClothSimulation::set_probing()
+    pub sdf_path: String, // an OBJ mesh to bake or a volume baked before, see load_sdf()
+    sdf_volume: Option<SdfVolume>, // kept across rebuilds, like the colliders sampling it
+    pub collision_mesh_path: String, // an OBJ mesh, see load_collision_mesh()
+    collision_mesh: Option<TriangleBvh>, // same
+    pub height_field_path: String, // a grayscale image, see load_height_field()
+    height_field: Option<HeightField>, // same
+    cloth: Option<ClothSimulation>,
+    // Twin of the cloth stepped with other iterations, see start_comparison()
+    comparison: Option<ClothSimulation>,
+    pub comparison_iterations: u32, // per substep of the twin
+    pieces: Vec<ClothPiece>, // the cloth was built from, for add_piece()
+    rig: TransformHierarchy,
+    rest_rig: TransformHierarchy, // the animation is applied on top of it
+    rig_time: f32,
+    rig_colliders: Vec<RigCollider>, // the first one can be the sphere the cloth pushes around
+    added_colliders: Vec<AddedCollider>, // kept across rebuilds
+    next_collider_id: u32,
+    collider_time: f32, // of the collider motions
+    sphere_body_started: bool, // false until the body is placed where the rig left the sphere
+    // Kept across rebuilds, the systems they couple outlive a given cloth
+    pre_step_hooks: Vec<StepHook>,
+    post_step_hooks: Vec<StepHook>,
+    tracker: BufferTracker, // the buffers of the cloth and its twin, checked by clear()
+}
+
+impl Scene {
+    // Empty until the first rebuild
+    pub fn new(preset: ScenePreset, mesh_path: String) -> Self {
+        Self {
+            preset,
+            mesh_path,
+            pin_mesh_top: true,
+            tear_map: TearMap::Never,
+            file_cloths: Vec::new(),
+            animate_rig: false,
+            materials: Vec::new(),
+            pin_groups: Vec::new(),
+            attachments: Vec::new(),
+            fans: Vec::new(),
+            animation_path: DEFAULT_ANIMATION_PATH.to_string(),
+            animation: None,
+            dynamic_sphere: false,
+            sphere_mass: SPHERE_MASS,
+            sphere_radius: SPHERE_RADIUS,
+            has_ground: true,
+            ground: GROUND,
+            probe_stages: false,
+            sdf_path: DEFAULT_SDF_PATH.to_string(),
+            sdf_volume: None,
+            collision_mesh_path: DEFAULT_COLLISION_MESH_PATH.to_string(),
+            collision_mesh: None,
+            height_field_path: DEFAULT_HEIGHT_FIELD_PATH.to_string(),
+            height_field: None,
+            cloth: None,
+            comparison: None,
+            comparison_iterations: COMPARISON_ITERATIONS,
+            pieces: Vec::new(),
+            rig: TransformHierarchy::default(),
+            rest_rig: TransformHierarchy::default(),
+            rig_time: 0.0,
+            rig_colliders: Vec::new(),
+            added_colliders: Vec::new(),
+            next_collider_id: 0,
+            collider_time: 0.0,
+            sphere_body_started: false,
+            pre_step_hooks: Vec::new(),
+            post_step_hooks: Vec::new(),
+            tracker: BufferTracker::default(),
+        }
+    }
+
+    /// Runs before every step, in registration order.
+    pub fn on_pre_step(&mut self, hook: impl FnMut(&mut StepHandle<'_>) + Send + Sync + 'static) {
+        self.pre_step_hooks.push(Box::new(hook));
+    }
+
+    /// Runs after every step, in registration order.
+    pub fn on_post_step(&mut self, hook: impl FnMut(&mut StepHandle<'_>) + Send + Sync + 'static) {
+        self.post_step_hooks.push(Box::new(hook));
+    }
+
+    // Records step `step` of the cloth surrounded by the hooks, nothing happens in an empty scene.
+    // The twin of a comparison takes the same step after the hooks, with its own iterations.
+    pub fn encode_step(
+        &mut self,
+        context: &Context,
+        encoder: &mut wgpu::CommandEncoder,
+        step: u64,
+        params: &SimParams,
+        solver_mode: SolverMode,
+    ) {
+        let (nodes, colliders) = (self.rig.world_matrices(), self.colliders());
+        let (spins, one_sided) = (self.collider_spins(), self.one_sided_colliders());
+        let materials: Vec<Material> = self.materials.iter().map(MaterialBlend::material).collect();
+        let Some(cloth) = &mut self.cloth else {
+            return;
+        };
+        cloth.set_collider_spins(&spins);
+        cloth.set_one_sided_colliders(&one_sided);
+        cloth.update_rig(context, &nodes, &colliders, &self.attachments, &self.fans, &self.pin_groups);
+        match colliders.first() {
+            Some(&Collider::Sphere { center, .. }) if self.dynamic_sphere => {
+                if !self.sphere_body_started {
+                    cloth.start_sphere_body(context, center);
+                    self.sphere_body_started = true;
+                }
+                cloth.set_sphere_mass(context, Some(self.sphere_mass));
+            }
+            _ => {
+                cloth.set_sphere_mass(context, None);
+                self.sphere_body_started = false;
+            }
+        }
+        cloth.update_materials(context, &materials);
+        cloth.set_probing(self.probe_stages);
+
+        let mut params = *params;
+        for hook in &mut self.pre_step_hooks {
+            hook(&mut StepHandle {
+                step,
+                params: &mut params,
+                context,
+                encoder,
+                particles: cloth.instance_buffer(),
+            });
+        }
+
+        cloth.encode_step(context, encoder, &params, solver_mode);
+
+        for hook in &mut self.post_step_hooks {
+            hook(&mut StepHandle {
+                step,
+                params: &mut params,
+                context,
+                encoder,
+                particles: cloth.instance_buffer(),
+            });
+        }
+
+        // The twin pushes the sphere where the rig has it, only the cloth moves the sphere body
+        if let Some(twin) = &mut self.comparison {
+            twin.set_collider_spins(&spins);
+            twin.set_one_sided_colliders(&one_sided);
+            twin.update_rig(context, &nodes, &colliders, &self.attachments, &self.fans, &self.pin_groups);
+            twin.set_sphere_mass(context, None);
+            twin.update_materials(context, &materials);
+            params.set_iterations(self.comparison_iterations);
+            twin.encode_step(context, encoder, &params, solver_mode);
+        }
+    }
+
+    // Live buffers of the cloth and its twin, the scene owns no others
+    pub fn tracker(&self) -> &BufferTracker {
+        &self.tracker
+    }
+
+    pub fn cloth(&self) -> Option<&ClothSimulation> {
+        self.cloth.as_ref()
+    }
+
+    pub fn cloth_mut(&mut self) -> Option<&mut ClothSimulation> {
+        self.cloth.as_mut()
+    }
+
+    // The twin while a comparison runs
+    pub fn comparison(&self) -> Option<&ClothSimulation> {
+        self.comparison.as_ref()
+    }
+
+    // The cloth then its twin, for what both need
+    pub fn cloths(&self) -> impl Iterator<Item = &ClothSimulation> {
+        self.cloth.iter().chain(&self.comparison)
+    }
+
+    pub fn cloths_mut(&mut self) -> impl Iterator<Item = &mut ClothSimulation> {
+        self.cloth.iter_mut().chain(&mut self.comparison)
+    }
+
+    /// Drops the cloth again next to a twin made of the same pieces, stepped with
+    /// `comparison_iterations` and the same everything else, to compare solver settings side by
+    /// side. False in an empty scene.
+    pub fn start_comparison(&mut self, context: &Context) -> bool {
+        if self.cloth.is_none() {
+            return false;
+        }
+        self.stop_comparison();
+        self.reset(context);
+        self.comparison = Some(self.new_cloth(context, &self.pieces));
+        true
+    }
+
+    pub fn stop_comparison(&mut self) {
+        if let Some(twin) = self.comparison.take() {
+            twin.destroy();
+        }
+    }
+
+    // Holds the center of the first collider while the cloth pushes it around, the rig no longer
+    // knows where it is
+    pub fn sphere_body_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.cloth
+            .as_ref()
+            .filter(|_| self.sphere_body_started)
+            .map(ClothSimulation::sphere_body_buffer)
+    }
+
+    /// Reads the rest state of the next rebuild in the background, the mesh of the mesh preset
+    /// can take a while. The scene is left as it is until then, and if the mesh can't be loaded.
+    pub fn load_cloth(&self) -> AssetLoad {
+        let (preset, mesh_path, pin_top) = (self.preset, self.mesh_path.clone(), self.pin_mesh_top);
+        let cloths = self.grid_cloths();
+        let what = if preset == ScenePreset::Mesh {
+            format!("Loading {mesh_path}")
+        } else {
+            format!("Building {}", preset.name())
+        };
+        AssetLoad::spawn(what, move |_| Self::cloth_sources(preset, &cloths, &mesh_path, pin_top).map(Asset::Cloth))
+    }
+
+    // The pieces of the next rebuild unless it loads a mesh
+    fn grid_cloths(&self) -> Vec<ClothBuilder> {
+        if self.file_cloths.is_empty() {
+            self.preset.cloths()
+        } else {
+            self.file_cloths.clone()
+        }
+    }
+
+    // Starts over from the rest state read by load_cloth(), the previous buffers are released first
+    pub fn rebuild(&mut self, context: &Context, sources: Vec<ClothSource>) {
+        let comparing = self.comparison.is_some();
+        self.clear();
+
+        // Cotton for a mesh
+        self.materials = match self.preset {
+            ScenePreset::Mesh => vec![MaterialBlend::new(Preset::Cotton)],
+            _ => self.grid_cloths().iter().map(ClothBuilder::material).collect(),
+        };
+        self.pin_groups = vec![PinGroup::default(); self.materials.len()];
+        self.rig = self.preset.rig();
+        self.rest_rig = self.preset.rig();
+        self.rig_time = 0.0;
+        self.collider_time = 0.0;
+        if let Some(animation) = &mut self.animation {
+            animation.time = 0.0;
+        }
+        self.sphere_body_started = false;
+        let anchor = self.rig.find("anchor").expect("preset rigs have an anchor node");
+        self.rig_colliders = self.preset.rig_colliders(&self.rig, self.sphere_radius);
+
+        let anchor_frame = AnchorFrame {
+            node: anchor,
+            world: self.rig.world_matrices()[anchor.index()],
+        };
+        // Every piece hangs from the same node
+        let pieces: Vec<ClothPiece> = sources
+            .into_iter()
+            .map(|source| ClothPiece {
+                source,
+                anchor_frame,
+                tear_map: self.tear_map,
+            })
+            .collect();
+        self.cloth = Some(self.new_cloth(context, &pieces));
+        if comparing {
+            self.comparison = Some(self.new_cloth(context, &pieces));
+        }
+        self.pieces = pieces;
+    }
+
+    // Cloth made of `pieces` among the volume, mesh and terrain of the scene
+    fn new_cloth(&self, context: &Context, pieces: &[ClothPiece]) -> ClothSimulation {
+        let mut cloth = ClothSimulation::with_tracker(context, self.tracker.clone(), pieces);
+        if let Some(volume) = &self.sdf_volume {
+            cloth.set_sdf_volume(context, volume);
+        }
+        if let Some(bvh) = &self.collision_mesh {
+            cloth.set_collision_mesh(context, bvh);
+        }
+        if let Some(field) = &self.height_field {
+            cloth.set_height_field(context, field);
+        }
+        cloth
+    }
+
+    /// Adds a piece to the running cloth, hanging from the same node as the others, in the fabric
+    /// of `builder`. The pieces already there keep moving from where they are. False in an empty
+    /// scene.
+    pub fn add_piece(&mut self, context: &Context, builder: ClothBuilder) -> bool {
+        let (Some(cloth), Some(first)) = (&mut self.cloth, self.pieces.first()) else {
+            return false;
+        };
+        self.pieces.push(builder.piece(first.anchor_frame, self.tear_map));
+        cloth.resize_particles(context, &self.pieces);
+        if let Some(twin) = &mut self.comparison {
+            twin.resize_particles(context, &self.pieces);
+        }
+        self.materials.push(builder.material());
+        self.pin_groups.push(PinGroup::default());
+        true
+    }
+
+    // Drops the cloth again from its rest state without rebuilding it, the rig animation starts
+    // over with it
+    pub fn reset(&mut self, context: &Context) {
+        self.rig_time = 0.0;
+        self.collider_time = 0.0;
+        self.sphere_body_started = false; // back where the rig puts it on the next step
+        if let Some(animation) = &mut self.animation {
+            animation.time = 0.0;
+            animation.playing = true;
+        }
+        for cloth in self.cloths_mut() {
+            cloth.reset(context);
+        }
+    }
+
+    fn cloth_sources(
+        preset: ScenePreset,
+        cloths: &[ClothBuilder],
+        mesh_path: &str,
+        pin_top: bool,
+    ) -> io::Result<Vec<ClothSource>> {
+        if preset != ScenePreset::Mesh {
+            return Ok(cloths.iter().map(|cloth| ClothSource::Grid(cloth.grid())).collect());
+        }
+        let mut mesh = ClothMesh::load_obj(Path::new(mesh_path))?;
+        mesh.fit(preset.center(), MESH_SIZE);
+        mesh.pin_top = pin_top;
+        Ok(vec![ClothSource::Mesh(mesh)])
+    }
+
+    // Replaces `animation` once loaded
+    pub fn load_animation(&self) -> AssetLoad {
+        let path = PathBuf::from(&self.animation_path);
+        AssetLoad::spawn(format!("Loading {}", self.animation_path), move |_| {
+            RigAnimation::load_gltf(&path).map(Asset::Animation)
+        })
+    }
+
+    // Poses the rig from the CPU, called once per frame, from the loaded animation if any
+    pub fn update_rig(&mut self, delta_time: f32) {
+        if let Some(animation) = &mut self.animation {
+            animation.advance(delta_time);
+            for (name, pose) in animation.pose() {
+                if let Some(node) = self.rig.find(name) {
+                    self.rig.set_local(node, self.rest_rig.local(node) * pose);
+                }
+            }
+            return;
+        }
+        if !self.animate_rig {
+            return;
+        }
+        self.rig_time += delta_time;
+        let t = self.rig_time;
+
+        // Nodes missing from the rig of the preset are skipped, the joints only exist in the arm
+        let animation = [
+            ("root", Matrix4::from_angle_y(Rad(0.4 * t.sin()))),
+            ("collider", Matrix4::from_translation(cgmath::vec3(0.15 * (0.7 * t).sin(), 0.0, 0.0))),
+            ("anchor", Matrix4::from_translation(cgmath::vec3(0.0, 0.05 * (2.0 * t).sin(), 0.0))),
+            ("shoulder", Matrix4::from_angle_z(Rad(0.25 * (0.5 * t).sin()))),
+            // Only bends one way, like an elbow
+            ("elbow", Matrix4::from_angle_z(Rad(0.6 * (1.0 - (0.8 * t).cos())))),
+            ("wrist", Matrix4::from_angle_z(Rad(0.4 * (1.3 * t).sin()))),
+        ];
+        for (name, pose) in animation {
+            if let Some(node) = self.rig.find(name) {
+                self.rig.set_local(node, self.rest_rig.local(node) * pose);
+            }
+        }
+    }
+
+    /// Moves the sphere of the rig by `offset`, in the space of the rig root, on top of any
+    /// animation. Does nothing visible while the cloth pushes the sphere around.
+    pub fn move_sphere(&mut self, offset: Vector3<f32>) {
+        let Some(node) = self.rig.find("collider") else {
+            return;
+        };
+        let translation = Matrix4::from_translation(offset);
+        self.rest_rig.set_local(node, translation * self.rest_rig.local(node));
+        self.rig.set_local(node, translation * self.rig.local(node));
+    }
+
+    pub fn sphere_radius(&self) -> f32 {
+        self.sphere_radius
+    }
+
+    /// Resizes the sphere of the rig right away and after the next rebuilds, the arm has none.
+    pub fn set_sphere_radius(&mut self, radius: f32) {
+        self.sphere_radius = radius;
+        for rig_collider in &mut self.rig_colliders {
+            if let Collider::Sphere { radius: sphere_radius, .. } = &mut rig_collider.collider {
+                *sphere_radius = radius;
+            }
+        }
+    }
+
+    /// Adds a collider in world space, None once MAX_COLLIDERS are in the scene.
+    pub fn add_collider(&mut self, collider: Collider) -> Option<ColliderId> {
+        if self.colliders().len() >= MAX_COLLIDERS {
+            return None;
+        }
+        let id = ColliderId(self.next_collider_id);
+        self.next_collider_id += 1;
+        self.added_colliders.push(AddedCollider {
+            id,
+            placed: collider,
+            motion: None,
+            current: collider,
+            spin: [0.0; 3],
+            one_sided: false,
+        });
+        Some(id)
+    }
+
+    /// Loads the signed distance volume at `sdf_path` in the background, see place_sdf(). An OBJ
+    /// mesh is scaled to SDF_SIZE and baked first, the volume is saved next to it for the next time.
+    pub fn load_sdf(&self) -> AssetLoad {
+        let path = PathBuf::from(&self.sdf_path);
+        let bake = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("obj"));
+        let what = format!("{} {}", if bake { "Baking" } else { "Loading" }, self.sdf_path);
+        AssetLoad::spawn(what, move |progress| {
+            let volume = if bake {
+                let mut mesh = ClothMesh::load_obj(&path)?;
+                mesh.fit([0.0; 3], SDF_SIZE);
+                let volume = SdfVolume::bake(&mesh, SDF_RESOLUTION, progress);
+                volume.save(&path.with_extension("sdf"))?;
+                volume
+            } else {
+                SdfVolume::load(&path)?
+            };
+            Ok(Asset::Sdf(volume))
+        })
+    }
+
+    /// Stands a volume from load_sdf() on the ground as a collider. The previous volume and its
+    /// colliders are replaced, None if there is no room for the collider.
+    pub fn place_sdf(&mut self, context: &Context, volume: SdfVolume) -> Option<ColliderId> {
+        for cloth in self.cloths_mut() {
+            cloth.set_sdf_volume(context, &volume);
+        }
+
+        self.added_colliders
+            .retain(|added| !matches!(added.placed, Collider::Sdf { .. }));
+        let [_, half_height, _] = volume.half_extents();
+        let collider = volume.collider([0.0, self.ground.height + half_height, -0.45], Quaternion::one());
+        self.sdf_volume = Some(volume);
+        self.add_collider(collider)
+    }
+
+    /// Loads the OBJ mesh at `collision_mesh_path` in the background, scaled to
+    /// COLLISION_MESH_SIZE, and builds its BVH, see place_collision_mesh().
+    pub fn load_collision_mesh(&self) -> AssetLoad {
+        let path = PathBuf::from(&self.collision_mesh_path);
+        AssetLoad::spawn(format!("Loading {}", self.collision_mesh_path), move |progress| {
+            let mut mesh = ClothMesh::load_obj(&path)?;
+            progress.set(0.5);
+            mesh.fit([0.0; 3], COLLISION_MESH_SIZE);
+            Ok(Asset::CollisionMesh(TriangleBvh::build(&mesh)))
+        })
+    }
+
+    /// Stands a mesh from load_collision_mesh() on the ground as a collider. Open meshes work too,
+    /// the cloth stays in front of the faces. The previous mesh and its colliders are replaced,
+    /// None if there is no room for the collider.
+    pub fn place_collision_mesh(&mut self, context: &Context, bvh: TriangleBvh) -> Option<ColliderId> {
+        for cloth in self.cloths_mut() {
+            cloth.set_collision_mesh(context, &bvh);
+        }
+
+        self.added_colliders
+            .retain(|added| !matches!(added.placed, Collider::Mesh { .. }));
+        let ([_, bottom, _], _) = bvh.bounds();
+        let collider = bvh.collider(
+            [0.0, self.ground.height - bottom, -0.45],
+            Quaternion::one(),
+            COLLISION_MESH_THICKNESS,
+        );
+        self.collision_mesh = Some(bvh);
+        self.add_collider(collider)
+    }
+
+    /// Loads the grayscale image at `height_field_path` as a terrain in the background, white
+    /// TERRAIN_HEIGHT above the ground, see place_height_field().
+    pub fn load_height_field(&self) -> AssetLoad {
+        let path = PathBuf::from(&self.height_field_path);
+        AssetLoad::spawn(format!("Loading {}", self.height_field_path), move |_| {
+            HeightField::load_image(&path, TERRAIN_HEIGHT).map(Asset::HeightField)
+        })
+    }
+
+    /// Generates a terrain of noise in the background, the same for the same seed, see
+    /// place_height_field().
+    pub fn generate_height_field(&self, seed: u32) -> AssetLoad {
+        AssetLoad::spawn("Generating the terrain".to_string(), move |_| {
+            let resolution = [TERRAIN_RESOLUTION; 2];
+            Ok(Asset::HeightField(HeightField::from_noise(resolution, TERRAIN_HEIGHT, TERRAIN_FEATURE, seed)))
+        })
+    }
+
+    /// Lays a terrain from load_height_field() or generate_height_field() over TERRAIN_SIZE of the
+    /// ground. The previous terrain and its colliders are replaced, None if there is no room for
+    /// the collider.
+    pub fn place_height_field(&mut self, context: &Context, field: HeightField) -> Option<ColliderId> {
+        for cloth in self.cloths_mut() {
+            cloth.set_height_field(context, &field);
+        }
+
+        self.added_colliders
+            .retain(|added| !matches!(added.placed, Collider::HeightField { .. }));
+        let collider = field.collider([0.0, self.ground.height, 0.0], [0.5 * TERRAIN_SIZE; 2]);
+        self.height_field = Some(field);
+        self.add_collider(collider)
+    }
+
+    /// False if there was no such collider.
+    pub fn remove_collider(&mut self, id: ColliderId) -> bool {
+        let count = self.added_colliders.len();
+        self.added_colliders.retain(|added| added.id != id);
+        self.added_colliders.len() < count
+    }
+
+    /// Moves or resizes an added collider in place, an animated one keeps moving from its new
+    /// placement. False if there was no such collider.
+    pub fn set_collider(&mut self, id: ColliderId, collider: Collider) -> bool {
+        let Some(added) = self.added_colliders.iter_mut().find(|added| added.id == id) else {
+            return false;
+        };
+        added.placed = collider;
+        added.current = collider;
+        true
+    }
+
+    /// Spins an added collider in place at `angular_velocity` in rad/s, about its center or the
+    /// middle of a capsule. Only the friction sees it, the cloth touching a spinning drum is
+    /// carried along while the shape stays put. False if there was no such collider.
+    pub fn spin_collider(&mut self, id: ColliderId, angular_velocity: [f32; 3]) -> bool {
+        let Some(added) = self.added_colliders.iter_mut().find(|added| added.id == id) else {
+            return false;
+        };
+        added.spin = angular_velocity;
+        true
+    }
+
+    /// Makes an added collider push out only the particles reaching it from outside, those already
+    /// inside are left alone, or both again. False if there was no such collider.
+    pub fn set_one_sided(&mut self, id: ColliderId, one_sided: bool) -> bool {
+        let Some(added) = self.added_colliders.iter_mut().find(|added| added.id == id) else {
+            return false;
+        };
+        added.one_sided = one_sided;
+        true
+    }
+
+    pub fn is_one_sided(&self, id: ColliderId) -> bool {
+        self.added_colliders.iter().any(|added| added.id == id && added.one_sided)
+    }
+
+    /// Moves an added collider from now on, the cloth is dragged along by its velocity. False if
+    /// there was no such collider.
+    pub fn animate_collider(&mut self, id: ColliderId, motion: ColliderMotion) -> bool {
+        let Some(added) = self.added_colliders.iter_mut().find(|added| added.id == id) else {
+            return false;
+        };
+        added.motion = Some(motion);
+        true
+    }
+
+    // Poses the animated colliders from the CPU, called once per frame
+    pub fn update_colliders(&mut self, delta_time: f32) {
+        self.collider_time += delta_time;
+        for added in &mut self.added_colliders {
+            if let Some(motion) = &mut added.motion {
+                added.current = added.placed.transformed(&motion.transform(self.collider_time));
+            }
+        }
+    }
+
+    // Where every added collider is now, and whether it moves
+    pub fn added_colliders(&self) -> impl Iterator<Item = (ColliderId, &Collider, bool)> {
+        self.added_colliders
+            .iter()
+            .map(|added| (added.id, &added.current, added.motion.is_some()))
+    }
+
+    // Every collider in world space: those of the rig first, then the added ones and the ground
+    pub fn colliders(&self) -> Vec<Collider> {
+        let world = self.rig.world_matrices();
+        let rig = self
+            .rig_colliders
+            .iter()
+            .map(|rig_collider| rig_collider.collider.transformed(&world[rig_collider.node.index()]));
+        let added = self.added_colliders.iter().map(|added| added.current);
+        let ground = self.has_ground.then(|| self.ground.collider());
+        rig.chain(added).chain(ground).collect()
+    }
+
+    // In the order of colliders(), up to the last added one, the others don't spin
+    fn collider_spins(&self) -> Vec<[f32; 3]> {
+        let rig = self.rig_colliders.iter().map(|_| [0.0; 3]);
+        rig.chain(self.added_colliders.iter().map(|added| added.spin)).collect()
+    }
+
+    // Same, the ground and the colliders of the rig are two-sided
+    fn one_sided_colliders(&self) -> Vec<bool> {
+        let rig = self.rig_colliders.iter().map(|_| false);
+        rig.chain(self.added_colliders.iter().map(|added| added.one_sided)).collect()
+    }
+
+    // World space center and radius of the sphere colliders, the only ones drawn
+    pub fn collider_spheres(&self) -> Vec<[f32; 4]> {
+        self.colliders()
+            .into_iter()
+            .filter_map(|collider| match collider {
+                Collider::Sphere { center: [x, y, z], radius } => Some([x, y, z, radius]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Destroys every GPU resource of the scene, checked for leaks in debug builds.
+    pub fn clear(&mut self) {
+        self.attachments.clear();
+        self.pieces.clear();
+        if let Some(cloth) = self.cloth.take() {
+            cloth.destroy();
+        }
+        self.stop_comparison();
+        self.tracker.debug_assert_released();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn added_center(scene: &Scene, id: ColliderId) -> [f32; 3] {
+        match scene.added_colliders().find(|(added, _, _)| *added == id) {
+            Some((_, Collider::Sphere { center, .. }, true)) => *center,
+            _ => panic!("expected a moving sphere"),
+        }
+    }
+
+    #[test]
+    fn a_script_moves_its_collider_from_where_it_was_added() {
+        let mut scene = Scene::new(ScenePreset::default(), DEFAULT_MESH_PATH.to_string());
+        let sphere = Collider::Sphere {
+            center: [1.0, 0.0, 0.0],
+            radius: 0.1,
+        };
+        let id = scene.add_collider(sphere).unwrap();
+
+        // Called with the time since the reset, the scripts keep their own state
+        let mut calls = 0;
+        let script = move |time: f32| {
+            calls += 1;
+            Matrix4::from_translation(Vector3::new(0.0, time, calls as f32))
+        };
+        assert!(scene.animate_collider(id, ColliderMotion::Script(Box::new(script))));
+        scene.update_colliders(0.5);
+        scene.update_colliders(0.25);
+
+        let [x, y, z] = added_center(&scene, id);
+        assert!((x - 1.0).abs() < 1e-6 && (y - 0.75).abs() < 1e-6 && (z - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_path_loops_after_its_last_key() {
+        let mut scene = Scene::new(ScenePreset::default(), DEFAULT_MESH_PATH.to_string());
+        let sphere = Collider::Sphere {
+            center: [0.0; 3],
+            radius: 0.1,
+        };
+        let id = scene.add_collider(sphere).unwrap();
+        let keys = vec![(0.0, [0.0; 3]), (1.0, [2.0, 0.0, 0.0])];
+        scene.animate_collider(id, ColliderMotion::Path(keys));
+
+        scene.update_colliders(0.25);
+        assert!((added_center(&scene, id)[0] - 0.5).abs() < 1e-6);
+        scene.update_colliders(1.0); // a second later, a loop on
+        assert!((added_center(&scene, id)[0] - 0.5).abs() < 1e-6);
+    }
+}
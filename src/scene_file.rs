@@ -0,0 +1,377 @@
+// Scene files: a JSON description of an experiment, the cloths with their size, spacing, pins and
+// fabric, the colliders, the wind and the camera, loaded at startup with `--scene`. Everything the
+// file leaves out keeps the value of its preset, so a file can be as short as a single cloth:
+//
+//     {
+//         "preset": "tablecloth",
+//         "cloths": [{ "rows": 128, "cols": 128, "spacing": 0.004, "pins": "top_corners",
+//                      "fabric": "silk", "density": 0.3 }],
+//         "colliders": [{ "shape": "sphere", "center": [0.4, 0.5, 0.0], "radius": 0.1 }],
+//         "wind": [{ "position": [-0.8, 0.7, 0.0], "direction": [1.0, 0.0, 0.0], "speed": 4.0 }],
+//         "camera": { "distance": 1.5, "theta": 0.0, "phi": 0.3, "target": [0.0, 0.5, 0.0] }
+//     }
+//
+// Angles are in radians like in the bookmarks file, the wind is a list of fans, see Fan.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+use wgpu_bootstrap::cgmath::{self, One, Point3, Quaternion};
+
+use cloth::colliders::{Collider, Fan, MAX_COLLIDERS, MAX_FANS};
+use cloth::scene::{Scene, ScenePreset, DEFAULT_MESH_PATH};
+use cloth::sim::{ClothBuilder, Orientation, Pins, Preset, Spacing, Stiffness};
+
+// The capsules of the arm and the ground take the others
+const MAX_FILE_COLLIDERS: usize = MAX_COLLIDERS - 4;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    preset: Option<String>, // its rig, its sphere and its cloths when the file has none
+    mesh: Option<String>, // an OBJ file for the cloth instead, see ScenePreset::Mesh
+    #[serde(default)]
+    cloths: Vec<ClothDescription>,
+    #[serde(default)]
+    colliders: Vec<ColliderDescription>,
+    ground: Option<bool>,
+    #[serde(default)]
+    wind: Vec<FanDescription>,
+    camera: Option<CameraDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClothDescription {
+    rows: u32,
+    cols: u32,
+    spacing: Option<SpacingDescription>,
+    center: Option<[f32; 3]>,
+    orientation: Option<OrientationDescription>,
+    pins: Option<PinsDescription>,
+    fabric: Option<String>, // the name of a material preset, cotton by default
+    stiffness: Option<StiffnessDescription>,
+    density: Option<f32>,
+}
+
+// A number for a uniform spacing
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpacingDescription {
+    Uniform(f32),
+    Graded { center: f32, edge: f32 },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OrientationDescription {
+    Horizontal,
+    Vertical,
+}
+
+// `"top_corners"`, or `{ "top_hooks": 32 }`
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PinsDescription {
+    None,
+    LeftEdge,
+    TopCorners,
+    TopHooks(u32),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StiffnessDescription {
+    horizontal: f32,
+    vertical: f32,
+    shear: f32,
+    bend: f32,
+}
+
+// Boxes are axis aligned, they can be turned from the colliders panel
+#[derive(Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+enum ColliderDescription {
+    Sphere { center: [f32; 3], radius: f32 },
+    Capsule { a: [f32; 3], b: [f32; 3], radius: f32 },
+    Box { center: [f32; 3], half_extents: [f32; 3] },
+}
+
+// Left out, the values of the fan added from the panel
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FanDescription {
+    position: [f32; 3],
+    direction: [f32; 3],
+    #[serde(default = "FanDescription::default_cone_angle")]
+    cone_angle: f32,
+    #[serde(default = "FanDescription::default_speed")]
+    speed: f32,
+    #[serde(default = "FanDescription::default_range")]
+    range: f32,
+    #[serde(default = "FanDescription::default_falloff")]
+    falloff: f32,
+}
+
+impl FanDescription {
+    fn default_cone_angle() -> f32 {
+        20f32.to_radians()
+    }
+
+    fn default_speed() -> f32 {
+        4.0
+    }
+
+    fn default_range() -> f32 {
+        1.5
+    }
+
+    fn default_falloff() -> f32 {
+        1.0
+    }
+
+    fn fan(&self) -> Fan {
+        Fan {
+            position: self.position,
+            direction: self.direction,
+            cone_angle: self.cone_angle,
+            speed: self.speed,
+            range: self.range,
+            falloff: self.falloff,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    distance: f32,
+    theta: f32,
+    phi: f32,
+    #[serde(default)]
+    target: [f32; 3],
+}
+
+/// A scene read from a file, checked against the limits of the simulation.
+pub struct SceneFile {
+    pub preset: ScenePreset,
+    pub mesh_path: String,
+    cloths: Vec<ClothBuilder>,
+    colliders: Vec<Collider>,
+    ground: Option<bool>,
+    fans: Vec<Fan>,
+    camera: Option<(Point3<f32>, Point3<f32>)>, // polar and target
+}
+
+impl SceneFile {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let description: SceneDescription = serde_json::from_str(text).map_err(|error| invalid(error.to_string()))?;
+
+        let preset = match (&description.mesh, &description.preset) {
+            (Some(_), _) => ScenePreset::Mesh,
+            (None, None) => ScenePreset::default(),
+            (None, Some(name)) => {
+                ScenePreset::from_name(name).ok_or_else(|| invalid(format!("unknown preset \"{name}\"")))?
+            }
+        };
+        let cloths = description
+            .cloths
+            .iter()
+            .map(ClothDescription::builder)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        if description.colliders.len() > MAX_FILE_COLLIDERS {
+            return Err(invalid(format!("at most {MAX_FILE_COLLIDERS} colliders are supported")));
+        }
+        if description.wind.len() > MAX_FANS {
+            return Err(invalid(format!("at most {MAX_FANS} fans are supported")));
+        }
+
+        Ok(Self {
+            preset,
+            mesh_path: description.mesh.unwrap_or_else(|| DEFAULT_MESH_PATH.to_string()),
+            cloths,
+            colliders: description.colliders.iter().map(ColliderDescription::collider).collect(),
+            ground: description.ground,
+            fans: description.wind.iter().map(FanDescription::fan).collect(),
+            camera: description.camera.map(|camera| {
+                let [x, y, z] = camera.target;
+                (cgmath::point3(camera.distance, camera.theta, camera.phi), cgmath::point3(x, y, z))
+            }),
+        })
+    }
+
+    // Before the first rebuild, the cloths are built by the next one
+    pub fn apply(&self, scene: &mut Scene) {
+        scene.file_cloths = self.cloths.clone();
+        for &collider in &self.colliders {
+            scene.add_collider(collider);
+        }
+        if let Some(ground) = self.ground {
+            scene.has_ground = ground;
+        }
+        scene.fans = self.fans.clone();
+    }
+
+    // Polar coordinates and target of the orbit camera, when the file places it
+    pub fn camera(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        self.camera
+    }
+}
+
+impl ClothDescription {
+    fn builder(&self) -> Result<ClothBuilder, String> {
+        if self.rows < 2 || self.cols < 2 {
+            return Err(format!("a cloth of {} by {} particles, at least 2 by 2 are needed", self.rows, self.cols));
+        }
+        let mut builder = ClothBuilder::new(self.rows, self.cols);
+        if let Some(spacing) = &self.spacing {
+            builder = builder.spacing(match *spacing {
+                SpacingDescription::Uniform(spacing) => Spacing::Uniform(spacing),
+                SpacingDescription::Graded { center, edge } => Spacing::Graded { center, edge },
+            });
+        }
+        if let Some(center) = self.center {
+            builder = builder.center(center);
+        }
+        if let Some(orientation) = &self.orientation {
+            builder = builder.orientation(match orientation {
+                OrientationDescription::Horizontal => Orientation::Horizontal,
+                OrientationDescription::Vertical => Orientation::Vertical,
+            });
+        }
+        if let Some(pins) = &self.pins {
+            builder = builder.pins(match *pins {
+                PinsDescription::None => Pins::None,
+                PinsDescription::LeftEdge => Pins::LeftEdge,
+                PinsDescription::TopCorners => Pins::TopCorners,
+                PinsDescription::TopHooks(every) => Pins::TopHooks(every),
+            });
+        }
+        if let Some(name) = &self.fabric {
+            let fabric = Preset::ALL
+                .into_iter()
+                .find(|preset| preset.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("unknown fabric \"{name}\""))?;
+            builder = builder.fabric(fabric);
+        }
+        if let Some(stiffness) = &self.stiffness {
+            builder = builder.stiffness(Stiffness {
+                horizontal: stiffness.horizontal,
+                vertical: stiffness.vertical,
+                shear: stiffness.shear,
+                bend: stiffness.bend,
+            });
+        }
+        if let Some(density) = self.density {
+            builder = builder.density(density);
+        }
+        Ok(builder)
+    }
+}
+
+impl ColliderDescription {
+    fn collider(&self) -> Collider {
+        match *self {
+            ColliderDescription::Sphere { center, radius } => Collider::Sphere { center, radius },
+            ColliderDescription::Capsule { a, b, radius } => Collider::Capsule { a, b, radius },
+            ColliderDescription::Box { center, half_extents } => Collider::Box {
+                center,
+                half_extents,
+                rotation: Quaternion::one(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cloth::sim::ClothGrid;
+
+    fn error(text: &str) -> String {
+        match SceneFile::parse(text) {
+            Ok(_) => panic!("accepted {text}"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_a_full_scene() {
+        let file = SceneFile::parse(
+            r#"{
+                "preset": "tablecloth",
+                "cloths": [
+                    { "rows": 16, "cols": 32, "spacing": 0.01, "pins": "top_corners", "fabric": "Silk" },
+                    { "rows": 8, "cols": 8, "spacing": { "center": 0.01, "edge": 0.02 },
+                      "orientation": "vertical", "pins": { "top_hooks": 4 }, "density": 0.5 }
+                ],
+                "colliders": [
+                    { "shape": "sphere", "center": [0.4, 0.5, 0.0], "radius": 0.1 },
+                    { "shape": "box", "center": [0.0, 0.0, 0.0], "half_extents": [0.1, 0.2, 0.3] }
+                ],
+                "ground": false,
+                "wind": [{ "position": [-0.8, 0.7, 0.0], "direction": [1.0, 0.0, 0.0], "speed": 6.0 }],
+                "camera": { "distance": 1.5, "theta": 0.0, "phi": 0.3 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(file.preset, ScenePreset::Tablecloth);
+        assert_eq!(file.mesh_path, DEFAULT_MESH_PATH);
+
+        let grids: Vec<ClothGrid> = file.cloths.iter().map(ClothBuilder::grid).collect();
+        assert_eq!((grids[0].rows, grids[0].cols, grids[0].pins), (16, 32, Pins::TopCorners));
+        assert_eq!(grids[1].pins, Pins::TopHooks(4));
+        assert!(matches!(grids[1].spacing, Spacing::Graded { center, edge } if center == 0.01 && edge == 0.02));
+        assert_eq!(file.cloths[0].material().from, Preset::Silk);
+        assert!(file.cloths[0].material().custom.is_none());
+        assert!(file.cloths[1].material().custom.is_some());
+
+        assert_eq!(file.colliders.len(), 2);
+        assert_eq!(file.ground, Some(false));
+        assert_eq!(file.fans[0].speed, 6.0);
+        assert_eq!(file.fans[0].range, FanDescription::default_range());
+        assert_eq!(file.camera, Some((cgmath::point3(1.5, 0.0, 0.3), cgmath::point3(0.0, 0.0, 0.0))));
+    }
+
+    #[test]
+    fn an_empty_scene_keeps_the_default_preset() {
+        let file = SceneFile::parse("{}").unwrap();
+        assert_eq!(file.preset, ScenePreset::default());
+        assert!(file.cloths.is_empty() && file.colliders.is_empty() && file.fans.is_empty());
+        assert_eq!(file.ground, None);
+        assert_eq!(file.camera, None);
+    }
+
+    #[test]
+    fn a_mesh_replaces_the_preset() {
+        let file = SceneFile::parse(r#"{ "preset": "flag", "mesh": "cape.obj" }"#).unwrap();
+        assert_eq!(file.preset, ScenePreset::Mesh);
+        assert_eq!(file.mesh_path, "cape.obj");
+    }
+
+    #[test]
+    fn rejects_invalid_scenes() {
+        assert!(error(r#"{ "preset": "hammock" }"#).contains("unknown preset"));
+        assert!(error(r#"{ "cloths": [{ "rows": 1, "cols": 8 }] }"#).contains("at least 2 by 2"));
+        assert!(error(r#"{ "cloths": [{ "rows": 8, "cols": 8, "fabric": "tweed" }] }"#).contains("unknown fabric"));
+        assert!(error(r#"{ "cloths": [{ "rows": 8, "cols": 8, "pins": "bottom_edge" }] }"#).contains("unknown variant"));
+        assert!(error(r#"{ "cloths": [{ "rows": 8, "cols": 8, "pins": { "top_hooks": -1 } }] }"#).contains("invalid value"));
+        assert!(error(r#"{ "cloths": [{ "rows": 8 }] }"#).contains("missing field `cols`"));
+        assert!(error(r#"{ "cloth": [] }"#).contains("unknown field"));
+        assert!(error(r#"{ "colliders": [{ "shape": "cone", "center": [0, 0, 0] }] }"#).contains("unknown variant"));
+
+        let spheres = [r#"{ "shape": "sphere", "center": [0, 0, 0], "radius": 0.1 }"#; MAX_FILE_COLLIDERS + 1];
+        assert!(error(&format!(r#"{{ "colliders": [{}] }}"#, spheres.join(","))).contains("colliders are supported"));
+        let fans = [r#"{ "position": [0, 0, 0], "direction": [1, 0, 0] }"#; MAX_FANS + 1];
+        assert!(error(&format!(r#"{{ "wind": [{}] }}"#, fans.join(","))).contains("fans are supported"));
+    }
+}
@@ -0,0 +1,244 @@
+// Signed distance volumes for colliders of any shape, e.g. a statue or a chair, sampled by
+// contacts.wgsl from a 3D texture.
+//
+// A volume is either baked from a closed triangle mesh or loaded from a file written by a
+// previous bake. Distances are in m, negative inside, on a grid of cubic voxels centered on the
+// origin of the volume.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use wgpu_bootstrap::cgmath::{InnerSpace, Quaternion, Vector3};
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::loading::Progress;
+use crate::mesh::ClothMesh;
+use crate::simulation::Collider;
+
+const MAGIC: &[u8; 4] = b"SDF1";
+const PADDING: u32 = 2; // voxels around the mesh, so the surface never touches the border
+
+/// Distances sampled at the centers of the voxels, x varying fastest, then y, then z.
+pub struct SdfVolume {
+    resolution: [u32; 3],
+    half_extents: [f32; 3],
+    distances: Vec<f32>,
+}
+
+impl SdfVolume {
+    // `resolution` voxels along the largest extent of the mesh, which is inside out or open
+    // wherever the distances come out wrong. `progress` goes up as the slices are done.
+    pub fn bake(mesh: &ClothMesh, resolution: u32, progress: &Progress) -> Self {
+        let triangles: Vec<[Vector3<f32>; 3]> = mesh.triangle_corners().map(|corners| corners.map(Vector3::from)).collect();
+        let (min, max) = triangles.iter().flatten().fold(
+            (Vector3::from([f32::MAX; 3]), Vector3::from([f32::MIN; 3])),
+            |(min, max), corner| {
+                (
+                    Vector3::new(min.x.min(corner.x), min.y.min(corner.y), min.z.min(corner.z)),
+                    Vector3::new(max.x.max(corner.x), max.y.max(corner.y), max.z.max(corner.z)),
+                )
+            },
+        );
+        let center = (min + max) / 2.0;
+        let extent = max - min;
+        let voxel = extent.x.max(extent.y).max(extent.z).max(f32::EPSILON) / resolution.max(1) as f32;
+        let resolution = [extent.x, extent.y, extent.z].map(|extent| (extent / voxel).ceil() as u32 + 2 * PADDING);
+        let half_extents = resolution.map(|count| count as f32 * voxel / 2.0);
+
+        // Slices along z baked in parallel, every voxel goes over every triangle
+        let [nx, ny, nz] = resolution;
+        let done = AtomicU32::new(0);
+        let slice = |z: u32| -> Vec<f32> {
+            let mut distances = Vec::with_capacity((nx * ny) as usize);
+            for y in 0..ny {
+                for x in 0..nx {
+                    let local = Vector3::new(x, y, z).map(|index| (index as f32 + 0.5) * voxel);
+                    let point = center + local - Vector3::from(half_extents);
+                    distances.push(signed_distance(point, &triangles));
+                }
+            }
+            progress.set((done.fetch_add(1, Ordering::Relaxed) + 1) as f32 / nz as f32);
+            distances
+        };
+        let threads = std::thread::available_parallelism().map_or(1, usize::from);
+        let slices: Vec<Vec<f32>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads as u32)
+                .map(|first| {
+                    let slice = &slice;
+                    scope.spawn(move || {
+                        (first..nz)
+                            .step_by(threads)
+                            .map(|z| (z, slice(z)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            let mut slices: Vec<(u32, Vec<f32>)> =
+                workers.into_iter().flat_map(|worker| worker.join().expect("baking thread panicked")).collect();
+            slices.sort_by_key(|(z, _)| *z);
+            slices.into_iter().map(|(_, slice)| slice).collect()
+        });
+
+        Self {
+            resolution,
+            half_extents,
+            distances: slices.concat(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+        let bytes = fs::read(path)?;
+        if bytes.len() < 28 {
+            return Err(invalid("truncated header"));
+        }
+        let (header, data) = bytes.split_at(28);
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a signed distance volume"));
+        }
+        let word = |index: usize| <[u8; 4]>::try_from(&header[4 + 4 * index..8 + 4 * index]).expect("4 bytes");
+        let resolution = [0, 1, 2].map(|axis| u32::from_le_bytes(word(axis)));
+        let half_extents = [3, 4, 5].map(|axis| f32::from_le_bytes(word(axis)));
+        let count = resolution.iter().map(|&count| count as usize).product::<usize>();
+        if count == 0 || data.len() != count * 4 {
+            return Err(invalid("the distances don't match the resolution"));
+        }
+        let distances = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+            .collect();
+        Ok(Self {
+            resolution,
+            half_extents,
+            distances,
+        })
+    }
+
+    // The magic, the resolution and half extents, then the distances, all little endian
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(self.resolution.iter().flat_map(|count| count.to_le_bytes()));
+        bytes.extend(self.half_extents.iter().flat_map(|extent| extent.to_le_bytes()));
+        bytes.extend(self.distances.iter().flat_map(|distance| distance.to_le_bytes()));
+        fs::write(path, bytes)
+    }
+
+    pub fn half_extents(&self) -> [f32; 3] {
+        self.half_extents
+    }
+
+    /// The volume placed with its center at `center`, there is one volume per scene.
+    pub fn collider(&self, center: [f32; 3], rotation: Quaternion<f32>) -> Collider {
+        Collider::Sdf {
+            center,
+            half_extents: self.half_extents,
+            rotation,
+        }
+    }
+
+    // An R32Float 3D texture, read with textureLoad() since that format can't always be filtered
+    pub fn create_texture(&self, context: &dyn Gpu) -> wgpu::Texture {
+        let [width, height, depth] = self.resolution;
+        create_volume_texture(context, [width, height, depth], bytemuck::cast_slice(&self.distances))
+    }
+}
+
+// A single voxel standing in for the volume while the scene has none
+pub fn create_empty_texture(context: &dyn Gpu) -> wgpu::Texture {
+    create_volume_texture(context, [1, 1, 1], bytemuck::bytes_of(&1.0f32))
+}
+
+fn create_volume_texture(context: &dyn Gpu, [width, height, depth]: [u32; 3], data: &[u8]) -> wgpu::Texture {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: depth,
+    };
+    let texture = context.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some("SDF Volume Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: wgpu::TextureFormat::R32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    context.queue().write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    texture
+}
+
+// Distance to the closest triangle, negative where the winding number says the point is inside
+fn signed_distance(point: Vector3<f32>, triangles: &[[Vector3<f32>; 3]]) -> f32 {
+    let mut closest = f32::MAX;
+    let mut solid_angle = 0.0;
+    for &[a, b, c] in triangles {
+        closest = closest.min((closest_on_triangle(point, a, b, c) - point).magnitude2());
+        solid_angle += triangle_solid_angle(a - point, b - point, c - point);
+    }
+    let winding = solid_angle / (4.0 * std::f32::consts::PI);
+    // Either orientation of the faces
+    if winding.abs() > 0.5 {
+        -closest.sqrt()
+    } else {
+        closest.sqrt()
+    }
+}
+
+// Van Oosterom and Strackee, signed by the orientation of the triangle
+fn triangle_solid_angle(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> f32 {
+    let (la, lb, lc) = (a.magnitude(), b.magnitude(), c.magnitude());
+    let numerator = a.dot(b.cross(c));
+    let denominator = la * lb * lc + a.dot(b) * lc + a.dot(c) * lb + b.dot(c) * la;
+    2.0 * numerator.atan2(denominator)
+}
+
+// From Real-Time Collision Detection, by the Voronoi region of the triangle the point is in
+fn closest_on_triangle(p: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Vector3<f32> {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
@@ -0,0 +1,244 @@
+// Self-collisions: particles of the cloth closer than its thickness, see SimParams, are pushed
+// apart every substep, see self_collision.wgsl, so folds don't pass through themselves. Separate
+// pieces collide with each other the same way, pair by pair.
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::shaders::create_compute_module;
+use crate::simulation::Instance;
+
+// Pieces past that many collide with nothing, must match self_collision.wgsl
+pub const MAX_COLLISION_PIECES: usize = 32;
+
+/// How the cloth collides with itself, set from the UI.
+#[derive(Copy, Clone, Debug)]
+pub struct SelfCollisionSettings {
+    pub enabled: bool,
+    pub stiffness: f32, // fraction of the overlap resolved per substep
+    pairs: [u32; MAX_COLLISION_PIECES], // per piece, a bit per piece it collides with
+}
+
+impl Default for SelfCollisionSettings {
+    // Every piece with itself only
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stiffness: 0.5,
+            pairs: std::array::from_fn(|piece| 1 << piece),
+        }
+    }
+}
+
+impl SelfCollisionSettings {
+    // Pieces in the same order as the materials, a piece and itself for self-collisions
+    pub fn collides(&self, a: usize, b: usize) -> bool {
+        a < MAX_COLLISION_PIECES && b < MAX_COLLISION_PIECES && self.pairs[a] & (1 << b) != 0
+    }
+
+    // Both ways, pieces past MAX_COLLISION_PIECES are ignored
+    pub fn set_collides(&mut self, a: usize, b: usize, collides: bool) {
+        if a >= MAX_COLLISION_PIECES || b >= MAX_COLLISION_PIECES {
+            return;
+        }
+        for (piece, other) in [(a, b), (b, a)] {
+            if collides {
+                self.pairs[piece] |= 1 << other;
+            } else {
+                self.pairs[piece] &= !(1 << other);
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SelfCollisionParams {
+    stiffness: f32,
+    _padding: [f32; 3],
+    pairs: [u32; MAX_COLLISION_PIECES],
+}
+
+pub struct SelfCollision {
+    clear_pipeline: wgpu::ComputePipeline,
+    count_pipeline: wgpu::ComputePipeline,
+    prefix_sum_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    separate_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: TrackedBuffer,
+    _buffers: [TrackedBuffer; 3], // only reached through the bind group
+    num_slots: u32,
+    workgroup_size: u32,
+}
+
+impl SelfCollision {
+    // Compute passes encode() records, one per stage of the spatial hash
+    pub const DISPATCHES: u32 = 5;
+
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        rest_state: &[Instance],
+        workgroup_size: u32,
+    ) -> Self {
+        let num_instances = rest_state.len() as u32;
+        let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = wgpu::BufferBindingType::Storage { read_only: false };
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Self Collision Bind Group Layout"),
+            entries: &[
+                entry(0, storage),
+                entry(1, storage),
+                entry(2, wgpu::BufferBindingType::Storage { read_only: true }),
+                entry(3, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+
+        // About a slot per particle, a power of two so the prefix sum splits it evenly
+        let num_slots = num_instances.next_power_of_two().max(workgroup_size);
+        let cell_counts_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Self Collision Cell Counts Buffer"),
+            size: (num_slots as usize * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // The start of every slot, then the sorted particles
+        let cell_data_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Self Collision Cell Data Buffer"),
+            size: ((num_slots + num_instances) as usize * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // Particles that close at rest are kept apart by the solver
+        let rest_positions: Vec<[f32; 4]> = rest_state
+            .iter()
+            .map(|instance| {
+                let [x, y, z] = instance.position();
+                [x, y, z, 0.0]
+            })
+            .collect();
+        let rest_positions_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Self Collision Rest Positions Buffer"),
+            contents: bytemuck::cast_slice(&rest_positions),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        // Filled by configure()
+        let params_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Self Collision Params Buffer"),
+            size: std::mem::size_of::<SelfCollisionParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Self Collision Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: cell_counts_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cell_data_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: rest_positions_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Self Collision Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = create_compute_module(
+            context,
+            "Self Collision Shader",
+            include_str!("self_collision.wgsl"),
+            workgroup_size,
+        );
+
+        let create_pipeline = |entry_point: &str| {
+            context
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    module: &module,
+                    entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: None,
+                })
+        };
+
+        let self_collision = Self {
+            clear_pipeline: create_pipeline("clear_cells"),
+            count_pipeline: create_pipeline("count_cells"),
+            prefix_sum_pipeline: create_pipeline("prefix_sum"),
+            scatter_pipeline: create_pipeline("scatter_particles"),
+            separate_pipeline: create_pipeline("separate_particles"),
+            bind_group,
+            params_buffer,
+            _buffers: [cell_counts_buffer, cell_data_buffer, rest_positions_buffer],
+            num_slots,
+            workgroup_size,
+        };
+        self_collision.configure(context, &SelfCollisionSettings::default());
+        self_collision
+    }
+
+    pub fn configure(&self, context: &dyn Gpu, settings: &SelfCollisionSettings) {
+        let params = SelfCollisionParams {
+            stiffness: settings.stiffness,
+            _padding: [0.0; 3],
+            pairs: settings.pairs,
+        };
+        context.queue().write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    // Sorts the particles of the first binding of the bind group at `current` and separates them
+    // into the second, which flips `current`
+    pub fn encode(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        bind_groups: &[wgpu::BindGroup; 2],
+        current: &mut usize,
+        num_instances: u32,
+    ) {
+        let particle_workgroups = num_instances.div_ceil(self.workgroup_size);
+        compute_pass.set_bind_group(0, &bind_groups[*current], &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        let passes: [_; Self::DISPATCHES as usize] = [
+            (&self.clear_pipeline, self.num_slots.div_ceil(self.workgroup_size)),
+            (&self.count_pipeline, particle_workgroups),
+            (&self.prefix_sum_pipeline, 1),
+            (&self.scatter_pipeline, particle_workgroups),
+            (&self.separate_pipeline, particle_workgroups),
+        ];
+        for (pipeline, workgroups) in passes {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        *current ^= 1;
+    }
+}
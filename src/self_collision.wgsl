@@ -0,0 +1,175 @@
+// self_collision.wgsl
+// Collisions of the cloth with itself and between pieces, appended to compute.wgsl and run on the predicted positions of
+// every substep, before the speeds are derived from them.
+//
+// The particles are counting sorted into a spatial hash of cells as wide as the thickness: every
+// cell is counted, a prefix sum gives where its particles start, and they are scattered there.
+// Then every particle is pushed away from the particles of the 27 cells around it that are closer
+// than the thickness of the cloth, except the ones of its own piece that were that close at rest,
+// which the solver keeps apart, and the ones of pieces its own piece doesn't collide with.
+
+#include "hashing.wgsl"
+
+// Pieces past the bits of a mask collide with nothing
+const MAX_COLLISION_PIECES: u32 = 32u;
+
+struct SelfCollisionParams {
+    stiffness: f32, // fraction of the overlap resolved per pass
+    pairs: array<vec4<u32>, 8>, // per piece, a bit per piece it collides with, itself included
+};
+
+@group(1) @binding(0) var<storage, read_write> cell_counts: array<atomic<u32>>; // also the cursors of the scatter
+// Where the particles of every cell start, an exclusive prefix sum of the counts, then the
+// particles sorted by cell. One buffer for both, the stage is out of storage bindings otherwise.
+@group(1) @binding(1) var<storage, read_write> cell_data: array<u32>;
+@group(1) @binding(2) var<storage, read> rest_positions: array<vec4<f32>>;
+@group(1) @binding(3) var<uniform> self_collision: SelfCollisionParams;
+
+fn cloth_thickness() -> f32 {
+    return max(params.thickness, 1e-5);
+}
+
+fn cell_of(position: vec3<f32>) -> vec3<i32> {
+    return vec3<i32>(floor(position / cloth_thickness()));
+}
+
+fn num_slots() -> u32 {
+    return arrayLength(&cell_counts);
+}
+
+// Distinct cells can share a slot, the distance test tells their particles apart
+fn cell_slot(cell: vec3<i32>) -> u32 {
+    return hash3(bitcast<vec3<u32>>(cell)) & (num_slots() - 1u);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn clear_cells(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x < num_slots()) {
+        atomicStore(&cell_counts[global_id.x], 0u);
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn count_cells(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    atomicAdd(&cell_counts[cell_slot(cell_of(instances_ping[index].position.xyz))], 1u);
+}
+
+var<workgroup> chunk_sums: array<u32, WORKGROUP_SIZE>;
+
+// A single workgroup, every invocation sums a contiguous chunk of the slots, the number of slots
+// is a power of two at least WORKGROUP_SIZE
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn prefix_sum(@builtin(local_invocation_index) local_index: u32) {
+    let chunk = num_slots() / u32(WORKGROUP_SIZE);
+    let first = local_index * chunk;
+    var sum = 0u;
+    for (var slot = first; slot < first + chunk; slot++) {
+        sum += atomicLoad(&cell_counts[slot]);
+    }
+
+    // Inclusive Hillis-Steele scan of the chunk sums
+    chunk_sums[local_index] = sum;
+    workgroupBarrier();
+    for (var stride = 1u; stride < u32(WORKGROUP_SIZE); stride *= 2u) {
+        var left = 0u;
+        if (local_index >= stride) {
+            left = chunk_sums[local_index - stride];
+        }
+        workgroupBarrier();
+        chunk_sums[local_index] += left;
+        workgroupBarrier();
+    }
+
+    var start = chunk_sums[local_index] - sum;
+    for (var slot = first; slot < first + chunk; slot++) {
+        cell_data[slot] = start;
+        start += atomicLoad(&cell_counts[slot]);
+    }
+}
+
+// The counts go back down to zero, so each cell is filled from its end
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn scatter_particles(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let slot = cell_slot(cell_of(instances_ping[index].position.xyz));
+    let rank = atomicSub(&cell_counts[slot], 1u) - 1u;
+    cell_data[num_slots() + cell_data[slot] + rank] = index;
+}
+
+// Pieces are told apart by their material, there is one per piece
+fn pieces_collide(piece: u32, other: u32) -> bool {
+    if (piece >= MAX_COLLISION_PIECES || other >= MAX_COLLISION_PIECES) {
+        return false;
+    }
+    return ((self_collision.pairs[piece / 4u][piece % 4u] >> other) & 1u) != 0u;
+}
+
+// Pieces are built apart, only particles of the same piece can be that close at rest
+fn close_at_rest(index: u32, other: u32, piece: u32, other_piece: u32) -> bool {
+    return piece == other_piece && distance(rest_positions[index].xyz, rest_positions[other].xyz) < cloth_thickness();
+}
+
+// Jacobi like the solver: reads the ping buffer, writes the pong one. A slot shared by several of
+// the 27 cells is visited once per cell, which only strengthens the push a little.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn separate_particles(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    var instance = instances_ping[index];
+    let inverse_mass = instance.position.w;
+    if (inverse_mass <= 0.0) {
+        instances_pong[index] = instance;
+        return;
+    }
+
+    let position = instance.position.xyz;
+    let piece = u32(instance.speed.w);
+    let thickness = cloth_thickness();
+    let center = cell_of(position);
+    var correction = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var neighbor = 0; neighbor < 27; neighbor++) {
+        let offset = vec3<i32>(neighbor % 3, (neighbor / 3) % 3, neighbor / 9) - 1;
+        let slot = cell_slot(center + offset);
+        let start = cell_data[slot];
+        // The scatter brought the count down to zero, the next start is where the cell ends
+        var end = params.num_particles;
+        if (slot + 1u < num_slots()) {
+            end = cell_data[slot + 1u];
+        }
+        for (var i = start; i < end; i++) {
+            let other = cell_data[num_slots() + i];
+            if (other == index) {
+                continue;
+            }
+            let other_instance = instances_ping[other];
+            let other_position = other_instance.position;
+            let other_piece = u32(other_instance.speed.w);
+            let delta = position - other_position.xyz;
+            let distance = length(delta);
+            if (distance >= thickness || distance < 1e-9 || !pieces_collide(piece, other_piece)) {
+                continue;
+            }
+            if (close_at_rest(index, other, piece, other_piece)) {
+                continue;
+            }
+            let weight = inverse_mass / (inverse_mass + other_position.w);
+            correction += weight * (thickness - distance) * delta / distance;
+            count += 1.0;
+        }
+    }
+
+    if (count > 0.0) {
+        instance.position = vec4<f32>(position + correction * self_collision.stiffness / count, inverse_mass);
+    }
+    instances_pong[index] = instance;
+}
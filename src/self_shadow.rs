@@ -0,0 +1,213 @@
+// Self-shadowing: an occlusion value per particle from the layers of cloth around it, see
+// self_shadow.wgsl, drawn by darkening the particles.
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::shaders::create_compute_module;
+
+const CELL_CAPACITY: usize = 64; // must match self_shadow.wgsl
+
+/// How the occlusion is computed, set from the UI.
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    pub radius: f32, // m, cloth further away casts no shadow
+    pub strength: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 0.008,
+            strength: 0.1,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowParams {
+    radius: f32,
+    strength: f32,
+    _padding: [f32; 2],
+}
+
+// Read by the cloth pipelines next to the attribute
+pub fn vertex_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<f32>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 7,
+            format: wgpu::VertexFormat::Float32,
+        }],
+    }
+}
+
+pub struct SelfShadow {
+    insert_pipeline: wgpu::ComputePipeline,
+    gather_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: TrackedBuffer,
+    cell_counts_buffer: TrackedBuffer,
+    occlusion_buffer: TrackedBuffer, // zero until the first pass
+    _cell_particles_buffer: TrackedBuffer, // only reached through the bind group
+    workgroup_size: u32,
+}
+
+impl SelfShadow {
+    pub fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        num_instances: u32,
+        workgroup_size: u32,
+    ) -> Self {
+        let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = wgpu::BufferBindingType::Storage { read_only: false };
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Self Shadow Bind Group Layout"),
+            entries: &[
+                entry(0, storage),
+                entry(1, storage),
+                entry(2, storage),
+                entry(3, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+
+        // About two particles per slot, most cells of a sheet hold a lot more than that
+        let num_slots = (num_instances / 2).next_power_of_two().max(256) as usize;
+        let cell_counts_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Self Shadow Cell Counts Buffer"),
+            size: (num_slots * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let cell_particles_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Self Shadow Cell Particles Buffer"),
+            size: (num_slots * CELL_CAPACITY * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let occlusion_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Occlusion Buffer"),
+            size: (num_instances.max(1) as usize * std::mem::size_of::<f32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Filled by encode()
+        let params_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Self Shadow Params Buffer"),
+            size: std::mem::size_of::<ShadowParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Self Shadow Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: cell_counts_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cell_particles_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: occlusion_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Self Shadow Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = create_compute_module(context, "Self Shadow Shader", include_str!("self_shadow.wgsl"), workgroup_size);
+
+        let create_pipeline = |entry_point: &str| {
+            context
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    module: &module,
+                    entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: None,
+                })
+        };
+
+        Self {
+            insert_pipeline: create_pipeline("insert_particles"),
+            gather_pipeline: create_pipeline("gather_occlusion"),
+            bind_group,
+            params_buffer,
+            cell_counts_buffer,
+            occlusion_buffer,
+            _cell_particles_buffer: cell_particles_buffer,
+            workgroup_size,
+        }
+    }
+
+    // Reads the latest state from the first binding of `instance_bind_group`
+    pub fn encode(
+        &self,
+        context: &dyn Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        instance_bind_group: &wgpu::BindGroup,
+        num_instances: u32,
+        settings: &ShadowSettings,
+    ) {
+        let params = ShadowParams {
+            radius: settings.radius,
+            strength: settings.strength,
+            _padding: [0.0; 2],
+        };
+        context.queue().write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+        encoder.clear_buffer(&self.cell_counts_buffer, 0, None);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Self Shadow Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, instance_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        for pipeline in [&self.insert_pipeline, &self.gather_pipeline] {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.dispatch_workgroups(num_instances.div_ceil(self.workgroup_size), 1, 1);
+        }
+    }
+
+    // Lights every particle again once the shadows are turned off
+    pub fn clear(&self, context: &dyn Gpu) {
+        let zeros = vec![0u8; self.occlusion_buffer.size() as usize];
+        context.queue().write_buffer(&self.occlusion_buffer, 0, &zeros);
+    }
+
+    pub fn occlusion_buffer(&self) -> &wgpu::Buffer {
+        &self.occlusion_buffer
+    }
+}
@@ -0,0 +1,109 @@
+// self_shadow.wgsl
+// Self-shadowing of the cloth, appended to compute.wgsl and run on the latest state.
+//
+// The particles are first sorted into a spatial hash of cells as wide as the shadow radius, then
+// every particle gathers the cloth within that radius from the 27 cells around it. Cloth in its
+// tangent plane is the sheet it belongs to and barely counts, cloth above or below it is another
+// layer of a fold and darkens it. Cloth is seen from both sides, so both hemispheres count.
+
+#include "hashing.wgsl"
+
+struct ShadowParams {
+    radius: f32, // m, also the size of a cell
+    strength: f32,
+};
+
+// Particles beyond that many in a hashed cell are left out of it
+const CELL_CAPACITY: u32 = 64u;
+const BEND: u32 = 3u; // kind of the constraints across two spacings, not part of the sheet
+
+@group(1) @binding(0) var<storage, read_write> cell_counts: array<atomic<u32>>; // cleared before every pass
+@group(1) @binding(1) var<storage, read_write> cell_particles: array<u32>;
+@group(1) @binding(2) var<storage, read_write> occlusion: array<f32>; // 0 lit to 1 fully shadowed
+@group(1) @binding(3) var<uniform> shadow: ShadowParams;
+
+fn cell_of(position: vec3<f32>) -> vec3<i32> {
+    return vec3<i32>(floor(position / shadow.radius));
+}
+
+// Distinct cells can share a slot, their particles are told apart by cell_of()
+fn cell_slot(cell: vec3<i32>) -> u32 {
+    return hash3(bitcast<vec3<u32>>(cell)) % arrayLength(&cell_counts);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn insert_particles(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let slot = cell_slot(cell_of(instances_ping[index].position.xyz));
+    let count = atomicAdd(&cell_counts[slot], 1u);
+    if (count < CELL_CAPACITY) {
+        cell_particles[slot * CELL_CAPACITY + count] = index;
+    }
+}
+
+// Normal of the sheet around a particle from the edges to its neighbors, zero without any. The
+// sign is arbitrary, both hemispheres are used.
+fn sheet_normal(index: u32, position: vec3<f32>) -> vec3<f32> {
+    var first = vec3<f32>(0.0);
+    var normal = vec3<f32>(0.0);
+    for (var slot = 0u; slot < MAX_CONSTRAINTS; slot++) {
+        let constraint = constraints[index * MAX_CONSTRAINTS + slot];
+        if (constraint.neighbor == NO_NEIGHBOR || constraint.kind == BEND) {
+            continue;
+        }
+        let edge = instances_ping[constraint.neighbor].position.xyz - position;
+        if (all(first == vec3<f32>(0.0))) {
+            first = edge;
+            continue;
+        }
+        // Neighbors on either side give opposite normals, they are flipped to agree
+        let across = cross(first, edge);
+        normal += select(across, -across, dot(across, normal) < 0.0);
+    }
+    let magnitude = length(normal);
+    if (magnitude < 1e-12) {
+        return vec3<f32>(0.0);
+    }
+    return normal / magnitude;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn gather_occlusion(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let position = instances_ping[index].position.xyz;
+    let normal = sheet_normal(index, position);
+    let center = cell_of(position);
+
+    // Nearer layers and those straight above or below cover more
+    var coverage = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var z = -1; z <= 1; z++) {
+                let cell = center + vec3<i32>(x, y, z);
+                let slot = cell_slot(cell);
+                let count = min(atomicLoad(&cell_counts[slot]), CELL_CAPACITY);
+                for (var i = 0u; i < count; i++) {
+                    let other = cell_particles[slot * CELL_CAPACITY + i];
+                    let other_position = instances_ping[other].position.xyz;
+                    if (other == index || any(cell_of(other_position) != cell)) {
+                        continue;
+                    }
+                    let offset = other_position - position;
+                    let distance = length(offset);
+                    if (distance >= shadow.radius || distance < 1e-6) {
+                        continue;
+                    }
+                    coverage += (1.0 - distance / shadow.radius) * abs(dot(normal, offset)) / distance;
+                }
+            }
+        }
+    }
+
+    occlusion[index] = 1.0 - exp(-shadow.strength * coverage);
+}
@@ -9,8 +9,44 @@ struct Instance {
     speed: vec3<f32>,
 };
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+// How far the rendered state is between the previous simulation step and the latest one
+struct Interpolation {
+    alpha: f32,
+};
+@group(1) @binding(0) var<uniform> interpolation: Interpolation;
 @group(1) @binding(1) var<storage> instances: array<Instance>;
 
+// Color ramp of the per-particle attribute, see attribute.rs
+struct Ramp {
+    ramp: u32, // 0 keeps the mesh colors, then viridis, heat and categories
+    min: f32,
+    max: f32,
+};
+@group(1) @binding(2) var<uniform> ramp: Ramp;
+
+#include "lighting.wgsl"
+
+@group(2) @binding(0) var<uniform> lighting: Lighting;
+
+// Indexed by the material of the particle
+struct Fabric {
+    sheen: vec4<f32>, // color, w is the roughness
+    opacity: vec4<f32>, // x, below 1 for the sheer fabrics drawn by fs_transparent
+};
+@group(2) @binding(1) var<uniform> fabrics: array<Fabric, MAX_FABRICS>;
+@group(2) @binding(2) var shadow_map: texture_depth_2d;
+@group(2) @binding(3) var shadow_sampler: sampler_comparison;
+
+// Texture the particles take their color from at their UV, see albedo.rs
+struct Albedo {
+    enabled: u32, // 0 keeps the mesh colors
+    repeat: f32, // tiling across the largest side of a piece
+};
+@group(3) @binding(0) var<uniform> albedo: Albedo;
+@group(3) @binding(1) var albedo_texture: texture_2d<f32>;
+@group(3) @binding(2) var albedo_sampler: sampler;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
@@ -19,25 +55,151 @@ struct VertexInput {
 
 struct InstanceInput {
     @location(3) pos: vec3<f32>,
+    @location(4) speed: vec4<f32>, // w is the index of the material
+    @location(5) previous_pos: vec3<f32>,
+    @location(6) ramp_value: f32, // the per-particle attribute
+    @location(7) occlusion: f32, // self shadow, see self_shadow.wgsl
+    @location(8) uv: vec2<f32>, // rest position on the fabric
+    @location(9) tint: vec4<f32>, // laid over the other colors by its alpha, see tint.rs
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) velocity: vec2<f32>, // texture space motion over the last step
+    @location(2) world_position: vec3<f32>,
+    @location(3) normal: vec3<f32>, // of the small sphere drawn at the particle
+    @location(4) @interpolate(flat) material: u32,
+};
+
+// Color and motion, for the motion blur targets
+struct MotionOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+// Weighted sums of the sheer fabrics, see oit.wgsl
+struct TransparentOutput {
+    @location(0) accumulation: vec4<f32>,
+    @location(1) revealage: f32,
 };
 
+fn to_texture_space(clip_position: vec4<f32>) -> vec2<f32> {
+    return clip_position.xy / clip_position.w * vec2<f32>(0.5, -0.5);
+}
+
+// Polynomial fit of matplotlib's viridis
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273, 0.0054073, 0.3340998);
+    let c1 = vec3<f32>(0.1050930, 1.4046135, 1.3845902);
+    let c2 = vec3<f32>(-0.3308618, 0.2148476, 0.0950952);
+    let c3 = vec3<f32>(-4.6342305, -5.7991010, -19.3324410);
+    let c4 = vec3<f32>(6.2282699, 14.1799334, 56.6905526);
+    let c5 = vec3<f32>(4.7763850, -13.7451454, -65.3530326);
+    let c6 = vec3<f32>(-5.4354559, 4.6458526, 26.3124352);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+// Black, red, yellow, white
+fn heat(t: f32) -> vec3<f32> {
+    return clamp(vec3<f32>(3.0 * t, 3.0 * t - 1.0, 3.0 * t - 2.0), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Hues spread by the golden ratio so neighboring ids differ
+fn category(value: f32) -> vec3<f32> {
+    let hue = fract(round(value) * 0.618034);
+    let k = fract(vec3<f32>(1.0, 2.0 / 3.0, 1.0 / 3.0) + hue) * 6.0 - 3.0;
+    return 0.9 * mix(vec3<f32>(1.0), clamp(abs(k) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0)), 0.6);
+}
+
+fn ramp_color(value: f32, mesh_color: vec3<f32>) -> vec3<f32> {
+    let t = clamp((value - ramp.min) / max(ramp.max - ramp.min, 1e-6), 0.0, 1.0);
+    var color = mesh_color;
+    switch ramp.ramp {
+        case 1u: { color = viridis(t); }
+        case 2u: { color = heat(t); }
+        case 3u: { color = category(value); }
+        default: {}
+    }
+    return color;
+}
+
 @vertex
 fn vs_main(
     model: VertexInput,
     instance: InstanceInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.color = model.color;
-    out.clip_position = camera.proj * camera.view * vec4<f32>(model.position + instance.pos, 1.0);
+    var base_color = model.color;
+    if (albedo.enabled != 0u) {
+        base_color = textureSampleLevel(albedo_texture, albedo_sampler, instance.uv * albedo.repeat, 0.0).rgb;
+    }
+    let color = mix(ramp_color(instance.ramp_value, base_color), instance.tint.rgb, instance.tint.a);
+    out.color = color * (1.0 - instance.occlusion);
+    let view_projection = camera.proj * camera.view;
+    out.world_position = model.position + mix(instance.previous_pos, instance.pos, interpolation.alpha);
+    out.normal = model.normal;
+    out.material = min(u32(instance.speed.w), MAX_FABRICS - 1u);
+    out.clip_position = view_projection * vec4<f32>(out.world_position, 1.0);
+    // Motion of the particles only, the camera is assumed still
+    let current = view_projection * vec4<f32>(model.position + instance.pos, 1.0);
+    let previous = view_projection * vec4<f32>(model.position + instance.previous_pos, 1.0);
+    out.velocity = to_texture_space(current) - to_texture_space(previous);
     return out;
 }
 
+// Back faces are only drawn by the double-sided pipelines, they face the other way
+fn shade(in: VertexOutput, front: bool) -> vec4<f32> {
+    var sheen = vec4<f32>(0.0);
+    if (lighting.fabric_brdf != 0u) {
+        sheen = fabrics[in.material].sheen;
+    }
+    var color = in.color;
+    var normal = in.normal;
+    if (!front) {
+        normal = -normal;
+        if (lighting.back_color.w > 0.0) {
+            color = lighting.back_color.rgb;
+        }
+    }
+    let sun = sun_visibility(lighting, shadow_map, shadow_sampler, in.world_position);
+    return vec4<f32>(shade_surface(lighting, color, sheen, in.world_position, normal, eye_position(camera.view), sun), 1.0);
+}
+
+// The sheer fabrics are left to fs_transparent
+fn is_sheer(in: VertexOutput) -> bool {
+    return fabrics[in.material].opacity.x < 1.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front: bool) -> @location(0) vec4<f32> {
+    if (is_sheer(in)) {
+        discard;
+    }
+    return shade(in, front);
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+fn fs_motion(in: VertexOutput, @builtin(front_facing) front: bool) -> MotionOutput {
+    if (is_sheer(in)) {
+        discard;
+    }
+    return MotionOutput(shade(in, front), in.velocity);
+}
+
+// Weighted blended order-independent transparency of McGuire and Bavoil: every layer adds its
+// color weighted by its opacity and closeness to the eye, and multiplies the revealage by what it
+// lets through. Both faces are drawn, the back ones lit from their own side.
+@fragment
+fn fs_transparent(in: VertexOutput, @builtin(front_facing) front: bool) -> TransparentOutput {
+    if (!is_sheer(in)) {
+        discard;
+    }
+    let alpha = fabrics[in.material].opacity.x;
+    let color = shade(in, front).rgb;
+    let weight = alpha * clamp(3e3 * pow(1.0 - in.clip_position.z, 3.0), 1e-2, 3e3);
+    var out: TransparentOutput;
+    out.accumulation = vec4<f32>(color * alpha, alpha) * weight;
+    out.revealage = alpha;
+    return out;
 }
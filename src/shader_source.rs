@@ -0,0 +1,67 @@
+// Assembly of the WGSL sources of the compute and render passes, shared with build.rs which
+// validates them.
+//
+// WGSL has no modules, so the snippets of src/wgsl are pasted into a source by an
+// `#include "name.wgsl"` line of their own. A snippet is only pasted the first time it is included,
+// later lines are dropped, and snippets can include one another.
+
+use std::collections::HashSet;
+
+const SNIPPETS: [(&str, &str); 6] = [
+    ("reductions.wgsl", include_str!("wgsl/reductions.wgsl")),
+    ("hashing.wgsl", include_str!("wgsl/hashing.wgsl")),
+    ("noise.wgsl", include_str!("wgsl/noise.wgsl")),
+    ("sdf.wgsl", include_str!("wgsl/sdf.wgsl")),
+    ("lighting.wgsl", include_str!("wgsl/lighting.wgsl")),
+    ("tonemap.wgsl", include_str!("wgsl/tonemap.wgsl")),
+];
+
+fn snippet(name: &str) -> &'static str {
+    SNIPPETS
+        .iter()
+        .find(|(snippet, _)| *snippet == name)
+        .map(|(_, source)| *source)
+        .unwrap_or_else(|| panic!("no WGSL snippet named {name}"))
+}
+
+fn expand_into(source: &str, included: &mut HashSet<String>, output: &mut String) {
+    for line in source.lines() {
+        match line.trim().strip_prefix("#include") {
+            Some(name) => {
+                let name = name.trim().trim_matches('"');
+                if included.insert(name.to_string()) {
+                    expand_into(snippet(name), included, output);
+                }
+            }
+            None => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+}
+
+// Replaces the `#include` lines of `source` by the snippets they name, panics on an unknown one
+pub fn expand_includes(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    expand_into(source, &mut HashSet::new(), &mut output);
+    output
+}
+
+// compute.wgsl holds the declarations shared by every solver, `extra_source` adds the entry
+// points of a specific solver on top of it.
+pub fn compute_source(extra_source: &str, workgroup_size: u32) -> String {
+    expand_includes(&format!("{}\n{}", include_str!("compute.wgsl"), extra_source))
+        .replace("WORKGROUP_SIZE", &format!("{}", workgroup_size))
+}
+
+// With --msaa the depth of field reads the multisampled depth buffer. textureLoad() takes a sample
+// index where it took a mip level, only the type of the texture changes.
+pub fn depth_of_field_source(multisampled: bool) -> String {
+    let source = expand_includes(include_str!("dof.wgsl"));
+    if multisampled {
+        source.replace("var depth_texture: texture_depth_2d;", "var depth_texture: texture_depth_multisampled_2d;")
+    } else {
+        source
+    }
+}
@@ -0,0 +1,21 @@
+// Shader module creation for the compute passes.
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::shader_source::compute_source;
+
+// See compute_source() for how the source is put together
+pub fn create_compute_module(
+    context: &dyn Gpu,
+    label: &str,
+    extra_source: &str,
+    workgroup_size: u32,
+) -> wgpu::ShaderModule {
+    context
+        .device()
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(compute_source(extra_source, workgroup_size).into()),
+        })
+}
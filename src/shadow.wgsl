@@ -0,0 +1,30 @@
+// shadow.wgsl
+// Depth of the particles and the colliders seen from the sun, see shadow_map.rs
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform; // of the sun
+
+struct Interpolation {
+    alpha: f32,
+};
+@group(1) @binding(0) var<uniform> interpolation: Interpolation;
+
+// Same inputs as shader.wgsl, the particles are where the main view draws them
+@vertex
+fn vs_particle(
+    @location(0) position: vec3<f32>,
+    @location(3) pos: vec3<f32>,
+    @location(5) previous_pos: vec3<f32>,
+) -> @builtin(position) vec4<f32> {
+    let world_position = position + mix(previous_pos, pos, interpolation.alpha);
+    return camera.proj * camera.view * vec4<f32>(world_position, 1.0);
+}
+
+// Same inputs as sphere_shader.wgsl
+@vertex
+fn vs_collider(@location(0) position: vec3<f32>, @location(3) sphere: vec4<f32>) -> @builtin(position) vec4<f32> {
+    return camera.proj * camera.view * vec4<f32>(sphere.xyz + position * sphere.w, 1.0);
+}
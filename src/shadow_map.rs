@@ -0,0 +1,216 @@
+// Shadows of the sun: the particles and the collider spheres are rendered into a depth texture
+// from the direction of the light, then every fragment of the main view looks itself up in it,
+// with a 3×3 percentage-closer filter for soft edges. See shadow.wgsl and wgsl/lighting.wgsl.
+
+use wgpu_bootstrap::{
+    cgmath::{self, Matrix4},
+    egui, wgpu, Context,
+};
+
+use crate::camera::{CameraUniform, OPENGL_TO_WGPU_MATRIX};
+
+const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const SHADOW_SIZE: u32 = 2048; // texels along each side
+const SHADOW_EXTENT: f32 = 1.5; // m, half the width of the square around the origin casting shadows
+const LIGHT_DISTANCE: f32 = 5.0; // m, from the origin to the eye of the light
+const LIGHT_DEPTH: f32 = 10.0; // m, from the eye of the light to its far plane
+
+pub struct ShadowMap {
+    pub enabled: bool,
+    pub bias: f32, // m, keeps the casters from shadowing themselves
+    pub strength: f32, // from 0 for no shadow to 1 for no sunlight at all
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    depth_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    particle_pipeline: wgpu::RenderPipeline,
+    collider_pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    // `interpolation_layout` places the particles in between the last two steps like the main
+    // view, the vertex buffers are those of the particle and collider pipelines
+    pub fn new(
+        context: &Context,
+        interpolation_layout: &wgpu::BindGroupLayout,
+        particle_buffers: &[wgpu::VertexBufferLayout],
+        collider_buffers: &[wgpu::VertexBufferLayout],
+    ) -> Self {
+        let camera_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Camera Buffer"),
+            size: std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_layout = context.device().create_bind_group_layout(&CameraUniform::desc());
+        let camera_bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Camera Bind Group"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let depth_view = context
+            .device()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Shadow Map"),
+                size: wgpu::Extent3d {
+                    width: SHADOW_SIZE,
+                    height: SHADOW_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: SHADOW_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Outside of the map is lit
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToBorder,
+            address_mode_v: wgpu::AddressMode::ClampToBorder,
+            border_color: Some(wgpu::SamplerBorderColor::OpaqueWhite),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let shader = context.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+        });
+        let create_pipeline = |label: &str, layouts: &[&wgpu::BindGroupLayout], entry_point: &str, buffers| {
+            let layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            context.device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point,
+                    buffers,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: None,
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Back),
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: SHADOW_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    // Steep surfaces need more than the bias of the lookup
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let particle_pipeline = create_pipeline(
+            "Shadow Particle Pipeline",
+            &[&camera_layout, interpolation_layout],
+            "vs_particle",
+            particle_buffers,
+        );
+        let collider_pipeline = create_pipeline("Shadow Collider Pipeline", &[&camera_layout], "vs_collider", collider_buffers);
+
+        Self {
+            enabled: false,
+            bias: 0.01,
+            strength: 0.8,
+            camera_buffer,
+            camera_bind_group,
+            depth_view,
+            sampler,
+            particle_pipeline,
+            collider_pipeline,
+        }
+    }
+
+    // Looks along `direction`, towards the light, and returns the matrix from world space to the
+    // clip space of the light
+    pub fn update(&self, context: &Context, direction: [f32; 3]) -> Matrix4<f32> {
+        let direction = cgmath::Vector3::from(direction);
+        let direction = if direction == cgmath::Vector3::new(0.0, 0.0, 0.0) {
+            cgmath::Vector3::unit_y()
+        } else {
+            cgmath::InnerSpace::normalize(direction)
+        };
+        // Looking straight down, up can't be +y
+        let up = if direction.y.abs() > 0.99 { cgmath::Vector3::unit_z() } else { cgmath::Vector3::unit_y() };
+        let eye = cgmath::Point3::new(0.0, 0.0, 0.0) + direction * LIGHT_DISTANCE;
+        let view = Matrix4::look_at_rh(eye, cgmath::Point3::new(0.0, 0.0, 0.0), up);
+        let projection = OPENGL_TO_WGPU_MATRIX
+            * cgmath::ortho(-SHADOW_EXTENT, SHADOW_EXTENT, -SHADOW_EXTENT, SHADOW_EXTENT, 0.0, LIGHT_DEPTH);
+        context
+            .queue()
+            .write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&CameraUniform::new(view, projection)));
+        projection * view
+    }
+
+    // Shadow map parameters of the lighting uniform: strength, bias in depth units and the size
+    // of a texel in texture space
+    pub fn lookup(&self) -> [f32; 4] {
+        let strength = if self.enabled { self.strength } else { 0.0 };
+        [strength, self.bias / LIGHT_DEPTH, 1.0 / SHADOW_SIZE as f32, 0.0]
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    // `draw` binds the vertex buffers and draws, the particles with the first pipeline and the
+    // colliders with the second one. The camera of the light is bound to group 0.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        draw: impl FnOnce(&mut wgpu::RenderPass<'_>, [&wgpu::RenderPipeline; 2]),
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        draw(&mut render_pass, [&self.particle_pipeline, &self.collider_pipeline]);
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Shadows")
+            .on_hover_text("Of the cloth and the colliders, cast by the sun");
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.add(egui::Slider::new(&mut self.strength, 0.0..=1.0).text("Shadow strength"));
+            ui.add(egui::Slider::new(&mut self.bias, 0.0..=0.05).text("Shadow bias (m)"))
+                .on_hover_text("Raise it if the cloth is speckled with its own shadow");
+        });
+    }
+}
@@ -0,0 +1,2667 @@
+// GPU state of one cloth: particles, constraints, solvers and the passes that step them.
+
+use bytemuck::Zeroable;
+use wgpu_bootstrap::{
+    cgmath::{self, InnerSpace, Matrix3, Matrix4, Quaternion, Rotation, SquareMatrix, Vector3},
+    wgpu,
+};
+
+use crate::bvh::{self, TriangleBvh};
+use crate::checksum::StateChecksum;
+use crate::convergence::{ConvergenceCheck, ConvergenceSettings};
+use crate::divergence::{self, StepInputs};
+use crate::energy::EnergyMeter;
+use crate::gauss_seidel::GaussSeidelSolver;
+use crate::gpu::Gpu;
+use crate::gpu_resources::{BufferTracker, TrackedBuffer};
+use crate::heightfield::{self, HeightField};
+use crate::implicit::ImplicitSolver;
+use crate::kinematics::{Kinematics, MotionQuantity};
+use crate::material::{Material, Preset};
+use crate::mesh::ClothMesh;
+use crate::readback::{copy_to_staging, map_staging};
+use crate::sdf::{self, SdfVolume};
+use crate::self_collision::{SelfCollision, SelfCollisionSettings};
+use crate::self_shadow::{SelfShadow, ShadowSettings};
+use crate::shaders::create_compute_module;
+use crate::strain::Strain;
+use crate::tearing::TearMap;
+use crate::transform::NodeId;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    position: [f32; 4], // w holds the inverse mass, 0.0 pins the particle
+    speed: [f32; 4],    // w holds the index of the material of the particle's cloth
+    previous: [f32; 4], // position before the step, to recover the speed, w counts still substeps
+}
+
+impl Instance {
+    pub fn at_rest(position: [f32; 3], inverse_mass: f32) -> Self {
+        let [x, y, z] = position;
+        Self {
+            position: [x, y, z, inverse_mass],
+            speed: [0.0, 0.0, 0.0, 0.0],
+            previous: [x, y, z, 0.0], // awake
+        }
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        [self.position[0], self.position[1], self.position[2]]
+    }
+
+    pub fn speed(&self) -> [f32; 3] {
+        [self.speed[0], self.speed[1], self.speed[2]]
+    }
+
+    pub fn previous(&self) -> [f32; 3] {
+        [self.previous[0], self.previous[1], self.previous[2]]
+    }
+
+    pub fn inverse_mass(&self) -> f32 {
+        self.position[3]
+    }
+
+    pub fn material(&self) -> usize {
+        self.speed[3] as usize
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 3,
+                format: wgpu::VertexFormat::Float32x3,
+                },
+                // Speed, w is the index of the material
+                wgpu::VertexAttribute {
+                offset: std::mem::size_of::<[f32;4]>() as wgpu::BufferAddress,
+                shader_location: 4,
+                format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+
+        }
+
+    }
+
+    // Position of the state before the last step, read alongside desc() to interpolate
+    pub fn previous_desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 5,
+                format: wgpu::VertexFormat::Float32x3,
+            }],
+        }
+    }
+}
+
+// Constraint groups, each one gets its own stiffness in the sim params
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ConstraintKind {
+    Horizontal = 0,
+    Vertical = 1,
+    Shear = 2,
+    Bend = 3,
+}
+
+impl ConstraintKind {
+    pub const ALL: [ConstraintKind; 4] = [
+        ConstraintKind::Horizontal,
+        ConstraintKind::Vertical,
+        ConstraintKind::Shear,
+        ConstraintKind::Bend,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConstraintKind::Horizontal => "Horizontal",
+            ConstraintKind::Vertical => "Vertical",
+            ConstraintKind::Shear => "Shear",
+            ConstraintKind::Bend => "Bend",
+        }
+    }
+}
+
+// Every particle owns MAX_CONSTRAINTS slots in the constraint buffer,
+// unused slots have `neighbor == NO_NEIGHBOR`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Constraint {
+    pub neighbor: u32,
+    pub kind: u32,
+    pub rest_length: f32,
+    pub tear_strain: f32, // stretch over the rest length it breaks at, 0.0 never does
+}
+
+pub const MAX_CONSTRAINTS: usize = 12;
+pub const NO_NEIGHBOR: u32 = u32::MAX;
+
+// Latest state and constraints, read by the vertex shader of the spring overlay, see edge_view.rs
+pub fn edge_bind_group_layout_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Edge Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    }
+}
+
+impl Constraint {
+    pub const NONE: Constraint = Constraint {
+        neighbor: NO_NEIGHBOR,
+        kind: 0,
+        rest_length: 0.0,
+        tear_strain: 0.0,
+    };
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SimParams {
+    delta_time: f32,
+    relaxation: f32,
+    spring_constant: f32,
+    iterations: u32,
+    substeps: u32,
+    num_particles: u32, // filled in by the simulation, dispatches are rounded up to whole workgroups
+    num_anchors: u32,   // same
+    num_colliders: u32, // same
+    num_attachments: u32, // same
+    gravity_scale: f32,
+    max_speed: f32,
+    static_friction: f32,
+    dynamic_friction: f32,
+    num_fans: u32, // same
+    thickness: f32, // m, kept between the cloth and the colliders, and between its layers
+    sleep_speed: f32, // m/s
+    sleep_substeps: u32, // 0 keeps every particle awake
+    _padding: [u32; 3],
+}
+
+impl SimParams {
+    pub fn new(substeps: u32, iterations: u32) -> Self {
+        Self {
+            delta_time: TIME_STEP / substeps as f32,
+            relaxation: RELAXATION,
+            spring_constant: IMPLICIT_SPRING_CONSTANT,
+            iterations,
+            substeps,
+            num_particles: 0,
+            num_anchors: 0,
+            num_colliders: 0,
+            num_attachments: 0,
+            gravity_scale: 1.0,
+            max_speed: MAX_SPEED,
+            static_friction: STATIC_FRICTION,
+            dynamic_friction: DYNAMIC_FRICTION,
+            num_fans: 0,
+            thickness: THICKNESS,
+            sleep_speed: 0.0,
+            sleep_substeps: 0,
+            _padding: [0; 3],
+        }
+    }
+
+    pub fn scale_time(&mut self, factor: f32) {
+        self.delta_time *= factor;
+    }
+
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations;
+    }
+
+    pub fn set_gravity_scale(&mut self, scale: f32) {
+        self.gravity_scale = scale;
+    }
+
+    pub fn scale_gravity(&mut self, factor: f32) {
+        self.gravity_scale *= factor;
+    }
+
+    // Simulated seconds the step takes, over all its substeps
+    pub fn step_time(&self) -> f32 {
+        self.delta_time * self.substeps as f32
+    }
+
+    pub fn set_thickness(&mut self, thickness: f32) {
+        self.thickness = thickness;
+    }
+
+    // Particles moving slower than `speed` for `substeps` in a row stop being integrated until a
+    // neighbor or a moving collider wakes them, 0 substeps turns sleeping off
+    pub fn set_sleeping(&mut self, speed: f32, substeps: u32) {
+        self.sleep_speed = speed;
+        self.sleep_substeps = substeps;
+    }
+
+    // Coefficients of the sphere surface, scaling the friction of the fabric touching it
+    pub fn set_friction(&mut self, static_friction: f32, dynamic_friction: f32) {
+        self.static_friction = static_friction;
+        self.dynamic_friction = dynamic_friction;
+    }
+}
+
+// Fabric of one cloth as the shader sees it, the materials buffer holds one per piece
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialBlock {
+    stiffness: [f32; 4], // indexed by ConstraintKind
+    linear_drag: f32,
+    quadratic_drag: f32,
+    density: f32,
+    friction: f32,
+    far: u32, // 1 past the distance of the level of detail, see ClothSimulation::set_lod()
+    _padding: [u32; 3],
+}
+
+impl From<&Material> for MaterialBlock {
+    fn from(material: &Material) -> Self {
+        Self {
+            stiffness: material.stiffness.to_array(),
+            linear_drag: material.linear_drag,
+            quadratic_drag: material.quadratic_drag,
+            density: material.density,
+            friction: material.friction,
+            far: 0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+// A pinned particle following a node of the transform hierarchy
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Anchor {
+    offset: [f32; 4], // position in the space of the node, w is unused
+    particle: u32,
+    node: u32,
+    group: u32,  // PinGroup, the piece of the particle
+    broken: u32, // set by the GPU once the pin snapped, cleared by reset()
+}
+
+pub const MAX_NODES: usize = 16;
+pub const MAX_COLLIDERS: usize = 32;
+pub const MAX_ATTACHMENTS: usize = 32;
+pub const MAX_FANS: usize = 8;
+pub const MAX_PIN_GROUPS: usize = 16; // pins of the pieces past it stay rigid
+
+// Contact of a particle with the last collider it touched in a substep, for the contact overlay:
+// the point on the surface with w 1.0, 0.0 for no contact, then the normal out of the collider
+pub const CONTACT_MARKER_SIZE: usize = 2 * std::mem::size_of::<[f32; 4]>();
+
+/// How the pinned particles of a piece hold on to their node. Compliant pins are springs the
+/// particle hangs from, rigid ones move it with the node and never break.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PinGroup {
+    pub compliance: f32,  // m/N, stretch of a pin per newton pulling on it, 0.0 for rigid pins
+    pub break_force: f32, // N, a pin pulled harder than that lets go until reset, 0.0 never does
+}
+
+/// A particle pulled towards a target moved from the CPU every frame, e.g. held by a hand.
+#[derive(Copy, Clone, Debug)]
+pub struct Attachment {
+    pub particle: u32,
+    pub target: [f32; 3],
+    pub stiffness: f32, // fraction of the way to the target covered every substep, 1.0 holds it there
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AttachmentBlock {
+    target: [f32; 4], // w is the stiffness
+    particle: u32,
+    _padding: [u32; 3],
+}
+
+// World transforms of the hierarchy and the attachment targets, uploaded every step
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RigUniform {
+    nodes: [[[f32; 4]; 4]; MAX_NODES],
+    attachments: [AttachmentBlock; MAX_ATTACHMENTS],
+    fans: [FanBlock; MAX_FANS],
+    pin_groups: [[f32; 4]; MAX_PIN_GROUPS], // compliance and break force, zw are unused
+}
+
+/// A blower pushing the air along `direction` within a cone, the cloth is dragged towards the
+/// speed of the air by the drag of its fabric.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fan {
+    pub position: [f32; 3],
+    pub direction: [f32; 3], // normalized on upload
+    pub cone_angle: f32,     // rad, from the axis to the edge of the cone
+    pub speed: f32,          // m/s, of the air leaving the fan
+    pub range: f32,          // m, no wind further away
+    pub falloff: f32,        // exponent of the decrease of the speed with the distance, 1 is linear
+}
+
+impl Fan {
+    // wind_at() of compute.wgsl
+    pub fn wind_at(&self, position: Vector3<f32>) -> Vector3<f32> {
+        let block = FanBlock::from(self);
+        let axis = Vector3::new(block.direction[0], block.direction[1], block.direction[2]);
+        let offset = position - Vector3::new(block.position[0], block.position[1], block.position[2]);
+        let distance = offset.magnitude();
+        let along = offset.dot(axis);
+        if distance >= block.range || along <= 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let cos_edge = block.direction[3];
+        let edge = smoothstep(cos_edge, cos_edge + (1.0 - cos_edge) * FAN_EDGE, along / distance);
+        axis * block.position[3] * edge * (1.0 - distance / block.range).powf(block.falloff)
+    }
+}
+
+// The wind fades out over that fraction of the cone towards its edge, must match compute.wgsl
+const FAN_EDGE: f32 = 0.2;
+
+// WGSL's smoothstep()
+fn smoothstep(low: f32, high: f32, x: f32) -> f32 {
+    let t = ((x - low) / (high - low)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FanBlock {
+    position: [f32; 4],  // w is the speed
+    direction: [f32; 4], // normalized, w is the cosine of the cone angle
+    range: f32,
+    falloff: f32,
+    _padding: [f32; 2],
+}
+
+impl From<&Fan> for FanBlock {
+    fn from(fan: &Fan) -> Self {
+        let [px, py, pz] = fan.position;
+        let direction = Vector3::from(fan.direction);
+        let [dx, dy, dz]: [f32; 3] = if direction.magnitude2() > 0.0 {
+            direction.normalize().into()
+        } else {
+            [0.0; 3]
+        };
+        Self {
+            position: [px, py, pz, fan.speed],
+            direction: [dx, dy, dz, fan.cone_angle.cos()],
+            range: fan.range.max(f32::EPSILON),
+            falloff: fan.falloff,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// A primitive the cloth collides with. The cloth and fabric friction coefficients apply to every
+/// collider, planes scale them with their own `friction`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Collider {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+    },
+    // Every point within `radius` of the segment from `a` to `b`
+    Capsule {
+        a: [f32; 3],
+        b: [f32; 3],
+        radius: f32,
+    },
+    Box {
+        center: [f32; 3],
+        half_extents: [f32; 3],
+        rotation: Quaternion<f32>,
+    },
+    // Points p with dot(p, normal) < offset are inside, `normal` is normalized on upload
+    Plane {
+        normal: [f32; 3],
+        offset: f32,
+        friction: f32,
+    },
+    // The signed distance volume of the simulation mapped onto a box, see set_sdf_volume()
+    Sdf {
+        center: [f32; 3],
+        half_extents: [f32; 3],
+        rotation: Quaternion<f32>,
+    },
+    // The triangle mesh of the simulation with the origin of its space at `center`, particles are
+    // kept `thickness` in front of its faces, see set_collision_mesh()
+    Mesh {
+        center: [f32; 3],
+        rotation: Quaternion<f32>,
+        thickness: f32,
+    },
+    // The terrain of the simulation over `half_extents` along x and z around `center`, axis
+    // aligned, see set_height_field()
+    HeightField {
+        center: [f32; 3],
+        half_extents: [f32; 2],
+    },
+}
+
+impl Collider {
+    // Moved rigidly by `transform`, for colliders following a node of the rig
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Collider {
+        let point = |[x, y, z]: [f32; 3]| -> [f32; 3] { (*transform * cgmath::vec4(x, y, z, 1.0)).truncate().into() };
+        let rotation = Quaternion::from(Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        ));
+        match *self {
+            Collider::Sphere { center, radius } => Collider::Sphere {
+                center: point(center),
+                radius,
+            },
+            Collider::Capsule { a, b, radius } => Collider::Capsule {
+                a: point(a),
+                b: point(b),
+                radius,
+            },
+            Collider::Box {
+                center,
+                half_extents,
+                rotation: box_rotation,
+            } => Collider::Box {
+                center: point(center),
+                half_extents,
+                rotation: rotation * box_rotation,
+            },
+            Collider::Sdf {
+                center,
+                half_extents,
+                rotation: volume_rotation,
+            } => Collider::Sdf {
+                center: point(center),
+                half_extents,
+                rotation: rotation * volume_rotation,
+            },
+            Collider::Mesh {
+                center,
+                rotation: mesh_rotation,
+                thickness,
+            } => Collider::Mesh {
+                center: point(center),
+                rotation: rotation * mesh_rotation,
+                thickness,
+            },
+            // Stays level, only its center follows
+            Collider::HeightField { center, half_extents } => Collider::HeightField {
+                center: point(center),
+                half_extents,
+            },
+            Collider::Plane { normal, offset, friction } => {
+                let normal = Vector3::from(normal).normalize();
+                let on_plane = point((normal * offset).into());
+                let normal = rotation.rotate_vector(normal);
+                Collider::Plane {
+                    normal: normal.into(),
+                    offset: normal.dot(on_plane.into()),
+                    friction,
+                }
+            }
+        }
+    }
+}
+
+// Must match the constants of contacts.wgsl
+#[repr(u32)]
+enum ColliderKind {
+    Sphere,
+    Capsule,
+    Box,
+    Plane,
+    Sdf,
+    Mesh,
+    HeightField,
+}
+
+// A collider as the shader sees it, the colliders buffer holds MAX_COLLIDERS of them
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColliderBlock {
+    kind: u32,
+    friction: f32,
+    radius: f32,
+    one_sided: u32, // 1 or 0, see set_one_sided_colliders()
+    a: [f32; 4],
+    b: [f32; 4],
+    rotation: [f32; 4],
+    motion: [f32; 4], // displacement since the previous step, w is unused
+    spin: [f32; 4],   // angular velocity in rad/s about the center, w is unused
+}
+
+impl ColliderBlock {
+    // How far the collider moved since `previous`, the same collider one step earlier. Only the
+    // translation of its first point is followed, rotations don't drag the cloth. Capsules follow
+    // the middle of their segment instead, so a limb turning about its joint still does.
+    fn displacement(&self, previous: &ColliderBlock) -> [f32; 3] {
+        if self.kind != previous.kind {
+            return [0.0; 3];
+        }
+        let [x, y, z, offset] = self.a;
+        if self.kind == ColliderKind::Plane as u32 {
+            let shift = offset - previous.a[3];
+            return [x * shift, y * shift, z * shift];
+        }
+        if self.kind == ColliderKind::Capsule as u32 {
+            let middle = |block: &ColliderBlock| [0, 1, 2].map(|i| 0.5 * (block.a[i] + block.b[i]));
+            let (now, before) = (middle(self), middle(previous));
+            return [0, 1, 2].map(|i| now[i] - before[i]);
+        }
+        [x - previous.a[0], y - previous.a[1], z - previous.a[2]]
+    }
+}
+
+impl From<&Collider> for ColliderBlock {
+    fn from(collider: &Collider) -> Self {
+        let point = |[x, y, z]: [f32; 3]| [x, y, z, 0.0];
+        let block = ColliderBlock {
+            friction: 1.0,
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            ..ColliderBlock::zeroed()
+        };
+        match *collider {
+            Collider::Sphere { center, radius } => ColliderBlock {
+                kind: ColliderKind::Sphere as u32,
+                radius,
+                a: point(center),
+                ..block
+            },
+            Collider::Capsule { a, b, radius } => ColliderBlock {
+                kind: ColliderKind::Capsule as u32,
+                radius,
+                a: point(a),
+                b: point(b),
+                ..block
+            },
+            Collider::Box {
+                center,
+                half_extents,
+                rotation,
+            } => ColliderBlock {
+                kind: ColliderKind::Box as u32,
+                a: point(center),
+                b: point(half_extents),
+                rotation: [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s],
+                ..block
+            },
+            Collider::Sdf {
+                center,
+                half_extents,
+                rotation,
+            } => ColliderBlock {
+                kind: ColliderKind::Sdf as u32,
+                a: point(center),
+                b: point(half_extents),
+                rotation: [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s],
+                ..block
+            },
+            Collider::Mesh {
+                center,
+                rotation,
+                thickness,
+            } => ColliderBlock {
+                kind: ColliderKind::Mesh as u32,
+                radius: thickness,
+                a: point(center),
+                rotation: [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s],
+                ..block
+            },
+            Collider::HeightField {
+                center,
+                half_extents: [x, z],
+            } => ColliderBlock {
+                kind: ColliderKind::HeightField as u32,
+                a: point(center),
+                b: [x, 0.0, z, 0.0],
+                ..block
+            },
+            Collider::Plane { normal, offset, friction } => {
+                let [x, y, z]: [f32; 3] = Vector3::from(normal).normalize().into();
+                ColliderBlock {
+                    kind: ColliderKind::Plane as u32,
+                    friction,
+                    a: [x, y, z, offset],
+                    ..block
+                }
+            }
+        }
+    }
+}
+
+// State of the sphere body, read and written by the contact passes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SphereBody {
+    center: [f32; 4],
+    velocity: [f32; 4], // w is the inverse mass in particle masses, 0.0 while the rig moves it
+    floor: f32,
+    damping: f32,
+    impulse: [i32; 3],
+    substep: u32, // of the step being solved, counted by the contact passes
+    _padding: [u32; 2],
+}
+
+/// Node the pinned particles of a cloth are attached to, with its world transform at rest.
+#[derive(Copy, Clone, Debug)]
+pub struct AnchorFrame {
+    pub node: NodeId,
+    pub world: Matrix4<f32>,
+}
+
+// Pins in world space, for a cloth stepped on its own with ClothSimulation::step()
+impl Default for AnchorFrame {
+    fn default() -> Self {
+        Self {
+            node: NodeId::ROOT,
+            world: Matrix4::identity(),
+        }
+    }
+}
+
+/// What a step hook gets to couple an external system to the solver.
+pub struct StepHandle<'a> {
+    pub step: u64,
+    // Edits from pre-step hooks are used by the step, post-step hooks only see what was used
+    pub params: &'a mut SimParams,
+    pub context: &'a dyn Gpu,
+    // GPU work recorded here runs right before or after the solver passes
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    // Latest state, before the step for pre-step hooks and after it for post-step hooks
+    pub particles: &'a wgpu::Buffer,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SolverMode {
+    Jacobi,
+    GaussSeidel,
+    Implicit, // the "stiff solver"
+}
+
+impl SolverMode {
+    pub const ALL: [SolverMode; 3] = [SolverMode::Jacobi, SolverMode::GaussSeidel, SolverMode::Implicit];
+
+    // Solver iterations per substep, conjugate gradient iterations for the implicit solver
+    pub fn default_iterations(self) -> u32 {
+        match self {
+            SolverMode::Jacobi => 8,
+            SolverMode::GaussSeidel => 4, // converges faster than Jacobi, so fewer are needed
+            SolverMode::Implicit => 24,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SolverMode::Jacobi => "Position based (Jacobi)",
+            SolverMode::GaussSeidel => "Position based (Gauss-Seidel)",
+            SolverMode::Implicit => "Stiff (implicit Euler)",
+        }
+    }
+}
+
+const WORKGROUP_SIZE: u32 = 128;
+pub const TIME_STEP: f32 = 0.016;
+// m, the width of the drawn particles, so they rest on the colliders instead of sinking halfway
+pub const THICKNESS: f32 = 0.006;
+const MAX_SPEED: f32 = 50.0; // m/s, far above anything a falling cloth reaches
+// m/s, a settled drape creeps slower than that
+pub const SLEEP_SPEED: f32 = 0.005;
+// Sphere surface, the coefficient of a contact is the fabric's times these
+pub const STATIC_FRICTION: f32 = 1.5;
+pub const DYNAMIC_FRICTION: f32 = 1.0;
+// Only used to weigh the cloth against the sphere body, the solver itself works with unit masses
+const PARTICLE_MASS: f32 = 2.0e-5; // kg, the tablecloth weighs about 1.3 kg, must match compute.wgsl
+const SPHERE_DAMPING: f32 = 2.0; // 1/s, stops the sphere from rolling away forever
+const RELAXATION: f32 = 1.0; // Scales the averaged Jacobi correction
+const IMPLICIT_SPRING_CONSTANT: f32 = 1.0e6; // Spring constant of a stiffness of 1.0, per unit of mass
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Horizontal, // rows along z
+    Vertical,   // rows along -y, row 0 at the top
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pins {
+    None,
+    LeftEdge,
+    TopCorners,
+    TopHooks(u32), // every that many columns along the top row, and the last one
+}
+
+/// Distance between neighboring columns and rows at rest.
+#[derive(Copy, Clone, Debug)]
+pub enum Spacing {
+    Uniform(f32),
+    // Finest at the center of the cloth and growing linearly towards its edges
+    Graded { center: f32, edge: f32 },
+}
+
+impl Spacing {
+    // Gap between the columns (or rows) `i - 1` and `i` out of `count`
+    fn gap(self, i: u32, count: u32) -> f32 {
+        match self {
+            Spacing::Uniform(spacing) => spacing,
+            Spacing::Graded { center, edge } => {
+                // 0 for a gap across the middle of the cloth, 1 for the outermost ones
+                let middle = (count - 1) as f32 / 2.0;
+                let distance = ((i as f32 - 0.5) - middle).abs() / (middle - 0.5).max(0.5);
+                center + (edge - center) * distance
+            }
+        }
+    }
+
+    // Rest coordinate of every column or row, centered on 0
+    fn axis(self, count: u32) -> Vec<f32> {
+        if count < 2 {
+            return vec![0.0; count as usize];
+        }
+        let mut coordinates = vec![0.0];
+        for i in 1..count {
+            coordinates.push(coordinates[i as usize - 1] + self.gap(i, count));
+        }
+        let half_extent = coordinates[count as usize - 1] / 2.0;
+        coordinates.iter().map(|coordinate| coordinate - half_extent).collect()
+    }
+}
+
+/// Rest state of a rectangular cloth, the grid is centered on `center`.
+#[derive(Copy, Clone, Debug)]
+pub struct ClothGrid {
+    pub rows: u32,
+    pub cols: u32,
+    pub spacing: Spacing,
+    pub center: [f32; 3],
+    pub orientation: Orientation,
+    pub pins: Pins,
+}
+
+impl ClothGrid {
+    fn is_pinned(&self, row: u32, col: u32) -> bool {
+        match self.pins {
+            Pins::None => false,
+            Pins::LeftEdge => col == 0,
+            Pins::TopCorners => row == 0 && (col == 0 || col == self.cols - 1),
+            Pins::TopHooks(every) => row == 0 && (col.is_multiple_of(every.max(1)) || col == self.cols - 1),
+        }
+    }
+
+
+    pub(crate) fn rest_state(&self) -> RestState {
+        let columns = self.spacing.axis(self.cols);
+        let rows = self.spacing.axis(self.rows);
+
+        let instances: Vec<Instance> = (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let (u, v) = (columns[col as usize], rows[row as usize]);
+                let [x, y, z] = self.center;
+                let inverse_mass = if self.is_pinned(row, col) { 0.0 } else { 1.0 };
+                let position = match self.orientation {
+                    Orientation::Horizontal => [x + u, y, z + v],
+                    Orientation::Vertical => [x + u, y - v, z],
+                };
+                Instance::at_rest(position, inverse_mass)
+            })
+            .collect();
+
+        let constraints = generate_constraints(self.rows, self.cols, &instances);
+
+        RestState { instances, constraints }
+    }
+}
+
+// Rest position of every particle on the fabric, scaled by the largest side of the grid so the
+// texture keeps its aspect
+fn generate_grid_uvs(grid: &ClothGrid) -> Vec<[f32; 2]> {
+    let columns = grid.spacing.axis(grid.cols);
+    let rows = grid.spacing.axis(grid.rows);
+    let span = |axis: &[f32]| axis[axis.len() - 1] - axis[0];
+    let size = span(&columns).max(span(&rows)).max(1e-6);
+    (0..grid.rows)
+        .flat_map(|row| (0..grid.cols).map(move |col| (row, col)))
+        .map(|(row, col)| [(columns[col as usize] - columns[0]) / size, (rows[row as usize] - rows[0]) / size])
+        .collect()
+}
+
+/// Particles of a cloth at rest, with the MAX_CONSTRAINTS constraint slots of every one of them.
+pub(crate) struct RestState {
+    pub(crate) instances: Vec<Instance>,
+    pub(crate) constraints: Vec<Constraint>,
+}
+
+/// Where the rest state of a cloth comes from.
+pub enum ClothSource {
+    Grid(ClothGrid),
+    Mesh(ClothMesh),
+}
+
+impl ClothSource {
+    fn rest_state(&self) -> RestState {
+        match self {
+            ClothSource::Grid(grid) => grid.rest_state(),
+            ClothSource::Mesh(mesh) => mesh.rest_state(),
+        }
+    }
+
+    fn uvs(&self) -> Vec<[f32; 2]> {
+        match self {
+            ClothSource::Grid(grid) => generate_grid_uvs(grid),
+            ClothSource::Mesh(mesh) => mesh.uvs(),
+        }
+    }
+}
+
+/// One cloth among those simulated together.
+pub struct ClothPiece {
+    pub source: ClothSource,
+    pub anchor_frame: AnchorFrame,
+    pub tear_map: TearMap,
+}
+
+/// Particles of a piece in the buffers shared by every piece.
+#[derive(Copy, Clone, Debug)]
+pub struct PieceRange {
+    pub offset: u32,
+    pub count: u32,
+    pub grid: Option<(u32, u32)>, // rows and columns, for pieces built from a ClothGrid
+}
+
+// Appends the rest states of the pieces one after the other, with the constraints and anchors
+// shifted to the particles of their piece. Pieces never share a constraint, so they stay
+// independent while being solved by the same dispatches. The particles of piece `i` use
+// material `i`.
+// The particles, constraints and pins of every piece of a cloth, one after the other
+struct GeneratedPieces {
+    instances: Vec<Instance>,
+    constraints: Vec<Constraint>,
+    anchors: Vec<Anchor>,
+    ranges: Vec<PieceRange>,
+}
+
+fn generate_pieces(pieces: &[ClothPiece]) -> GeneratedPieces {
+    let (mut instances, mut constraints, mut anchors, mut ranges) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (index, piece) in pieces.iter().enumerate() {
+        let RestState {
+            instances: mut piece_instances,
+            constraints: mut piece_constraints,
+        } = piece.source.rest_state();
+        piece.tear_map.apply(&piece_instances, &mut piece_constraints);
+        for instance in &mut piece_instances {
+            instance.speed[3] = index as f32;
+        }
+        let offset = instances.len() as u32;
+
+        constraints.extend(piece_constraints.into_iter().map(|constraint| Constraint {
+            neighbor: if constraint.neighbor == NO_NEIGHBOR {
+                NO_NEIGHBOR
+            } else {
+                constraint.neighbor + offset
+            },
+            ..constraint
+        }));
+        anchors.extend(
+            generate_anchors(&piece_instances, &piece.anchor_frame)
+                .into_iter()
+                .map(|anchor| Anchor {
+                    particle: anchor.particle + offset,
+                    group: index as u32,
+                    ..anchor
+                }),
+        );
+        ranges.push(PieceRange {
+            offset,
+            count: piece_instances.len() as u32,
+            grid: match &piece.source {
+                ClothSource::Grid(grid) => Some((grid.rows, grid.cols)),
+                ClothSource::Mesh(_) => None,
+            },
+        });
+        instances.extend(piece_instances);
+    }
+    GeneratedPieces {
+        instances,
+        constraints,
+        anchors,
+        ranges,
+    }
+}
+
+// UVs of the particles of every piece, in particle order like generate_pieces()
+fn generate_uvs(pieces: &[ClothPiece]) -> Vec<[f32; 2]> {
+    pieces.iter().flat_map(|piece| piece.source.uvs()).collect()
+}
+
+fn can_tear(constraints: &[Constraint]) -> bool {
+    constraints
+        .iter()
+        .any(|constraint| constraint.neighbor != NO_NEIGHBOR && constraint.tear_strain > 0.0)
+}
+
+fn generate_anchors(instances: &[Instance], frame: &AnchorFrame) -> Vec<Anchor> {
+    let inverse_world = frame.world.invert().expect("anchor frame is not invertible");
+    instances
+        .iter()
+        .enumerate()
+        .filter(|(_, instance)| instance.position[3] == 0.0)
+        .map(|(particle, instance)| {
+            let [x, y, z] = instance.position();
+            Anchor {
+                offset: (inverse_world * cgmath::vec4(x, y, z, 1.0)).into(),
+                particle: particle as u32,
+                node: frame.node.index() as u32,
+                group: 0,
+                broken: 0,
+            }
+        })
+        .collect()
+}
+
+// Fills the MAX_CONSTRAINTS slots of every particle with its structural, shear and bend neighbors,
+// the rest lengths are taken from the rest positions so any spacing works
+fn generate_constraints(rows: u32, cols: u32, instances: &[Instance]) -> Vec<Constraint> {
+    let neighbors: [(i32, i32, ConstraintKind); MAX_CONSTRAINTS] = [
+        (0, -1, ConstraintKind::Horizontal),
+        (0, 1, ConstraintKind::Horizontal),
+        (-1, 0, ConstraintKind::Vertical),
+        (1, 0, ConstraintKind::Vertical),
+        (-1, -1, ConstraintKind::Shear),
+        (-1, 1, ConstraintKind::Shear),
+        (1, -1, ConstraintKind::Shear),
+        (1, 1, ConstraintKind::Shear),
+        (0, -2, ConstraintKind::Bend),
+        (0, 2, ConstraintKind::Bend),
+        (-2, 0, ConstraintKind::Bend),
+        (2, 0, ConstraintKind::Bend),
+    ];
+
+    let rest_length = |a: usize, b: usize| {
+        let (a, b) = (instances[a].position(), instances[b].position());
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    };
+
+    (0..rows as i32)
+        .flat_map(|row| (0..cols as i32).map(move |col| (row, col)))
+        .flat_map(|(row, col)| {
+            neighbors.iter().map(move |&(d_row, d_col, kind)| {
+                let (n_row, n_col) = (row + d_row, col + d_col);
+                if n_row < 0 || n_row >= rows as i32 || n_col < 0 || n_col >= cols as i32 {
+                    Constraint::NONE
+                } else {
+                    let (index, neighbor) = ((row * cols as i32 + col) as usize, (n_row * cols as i32 + n_col) as usize);
+                    Constraint {
+                        neighbor: neighbor as u32,
+                        kind: kind as u32,
+                        rest_length: rest_length(index, neighbor),
+                        tear_strain: 0.0,
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Every GPU resource of the cloth pieces of a scene, all stepped by the same dispatches. All of
+/// its buffers are tracked, see `destroy`.
+pub struct ClothSimulation {
+    instance_bind_group_layout: wgpu::BindGroupLayout, // kept to rebuild the particle resources
+    particles: ParticleResources,
+    params_buffer: TrackedBuffer,
+    rig_buffer: TrackedBuffer,
+    guard_buffer: TrackedBuffer, // particles reset by the guard pass since the cloth was built
+    body_buffer: TrackedBuffer,  // the first collider as a rigid body, see start_sphere_body()
+    collider_buffer: TrackedBuffer,
+    guard_bind_group: wgpu::BindGroup,
+    contact_bind_group_layout: wgpu::BindGroupLayout, // kept to bind another volume or mesh
+    contact_bind_group: wgpu::BindGroup,
+    sdf_texture: wgpu::Texture, // a single voxel until set_sdf_volume()
+    height_field_texture: wgpu::Texture, // flat until set_height_field()
+    mesh_buffer: TrackedBuffer, // no nodes until set_collision_mesh()
+    anchor_pipeline: wgpu::ComputePipeline,
+    attachment_pipeline: wgpu::ComputePipeline,
+    tear_pipeline: wgpu::ComputePipeline,
+    elastic_anchor_pipeline: wgpu::ComputePipeline,
+    integrate_pipeline: wgpu::ComputePipeline,
+    solve_pipeline: wgpu::ComputePipeline,
+    near_solve_pipeline: wgpu::ComputePipeline, // past the iterations of the far pieces
+    far_pieces: Vec<bool>,
+    far_iterations: usize,
+    convergence: ConvergenceCheck, // replaces solve_pipeline while early termination is on
+    checksum: StateChecksum,
+    energy: EnergyMeter,
+    early_termination: bool,
+    self_collision: bool,
+    tearing: bool, // some constraint can tear
+    severed: bool, // some constraint was cut since the last reset
+    finalize_pipeline: wgpu::ComputePipeline,
+    guard_pipeline: wgpu::ComputePipeline,
+    collide_pipeline: wgpu::ComputePipeline,
+    placement_pipeline: wgpu::ComputePipeline,
+    body_pipeline: wgpu::ComputePipeline,
+    num_instances: u32,
+    num_anchors: u32,
+    colliders: Vec<ColliderBlock>, // as uploaded by the last update_rig()
+    collider_spins: Vec<[f32; 3]>, // see set_collider_spins()
+    one_sided_colliders: Vec<bool>, // see set_one_sided_colliders()
+    num_attachments: u32,
+    fans: Vec<Fan>, // as uploaded by the last update_rig()
+    pieces: Vec<PieceRange>,
+    rest_state: Vec<Instance>, // as built, for reset()
+    anchors: Vec<Anchor>,      // same, none of them broken
+    anchor_frames: Vec<AnchorFrame>, // of every piece, for the pins added by toggle_pins()
+    constraints: Vec<Constraint>, // as uploaded, for the CPU reference of the divergence monitor
+    materials: Vec<Material>,     // last ones given to update_materials(), same
+    probing: bool,
+    probe: Option<StageProbe>, // allocated by the first probed step
+    placement_pending: bool,   // the particles may start inside a collider, see encode_placement()
+    dispatches: u32,           // recorded by the latest encode_step()
+    own_colliders: Vec<Collider>, // of step(), a scene passes its own to update_rig() instead
+    tracker: BufferTracker, // counts the buffers of the cloth, shared with the scene it was built for
+}
+
+// Copies of a window of particles taken after every stage of the first substep of a frame, along
+// with what the CPU reference needs to redo the stages, see divergence.rs
+struct StageProbe {
+    buffer: TrackedBuffer,
+    inputs: StepInputs,
+}
+
+impl StageProbe {
+    fn capture(&self, encoder: &mut wgpu::CommandEncoder, latest: &wgpu::Buffer, stage: usize) {
+        let instance_size = std::mem::size_of::<Instance>() as wgpu::BufferAddress;
+        let window = &self.inputs.window;
+        let size = window.len() as wgpu::BufferAddress * instance_size;
+        encoder.copy_buffer_to_buffer(
+            latest,
+            u64::from(window.start) * instance_size,
+            &self.buffer,
+            stage as wgpu::BufferAddress * size,
+            size,
+        );
+    }
+}
+
+// `buffers` are the sphere body, the colliders, the triangle mesh and the contact markers,
+// `textures` the SDF volume and the terrain
+fn create_contact_bind_group(
+    context: &dyn Gpu,
+    layout: &wgpu::BindGroupLayout,
+    buffers: [&wgpu::Buffer; 4],
+    textures: [&wgpu::Texture; 2],
+) -> wgpu::BindGroup {
+    let [body_buffer, collider_buffer, mesh_buffer, contact_buffer] = buffers;
+    let [sdf_view, height_field_view] = textures.map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+    context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Contact Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: body_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: collider_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&sdf_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: mesh_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: contact_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&height_field_view),
+            },
+        ],
+    })
+}
+
+fn create_mesh_buffer(context: &dyn Gpu, tracker: &BufferTracker, data: &[[f32; 4]]) -> TrackedBuffer {
+    tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+        label: Some("Collision Mesh Buffer"),
+        contents: bytemuck::cast_slice(data),
+        usage: wgpu::BufferUsages::STORAGE,
+    })
+}
+
+fn begin_probed_pass(encoder: &mut wgpu::CommandEncoder) -> wgpu::ComputePass<'_> {
+    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("Probed Compute Pass"),
+        timestamp_writes: None,
+    })
+}
+
+// The ping group reads the first buffer and writes the second one, the pong group the reverse.
+// `shared` are the sim params, constraint, rig, anchor and material buffers, in binding order.
+fn create_instance_bind_groups(
+    context: &dyn Gpu,
+    layout: &wgpu::BindGroupLayout,
+    instance_buffer: &[TrackedBuffer; 2],
+    shared: [&wgpu::Buffer; 5],
+) -> [wgpu::BindGroup; 2] {
+    let create_bind_group = |label: &str, read: &wgpu::Buffer, write: &wgpu::Buffer| {
+        let entries: Vec<_> = [read, write]
+            .into_iter()
+            .chain(shared)
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &entries,
+        })
+    };
+    [
+        create_bind_group("Bind Group Ping", &instance_buffer[0], &instance_buffer[1]),
+        create_bind_group("Bind Group Pong", &instance_buffer[1], &instance_buffer[0]),
+    ]
+}
+
+// Everything sized by the particles, rebuilt as a whole by resize_particles()
+struct ParticleResources {
+    instance_buffer: [TrackedBuffer; 2],
+    previous_buffer: TrackedBuffer, // copy of the latest state taken before each step
+    constraint_buffer: TrackedBuffer, // rewritten by reset() to mend the torn constraints
+    anchor_buffer: TrackedBuffer, // rewritten by reset() to mend the broken pins
+    material_buffer: TrackedBuffer,
+    attribute_buffer: TrackedBuffer, // a value per particle for the color ramp, zero until set
+    uv_buffer: TrackedBuffer,        // where every particle is on the albedo texture
+    tint_buffer: TrackedBuffer,      // a color per particle over the others, clear until set
+    contact_buffer: TrackedBuffer,   // a contact marker per particle, written by the contact passes
+    bind_group: [wgpu::BindGroup; 2],
+    edge_bind_group: wgpu::BindGroup, // for the spring overlay
+    implicit_solver: ImplicitSolver,
+    gauss_seidel_solver: GaussSeidelSolver,
+    self_shadow: SelfShadow,
+    self_collision: SelfCollision,
+    strain: Strain,
+    kinematics: Kinematics,
+}
+
+impl ParticleResources {
+    // `uniforms` are the sim params and rig buffers, shared by every size
+    fn new(
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: [&wgpu::Buffer; 2],
+        generated: &GeneratedPieces,
+    ) -> Self {
+        let [params_buffer, rig_buffer] = uniforms;
+        let (instances, constraints, anchors) = (&generated.instances, &generated.constraints, &generated.anchors);
+        let constraint_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Constraint Buffer"),
+            contents: bytemuck::cast_slice(constraints),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Never empty, zero sized bindings aren't allowed
+        let empty_anchor = Anchor::zeroed();
+        let anchor_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Anchor Buffer"),
+            contents: if anchors.is_empty() {
+                bytemuck::bytes_of(&empty_anchor)
+            } else {
+                bytemuck::cast_slice(anchors)
+            },
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Filled by update_materials()
+        let material_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Material Buffer"),
+            size: (generated.ranges.len().max(1) * std::mem::size_of::<MaterialBlock>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let instance_usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::VERTEX
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST;
+        let instance_buffer = [
+            tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+                label: Some("Instance Buffer Ping"),
+                contents: bytemuck::cast_slice(instances),
+                usage: instance_usage,
+            }),
+            tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+                label: Some("Instance Buffer Pong"),
+                contents: bytemuck::cast_slice(instances),
+                usage: instance_usage,
+            }),
+        ];
+
+        let previous_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Previous Instance Buffer"),
+            contents: bytemuck::cast_slice(instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let attribute_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Attribute Buffer"),
+            size: (instances.len().max(1) * std::mem::size_of::<f32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Filled by write_uvs()
+        let uv_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("UV Buffer"),
+            size: (instances.len().max(1) * std::mem::size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let tint_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Tint Buffer"),
+            size: (instances.len().max(1) * std::mem::size_of::<[u8; 4]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let contact_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Contact Marker Buffer"),
+            size: (instances.len().max(1) * CONTACT_MARKER_SIZE) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = create_instance_bind_groups(
+            context,
+            layout,
+            &instance_buffer,
+            [params_buffer, &constraint_buffer, rig_buffer, &anchor_buffer, &material_buffer],
+        );
+
+        let edge_bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Edge Bind Group"),
+            layout: &context.device().create_bind_group_layout(&edge_bind_group_layout_desc()),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: instance_buffer[0].as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: constraint_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            implicit_solver: ImplicitSolver::new(context, tracker, layout, instances.len() as u32, WORKGROUP_SIZE),
+            gauss_seidel_solver: GaussSeidelSolver::new(context, tracker, layout, constraints, WORKGROUP_SIZE),
+            self_shadow: SelfShadow::new(context, tracker, layout, instances.len() as u32, WORKGROUP_SIZE),
+            self_collision: SelfCollision::new(context, tracker, layout, instances, WORKGROUP_SIZE),
+            strain: Strain::new(context, layout, &attribute_buffer, WORKGROUP_SIZE),
+            kinematics: Kinematics::new(
+                context,
+                tracker,
+                layout,
+                &attribute_buffer,
+                instances.len() as u32,
+                WORKGROUP_SIZE,
+            ),
+            instance_buffer,
+            previous_buffer,
+            constraint_buffer,
+            anchor_buffer,
+            material_buffer,
+            attribute_buffer,
+            uv_buffer,
+            tint_buffer,
+            contact_buffer,
+            bind_group,
+            edge_bind_group,
+        }
+    }
+
+    fn write_uvs(&self, context: &dyn Gpu, pieces: &[ClothPiece]) {
+        context.queue().write_buffer(&self.uv_buffer, 0, bytemuck::cast_slice(&generate_uvs(pieces)));
+    }
+
+    // Uploads a new set of pins, into a larger buffer bound again when they don't fit
+    fn write_anchors(
+        &mut self,
+        context: &dyn Gpu,
+        tracker: &BufferTracker,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: [&wgpu::Buffer; 2],
+        anchors: &[Anchor],
+    ) {
+        let size = std::mem::size_of_val(anchors) as wgpu::BufferAddress;
+        if size > self.anchor_buffer.size() {
+            let [params_buffer, rig_buffer] = uniforms;
+            self.anchor_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+                label: Some("Anchor Buffer"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.bind_group = create_instance_bind_groups(
+                context,
+                layout,
+                &self.instance_buffer,
+                [params_buffer, &self.constraint_buffer, rig_buffer, &self.anchor_buffer, &self.material_buffer],
+            );
+        }
+        if !anchors.is_empty() {
+            context.queue().write_buffer(&self.anchor_buffer, 0, bytemuck::cast_slice(anchors));
+        }
+    }
+}
+
+impl ClothSimulation {
+    pub fn new(context: &dyn Gpu, pieces: &[ClothPiece]) -> Self {
+        Self::with_tracker(context, BufferTracker::default(), pieces)
+    }
+
+    // Same, counting its buffers with those of the other owners of `tracker`
+    pub fn with_tracker(context: &dyn Gpu, tracker: BufferTracker, pieces: &[ClothPiece]) -> Self {
+        let generated = generate_pieces(pieces);
+        let num_instances = generated.instances.len() as u32;
+
+        // Filled by step() before every frame
+        let params_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Sim Params Buffer"),
+            size: std::mem::size_of::<SimParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Filled by update_rig()
+        let rig_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Rig Buffer"),
+            size: std::mem::size_of::<RigUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let compute_shader = create_compute_module(context, "Compute Shader", "", WORKGROUP_SIZE);
+
+        let storage_entry = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniform_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let instance_bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Bind Group Layout"),
+            entries: &[
+                storage_entry(0, false),
+                storage_entry(1, false),
+                // Uniform buffer for the sim params
+                uniform_entry(2),
+                // Constraint slots of every particle, torn ones are written over
+                storage_entry(3, false),
+                // Node transforms and attachment targets, a uniform to leave storage slots to the solvers
+                uniform_entry(4),
+                // Written by the elastic pins when they break
+                storage_entry(5, false),
+                // One material per piece, indexed with the speed.w of the particles
+                storage_entry(6, true),
+            ],
+        });
+
+        let compute_pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Pipeline Layout"),
+            bind_group_layouts: &[&instance_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // One pipeline per entry point, they all share the same bind group layout
+        let create_compute_pipeline = |label: &str, entry_point: &str| {
+            context
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&compute_pipeline_layout),
+                module: &compute_shader,
+                entry_point,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+                })
+        };
+
+        let particles = ParticleResources::new(
+            context,
+            &tracker,
+            &instance_bind_group_layout,
+            [&params_buffer, &rig_buffer],
+            &generated,
+        );
+        particles.write_uvs(context, pieces);
+        let GeneratedPieces {
+            instances,
+            constraints,
+            anchors,
+            ranges,
+        } = generated;
+
+        // The guard pass has its own group for the counter, the shared one has no storage slot left
+        // for the implicit solver
+        let guard_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Guard Count Buffer"),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let guard_bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Guard Bind Group Layout"),
+            entries: &[storage_entry(0, false)],
+        });
+        let guard_bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Guard Bind Group"),
+            layout: &guard_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: guard_buffer.as_entire_binding(),
+            }],
+        });
+        let guard_pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Guard Pipeline Layout"),
+            bind_group_layouts: &[&instance_bind_group_layout, &guard_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let guard_pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Guard Pipeline"),
+                layout: Some(&guard_pipeline_layout),
+                module: &create_compute_module(context, "Guard Shader", include_str!("guard.wgsl"), WORKGROUP_SIZE),
+                entry_point: "guard",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        // Collisions run in place after every solver, with the colliders and the sphere body in
+        // their own group for the same reason
+        let body_buffer = tracker.create_buffer_init(context, &wgpu::util::BufferInitDescriptor {
+            label: Some("Sphere Body Buffer"),
+            contents: bytemuck::bytes_of(&SphereBody::zeroed()),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        // Filled by update_rig()
+        let collider_buffer = tracker.create_buffer(context, &wgpu::BufferDescriptor {
+            label: Some("Collider Buffer"),
+            size: (MAX_COLLIDERS * std::mem::size_of::<ColliderBlock>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let contact_bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Contact Bind Group Layout"),
+            entries: &[
+                storage_entry(0, false),
+                // A uniform, the markers take the last storage slot of the stage
+                uniform_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_entry(3, true),
+                storage_entry(4, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        // Replaced by set_sdf_volume(), set_collision_mesh() and set_height_field()
+        let sdf_texture = sdf::create_empty_texture(context);
+        let height_field_texture = heightfield::create_empty_texture(context);
+        let mesh_buffer = create_mesh_buffer(context, &tracker, &bvh::empty_vec4s());
+        let contact_bind_group = create_contact_bind_group(
+            context,
+            &contact_bind_group_layout,
+            [&body_buffer, &collider_buffer, &mesh_buffer, &particles.contact_buffer],
+            [&sdf_texture, &height_field_texture],
+        );
+        let contact_pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Contact Pipeline Layout"),
+            bind_group_layouts: &[&instance_bind_group_layout, &contact_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let contact_shader = create_compute_module(context, "Contact Shader", include_str!("contacts.wgsl"), WORKGROUP_SIZE);
+        let create_contact_pipeline = |label: &str, entry_point: &str| {
+            context
+                .device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&contact_pipeline_layout),
+                    module: &contact_shader,
+                    entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: None,
+                })
+        };
+
+        Self {
+            anchor_pipeline: create_compute_pipeline("Apply Anchors Pipeline", "apply_anchors"),
+            attachment_pipeline: create_compute_pipeline("Apply Attachments Pipeline", "apply_attachments"),
+            tear_pipeline: create_compute_pipeline("Tear Constraints Pipeline", "tear_constraints"),
+            elastic_anchor_pipeline: create_compute_pipeline("Elastic Anchors Pipeline", "pull_elastic_anchors"),
+            integrate_pipeline: create_compute_pipeline("Integrate Pipeline", "integrate"),
+            solve_pipeline: create_compute_pipeline("Solve Constraints Pipeline", "solve_constraints"),
+            near_solve_pipeline: create_compute_pipeline("Solve Near Constraints Pipeline", "solve_near_constraints"),
+            far_pieces: Vec::new(),
+            far_iterations: 0,
+            convergence: ConvergenceCheck::new(context, &tracker, &instance_bind_group_layout, WORKGROUP_SIZE),
+            checksum: StateChecksum::new(context, &tracker, &instance_bind_group_layout, WORKGROUP_SIZE),
+            energy: EnergyMeter::new(context, &tracker, &instance_bind_group_layout, WORKGROUP_SIZE),
+            early_termination: false,
+            self_collision: false,
+            tearing: can_tear(&constraints),
+            severed: false,
+            finalize_pipeline: create_compute_pipeline("Finalize Pipeline", "finalize"),
+            guard_pipeline,
+            collide_pipeline: create_contact_pipeline("Collide Pipeline", "collide"),
+            placement_pipeline: create_contact_pipeline("Placement Pipeline", "place_outside_colliders"),
+            body_pipeline: create_contact_pipeline("Sphere Body Pipeline", "integrate_body"),
+            instance_bind_group_layout,
+            particles,
+            params_buffer,
+            rig_buffer,
+            guard_buffer,
+            body_buffer,
+            collider_buffer,
+            guard_bind_group,
+            contact_bind_group_layout,
+            contact_bind_group,
+            sdf_texture,
+            height_field_texture,
+            mesh_buffer,
+            num_instances,
+            num_anchors: anchors.len() as u32,
+            colliders: Vec::new(),
+            collider_spins: Vec::new(),
+            one_sided_colliders: Vec::new(),
+            num_attachments: 0,
+            fans: Vec::new(),
+            pieces: ranges,
+            rest_state: instances,
+            anchors,
+            anchor_frames: pieces.iter().map(|piece| piece.anchor_frame).collect(),
+            constraints,
+            materials: Vec::new(),
+            probing: false,
+            probe: None,
+            placement_pending: true,
+            dispatches: 0,
+            own_colliders: Vec::new(),
+            tracker,
+        }
+    }
+
+    // Advances the cloth by `dt` seconds and submits the step right away, for a cloth used
+    // without a scene. The colliders are those of add_collider(), the pins hold their anchor
+    // frame at rest, and the pieces are cotton until update_materials() says otherwise. `dt` is
+    // split into substeps no longer than TIME_STEP, of the default iterations of the Jacobi solver.
+    pub fn step(&mut self, context: &dyn Gpu, dt: f32) {
+        if self.materials.is_empty() {
+            self.update_materials(context, &vec![Preset::Cotton.material(); self.pieces.len()]);
+        }
+        let num_nodes = self.anchor_frames.iter().map(|frame| frame.node.index() + 1).max().unwrap_or(0);
+        let mut nodes = vec![Matrix4::identity(); num_nodes];
+        for frame in &self.anchor_frames {
+            nodes[frame.node.index()] = frame.world;
+        }
+        let colliders = std::mem::take(&mut self.own_colliders);
+        self.update_rig(context, &nodes, &colliders, &[], &[], &[]);
+        self.own_colliders = colliders;
+
+        let substeps = (dt / TIME_STEP).ceil().max(1.0) as u32;
+        let solver_mode = SolverMode::Jacobi;
+        let mut params = SimParams::new(substeps, solver_mode.default_iterations());
+        params.scale_time(dt / TIME_STEP);
+        let mut encoder = context.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Step Encoder"),
+        });
+        self.encode_step(context, &mut encoder, &params, solver_mode);
+        context.queue().submit(Some(encoder.finish()));
+    }
+
+    // Latest particle positions, waiting for the GPU. Only meant for a cloth used without a
+    // scene, the app reads its particles back asynchronously.
+    pub fn positions(&self, context: &dyn Gpu) -> Result<Vec<[f32; 3]>, wgpu::BufferAsyncError> {
+        Ok(self.latest_instances(context)?.iter().map(Instance::position).collect())
+    }
+
+    fn latest_instances(&self, context: &dyn Gpu) -> Result<Vec<Instance>, wgpu::BufferAsyncError> {
+        let mut encoder = context.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        let staging_buffer = copy_to_staging(context, &mut encoder, self.instance_buffer());
+        context.queue().submit(Some(encoder.finish()));
+        Ok(bytemuck::pod_collect_to_vec(&map_staging(context, &staging_buffer)?))
+    }
+
+    // A collider of step(), in world space, with its index for collider_mut(). None once
+    // MAX_COLLIDERS are in.
+    pub fn add_collider(&mut self, collider: Collider) -> Option<usize> {
+        if self.own_colliders.len() >= MAX_COLLIDERS {
+            return None;
+        }
+        self.own_colliders.push(collider);
+        Some(self.own_colliders.len() - 1)
+    }
+
+    // Moved by the next step(), which drags the cloth along like a scene's animated colliders
+    pub fn collider_mut(&mut self, index: usize) -> Option<&mut Collider> {
+        self.own_colliders.get_mut(index)
+    }
+
+    // Pins `particle` where it is now, or frees it, see toggle_pins(). Waits for the GPU to know
+    // where the particle is, and leaves the pins as they were if it could not read it back.
+    pub fn pin(&mut self, context: &dyn Gpu, particle: u32, pinned: bool) -> Result<(), wgpu::BufferAsyncError> {
+        if self.pinned_particles().any(|pinned_particle| pinned_particle == particle) == pinned {
+            return Ok(());
+        }
+        let latest = self.latest_instances(context)?;
+        self.toggle_pins(context, &[particle], &latest);
+        Ok(())
+    }
+
+    pub fn tracker(&self) -> &BufferTracker {
+        &self.tracker
+    }
+
+    pub fn num_instances(&self) -> u32 {
+        self.num_instances
+    }
+
+    // Constraints between two particles as uploaded, torn ones included, each stored at both ends
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.iter().filter(|constraint| constraint.neighbor != NO_NEIGHBOR).count() / 2
+    }
+
+    // Used slots of `particle`, as uploaded: torn constraints still show
+    pub fn particle_constraints(&self, particle: u32) -> impl Iterator<Item = &Constraint> {
+        let start = particle as usize * MAX_CONSTRAINTS;
+        self.constraints
+            .get(start..start + MAX_CONSTRAINTS)
+            .unwrap_or_default()
+            .iter()
+            .filter(|constraint| constraint.neighbor != NO_NEIGHBOR)
+    }
+
+    // Compute dispatches the latest step recorded, the probed substep counted like the others
+    pub fn dispatches(&self) -> u32 {
+        self.dispatches
+    }
+
+    pub fn pieces(&self) -> &[PieceRange] {
+        &self.pieces
+    }
+
+    pub fn num_colors(&self) -> usize {
+        self.particles.gauss_seidel_solver.num_colors()
+    }
+
+    // Latest state, to be drawn as instances
+    pub fn instance_buffer(&self) -> &wgpu::Buffer {
+        &self.particles.instance_buffer[0]
+    }
+
+    // Latest state and constraints, see edge_bind_group_layout_desc()
+    pub fn edge_bind_group(&self) -> &wgpu::BindGroup {
+        &self.particles.edge_bind_group
+    }
+
+    // State before the last step, for rendering in between the two
+    pub fn previous_instance_buffer(&self) -> &wgpu::Buffer {
+        &self.particles.previous_buffer
+    }
+
+    // Fabric of every piece, in the same order as the pieces
+    pub fn update_materials(&mut self, context: &dyn Gpu, materials: &[Material]) {
+        assert_eq!(materials.len(), self.pieces.len(), "every piece needs a material");
+        let blocks: Vec<MaterialBlock> = materials
+            .iter()
+            .enumerate()
+            .map(|(piece, material)| MaterialBlock {
+                far: u32::from(self.far_pieces.get(piece).copied().unwrap_or(false)),
+                ..MaterialBlock::from(material)
+            })
+            .collect();
+        context.queue().write_buffer(&self.particles.material_buffer, 0, bytemuck::cast_slice(&blocks));
+        self.materials = materials.to_vec();
+    }
+
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    // Copies a window of particles after every stage of the first substep of the next Jacobi
+    // steps, the probe buffer is released once probing stops
+    pub fn set_probing(&mut self, probing: bool) {
+        self.probing = probing;
+        if !probing {
+            self.probe = None;
+        }
+    }
+
+    // Stage copies of the last probed step, see divergence.rs for their layout
+    pub fn probe_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.probe.as_ref().map(|probe| &*probe.buffer)
+    }
+
+    pub fn probe_inputs(&self) -> Option<&StepInputs> {
+        self.probe.as_ref().map(|probe| &probe.inputs)
+    }
+
+    // Rest position of a particle, as it was built
+    pub fn rest_position(&self, particle: u32) -> [f32; 3] {
+        self.rest_state[particle as usize].position()
+    }
+
+    // Rebuilds everything sized by the particles for a new set of pieces, e.g. after remeshing, a
+    // change of grid resolution or a piece added to the scene. The particles both sets have in
+    // common keep their latest state, masses and materials included, the others start from their
+    // new rest state. The render passes fetch the buffers every frame, so nothing refers to the
+    // old ones once they are dropped.
+    pub fn resize_particles(&mut self, context: &dyn Gpu, pieces: &[ClothPiece]) {
+        let generated = generate_pieces(pieces);
+        let particles = ParticleResources::new(
+            context,
+            &self.tracker,
+            &self.instance_bind_group_layout,
+            [&self.params_buffer, &self.rig_buffer],
+            &generated,
+        );
+        particles.write_uvs(context, pieces);
+        let GeneratedPieces {
+            instances,
+            constraints,
+            anchors,
+            ranges,
+        } = generated;
+
+        let kept = instances.len().min(self.num_instances as usize) * std::mem::size_of::<Instance>();
+        if kept > 0 {
+            let mut encoder = context.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Resize Encoder"),
+            });
+            let latest = &self.particles.instance_buffer[0];
+            for buffer in particles.instance_buffer.iter().chain([&particles.previous_buffer]) {
+                encoder.copy_buffer_to_buffer(latest, 0, buffer, 0, kept as wgpu::BufferAddress);
+            }
+            context.queue().submit(Some(encoder.finish()));
+        }
+
+        self.particles = particles;
+        // The markers are sized by the particles too
+        self.contact_bind_group = create_contact_bind_group(
+            context,
+            &self.contact_bind_group_layout,
+            [&self.body_buffer, &self.collider_buffer, &self.mesh_buffer, &self.particles.contact_buffer],
+            [&self.sdf_texture, &self.height_field_texture],
+        );
+        self.num_instances = instances.len() as u32;
+        self.num_anchors = anchors.len() as u32;
+        self.pieces = ranges;
+        self.rest_state = instances;
+        self.anchors = anchors;
+        self.anchor_frames = pieces.iter().map(|piece| piece.anchor_frame).collect();
+        self.tearing = can_tear(&constraints);
+        self.severed = false;
+        self.constraints = constraints;
+        self.probe = None; // the window moves with the particle count
+        self.placement_pending = true;
+    }
+
+    // Puts every particle back where it was built, at rest, with the broken pins and the torn
+    // or cut constraints mended. Both
+    // ping-pong buffers and the previous state are rewritten, so the next step and the
+    // interpolation start from there.
+    pub fn reset(&mut self, context: &dyn Gpu) {
+        self.write_state(context, &self.rest_state);
+        self.severed = false;
+        self.placement_pending = true;
+        context.queue().write_buffer(&self.guard_buffer, 0, bytemuck::bytes_of(&0u32));
+        self.convergence.clear(context);
+    }
+
+    // Same as reset(), with the free particles moved by `offset` from their rest position
+    pub fn reset_jittered(&mut self, context: &dyn Gpu, offset: impl Fn(u32) -> [f32; 3]) {
+        let state: Vec<Instance> = self
+            .rest_state
+            .iter()
+            .enumerate()
+            .map(|(particle, rest)| {
+                if rest.position[3] == 0.0 {
+                    return *rest;
+                }
+                let [x, y, z] = rest.position();
+                let [dx, dy, dz] = offset(particle as u32);
+                let position = [x + dx, y + dy, z + dz, rest.position[3]];
+                Instance {
+                    position,
+                    previous: position,
+                    ..*rest
+                }
+            })
+            .collect();
+        self.write_state(context, &state);
+        self.severed = false;
+        context.queue().write_buffer(&self.guard_buffer, 0, bytemuck::bytes_of(&0u32));
+        self.convergence.clear(context);
+        self.placement_pending = true;
+    }
+
+    fn write_state(&self, context: &dyn Gpu, state: &[Instance]) {
+        if self.tearing || self.severed {
+            context
+                .queue()
+                .write_buffer(&self.particles.constraint_buffer, 0, bytemuck::cast_slice(&self.constraints));
+        }
+        if !self.anchors.is_empty() {
+            context
+                .queue()
+                .write_buffer(&self.particles.anchor_buffer, 0, bytemuck::cast_slice(&self.anchors));
+        }
+        for buffer in self.particles.instance_buffer.iter().chain([&self.particles.previous_buffer]) {
+            context.queue().write_buffer(buffer, 0, bytemuck::cast_slice(state));
+        }
+    }
+
+    // Values drawn through the color ramp, in particle order. Missing values are zero, extra
+    // ones are ignored.
+    pub fn set_attribute(&self, context: &dyn Gpu, values: &[f32]) {
+        let mut values = values[..values.len().min(self.num_instances as usize)].to_vec();
+        values.resize(self.num_instances as usize, 0.0);
+        context.queue().write_buffer(&self.particles.attribute_buffer, 0, bytemuck::cast_slice(&values));
+    }
+
+    // RGBA8 colors laid over the others by their alpha, in particle order. Missing ones are
+    // clear, extra ones are ignored.
+    pub fn set_tints(&self, context: &dyn Gpu, tints: &[[u8; 4]]) {
+        let mut tints = tints[..tints.len().min(self.num_instances as usize)].to_vec();
+        tints.resize(self.num_instances as usize, [0; 4]);
+        context.queue().write_buffer(&self.particles.tint_buffer, 0, bytemuck::cast_slice(&tints));
+    }
+
+    // Particles held by a pin, broken or not
+    pub fn pinned_particles(&self) -> impl Iterator<Item = u32> + '_ {
+        self.anchors.iter().map(|anchor| anchor.particle)
+    }
+
+    // Pins the free particles among `particles` where `latest` has them, to the anchor node of
+    // their piece, and frees the pinned ones. The rest state changes along, so the pins stay
+    // through a reset until the next rebuild. The node is taken at rest, pins added while an
+    // animation moved it away jump with it.
+    pub fn toggle_pins(&mut self, context: &dyn Gpu, particles: &[u32], latest: &[Instance]) {
+        for &particle in particles {
+            let (Some(instance), Some(piece)) = (
+                latest.get(particle as usize),
+                self.pieces.iter().position(|range| (range.offset..range.offset + range.count).contains(&particle)),
+            ) else {
+                continue;
+            };
+            let inverse_mass = if let Some(index) = self.anchors.iter().position(|anchor| anchor.particle == particle) {
+                self.anchors.remove(index);
+                1.0
+            } else {
+                let frame = &self.anchor_frames[piece];
+                let [x, y, z] = instance.position();
+                let inverse_world = frame.world.invert().expect("anchor frame is not invertible");
+                self.anchors.push(Anchor {
+                    offset: (inverse_world * cgmath::vec4(x, y, z, 1.0)).into(),
+                    particle,
+                    node: frame.node.index() as u32,
+                    group: piece as u32,
+                    broken: 0,
+                });
+                0.0
+            };
+            self.rest_state[particle as usize].position[3] = inverse_mass;
+            // Only the inverse mass in the w of the position, the particles keep moving
+            let offset = particle as usize * std::mem::size_of::<Instance>() + 3 * std::mem::size_of::<f32>();
+            for buffer in &self.particles.instance_buffer {
+                context
+                    .queue()
+                    .write_buffer(buffer, offset as wgpu::BufferAddress, bytemuck::bytes_of(&inverse_mass));
+            }
+        }
+        self.num_anchors = self.anchors.len() as u32;
+        let uniforms = [&*self.params_buffer, &*self.rig_buffer];
+        self.particles
+            .write_anchors(context, &self.tracker, &self.instance_bind_group_layout, uniforms, &self.anchors);
+    }
+
+    // Adds up to `strength` m/s away from `center` to the free particles within `radius` of it,
+    // fading out linearly to the edge, and wakes them. `latest` must be a copy of the latest state,
+    // it's written back with the new speeds. A particle right at the center has no way out.
+    pub fn poke(&self, context: &dyn Gpu, latest: &[Instance], center: [f32; 3], radius: f32, strength: f32) {
+        let center = Vector3::from(center);
+        for (particle, instance) in latest.iter().enumerate() {
+            let offset = Vector3::from(instance.position()) - center;
+            let distance = offset.magnitude();
+            if instance.inverse_mass() == 0.0 || distance >= radius || distance == 0.0 {
+                continue;
+            }
+            let impulse = offset * (strength * (1.0 - distance / radius) / distance);
+            let mut poked = *instance;
+            poked.speed[0] += impulse.x;
+            poked.speed[1] += impulse.y;
+            poked.speed[2] += impulse.z;
+            poked.previous[3] = 0.0; // awake
+            let offset = (particle * std::mem::size_of::<Instance>()) as wgpu::BufferAddress;
+            context
+                .queue()
+                .write_buffer(&self.particles.instance_buffer[0], offset, bytemuck::bytes_of(&poked));
+        }
+    }
+
+    // Removes the constraints between the pairs of particles at both ends, as if they had torn, until
+    // the next reset mends them
+    pub fn cut(&mut self, context: &dyn Gpu, pairs: &[(u32, u32)]) {
+        let slot_of = |particle: u32, neighbor: u32| {
+            let start = particle as usize * MAX_CONSTRAINTS;
+            (start..start + MAX_CONSTRAINTS)
+                .find(|&slot| self.constraints.get(slot).is_some_and(|constraint| constraint.neighbor == neighbor))
+        };
+        for &(a, b) in pairs {
+            for slot in [slot_of(a, b), slot_of(b, a)].into_iter().flatten() {
+                // The neighbor comes first in a Constraint
+                let offset = (slot * std::mem::size_of::<Constraint>()) as wgpu::BufferAddress;
+                context
+                    .queue()
+                    .write_buffer(&self.particles.constraint_buffer, offset, bytemuck::bytes_of(&NO_NEIGHBOR));
+            }
+        }
+        self.severed |= !pairs.is_empty();
+    }
+
+    // The volume Collider::Sdf colliders sample, a single one for every collider of that kind
+    pub fn set_sdf_volume(&mut self, context: &dyn Gpu, volume: &SdfVolume) {
+        let texture = volume.create_texture(context);
+        self.contact_bind_group = create_contact_bind_group(
+            context,
+            &self.contact_bind_group_layout,
+            [&self.body_buffer, &self.collider_buffer, &self.mesh_buffer, &self.particles.contact_buffer],
+            [&texture, &self.height_field_texture],
+        );
+        std::mem::replace(&mut self.sdf_texture, texture).destroy();
+    }
+
+    // The terrain Collider::HeightField colliders sample, a single one for every collider of that kind
+    pub fn set_height_field(&mut self, context: &dyn Gpu, field: &HeightField) {
+        let texture = field.create_texture(context);
+        self.contact_bind_group = create_contact_bind_group(
+            context,
+            &self.contact_bind_group_layout,
+            [&self.body_buffer, &self.collider_buffer, &self.mesh_buffer, &self.particles.contact_buffer],
+            [&self.sdf_texture, &texture],
+        );
+        std::mem::replace(&mut self.height_field_texture, texture).destroy();
+    }
+
+    // The mesh Collider::Mesh colliders collide with, a single one for every collider of that kind
+    pub fn set_collision_mesh(&mut self, context: &dyn Gpu, bvh: &TriangleBvh) {
+        self.mesh_buffer = create_mesh_buffer(context, &self.tracker, &bvh.to_vec4s());
+        self.contact_bind_group = create_contact_bind_group(
+            context,
+            &self.contact_bind_group_layout,
+            [&self.body_buffer, &self.collider_buffer, &self.mesh_buffer, &self.particles.contact_buffer],
+            [&self.sdf_texture, &self.height_field_texture],
+        );
+    }
+
+    pub fn attribute_buffer(&self) -> &wgpu::Buffer {
+        &self.particles.attribute_buffer
+    }
+
+    // Two floats per particle, see albedo::uv_vertex_desc()
+    pub fn uv_buffer(&self) -> &wgpu::Buffer {
+        &self.particles.uv_buffer
+    }
+
+    pub fn tint_buffer(&self) -> &wgpu::Buffer {
+        &self.particles.tint_buffer
+    }
+
+    // Where every particle touched a collider in the last substep, a marker per particle
+    pub fn contact_buffer(&self) -> &wgpu::Buffer {
+        &self.particles.contact_buffer
+    }
+
+    // Shadows the particles under other layers of the cloth, from the latest state
+    pub fn encode_self_shadow(&self, context: &dyn Gpu, encoder: &mut wgpu::CommandEncoder, settings: &ShadowSettings) {
+        let particles = &self.particles;
+        particles
+            .self_shadow
+            .encode(context, encoder, &particles.bind_group[0], self.num_instances, settings);
+    }
+
+    // Overwrites the attribute with the strain of every particle, from the latest state
+    pub fn encode_strain(&self, encoder: &mut wgpu::CommandEncoder) {
+        let particles = &self.particles;
+        particles.strain.encode(encoder, &particles.bind_group[0], self.num_instances);
+    }
+
+    // Overwrites the attribute with the motion of every particle, from the latest state.
+    // `interval` is the time simulated since the last call, for the acceleration.
+    pub fn encode_kinematics(
+        &self,
+        context: &dyn Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        quantity: MotionQuantity,
+        interval: f32,
+    ) {
+        let particles = &self.particles;
+        particles
+            .kinematics
+            .encode(context, encoder, &particles.bind_group[0], self.num_instances, quantity, interval);
+    }
+
+    pub fn clear_self_shadow(&self, context: &dyn Gpu) {
+        self.particles.self_shadow.clear(context);
+    }
+
+    // How shadowed every particle is, from 0 to 1, zero until encode_self_shadow()
+    pub fn occlusion_buffer(&self) -> &wgpu::Buffer {
+        self.particles.self_shadow.occlusion_buffer()
+    }
+
+    // Number of particles the guard pass had to reset, a single u32 for the frame graph to read back
+    pub fn guard_count_buffer(&self) -> &wgpu::Buffer {
+        &self.guard_buffer
+    }
+
+    // Jacobi iterations stop once the constraints are within the tolerance, the other solvers
+    // always run all of theirs
+    pub fn set_convergence(&mut self, context: &dyn Gpu, settings: &ConvergenceSettings) {
+        self.early_termination = settings.enabled;
+        self.convergence.configure(context, settings);
+    }
+
+    // Particles are kept apart from the rest of the cloth on top of the solver, with the Jacobi and
+    // Gauss-Seidel solvers. The implicit one has the speeds final before the push could count.
+    pub fn set_self_collision(&mut self, context: &dyn Gpu, settings: &SelfCollisionSettings) {
+        self.self_collision = settings.enabled;
+        self.particles.self_collision.configure(context, settings);
+    }
+
+    // Pieces flagged in `far_pieces` stop after `far_iterations` Jacobi iterations, the plain
+    // Jacobi solver only. Takes effect with the next update_materials().
+    pub fn set_lod(&mut self, far_pieces: &[bool], far_iterations: u32) {
+        self.far_pieces = far_pieces.to_vec();
+        self.far_iterations = far_iterations as usize;
+    }
+
+    // Angular velocity in rad/s of every collider, in the order of update_rig(), the colliders
+    // past the end don't spin. Takes effect with the next update_rig().
+    pub fn set_collider_spins(&mut self, spins: &[[f32; 3]]) {
+        self.collider_spins = spins.to_vec();
+    }
+
+    // Whether every collider, in the order of update_rig(), only pushes out the particles coming
+    // from outside. Those already inside, e.g. below a plane or spawned within a container, pass
+    // through instead of being shot out. The colliders past the end are two-sided. Takes effect
+    // with the next update_rig().
+    pub fn set_one_sided_colliders(&mut self, one_sided: &[bool]) {
+        self.one_sided_colliders = one_sided.to_vec();
+    }
+
+    // Average rest position of every piece, in the order of the pieces
+    pub fn piece_centers(&self) -> Vec<[f32; 3]> {
+        self.pieces
+            .iter()
+            .map(|piece| {
+                let particles = &self.rest_state[piece.offset as usize..(piece.offset + piece.count) as usize];
+                let sum = particles.iter().fold([0.0; 3], |sum, particle| {
+                    let position = particle.position();
+                    [0, 1, 2].map(|i| sum[i] + position[i])
+                });
+                sum.map(|value| value / particles.len().max(1) as f32)
+            })
+            .collect()
+    }
+
+    // Four u32, the last one the number of Jacobi iterations skipped since the cloth was built
+    pub fn convergence_buffer(&self) -> &wgpu::Buffer {
+        self.convergence.state_buffer()
+    }
+
+    // Checksum of the latest state into checksum_buffer(), after the step or replay frame
+    pub fn encode_checksum(&self, encoder: &mut wgpu::CommandEncoder) {
+        let workgroups = self.num_instances.div_ceil(WORKGROUP_SIZE);
+        self.checksum.encode(encoder, &self.particles.bind_group[0], workgroups);
+    }
+
+    // Two u32, see checksum::checksum()
+    pub fn checksum_buffer(&self) -> &wgpu::Buffer {
+        self.checksum.buffer()
+    }
+
+    // Energies and constraint totals of the latest state into energy_buffer()
+    pub fn encode_energy(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.energy.encode(encoder, &self.particles.bind_group[0]);
+    }
+
+    // An energy::EnergySample
+    pub fn energy_buffer(&self) -> &wgpu::Buffer {
+        self.energy.buffer()
+    }
+
+    // Turns the first collider into a rigid body at `center`, at rest, sitting on a support at
+    // that height. It only moves once it has a mass, see set_sphere_mass().
+    pub fn start_sphere_body(&self, context: &dyn Gpu, center: [f32; 3]) {
+        let [x, y, z] = center;
+        let body = SphereBody {
+            center: [x, y, z, 0.0],
+            floor: y,
+            damping: SPHERE_DAMPING,
+            ..SphereBody::zeroed()
+        };
+        context.queue().write_buffer(&self.body_buffer, 0, bytemuck::bytes_of(&body));
+    }
+
+    // Mass of the sphere body in kg, None hands the first collider back to the rig
+    pub fn set_sphere_mass(&self, context: &dyn Gpu, mass: Option<f32>) {
+        let inverse_mass = mass.map_or(0.0, |mass| PARTICLE_MASS / mass.max(1e-6));
+        let offset = std::mem::offset_of!(SphereBody, velocity) + 3 * std::mem::size_of::<f32>();
+        context
+            .queue()
+            .write_buffer(&self.body_buffer, offset as wgpu::BufferAddress, bytemuck::bytes_of(&inverse_mass));
+    }
+
+    // Starts with the world space center of the sphere body as three floats
+    pub fn sphere_body_buffer(&self) -> &wgpu::Buffer {
+        &self.body_buffer
+    }
+
+    // World transforms of the hierarchy the anchors refer to, the colliders in world space, the
+    // attachment targets, the fans and the pin group of every piece
+    pub fn update_rig(
+        &mut self,
+        context: &dyn Gpu,
+        nodes: &[Matrix4<f32>],
+        colliders: &[Collider],
+        attachments: &[Attachment],
+        fans: &[Fan],
+        pin_groups: &[PinGroup],
+    ) {
+        assert!(nodes.len() <= MAX_NODES, "at most {MAX_NODES} nodes are supported");
+        assert!(colliders.len() <= MAX_COLLIDERS, "at most {MAX_COLLIDERS} colliders are supported");
+        assert!(attachments.len() <= MAX_ATTACHMENTS, "at most {MAX_ATTACHMENTS} attachments are supported");
+        assert!(fans.len() <= MAX_FANS, "at most {MAX_FANS} fans are supported");
+
+        let mut rig = RigUniform::zeroed();
+        for (slot, node) in rig.nodes.iter_mut().zip(nodes) {
+            *slot = (*node).into();
+        }
+        let mut blocks: Vec<ColliderBlock> = colliders.iter().map(ColliderBlock::from).collect();
+        for (block, previous) in blocks.iter_mut().zip(&self.colliders) {
+            let [x, y, z] = block.displacement(previous);
+            block.motion = [x, y, z, 0.0];
+        }
+        // A plane has no center to spin about
+        for (block, &[x, y, z]) in blocks.iter_mut().zip(&self.collider_spins) {
+            if block.kind != ColliderKind::Plane as u32 {
+                block.spin = [x, y, z, 0.0];
+            }
+        }
+        for (block, &one_sided) in blocks.iter_mut().zip(&self.one_sided_colliders) {
+            block.one_sided = u32::from(one_sided);
+        }
+        context.queue().write_buffer(&self.collider_buffer, 0, bytemuck::cast_slice(&blocks));
+        self.colliders = blocks;
+        for (slot, attachment) in rig.attachments.iter_mut().zip(attachments) {
+            let [x, y, z] = attachment.target;
+            *slot = AttachmentBlock {
+                target: [x, y, z, attachment.stiffness],
+                particle: attachment.particle,
+                _padding: [0; 3],
+            };
+        }
+        self.num_attachments = attachments.len() as u32;
+        for (slot, fan) in rig.fans.iter_mut().zip(fans) {
+            *slot = FanBlock::from(fan);
+        }
+        self.fans = fans.to_vec();
+        for (slot, group) in rig.pin_groups.iter_mut().zip(pin_groups) {
+            *slot = [group.compliance, group.break_force, 0.0, 0.0];
+        }
+
+        context.queue().write_buffer(&self.rig_buffer, 0, bytemuck::bytes_of(&rig));
+    }
+
+    // Advances the cloth by one frame of `params.substeps` substeps. The buffers are swapped right
+    // away, so instance_buffer() already refers to the state the recorded commands will write.
+    pub fn encode_step(
+        &mut self,
+        context: &dyn Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        params: &SimParams,
+        solver_mode: SolverMode,
+    ) {
+        let params = SimParams {
+            num_particles: self.num_instances,
+            num_anchors: self.num_anchors,
+            num_colliders: self.colliders.len() as u32,
+            num_attachments: self.num_attachments,
+            num_fans: self.fans.len() as u32,
+            ..*params
+        };
+        context.queue().write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+
+        let workgroups = self.num_instances.div_ceil(WORKGROUP_SIZE);
+        let placement = std::mem::take(&mut self.placement_pending);
+        if placement {
+            self.encode_placement(encoder, workgroups);
+        }
+        self.dispatches = u32::from(placement)
+            + params.substeps * self.substep_dispatches(solver_mode, params.iterations as usize);
+
+        let latest = &self.particles.instance_buffer[0];
+        encoder.copy_buffer_to_buffer(latest, 0, &self.particles.previous_buffer, 0, latest.size());
+        // Index of the bind group whose first binding holds the latest state
+        let mut current = 0;
+
+        let mut substeps = 0..params.substeps;
+        if self.prepare_probe(context, &params, solver_mode) {
+            substeps.next();
+            self.encode_probed_substep(encoder, &mut current, workgroups, params.iterations as usize);
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Compute Pass"),
+                timestamp_writes: None,
+            });
+
+            for _ in substeps {
+                self.encode_substep(&mut compute_pass, &mut current, workgroups, solver_mode, params.iterations as usize);
+            }
+        }
+
+        // Swap the ping-pong buffers when the latest state ended up in the second one
+        if current == 1 {
+            self.particles.instance_buffer.swap(0, 1);
+            self.particles.bind_group.swap(0, 1);
+        }
+    }
+
+    // Shows recorded positions instead of a step: the particles are moved there at rest, and the
+    // latest state is kept as the previous one like a step would
+    pub fn encode_replay_frame(&mut self, context: &dyn Gpu, encoder: &mut wgpu::CommandEncoder, positions: &[[f32; 3]]) {
+        let instances: Vec<Instance> = self
+            .rest_state
+            .iter()
+            .zip(positions)
+            .map(|(rest, &[x, y, z])| Instance {
+                position: [x, y, z, rest.position[3]],
+                previous: [x, y, z, 0.0],
+                ..*rest
+            })
+            .collect();
+
+        let latest = &self.particles.instance_buffer[0];
+        encoder.copy_buffer_to_buffer(latest, 0, &self.particles.previous_buffer, 0, latest.size());
+        // Queue writes land before the copy, so they go to the other buffer, which becomes the latest
+        context
+            .queue()
+            .write_buffer(&self.particles.instance_buffer[1], 0, bytemuck::cast_slice(&instances));
+        self.particles.instance_buffer.swap(0, 1);
+        self.particles.bind_group.swap(0, 1);
+    }
+
+    // Every dispatch that reads one buffer and writes the other flips `current`
+    fn encode_substep(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        current: &mut usize,
+        workgroups: u32,
+        solver_mode: SolverMode,
+        iterations: usize,
+    ) {
+        self.encode_anchors(compute_pass, *current);
+        self.encode_tearing(compute_pass, *current, workgroups);
+
+        match solver_mode {
+            SolverMode::Jacobi if self.early_termination => {
+                compute_pass.set_pipeline(&self.integrate_pipeline);
+                compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                *current ^= 1;
+
+                self.convergence.encode(compute_pass, &self.particles.bind_group, current, workgroups, iterations);
+                self.encode_self_collision(compute_pass, current);
+                self.encode_attachments(compute_pass, *current);
+
+                compute_pass.set_pipeline(&self.finalize_pipeline);
+                compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                *current ^= 1;
+            }
+            SolverMode::Jacobi => {
+                let far_iterations = if self.far_pieces.contains(&true) {
+                    self.far_iterations
+                } else {
+                    iterations
+                };
+                let solve_pipelines = (0..iterations).map(|iteration| {
+                    if iteration < far_iterations {
+                        &self.solve_pipeline
+                    } else {
+                        &self.near_solve_pipeline
+                    }
+                });
+                let passes = std::iter::once(&self.integrate_pipeline).chain(solve_pipelines);
+
+                for pipeline in passes {
+                    compute_pass.set_pipeline(pipeline);
+                    compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                    compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                    *current ^= 1;
+                }
+
+                self.encode_self_collision(compute_pass, current);
+                self.encode_attachments(compute_pass, *current);
+
+                compute_pass.set_pipeline(&self.finalize_pipeline);
+                compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                *current ^= 1;
+            }
+            SolverMode::GaussSeidel => {
+                compute_pass.set_pipeline(&self.integrate_pipeline);
+                compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                *current ^= 1;
+
+                // The colors work in place on the predicted positions
+                self.particles.gauss_seidel_solver.encode(compute_pass, &self.particles.bind_group[*current], iterations);
+                self.encode_self_collision(compute_pass, current);
+                self.encode_attachments(compute_pass, *current);
+
+                compute_pass.set_pipeline(&self.finalize_pipeline);
+                compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                *current ^= 1;
+            }
+            SolverMode::Implicit => {
+                self.particles.implicit_solver.encode(compute_pass, &self.particles.bind_group[*current], workgroups, iterations);
+                *current ^= 1;
+                // The speeds are already final, attachments only move the particles
+                self.encode_attachments(compute_pass, *current);
+            }
+        }
+
+        self.encode_contacts(compute_pass, *current, workgroups);
+    }
+
+    // Dispatches encode_substep() records, to keep an eye on their overhead
+    fn substep_dispatches(&self, solver_mode: SolverMode, iterations: usize) -> u32 {
+        let anchors = u32::from(self.num_anchors > 0);
+        let attachments = u32::from(self.num_attachments > 0) + anchors;
+        let self_collision = if self.self_collision { SelfCollision::DISPATCHES } else { 0 };
+        let particles = &self.particles;
+        // Integration first and finalization last around the constraints
+        let solver = match solver_mode {
+            SolverMode::Jacobi if self.early_termination => 2 + self.convergence.dispatches(iterations),
+            SolverMode::Jacobi => 2 + iterations as u32,
+            SolverMode::GaussSeidel => 2 + particles.gauss_seidel_solver.dispatches(iterations),
+            SolverMode::Implicit => particles.implicit_solver.dispatches(iterations),
+        };
+        let self_collision = if solver_mode == SolverMode::Implicit { 0 } else { self_collision };
+        // The contacts are a collision, a body and a guard pass
+        anchors + u32::from(self.tearing) + solver + self_collision + attachments + 3
+    }
+
+    // Same stages as the Jacobi solver of encode_substep(), each in its own compute pass so the
+    // probe window of the latest state can be copied after it
+    fn encode_probed_substep(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        current: &mut usize,
+        workgroups: u32,
+        iterations: usize,
+    ) {
+        let Some(probe) = &self.probe else {
+            return;
+        };
+        {
+            let mut compute_pass = begin_probed_pass(encoder);
+            self.encode_anchors(&mut compute_pass, *current);
+            self.encode_tearing(&mut compute_pass, *current, workgroups);
+        }
+        probe.capture(encoder, &self.particles.instance_buffer[*current], 0);
+
+        let passes = std::iter::once(&self.integrate_pipeline).chain(std::iter::repeat_n(&self.solve_pipeline, iterations));
+        for (stage, pipeline) in passes.enumerate() {
+            {
+                let mut compute_pass = begin_probed_pass(encoder);
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+            *current ^= 1;
+            probe.capture(encoder, &self.particles.instance_buffer[*current], stage + 1);
+        }
+
+        self.encode_attachments(&mut begin_probed_pass(encoder), *current);
+        probe.capture(encoder, &self.particles.instance_buffer[*current], iterations + 2);
+
+        {
+            let mut compute_pass = begin_probed_pass(encoder);
+            compute_pass.set_pipeline(&self.finalize_pipeline);
+            compute_pass.set_bind_group(0, &self.particles.bind_group[*current], &[]);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        *current ^= 1;
+        probe.capture(encoder, &self.particles.instance_buffer[*current], iterations + 3);
+
+        self.encode_contacts(&mut begin_probed_pass(encoder), *current, workgroups);
+    }
+
+    // Whether the first substep of this step is probed, only the Jacobi solver has a CPU
+    // reference. The probe buffer is reallocated when the number of stages changes.
+    fn prepare_probe(&mut self, context: &dyn Gpu, params: &SimParams, solver_mode: SolverMode) -> bool {
+        if !self.probing || solver_mode != SolverMode::Jacobi {
+            return false;
+        }
+        let inputs = StepInputs {
+            window: divergence::probe_window(self.num_instances),
+            iterations: params.iterations as usize,
+            delta_time: params.delta_time,
+            relaxation: params.relaxation,
+            gravity_scale: params.gravity_scale,
+            materials: self.materials.clone(),
+            fans: self.fans.clone(),
+        };
+        let size = (inputs.num_stages() * inputs.window.len() * std::mem::size_of::<Instance>()) as wgpu::BufferAddress;
+        match &mut self.probe {
+            Some(probe) if probe.buffer.size() == size => probe.inputs = inputs,
+            _ => {
+                let buffer = self.tracker.create_buffer(context, &wgpu::BufferDescriptor {
+                    label: Some("Stage Probe Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                });
+                self.probe = Some(StageProbe { buffer, inputs });
+            }
+        }
+        true
+    }
+
+    // Rigidly pinned particles are moved in place to their node before anything reads them. They
+    // jump to the pose of the frame on the first substep, the rig is posed once per frame. The
+    // particles of elastic pins are freed instead, encode_attachments() pulls them.
+    fn encode_anchors(&self, compute_pass: &mut wgpu::ComputePass<'_>, current: usize) {
+        if self.num_anchors > 0 {
+            compute_pass.set_pipeline(&self.anchor_pipeline);
+            compute_pass.set_bind_group(0, &self.particles.bind_group[current], &[]);
+            compute_pass.dispatch_workgroups(self.num_anchors.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    // Breaks the constraints the last substep stretched past their tear strain, before the solver
+    // reads them
+    fn encode_tearing(&self, compute_pass: &mut wgpu::ComputePass<'_>, current: usize, workgroups: u32) {
+        if self.tearing {
+            compute_pass.set_pipeline(&self.tear_pipeline);
+            compute_pass.set_bind_group(0, &self.particles.bind_group[current], &[]);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+    }
+
+    // The solvers leave the colliders to the contact passes, in place on the latest state, then
+    // the guard catches what blew up. The body pass runs every substep, it counts them for the
+    // swept spheres of collide().
+    fn encode_contacts(&self, compute_pass: &mut wgpu::ComputePass<'_>, current: usize, workgroups: u32) {
+        compute_pass.set_bind_group(0, &self.particles.bind_group[current], &[]);
+        compute_pass.set_bind_group(1, &self.contact_bind_group, &[]);
+        compute_pass.set_pipeline(&self.collide_pipeline);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        compute_pass.set_pipeline(&self.body_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+
+        compute_pass.set_pipeline(&self.guard_pipeline);
+        compute_pass.set_bind_group(1, &self.guard_bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+
+    // Moves the particles of the latest state out of the colliders the rig placed, in place. Once
+    // per cloth, as the colliders are only known from the first step on.
+    fn encode_placement(&self, encoder: &mut wgpu::CommandEncoder, workgroups: u32) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Placement Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.placement_pipeline);
+        compute_pass.set_bind_group(0, &self.particles.bind_group[0], &[]);
+        compute_pass.set_bind_group(1, &self.contact_bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+
+    // Separates the particles of the latest state that got closer than the thickness of the cloth
+    fn encode_self_collision(&self, compute_pass: &mut wgpu::ComputePass<'_>, current: &mut usize) {
+        if self.self_collision {
+            let particles = &self.particles;
+            particles
+                .self_collision
+                .encode(compute_pass, &particles.bind_group, current, self.num_instances);
+        }
+    }
+
+    // Pulls the attached particles of the latest state towards their targets, and the particles
+    // of the elastic pins towards their node, in place. Runs after the constraints so the
+    // attachments win, the speed then follows from the move.
+    fn encode_attachments(&self, compute_pass: &mut wgpu::ComputePass<'_>, current: usize) {
+        compute_pass.set_bind_group(0, &self.particles.bind_group[current], &[]);
+        if self.num_attachments > 0 {
+            compute_pass.set_pipeline(&self.attachment_pipeline);
+            compute_pass.dispatch_workgroups(self.num_attachments.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        if self.num_anchors > 0 {
+            compute_pass.set_pipeline(&self.elastic_anchor_pipeline);
+            compute_pass.dispatch_workgroups(self.num_anchors.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    /// Frees the GPU memory of every buffer right away, instead of whenever the last bind group
+    /// and in-flight command buffer referencing them is dropped.
+    pub fn destroy(self) {
+        // Dropping the tracked buffers destroys them, the bind groups and pipelines go with them
+        self.sdf_texture.destroy();
+        self.height_field_texture.destroy();
+        drop(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gaps(spacing: Spacing, count: u32) -> Vec<f32> {
+        spacing.axis(count).windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+
+    #[test]
+    fn graded_spacing_is_symmetric() {
+        let (center, edge) = (0.01, 0.03);
+        let gaps = gaps(Spacing::Graded { center, edge }, 6);
+        assert_eq!(gaps.len(), 5);
+        assert!((gaps[0] - edge).abs() < 1e-6, "first gap {}", gaps[0]);
+        assert!((gaps[4] - edge).abs() < 1e-6, "last gap {}", gaps[4]);
+        assert!((gaps[2] - center).abs() < 1e-6, "middle gap {}", gaps[2]);
+        for (gap, mirrored) in gaps.iter().zip(gaps.iter().rev()) {
+            assert!((gap - mirrored).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn axis_is_centered() {
+        let axis = Spacing::Graded { center: 0.01, edge: 0.03 }.axis(7);
+        assert!((axis[0] + axis[6]).abs() < 1e-6);
+        assert_eq!(Spacing::Uniform(0.1).axis(1), [0.0]);
+        assert!(Spacing::Uniform(0.1).axis(0).is_empty());
+    }
+}
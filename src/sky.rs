@@ -0,0 +1,143 @@
+// Sky behind the scene instead of the flat clear color: a procedural gradient from the ground
+// through the horizon to the zenith, with a glow where the sun is, or a flat background color of
+// the user's choice. Its colors can also light the scene in place of the gray ambient light, see
+// sky_irradiance() of wgsl/lighting.wgsl, and the scene fades into its horizon with the distance
+// when there is fog, see apply_fog().
+
+use wgpu_bootstrap::{egui, wgpu, Context};
+
+use crate::shader_source::expand_includes;
+
+pub struct Sky {
+    pub enabled: bool, // the flat background color otherwise
+    pub lights_scene: bool, // ambient light from the colors of the sky
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    pub ground: [f32; 3],
+    pub background: [f32; 3],
+    pub fog_density: f32, // 1/m, 0 for none
+    pipeline: wgpu::RenderPipeline,
+    motion_pipeline: wgpu::RenderPipeline, // also fills the velocity target of the motion blur
+}
+
+impl Sky {
+    // `targets` are the color target, then the velocity one of the motion blur, drawn with
+    // `sample_count` samples like the rest of the scene
+    pub fn new(
+        context: &Context,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        lighting_bind_group_layout: &wgpu::BindGroupLayout,
+        targets: [Option<wgpu::ColorTargetState>; 2],
+        sample_count: u32,
+    ) -> Self {
+        let shader = context.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sky Shader"),
+            source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("sky.wgsl")).into()),
+        });
+        let layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, lighting_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, fragment_entry: &str, targets: &[Option<wgpu::ColorTargetState>]| {
+            context
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: fragment_entry,
+                        targets,
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    // Drawn first, everything else covers it
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: context.depth_stencil_format(),
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Always,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                })
+        };
+
+        let [color_target, velocity_target] = targets;
+        Self {
+            enabled: true,
+            lights_scene: false,
+            zenith: [0.25, 0.45, 0.8],
+            horizon: [0.8, 0.85, 0.9],
+            ground: [0.35, 0.33, 0.3],
+            background: [0.913, 0.913, 0.913], // the window clear color of the runner
+            fog_density: 0.0,
+            pipeline: create_pipeline("Sky Render Pipeline", "fs_main", std::slice::from_ref(&color_target)),
+            motion_pipeline: create_pipeline("Motion Sky Render Pipeline", "fs_motion", &[color_target, velocity_target]),
+        }
+    }
+
+    // Sky colors of the lighting uniform: zenith, horizon and ground, all three the background
+    // color without the sky. The w of the zenith is 1 when they light the scene, that of the
+    // horizon when the sun glows in the sky.
+    pub fn environment(&self) -> [[f32; 4]; 3] {
+        let with = |[r, g, b]: [f32; 3], w: bool| [r, g, b, if w { 1.0 } else { 0.0 }];
+        if !self.enabled {
+            return [
+                with(self.background, self.lights_scene),
+                with(self.background, false),
+                with(self.background, false),
+            ];
+        }
+        [
+            with(self.zenith, self.lights_scene),
+            with(self.horizon, true),
+            with(self.ground, false),
+        ]
+    }
+
+    // Fog of the lighting uniform, the color the scene fades into with w its density
+    pub fn fog(&self) -> [f32; 4] {
+        let [r, g, b] = if self.enabled { self.horizon } else { self.background };
+        [r, g, b, self.fog_density]
+    }
+
+    // Before the rest of the scene, with the camera bind group already set. Draws the background
+    // color without the sky.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, lighting_bind_group: &wgpu::BindGroup, motion_vectors: bool) {
+        render_pass.set_pipeline(if motion_vectors { &self.motion_pipeline } else { &self.pipeline });
+        render_pass.set_bind_group(1, lighting_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Sky");
+            if self.enabled {
+                ui.color_edit_button_rgb(&mut self.zenith).on_hover_text("Zenith");
+                ui.color_edit_button_rgb(&mut self.horizon).on_hover_text("Horizon");
+                ui.color_edit_button_rgb(&mut self.ground).on_hover_text("Ground");
+            } else {
+                ui.color_edit_button_rgb(&mut self.background).on_hover_text("Background");
+            }
+        });
+        ui.checkbox(&mut self.lights_scene, "Ambient light from the sky")
+            .on_hover_text("Surfaces facing up take the color of the zenith, facing down that of the ground");
+        ui.add(egui::Slider::new(&mut self.fog_density, 0.0..=2.0).text("Fog (1/m)"))
+            .on_hover_text("The scene fades into the horizon, or the background, with the distance");
+    }
+}
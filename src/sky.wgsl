@@ -0,0 +1,54 @@
+// sky.wgsl
+// Background of the scene: a gradient from the ground to the zenith with a glow around the sun,
+// or a flat color, drawn behind everything else, see sky.rs.
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+#include "lighting.wgsl"
+
+@group(1) @binding(0) var<uniform> lighting: Lighting;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>, // world space, from the eye
+};
+
+// One triangle over the whole view, on the far plane
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    // Undo the projection, then the rotation of the view, the offsets of a jittered projection
+    // are a fraction of a pixel and left out
+    let view_direction = vec3<f32>(ndc.x / camera.proj[0][0], ndc.y / camera.proj[1][1], -1.0);
+    let rotation = mat3x3<f32>(camera.view[0].xyz, camera.view[1].xyz, camera.view[2].xyz);
+    out.direction = transpose(rotation) * view_direction;
+    return out;
+}
+
+fn sky(in: VertexOutput) -> vec4<f32> {
+    let direction = normalize(in.direction);
+    let glow = pow(max(dot(direction, lighting.direction.xyz), 0.0), 256.0) * lighting.direction.w * lighting.sky_horizon.w;
+    return vec4<f32>(sky_gradient(lighting, direction) + glow * lighting.sun_color.rgb, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sky(in);
+}
+
+struct MotionOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+// The sky doesn't move with the particles
+@fragment
+fn fs_motion(in: VertexOutput) -> MotionOutput {
+    return MotionOutput(sky(in), vec2<f32>(0.0, 0.0));
+}
@@ -6,31 +6,57 @@ struct CameraUniform {
 
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
 
+#include "lighting.wgsl"
+
+@group(1) @binding(0) var<uniform> lighting: Lighting;
+@group(1) @binding(2) var shadow_map: texture_depth_2d;
+@group(1) @binding(3) var shadow_sampler: sampler_comparison;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) color: vec3<f32>,
 };
 
+// One instance per collider, the mesh is a unit sphere
+struct ColliderInput {
+    @location(3) sphere: vec4<f32>, // center and radius
+};
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
     @location(1) normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
 };
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(model: VertexInput, collider: ColliderInput) -> VertexOutput {
     var out: VertexOutput;
     out.color = model.color;
-    out.normal = (camera.view * vec4<f32>(model.normal, 0.0)).xyz;
-    out.clip_position = camera.proj * camera.view * vec4<f32>(model.position, 1.0);
+    out.normal = model.normal;
+    out.world_position = collider.sphere.xyz + model.position * collider.sphere.w;
+    out.clip_position = camera.proj * camera.view * vec4<f32>(out.world_position, 1.0);
     return out;
 }
 
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let sun = sun_visibility(lighting, shadow_map, shadow_sampler, in.world_position);
+    return vec4<f32>(shade_surface(lighting, in.color, vec4<f32>(0.0), in.world_position, in.normal, eye_position(camera.view), sun), 1.0);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let light_dir = normalize(vec3<f32>(1.0, 1.0, 1.0));
-    let diffuse = max(dot(normalize(in.normal), light_dir), 0.0);
-    let final_color = in.color * (diffuse * 0.7 + 0.3);
-    return vec4<f32>(final_color, 1.0);
+    return shade(in);
+}
+
+struct MotionOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+};
+
+// The colliders aren't blurred, only the particles keep their previous position
+@fragment
+fn fs_motion(in: VertexOutput) -> MotionOutput {
+    return MotionOutput(shade(in), vec2<f32>(0.0, 0.0));
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+// Strain heatmap: a compute pass writing the stretch of every particle into its attribute, drawn
+// through the color ramp to see where the cloth gives when tuning its stiffness, see strain.wgsl.
+
+use wgpu_bootstrap::wgpu;
+
+use crate::gpu::Gpu;
+use crate::shaders::create_compute_module;
+
+pub struct Strain {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    workgroup_size: u32,
+}
+
+impl Strain {
+    // Writes into `attribute_buffer`, a f32 per particle
+    pub fn new(
+        context: &dyn Gpu,
+        instance_bind_group_layout: &wgpu::BindGroupLayout,
+        attribute_buffer: &wgpu::Buffer,
+        workgroup_size: u32,
+    ) -> Self {
+        let bind_group_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Strain Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Strain Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: attribute_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Strain Pipeline Layout"),
+            bind_group_layouts: &[instance_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let module = create_compute_module(context, "Strain Shader", include_str!("strain.wgsl"), workgroup_size);
+        let pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("particle_strain"),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "particle_strain",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group,
+            workgroup_size,
+        }
+    }
+
+    // Reads the latest state from the first binding of `instance_bind_group`
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, instance_bind_group: &wgpu::BindGroup, num_instances: u32) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Strain Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, instance_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(num_instances.div_ceil(self.workgroup_size), 1, 1);
+    }
+}
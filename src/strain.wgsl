@@ -0,0 +1,27 @@
+// strain.wgsl
+// Strain of the cloth for the color ramp, appended to compute.wgsl and run on the latest state:
+// every particle gets the largest stretch of its structural and shear constraints, their length
+// over their rest length minus one. Bend constraints shorten in every fold and are left out.
+
+const BEND: u32 = 3u; // kind of the constraints across two spacings
+
+@group(1) @binding(0) var<storage, read_write> strain: array<f32>; // the attribute of the particles
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn particle_strain(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_particles) {
+        return;
+    }
+    let position = instances_ping[index].position.xyz;
+    var largest = 0.0;
+    for (var slot = 0u; slot < MAX_CONSTRAINTS; slot++) {
+        let constraint = constraints[index * MAX_CONSTRAINTS + slot];
+        if (constraint.neighbor == NO_NEIGHBOR || constraint.kind == BEND || constraint.rest_length <= 0.0) {
+            continue;
+        }
+        let length = distance(instances_ping[constraint.neighbor].position.xyz, position);
+        largest = max(largest, length / constraint.rest_length - 1.0);
+    }
+    strain[index] = largest;
+}
@@ -0,0 +1,189 @@
+// Parameter studies: the same drop repeated from slightly different starts, summarized as the mean
+// and variance of a few metrics so a change of parameters can be told apart from the noise of a
+// single run. There is no wind model to draw random gusts from, the runs differ by a seeded jitter
+// of the rest positions instead.
+//
+// Runs are simulated one after the other by the application, the device only holds one cloth.
+// Metrics are sampled every SAMPLE_INTERVAL steps, each sample waits for the GPU.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cloth::sim::{ClothSimulation, Instance, TIME_STEP};
+
+use crate::export::EXPORT_DIR;
+
+pub const STUDY_FILE: &str = "study.csv";
+pub const SAMPLE_INTERVAL: u64 = 10; // steps
+const JITTER: f32 = 1e-3; // m, largest offset of a particle from its rest position
+const SETTLE_SPEED: f32 = 0.05; // m/s, the cloth is settled once no particle moves faster
+const SETTLE_HOLD: f32 = 1.0; // s, settled for that long ends the run
+const MAX_RUN_TIME: f32 = 20.0; // s, runs that never settle end there
+
+// SplitMix64, enough to spread the seeds and keep the runs reproducible
+fn split_mix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Offset of a particle at the start of the run with `seed`, up to JITTER along every axis
+pub fn jitter(seed: u64, particle: u32) -> [f32; 3] {
+    let mut state = split_mix(seed ^ (u64::from(particle) << 32));
+    [(); 3].map(|()| {
+        state = split_mix(state);
+        ((state >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * JITTER
+    })
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn max_speed(instances: &[Instance]) -> f32 {
+    instances
+        .iter()
+        .map(|instance| {
+            let [x, y, z] = instance.speed();
+            (x * x + y * y + z * z).sqrt()
+        })
+        .fold(0.0, f32::max)
+}
+
+// Largest relative stretch between grid neighbors, pieces loaded from meshes are left out
+fn max_strain(cloth: &ClothSimulation, instances: &[Instance]) -> f32 {
+    let mut strain: f32 = 0.0;
+    for piece in cloth.pieces() {
+        let Some((rows, cols)) = piece.grid else {
+            continue;
+        };
+        let particle = |row: u32, col: u32| piece.offset + row * cols + col;
+        for row in 0..rows {
+            for col in 0..cols {
+                let a = particle(row, col);
+                let neighbors = [(row, col + 1), (row + 1, col)]
+                    .into_iter()
+                    .filter(|&(n_row, n_col)| n_row < rows && n_col < cols);
+                for (n_row, n_col) in neighbors {
+                    let b = particle(n_row, n_col);
+                    let rest = distance(cloth.rest_position(a), cloth.rest_position(b));
+                    let current = distance(instances[a as usize].position(), instances[b as usize].position());
+                    if rest > 0.0 {
+                        strain = strain.max((current - rest) / rest);
+                    }
+                }
+            }
+        }
+    }
+    strain
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct RunMetrics {
+    settle_time: Option<f32>, // s after the drop, None if the run never settled
+    max_strain: f32,
+}
+
+// Mean and sample variance, Welford style
+#[derive(Copy, Clone, Debug, Default)]
+struct Summary {
+    count: u32,
+    mean: f64,
+    m2: f64,
+}
+
+impl Summary {
+    fn of(values: impl Iterator<Item = f32>) -> Self {
+        values.fold(Self::default(), |mut summary, value| {
+            summary.count += 1;
+            let delta = f64::from(value) - summary.mean;
+            summary.mean += delta / f64::from(summary.count);
+            summary.m2 += delta * (f64::from(value) - summary.mean);
+            summary
+        })
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count > 1 {
+            self.m2 / f64::from(self.count - 1)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A study in progress, the application drops the cloth again with `seed()` for every run.
+pub struct Study {
+    runs: u32,
+    base_seed: u64,
+    current: RunMetrics,
+    results: Vec<RunMetrics>,
+}
+
+impl Study {
+    pub fn new(runs: u32, base_seed: u64) -> Self {
+        Self {
+            runs: runs.max(1),
+            base_seed,
+            current: RunMetrics::default(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn run(&self) -> u32 {
+        self.results.len() as u32
+    }
+
+    pub fn runs(&self) -> u32 {
+        self.runs
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.base_seed.wrapping_add(u64::from(self.run()))
+    }
+
+    // Samples the state `steps` steps after the drop, true once the current run is over
+    pub fn sample(&mut self, cloth: &ClothSimulation, instances: &[Instance], steps: u64) -> bool {
+        let time = steps as f32 * TIME_STEP;
+        self.current.max_strain = self.current.max_strain.max(max_strain(cloth, instances));
+        if max_speed(instances) < SETTLE_SPEED {
+            self.current.settle_time.get_or_insert(time);
+        } else {
+            self.current.settle_time = None;
+        }
+        let settled_for = self.current.settle_time.map_or(0.0, |settle_time| time - settle_time);
+        settled_for >= SETTLE_HOLD || time >= MAX_RUN_TIME
+    }
+
+    // Keeps the metrics of the current run, false once every run is done
+    pub fn next_run(&mut self) -> bool {
+        self.results.push(std::mem::take(&mut self.current));
+        self.run() < self.runs
+    }
+
+    // Writes every run to STUDY_FILE and returns the summary
+    pub fn finish(&self) -> io::Result<String> {
+        let mut csv = String::from("run,seed,settle_time,max_strain\n");
+        for (run, metrics) in self.results.iter().enumerate() {
+            let settle_time = metrics.settle_time.map_or(String::new(), |time| time.to_string());
+            let seed = self.base_seed.wrapping_add(run as u64);
+            csv += &format!("{run},{seed},{settle_time},{}\n", metrics.max_strain);
+        }
+        fs::create_dir_all(EXPORT_DIR)?;
+        fs::write(Path::new(EXPORT_DIR).join(STUDY_FILE), csv)?;
+
+        let settle = Summary::of(self.results.iter().filter_map(|metrics| metrics.settle_time));
+        let strain = Summary::of(self.results.iter().map(|metrics| metrics.max_strain));
+        Ok(format!(
+            "Settle time {:.2} s (variance {:.4}, {} of {} runs settled), max strain {:.2}% (variance {:.4})",
+            settle.mean,
+            settle.variance(),
+            settle.count,
+            self.results.len(),
+            100.0 * strain.mean,
+            100.0 * 100.0 * strain.variance(),
+        ))
+    }
+}
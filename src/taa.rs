@@ -0,0 +1,454 @@
+// Temporal anti-aliasing: the scene is rendered offscreen with a projection jittered by a fraction
+// of a pixel every frame, and accumulated into a history reprojected along the motion vectors of
+// the cloth. Edges converge to their coverage over a few frames, thin folds included.
+
+use std::time::{Duration, Instant};
+
+use wgpu_bootstrap::{
+    cgmath::Point3,
+    wgpu::{self, util::DeviceExt},
+    Context,
+};
+
+use crate::camera::{OrbitCamera, Projection};
+use crate::motion_blur::VELOCITY_FORMAT;
+use crate::hdr::{Tonemapping, HDR_FORMAT};
+use crate::msaa;
+use crate::oit::{OitTargets, Transparency};
+use crate::shader_source::expand_includes;
+
+const HISTORY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const HISTORY_WEIGHT: f32 = 0.9; // share of the history in every resolved frame
+const JITTER_PERIOD: u32 = 8; // frames before the jitter pattern repeats
+
+// Window clear color of the runner, (245, 245, 245) in sRGB
+const BACKGROUND: wgpu::Color = wgpu::Color { r: 0.913, g: 0.913, b: 0.913, a: 1.0 };
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaUniform {
+    velocity_scale: f32,
+    history_weight: f32,
+    _padding: [f32; 2],
+}
+
+// Element of the Halton low-discrepancy sequence, in [0, 1)
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+// Offscreen targets, sized like the window. The history is ping-ponged, `resolve_bind_group[i]`
+// reads history i and the resolve writes the other one.
+struct Targets {
+    size: (u32, u32),
+    color_view: wgpu::TextureView,
+    velocity_view: wgpu::TextureView,
+    // Drawn into and resolved into the two above when multisampling
+    multisampled_color_view: Option<wgpu::TextureView>,
+    multisampled_velocity_view: Option<wgpu::TextureView>,
+    depth_view: wgpu::TextureView,
+    history_views: [wgpu::TextureView; 2],
+    resolve_bind_group: [wgpu::BindGroup; 2],
+    output_bind_group: [wgpu::BindGroup; 2],
+    oit: Option<OitTargets>,
+}
+
+pub struct TemporalAntiAliasing {
+    pub enabled: bool,
+    camera: OrbitCamera, // the main camera, jittered
+    frame: u32,
+    latest: usize, // history written by the resolve of this frame, read by the next one
+    history_valid: bool,
+    last_view: Option<(Point3<f32>, Point3<f32>, Projection)>, // of the main camera
+    last_resolve: Instant,
+    sample_count: u32,
+    targets: Option<Targets>, // created on the first update
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    resolve_layout: wgpu::BindGroupLayout,
+    output_layout: wgpu::BindGroupLayout,
+    resolve_pipeline: wgpu::RenderPipeline,
+    output_pipeline: wgpu::RenderPipeline,
+}
+
+impl TemporalAntiAliasing {
+    pub fn new(context: &Context, camera: &OrbitCamera, sample_count: u32, tonemapping: &Tonemapping) -> Self {
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TAA Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = context.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Uniform Buffer"),
+            contents: bytemuck::bytes_of(&TaaUniform {
+                velocity_scale: 1.0,
+                history_weight: 0.0,
+                _padding: [0.0; 2],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let resolve_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("TAA Resolve Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                texture_entry(2),
+                sampler_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        // The output only reads the history
+        let output_layout = context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("TAA Output Bind Group Layout"),
+            entries: &[texture_entry(2), sampler_entry],
+        });
+
+        let shader = context
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("TAA Shader"),
+                source: wgpu::ShaderSource::Wgsl(expand_includes(include_str!("taa.wgsl")).into()),
+            });
+
+        let create_pipeline = |label: &str,
+                               layouts: &[&wgpu::BindGroupLayout],
+                               fragment_entry: &str,
+                               format: wgpu::TextureFormat,
+                               depth_stencil: Option<wgpu::DepthStencilState>| {
+            let pipeline_layout = context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            context
+                .device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: fragment_entry,
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleStrip,
+                        ..Default::default()
+                    },
+                    depth_stencil,
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                })
+        };
+        let resolve_pipeline = create_pipeline("TAA Resolve Pipeline", &[&resolve_layout], "fs_resolve", HISTORY_FORMAT, None);
+        // Replaces the scene, which isn't drawn in the main pass while anti-aliasing
+        let output_pipeline = create_pipeline(
+            "TAA Output Pipeline",
+            &[&output_layout, tonemapping.bind_group_layout()],
+            "fs_output",
+            context.format(),
+            Some(wgpu::DepthStencilState {
+                format: context.depth_stencil_format(),
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+        );
+
+        Self {
+            enabled: false,
+            camera: camera.duplicate(context),
+            frame: 0,
+            latest: 0,
+            history_valid: false,
+            last_view: None,
+            last_resolve: Instant::now(),
+            sample_count,
+            targets: None,
+            sampler,
+            uniform_buffer,
+            resolve_layout,
+            output_layout,
+            resolve_pipeline,
+            output_pipeline,
+        }
+    }
+
+    // Follows the window size and the main camera. The history is only reprojected along the
+    // motion of the cloth, so it starts over whenever the camera moves.
+    pub fn update(&mut self, context: &Context, camera: &OrbitCamera, step_duration: Duration, transparency: &Transparency) {
+        let size = (context.size().x as u32, context.size().y as u32);
+        if self.targets.as_ref().map(|targets| targets.size) != Some(size) {
+            self.targets = Some(self.create_targets(context, size));
+            self.history_valid = false;
+        }
+        if let Some(targets) = &mut self.targets {
+            transparency.prepare(context, &mut targets.oit, size, self.sample_count);
+        }
+        let view = (camera.polar(), camera.target(), camera.projection());
+        if self.last_view != Some(view) {
+            self.last_view = Some(view);
+            self.history_valid = false;
+        }
+
+        // Sub-pixel offset in [-0.5, 0.5) pixels, converted to normalized device coordinates
+        self.frame = (self.frame + 1) % JITTER_PERIOD;
+        let jitter = [
+            (halton(self.frame + 1, 2) - 0.5) * 2.0 / size.0.max(1) as f32,
+            (halton(self.frame + 1, 3) - 0.5) * 2.0 / size.1.max(1) as f32,
+        ];
+        self.camera
+            .set_polar(view.0)
+            .set_target(view.1)
+            .set_projection(view.2)
+            .set_jitter(jitter)
+            .update(context);
+
+        // The motion vectors span a whole step, frames usually cover less of it
+        let velocity_scale = (self.last_resolve.elapsed().as_secs_f32() / step_duration.as_secs_f32()).min(1.0);
+        self.last_resolve = Instant::now();
+        let uniform = TaaUniform {
+            velocity_scale,
+            history_weight: if self.history_valid { HISTORY_WEIGHT } else { 0.0 },
+            _padding: [0.0; 2],
+        };
+        context.queue().write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        self.history_valid = true;
+        self.latest = 1 - self.latest;
+    }
+
+    fn create_targets(&self, context: &Context, (width, height): (u32, u32)) -> Targets {
+        let create_view = |label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages, sample_count: u32| {
+            context
+                .device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: width.max(1),
+                        height: height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let sampled = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let color_view = create_view("TAA Color Texture", HDR_FORMAT, sampled, 1);
+        let velocity_view = create_view("TAA Velocity Texture", VELOCITY_FORMAT, sampled, 1);
+        let depth_view = create_view(
+            "TAA Depth Texture",
+            context.depth_stencil_format(),
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+            self.sample_count,
+        );
+        let multisampled_color_view = msaa::create_multisampled_view(
+            context,
+            "TAA Multisampled Color Texture",
+            HDR_FORMAT,
+            (width, height),
+            self.sample_count,
+        );
+        let multisampled_velocity_view = msaa::create_multisampled_view(
+            context,
+            "TAA Multisampled Velocity Texture",
+            VELOCITY_FORMAT,
+            (width, height),
+            self.sample_count,
+        );
+        let history_views = [
+            create_view("TAA History Texture 0", HISTORY_FORMAT, sampled, 1),
+            create_view("TAA History Texture 1", HISTORY_FORMAT, sampled, 1),
+        ];
+
+        let resolve_bind_group = [0, 1].map(|read| {
+            context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("TAA Resolve Bind Group"),
+                layout: &self.resolve_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&color_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&velocity_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&history_views[read]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: self.uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        let output_bind_group = [0, 1].map(|read| {
+            context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("TAA Output Bind Group"),
+                layout: &self.output_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&history_views[read]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        });
+
+        Targets {
+            size: (width, height),
+            color_view,
+            velocity_view,
+            multisampled_color_view,
+            multisampled_velocity_view,
+            depth_view,
+            history_views,
+            resolve_bind_group,
+            output_bind_group,
+            oit: None,
+        }
+    }
+
+    // Renders the scene from the jittered camera, `draw_scene` records the motion variant of the
+    // main view's draws and `draw_transparent` the sheer cloth, and blends it into the history
+    pub fn render_offscreen(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        transparency: &Transparency,
+        draw_scene: impl FnOnce(&mut wgpu::RenderPass<'_>, &wgpu::BindGroup),
+        draw_transparent: impl FnOnce(&mut wgpu::RenderPass<'_>, &wgpu::BindGroup),
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("TAA Scene Pass"),
+                color_attachments: &[
+                    Some(msaa::color_attachment(
+                        &targets.color_view,
+                        targets.multisampled_color_view.as_ref(),
+                        BACKGROUND,
+                    )),
+                    Some(msaa::color_attachment(
+                        &targets.velocity_view,
+                        targets.multisampled_velocity_view.as_ref(),
+                        wgpu::Color::TRANSPARENT,
+                    )),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            draw_scene(&mut render_pass, self.camera.bind_group());
+        }
+        transparency.render(
+            encoder,
+            targets.oit.as_ref(),
+            &targets.color_view,
+            &targets.depth_view,
+            |render_pass| draw_transparent(render_pass, self.camera.bind_group()),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("TAA Resolve Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &targets.history_views[self.latest],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.resolve_pipeline);
+        render_pass.set_bind_group(0, &targets.resolve_bind_group[1 - self.latest], &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+
+    // Composites the resolved history into the main pass, in place of the scene
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, tonemapping: &Tonemapping) {
+        if let Some(targets) = &self.targets {
+            render_pass.set_pipeline(&self.output_pipeline);
+            render_pass.set_bind_group(0, &targets.output_bind_group[self.latest], &[]);
+            render_pass.set_bind_group(1, tonemapping.bind_group(), &[]);
+            render_pass.draw(0..4, 0..1);
+        }
+    }
+}
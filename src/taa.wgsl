@@ -0,0 +1,66 @@
+// taa.wgsl
+// Temporal anti-aliasing: blends the jittered view of this frame into the history of the previous
+// ones, reprojected along the motion of every pixel, then draws the history over the whole window,
+// tonemapped.
+
+#include "tonemap.wgsl"
+
+struct TaaUniform {
+    velocity_scale: f32,  // fraction of the motion of the last step covered by this frame
+    history_weight: f32,  // 0 right after the history was invalidated
+};
+
+@group(0) @binding(0) var color_texture: texture_2d<f32>;
+@group(0) @binding(1) var velocity_texture: texture_2d<f32>;
+@group(0) @binding(2) var history_texture: texture_2d<f32>;
+@group(0) @binding(3) var taa_sampler: sampler;
+@group(0) @binding(4) var<uniform> taa: TaaUniform;
+@group(1) @binding(0) var<uniform> tonemapping: Tonemapping; // of the output only
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Four vertices drawn as a triangle strip
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var out: VertexOutput;
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_resolve(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(color_texture));
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let current = textureLoad(color_texture, pixel, 0);
+
+    // The history is clamped to the colors around the pixel, which rejects what was reprojected
+    // from surfaces that are no longer there instead of leaving ghosts
+    var low = current;
+    var high = current;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = textureLoad(color_texture, clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1), 0);
+            low = min(low, neighbor);
+            high = max(high, neighbor);
+        }
+    }
+
+    let velocity = textureLoad(velocity_texture, pixel, 0).xy * taa.velocity_scale;
+    let history_uv = in.uv - velocity;
+    var history_weight = taa.history_weight;
+    if any(history_uv < vec2<f32>(0.0)) || any(history_uv > vec2<f32>(1.0)) {
+        history_weight = 0.0; // came from outside the view
+    }
+    let history = clamp(textureSample(history_texture, taa_sampler, history_uv), low, high);
+    return mix(current, history, history_weight);
+}
+
+@fragment
+fn fs_output(in: VertexOutput) -> @location(0) vec4<f32> {
+    return tonemap(tonemapping, textureSample(history_texture, taa_sampler, in.uv));
+}
@@ -0,0 +1,84 @@
+// Where the cloth tears: every constraint gets the strain past which it breaks from the rest
+// state of its piece, see TearMap. The GPU checks them once per substep, see tear_constraints()
+// of compute.wgsl, and a torn constraint is gone until the cloth is reset.
+
+use crate::simulation::{Constraint, Instance, MAX_CONSTRAINTS, NO_NEIGHBOR};
+
+/// Strain, the stretch over the rest length, at which the constraints of a piece tear. Varies
+/// across the piece so tears can be made to run along a designed path.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum TearMap {
+    #[default]
+    Never,
+    Uniform(f32),
+    // From the lowest rest position along `axis` to the highest, linearly in between
+    Gradient {
+        axis: [f32; 3],
+        from: f32,
+        to: f32,
+    },
+    // Constraints crossing the plane through `point` tear at `perforated`, the others at `strain`,
+    // e.g. the dotted line of a tear-off coupon
+    Perforation {
+        point: [f32; 3],
+        normal: [f32; 3],
+        strain: f32,
+        perforated: f32,
+    },
+}
+
+impl TearMap {
+    pub const NAMES: [&'static str; 4] = ["Never", "Uniform", "Gradient", "Perforated line"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TearMap::Never => Self::NAMES[0],
+            TearMap::Uniform(_) => Self::NAMES[1],
+            TearMap::Gradient { .. } => Self::NAMES[2],
+            TearMap::Perforation { .. } => Self::NAMES[3],
+        }
+    }
+
+    // Sets the tear strain of every constraint of a piece, 0.0 for those that never tear.
+    // `instances` are the rest state of the piece and the constraints refer to them.
+    pub(crate) fn apply(&self, instances: &[Instance], constraints: &mut [Constraint]) {
+        let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        let (low, high) = match *self {
+            TearMap::Gradient { axis, .. } => instances
+                .iter()
+                .map(|instance| dot(instance.position(), axis))
+                .fold((f32::MAX, f32::MIN), |(low, high), along| (low.min(along), high.max(along))),
+            _ => (0.0, 0.0),
+        };
+
+        for (slot, constraint) in constraints.iter_mut().enumerate() {
+            if constraint.neighbor == NO_NEIGHBOR {
+                continue;
+            }
+            let a = instances[slot / MAX_CONSTRAINTS].position();
+            let b = instances[constraint.neighbor as usize].position();
+            constraint.tear_strain = match *self {
+                TearMap::Never => 0.0,
+                TearMap::Uniform(strain) => strain,
+                TearMap::Gradient { axis, from, to } => {
+                    let middle = [0, 1, 2].map(|i| 0.5 * (a[i] + b[i]));
+                    let t = if high > low { (dot(middle, axis) - low) / (high - low) } else { 0.0 };
+                    from + (to - from) * t
+                }
+                TearMap::Perforation {
+                    point,
+                    normal,
+                    strain,
+                    perforated,
+                } => {
+                    let side = |p: [f32; 3]| dot([0, 1, 2].map(|i| p[i] - point[i]), normal) >= 0.0;
+                    if side(a) == side(b) {
+                        strain
+                    } else {
+                        perforated
+                    }
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// A color per particle laid over the mesh colors, the albedo texture and the color ramp by its
+// alpha, to mark particles out one by one: the pins for now, anything a tool selects later.
+
+use wgpu_bootstrap::{egui, wgpu};
+
+use crate::simulation::ClothSimulation;
+
+const PIN_COLOR: [u8; 4] = [255, 140, 0, 255];
+
+// One RGBA8 color per particle in a vertex buffer stepped per instance, next to the particle states
+pub fn vertex_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<[u8; 4]>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 9,
+            format: wgpu::VertexFormat::Unorm8x4,
+        }],
+    }
+}
+
+/// Which particles get a tint, computed again whenever the particles change.
+#[derive(Default)]
+pub struct Highlights {
+    pub pins: bool,
+}
+
+impl Highlights {
+    // In particle order, clear where nothing is highlighted
+    pub fn tints(&self, cloth: &ClothSimulation) -> Vec<[u8; 4]> {
+        let mut tints = vec![[0; 4]; cloth.num_instances() as usize];
+        if self.pins {
+            for particle in cloth.pinned_particles() {
+                tints[particle as usize] = PIN_COLOR;
+            }
+        }
+        tints
+    }
+
+    // True when the tints have to be computed again
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.checkbox(&mut self.pins, "Highlight pins")
+            .on_hover_text("Pinned particles in orange, over any other color")
+            .changed()
+    }
+}
@@ -0,0 +1,59 @@
+// Transform hierarchy posed from the CPU every frame, anchors and colliders follow its nodes.
+
+use wgpu_bootstrap::cgmath::{Matrix4, SquareMatrix};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    // The first node added, or the world for a cloth stepped without a hierarchy
+    pub const ROOT: Self = Self(0);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+struct TransformNode {
+    name: String,
+    parent: Option<NodeId>,
+    local: Matrix4<f32>,
+}
+
+/// Nodes are stored parents first, so world transforms are resolved in a single pass.
+#[derive(Default)]
+pub struct TransformHierarchy {
+    nodes: Vec<TransformNode>,
+}
+
+impl TransformHierarchy {
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, local: Matrix4<f32>) -> NodeId {
+        self.nodes.push(TransformNode {
+            name: name.to_string(),
+            parent,
+            local,
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name).map(NodeId)
+    }
+
+    pub fn local(&self, node: NodeId) -> Matrix4<f32> {
+        self.nodes[node.0].local
+    }
+
+    pub fn set_local(&mut self, node: NodeId, local: Matrix4<f32>) {
+        self.nodes[node.0].local = local;
+    }
+
+    pub fn world_matrices(&self) -> Vec<Matrix4<f32>> {
+        let mut world: Vec<Matrix4<f32>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let parent = node.parent.map_or(Matrix4::identity(), |parent| world[parent.0]);
+            world.push(parent * node.local);
+        }
+        world
+    }
+}
@@ -0,0 +1,50 @@
+// Turntable: revolves the orbit camera around its target at a steady angular speed, for showcase
+// clips. Following the simulated time, it turns by the steps taken rather than by the frames, so a
+// recording turns evenly whatever the frame rate, and a batch render of the replay turns the same.
+
+use wgpu_bootstrap::{egui, Context};
+
+use cloth::render::OrbitCamera;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Turntable {
+    pub enabled: bool,
+    pub speed: f32,           // degrees per s
+    pub simulated_time: bool, // turns with the steps instead of the frames
+}
+
+impl Default for Turntable {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            speed: 20.0,
+            simulated_time: true,
+        }
+    }
+}
+
+impl Turntable {
+    // Turns the camera by `seconds` of the clock it follows
+    pub fn turn(&self, camera: &mut OrbitCamera, context: &Context, seconds: f32) {
+        if !self.enabled || seconds == 0.0 {
+            return;
+        }
+        let mut polar = camera.polar();
+        polar.y += self.speed.to_radians() * seconds;
+        camera.set_polar(polar).update(context);
+    }
+
+    // Radians turned per step of `time_step` s, for a batch render of a replay, None when off
+    pub fn step_angle(&self, time_step: f32) -> Option<f32> {
+        self.enabled.then(|| self.speed.to_radians() * time_step)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Turntable");
+            ui.add(egui::Slider::new(&mut self.speed, -90.0..=90.0).text("°/s"));
+        });
+        ui.checkbox(&mut self.simulated_time, "Turn with the simulated time")
+            .on_hover_text("Stops while paused, and batch renders of the replay turn the same");
+    }
+}
@@ -0,0 +1,22 @@
+// hashing.wgsl
+// Integer hashes for per particle or per cell randomness, stateless and reproducible.
+
+// PCG hash, from Jarzynski and Olano, Hash Functions for GPU Rendering (2020)
+fn pcg_hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn hash2(v: vec2<u32>) -> u32 {
+    return pcg_hash(v.x ^ pcg_hash(v.y));
+}
+
+fn hash3(v: vec3<u32>) -> u32 {
+    return pcg_hash(v.x ^ pcg_hash(v.y ^ pcg_hash(v.z)));
+}
+
+// Uniform in [0, 1), from the 24 high bits
+fn hash_to_unit(hash: u32) -> f32 {
+    return f32(hash >> 8u) / 16777216.0;
+}
@@ -0,0 +1,139 @@
+// lighting.wgsl
+// Shading under the lights of the scene, see lighting.rs. Everything is in world space.
+//
+// Blinn-Phong for the colliders, and for the cloth unless the fabric BRDF is on: a Lambert
+// diffuse with the Charlie sheen of Estevez and Kulla, the haze of light the fibers scatter back
+// at grazing angles, which is what tells cloth from plastic.
+
+const PI: f32 = 3.14159265;
+const MAX_FABRICS: u32 = 16u; // must match the Rust side
+
+// Must match the Rust side LightingUniform struct
+struct Lighting {
+    direction: vec4<f32>, // towards the directional light, normalized, w is its intensity
+    sun_color: vec4<f32>,
+    point: vec4<f32>, // position of the point light, w is its intensity
+    point_color: vec4<f32>, // w is the distance at which the point light fell to a half
+    ambient: vec4<f32>, // w is the shininess of the Blinn-Phong highlights
+    back_color: vec4<f32>, // of the back faces of the cloth, w is 1 to use it instead of theirs
+    fabric_brdf: u32, // 1 shades the cloth with the sheen of its fabric
+    shadow: vec4<f32>, // strength of the shadows of the sun, 0 for none, bias in depth and texel size
+    shadow_matrix: mat4x4<f32>, // world space to the clip space of the sun, see shadow_map.rs
+    sky_zenith: vec4<f32>, // w is 1 to take the ambient light from the sky, see sky.rs
+    sky_horizon: vec4<f32>, // w is 1 to draw the glow of the sun in the sky
+    sky_ground: vec4<f32>,
+    fog: vec4<f32>, // color, w is the density in 1/m, 0 for no fog
+};
+
+// Where the eye of a view matrix made of a rotation and a translation is
+fn eye_position(view: mat4x4<f32>) -> vec3<f32> {
+    let rotation = mat3x3<f32>(view[0].xyz, view[1].xyz, view[2].xyz);
+    return -(transpose(rotation) * view[3].xyz);
+}
+
+// Diffuse and specular of one light coming from `to_light`, normalized
+fn blinn_phong_term(albedo: vec3<f32>, normal: vec3<f32>, to_eye: vec3<f32>, to_light: vec3<f32>, shininess: f32) -> vec3<f32> {
+    let diffuse = max(dot(normal, to_light), 0.0);
+    if (diffuse <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let halfway = normalize(to_light + to_eye);
+    return albedo * diffuse + vec3<f32>(pow(max(dot(normal, halfway), 0.0), shininess));
+}
+
+// Charlie distribution, `roughness` perceptual
+fn charlie_distribution(roughness: f32, n_dot_h: f32) -> f32 {
+    let inverse_alpha = 1.0 / max(roughness * roughness, 1e-3);
+    let sin2 = max(1.0 - n_dot_h * n_dot_h, 0.0);
+    return (2.0 + inverse_alpha) * pow(sin2, 0.5 * inverse_alpha) / (2.0 * PI);
+}
+
+// Visibility term of Neubelt and Pettineo, cheaper than the exact one and close to it
+fn sheen_visibility(n_dot_l: f32, n_dot_v: f32) -> f32 {
+    return 1.0 / max(4.0 * (n_dot_l + n_dot_v - n_dot_l * n_dot_v), 1e-4);
+}
+
+// Same as blinn_phong_term() for a fabric, `sheen` is its color with w the roughness. Scaled by
+// π like the Lambert diffuse, so a light of intensity 1 lights a white surface facing it fully.
+fn sheen_term(albedo: vec3<f32>, sheen: vec4<f32>, normal: vec3<f32>, to_eye: vec3<f32>, to_light: vec3<f32>) -> vec3<f32> {
+    let n_dot_l = dot(normal, to_light);
+    if (n_dot_l <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let n_dot_v = max(dot(normal, to_eye), 1e-4);
+    let n_dot_h = max(dot(normal, normalize(to_light + to_eye)), 0.0);
+    let specular = sheen.rgb * charlie_distribution(sheen.w, n_dot_h) * sheen_visibility(n_dot_l, n_dot_v);
+    return (albedo + PI * specular) * n_dot_l;
+}
+
+fn light_term(lighting: Lighting, albedo: vec3<f32>, sheen: vec4<f32>, normal: vec3<f32>, to_eye: vec3<f32>, to_light: vec3<f32>) -> vec3<f32> {
+    if (sheen.w > 0.0) {
+        return sheen_term(albedo, sheen, normal, to_eye, to_light);
+    }
+    return blinn_phong_term(albedo, normal, to_eye, to_light, lighting.ambient.w);
+}
+
+// Color of the sky along `direction`, normalized: the ground below the horizon, the zenith above,
+// blended with the sharper curve up to the zenith that skies have
+fn sky_gradient(lighting: Lighting, direction: vec3<f32>) -> vec3<f32> {
+    let up = direction.y;
+    if (up < 0.0) {
+        return mix(lighting.sky_horizon.rgb, lighting.sky_ground.rgb, sqrt(-up));
+    }
+    return mix(lighting.sky_horizon.rgb, lighting.sky_zenith.rgb, sqrt(up));
+}
+
+// Light of the sky reaching a surface facing `normal`, from the half of the sky it sees. Faked by
+// the gradient along the normal, wider than the real integral but smooth like it.
+fn sky_irradiance(lighting: Lighting, normal: vec3<f32>) -> vec3<f32> {
+    let t = 0.5 + 0.5 * normal.y;
+    return mix(mix(lighting.sky_ground.rgb, lighting.sky_horizon.rgb, t), mix(lighting.sky_horizon.rgb, lighting.sky_zenith.rgb, t), t);
+}
+
+// How much of the sunlight reaches `position`, from 0 in full shadow to 1, filtered over the 3×3
+// texels around it
+fn sun_visibility(lighting: Lighting, shadow_map: texture_depth_2d, shadow_sampler: sampler_comparison, position: vec3<f32>) -> f32 {
+    let clip = lighting.shadow_matrix * vec4<f32>(position, 1.0);
+    let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+    let depth = clip.z / clip.w - lighting.shadow.y;
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * lighting.shadow.z;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, depth);
+        }
+    }
+    // Past the far plane of the sun is lit too
+    if (depth > 1.0 || lighting.shadow.x <= 0.0) {
+        return 1.0;
+    }
+    return 1.0 - lighting.shadow.x * (1.0 - lit / 9.0);
+}
+
+// Fades `color`, seen from `distance` m away, into the color of the fog
+fn apply_fog(lighting: Lighting, color: vec3<f32>, distance: f32) -> vec3<f32> {
+    return mix(lighting.fog.rgb, color, exp(-lighting.fog.w * distance));
+}
+
+// Every light on a surface, `sheen` the fabric it is made of or all zeroes for Blinn-Phong, `sun`
+// the visibility of the sun from sun_visibility(), faded into the fog
+fn shade_surface(lighting: Lighting, albedo: vec3<f32>, sheen: vec4<f32>, position: vec3<f32>, normal: vec3<f32>, eye: vec3<f32>, sun: f32) -> vec3<f32> {
+    let n = normalize(normal);
+    let to_eye = normalize(eye - position);
+
+    var ambient = lighting.ambient.rgb;
+    if (lighting.sky_zenith.w > 0.0) {
+        ambient *= sky_irradiance(lighting, n);
+    }
+    var color = albedo * ambient;
+    color += sun * lighting.sun_color.rgb * lighting.direction.w * light_term(lighting, albedo, sheen, n, to_eye, lighting.direction.xyz);
+
+    let to_lamp = lighting.point.xyz - position;
+    let distance = length(to_lamp);
+    if (distance > 1e-6) {
+        // Half the intensity at the range, falling off with the square of the distance past it
+        let falloff = 1.0 / (1.0 + distance * distance / max(lighting.point_color.w * lighting.point_color.w, 1e-6));
+        color += lighting.point_color.rgb * lighting.point.w * falloff * light_term(lighting, albedo, sheen, n, to_eye, to_lamp / distance);
+    }
+    return apply_fog(lighting, color, length(eye - position));
+}
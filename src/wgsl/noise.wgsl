@@ -0,0 +1,35 @@
+// noise.wgsl
+// Smooth pseudo random fields, e.g. for gusts or fabric irregularities.
+
+#include "hashing.wgsl"
+
+fn lattice_value(cell: vec3<i32>) -> f32 {
+    return hash_to_unit(hash3(bitcast<vec3<u32>>(cell)));
+}
+
+// Value noise in [0, 1), random on the integer lattice and smoothly interpolated in between
+fn value_noise(p: vec3<f32>) -> f32 {
+    let cell = vec3<i32>(floor(p));
+    let t = fract(p);
+    let s = t * t * (3.0 - 2.0 * t);
+    let x00 = mix(lattice_value(cell), lattice_value(cell + vec3<i32>(1, 0, 0)), s.x);
+    let x10 = mix(lattice_value(cell + vec3<i32>(0, 1, 0)), lattice_value(cell + vec3<i32>(1, 1, 0)), s.x);
+    let x01 = mix(lattice_value(cell + vec3<i32>(0, 0, 1)), lattice_value(cell + vec3<i32>(1, 0, 1)), s.x);
+    let x11 = mix(lattice_value(cell + vec3<i32>(0, 1, 1)), lattice_value(cell + vec3<i32>(1, 1, 1)), s.x);
+    return mix(mix(x00, x10, s.y), mix(x01, x11, s.y), s.z);
+}
+
+// Octaves of value noise, each twice as fine and half as strong, still in [0, 1)
+fn fractal_noise(p: vec3<f32>, octaves: u32) -> f32 {
+    var sum = 0.0;
+    var weight = 0.5;
+    var total = 0.0;
+    var scale = 1.0;
+    for (var octave = 0u; octave < octaves; octave++) {
+        sum += weight * value_noise(p * scale);
+        total += weight;
+        weight *= 0.5;
+        scale *= 2.0;
+    }
+    return sum / max(total, 1e-6);
+}
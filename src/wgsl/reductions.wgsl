@@ -0,0 +1,36 @@
+// reductions.wgsl
+// Workgroup reductions for compute modules, WORKGROUP_SIZE must be a power of two. Every
+// invocation of the workgroup has to call them, they synchronize on barriers.
+
+var<workgroup> reduction: array<f32, WORKGROUP_SIZE>;
+
+// Tree reduction over the workgroup, every invocation gets the sum back
+fn workgroup_sum(value: f32, local_index: u32) -> f32 {
+    reduction[local_index] = value;
+    workgroupBarrier();
+    for (var stride = u32(WORKGROUP_SIZE) / 2u; stride > 0u; stride = stride / 2u) {
+        if (local_index < stride) {
+            reduction[local_index] += reduction[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+    let sum = reduction[0];
+    // Keeps a following reduction from overwriting the result before everyone read it
+    workgroupBarrier();
+    return sum;
+}
+
+// Same with the largest value
+fn workgroup_max(value: f32, local_index: u32) -> f32 {
+    reduction[local_index] = value;
+    workgroupBarrier();
+    for (var stride = u32(WORKGROUP_SIZE) / 2u; stride > 0u; stride = stride / 2u) {
+        if (local_index < stride) {
+            reduction[local_index] = max(reduction[local_index], reduction[local_index + stride]);
+        }
+        workgroupBarrier();
+    }
+    let largest = reduction[0];
+    workgroupBarrier();
+    return largest;
+}
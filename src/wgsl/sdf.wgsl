@@ -0,0 +1,71 @@
+// sdf.wgsl
+// Signed distances to the collider primitives, negative inside, and the geometry they share.
+
+// Rotates `v` by the unit quaternion `q`, xyz the vector part
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
+fn closest_on_segment(p: vec3<f32>, a: vec3<f32>, b: vec3<f32>) -> vec3<f32> {
+    let segment = b - a;
+    let t = clamp(dot(p - a, segment) / max(dot(segment, segment), 1e-12), 0.0, 1.0);
+    return a + t * segment;
+}
+
+fn sd_sphere(p: vec3<f32>, center: vec3<f32>, radius: f32) -> f32 {
+    return length(p - center) - radius;
+}
+
+fn sd_capsule(p: vec3<f32>, a: vec3<f32>, b: vec3<f32>, radius: f32) -> f32 {
+    return length(p - closest_on_segment(p, a, b)) - radius;
+}
+
+fn sd_box(p: vec3<f32>, center: vec3<f32>, half_extents: vec3<f32>, rotation: vec4<f32>) -> f32 {
+    let local = rotate(vec4<f32>(-rotation.xyz, rotation.w), p - center);
+    let q = abs(local) - half_extents;
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+// `normal` is a unit vector, the inside is where dot(p, normal) < offset
+fn sd_plane(p: vec3<f32>, normal: vec3<f32>, offset: f32) -> f32 {
+    return dot(p, normal) - offset;
+}
+
+// From Real-Time Collision Detection, by the Voronoi region of the triangle the point is in
+fn closest_on_triangle(p: vec3<f32>, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>) -> vec3<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = dot(ab, ap);
+    let d2 = dot(ac, ap);
+    if (d1 <= 0.0 && d2 <= 0.0) {
+        return a;
+    }
+    let bp = p - b;
+    let d3 = dot(ab, bp);
+    let d4 = dot(ac, bp);
+    if (d3 >= 0.0 && d4 <= d3) {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if (vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0) {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let d5 = dot(ab, cp);
+    let d6 = dot(ac, cp);
+    if (d6 >= 0.0 && d5 <= d6) {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if (vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0) {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if (va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0) {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = 1.0 / (va + vb + vc);
+    return a + ab * (vb * denominator) + ac * (vc * denominator);
+}
@@ -0,0 +1,24 @@
+// tonemap.wgsl
+// From the HDR colors the scene is rendered in to the [0, 1] of the window, see hdr.rs.
+
+// Must match the Rust side TonemapUniform struct
+struct Tonemapping {
+    exposure: f32, // scale of the scene colors, 2 to the power of the exposure in stops
+    curve: u32, // 0 clips, 1 Reinhard, 2 ACES
+};
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    return clamp(color * (2.51 * color + 0.03) / (color * (2.43 * color + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn tonemap(tonemapping: Tonemapping, color: vec4<f32>) -> vec4<f32> {
+    let exposed = max(color.rgb * tonemapping.exposure, vec3<f32>(0.0));
+    var mapped = min(exposed, vec3<f32>(1.0));
+    switch tonemapping.curve {
+        case 1u: { mapped = exposed / (1.0 + exposed); }
+        case 2u: { mapped = aces(exposed); }
+        default: {}
+    }
+    return vec4<f32>(mapped, color.a);
+}